@@ -0,0 +1,5 @@
+package bisocm.swiftbot.lib;
+
+public interface MotionListener {
+    void onMotion(double score, long frameId);
+}
@@ -1,5 +1,7 @@
 package bisocm.swiftbot.lib;
 
+import java.nio.ByteBuffer;
+
 class NativeBindings {
 
     /***********************************************************************
@@ -61,6 +63,61 @@ class NativeBindings {
         }
     }
 
+    /***********************************************************************
+     *                     MOTION LISTENER CALLBACK                        *
+     ***********************************************************************
+     * The `MotionListener` object handles frames that change enough from
+     * the one before them. Java registers a listener with a threshold
+     * using `registerMotionListener` and Rust triggers `onMotion` from a
+     * thread of its own for every frame scoring at least the threshold.
+     ***********************************************************************/
+    private static MotionListener motionListener;
+
+    public static void registerMotionListener(MotionListener listener, double threshold) {
+        startMotionMonitoring(threshold); //Throws before the listener is replaced if the threshold is invalid
+        motionListener = listener;
+    }
+
+    public static void unregisterMotionListener() {
+        stopMotionMonitoring();
+        motionListener = null;
+    }
+
+    public static void onMotion(double score, long frameId) {
+        MotionListener listener = motionListener;
+        if (listener != null) {
+            listener.onMotion(score, frameId);
+        }
+    }
+
+    /***********************************************************************
+     *                RECORDING PROGRESS LISTENER CALLBACK                 *
+     ***********************************************************************
+     * The `RecordingProgressListener` object follows background
+     * recordings. Java registers a listener using
+     * `registerRecordingProgressListener`, or clears it by registering
+     * null, and Rust triggers `onRecordingProgress` from the recording
+     * thread as frames are written.
+     ***********************************************************************/
+    private static RecordingProgressListener recordingProgressListener;
+
+    public static void registerRecordingProgressListener(RecordingProgressListener listener, int everyFrames) {
+        if (listener == null) {
+            stopRecordingProgress();
+            recordingProgressListener = null;
+            return;
+        }
+        startRecordingProgress(everyFrames); //Throws before the listener is replaced if the interval is invalid
+        recordingProgressListener = listener;
+    }
+
+    public static void onRecordingProgress(long elapsedMillis, long framesWritten, long bytesWritten) {
+        RecordingProgressListener listener = recordingProgressListener;
+        if (listener != null) {
+            listener.onRecordingProgress(elapsedMillis, framesWritten, bytesWritten);
+        }
+    }
+
     /***********************************************************************
      *                        NATIVE METHODS SECTION                       *
      ***********************************************************************
@@ -91,4 +148,113 @@ class NativeBindings {
 
     //Camera control
     public static native byte[] captureImage();
+    public static native byte[] captureSnapshot(int quality);
+    public static native void captureSnapshotPng(String path);
+    public static native void captureStill(String path, int format, int quality);
+    public static native long snapshotFromStream(String path, int quality);
+    public static native void captureRawFrame(String path);
+    public static native int captureBurst(String dir, int count, int quality);
+    public static native byte[][] captureBurstToMemory(int count);
+    public static native int captureFrameSequence(String dir, String pattern, int durationSeconds, int maxFrames, int quality);
+    public static native int captureTimelapse(String dir, int intervalMs, int frames, int quality, boolean powerDown);
+    public static native int captureVideo(String path, int durationSeconds, int quality, int codec, int outputFps);
+    public static native int captureVideoFrames(String path, int frameCount, int quality, int codec, int outputFps);
+    public static native long[] getCameraVideoTiming();
+    public static native byte[] captureVideoToMemory(int durationSeconds, int quality, int maxBytes, int outputFps, boolean[] truncated);
+    public static native int captureVideoH264(String path, int durationSeconds, int bitrate);
+    public static native int captureGif(String path, int durationMs, int fps, int scale);
+    public static native void startBackgroundRecording(String path, int quality, int maxMegabytes, int maxSeconds, int scale);
+    public static native long[] stopBackgroundRecording();
+    public static native boolean isBackgroundRecording();
+    public static native long[] getBackgroundRecordingTiming();
+    public static native String[] getBackgroundRecordingSegments();
+    public static native void pauseBackgroundRecording();
+    public static native void resumeBackgroundRecording();
+    public static native boolean isBackgroundRecordingPaused();
+    private static native void startRecordingProgress(int everyFrames);
+    private static native void stopRecordingProgress();
+    public static native void startCameraPrebuffer(int seconds, int maxMegabytes, int quality);
+    public static native int dumpCameraPrebuffer(String path, int afterSeconds);
+    public static native void stopCameraPrebuffer();
+    public static native boolean isCameraPrebuffering();
+    public static native boolean isCameraH264Available();
+    public static native void stopRecording();
+    public static native void cancelCapture();
+    public static native void startMjpegServer(int port);
+    public static native void stopMjpegServer();
+    public static native void setCameraJpegQuality(int quality);
+    public static native int getCameraJpegQuality();
+    public static native void stopCamera();
+    public static native void setCameraReconnect(int attempts, int intervalMs);
+    public static native boolean isCameraReconnecting();
+    public static native void loadCameraConfig(String path);
+    public static native void setCameraResolution(int width, int height);
+    public static native void reconfigureCamera(int width, int height, int fps);
+    public static native void setCameraDevice(String path);
+    public static native void setCameraDeviceIndex(int index);
+    public static native void setCameraFrameRate(int fps);
+    public static native void setCameraBufferCount(int buffers);
+    public static native void setCameraWarmupFrames(int frames);
+    public static native int getCameraWarmupFramesDiscarded();
+    public static native void setCameraColorMode(int mode);
+    public static native int getCameraColorMode();
+    public static native void setCameraFlip(boolean horizontal, boolean vertical);
+    public static native boolean setCameraCrop(int x, int y, int width, int height);
+    public static native void resetCameraCrop();
+    public static native void setCameraOutputSize(int width, int height);
+    public static native void setCameraOverlay(boolean enabled, int corner, boolean frameCounter);
+    public static native void setCameraMotionDetection(boolean enabled);
+    public static native double getCameraMotionScore();
+    private static native void startMotionMonitoring(double threshold);
+    private static native void stopMotionMonitoring();
+    public static native int[] getCameraHistogram();
+    public static native double getCameraAverageBrightness();
+    public static native void setCameraEdgeDetection(boolean enabled);
+    public static native void setCameraBlur(int radius);
+    public static native byte[] getCameraEdgeMap();
+    public static native void setCameraColorFilter(int hueMin, int hueMax, int saturationMin, int saturationMax, int valueMin, int valueMax);
+    public static native void clearCameraColorFilter();
+    public static native byte[] getCameraColorMask();
+    public static native int[] getCameraLargestBlob();
+    public static native String[] decodeCameraQr();
+    public static native double[] getCameraQrCorners();
+    public static native double[][] detectCameraAprilTags();
+    public static native double getCameraFrameRate();
+    public static native long getCameraFrameId();
+    public static native long waitForCameraFrame(long timeoutMs);
+    public static native ByteBuffer acquireCameraFrameBuffer();
+    public static native void releaseCameraFrameBuffer(ByteBuffer buffer);
+    public static native long[] getCameraFrameBufferInfo(ByteBuffer buffer);
+    public static native int getCameraFrameWidth();
+    public static native int getCameraFrameHeight();
+    public static native int getCameraFrameChannels();
+    public static native String getCameraInfo();
+    public static native String[] listCameras();
+    public static native String[] getCameraFormats();
+    public static native double[] getCameraFrameRates(String format, int width, int height);
+    public static native void setCameraExposureAuto(boolean enabled);
+    public static native void setCameraAutoExposure(boolean enabled);
+    public static native boolean isCameraExposureAuto();
+    public static native void setCameraExposure(int value);
+    public static native int getCameraExposure();
+    public static native void setCameraWhiteBalanceAuto(boolean enabled);
+    public static native void setCameraAutoWhiteBalance(boolean enabled);
+    public static native boolean isCameraWhiteBalanceAuto();
+    public static native int setCameraWhiteBalanceTemperature(int kelvin);
+    public static native int getCameraWhiteBalanceTemperature();
+    public static native int setCameraControl(int controlId, int value);
+    public static native int getCameraControl(int controlId);
+    public static native int[] getCameraControlRange(int controlId);
+    public static native int[] queryCameraControlRange(int controlId);
+    public static native int setCameraBrightness(int value);
+    public static native int setCameraContrast(int value);
+    public static native int setCameraSaturation(int value);
+    public static native int setCameraGain(int value);
+    public static native int getCameraGain();
+    public static native boolean setCameraPowerLineFrequency(int frequency);
+    public static native int getCameraPowerLineFrequency();
+    public static native void setCameraAutofocus(boolean enabled);
+    public static native boolean isCameraAutofocus();
+    public static native int setCameraFocus(int value);
+    public static native int getCameraFocus();
 }
\ No newline at end of file
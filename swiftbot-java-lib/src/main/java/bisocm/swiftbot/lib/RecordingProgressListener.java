@@ -0,0 +1,5 @@
+package bisocm.swiftbot.lib;
+
+public interface RecordingProgressListener {
+    void onRecordingProgress(long elapsedMillis, long framesWritten, long bytesWritten);
+}
@@ -1,5 +1,7 @@
 package bisocm.swiftbot.lib;
 
+import java.nio.ByteBuffer;
+
 public class SwiftBot {
     private ButtonListener buttonListener;
 
@@ -20,6 +22,103 @@ public class SwiftBot {
     public static final int BUTTON_X = 2;
     public static final int BUTTON_Y = 3;
 
+    /***********************************************************************
+     *                      CAMERA CONTROL CONSTANTS                       *
+     ***********************************************************************
+     * V4L2 control IDs for the common picture controls, for use with
+     * `setCameraControl`, `getCameraControl` and `getCameraControlRange`.
+     ***********************************************************************/
+    public static final int CAMERA_CONTROL_BRIGHTNESS = 0x00980900;
+    public static final int CAMERA_CONTROL_CONTRAST = 0x00980901;
+    public static final int CAMERA_CONTROL_SATURATION = 0x00980902;
+    public static final int CAMERA_CONTROL_GAIN = 0x00980913;
+    public static final int CAMERA_CONTROL_WHITE_BALANCE_TEMPERATURE = 0x0098091A;
+    public static final int CAMERA_CONTROL_SHARPNESS = 0x0098091B;
+    public static final int CAMERA_CONTROL_EXPOSURE = 0x009A0902;
+
+    /***********************************************************************
+     *                   CAMERA CONTROL RANGE CONSTANTS                    *
+     ***********************************************************************
+     * Indices into the array returned by `getCameraControlRange`.
+     ***********************************************************************/
+    public static final int CAMERA_RANGE_MIN = 0;
+    public static final int CAMERA_RANGE_MAX = 1;
+    public static final int CAMERA_RANGE_STEP = 2;
+    public static final int CAMERA_RANGE_DEFAULT = 3;
+    public static final int CAMERA_RANGE_VALUE = 4;
+    public static final int CAMERA_RANGE_ENABLED = 5;
+    public static final int CAMERA_RANGE_READ_ONLY = 6;
+
+    /***********************************************************************
+     *                     CAMERA COLOR MODE CONSTANTS                     *
+     ***********************************************************************
+     * Layouts of the image data returned by `captureImage`, for use with
+     * `setCameraColorMode`.
+     ***********************************************************************/
+    public static final int CAMERA_COLOR_MODE_JPEG = 0;
+    public static final int CAMERA_COLOR_MODE_GRAYSCALE = 1;
+    public static final int CAMERA_COLOR_MODE_RGB = 2;
+    public static final int CAMERA_COLOR_MODE_BGR = 3;
+
+    /***********************************************************************
+     *                  CAMERA POWER LINE FREQUENCY CONSTANTS              *
+     ***********************************************************************
+     * Anti-flicker settings for use with `setCameraPowerLineFrequency`.
+     ***********************************************************************/
+    public static final int CAMERA_POWER_LINE_DISABLED = 0;
+    public static final int CAMERA_POWER_LINE_50HZ = 1;
+    public static final int CAMERA_POWER_LINE_60HZ = 2;
+    public static final int CAMERA_POWER_LINE_AUTO = 3;
+
+    /***********************************************************************
+     *                     CAMERA VIDEO CODEC CONSTANTS                    *
+     ***********************************************************************
+     * Codecs for use with `captureVideo`.
+     ***********************************************************************/
+    public static final int CAMERA_CODEC_MJPEG = 0;
+    public static final int CAMERA_CODEC_H264 = 1;
+    public static final int CAMERA_CODEC_RAW_MJPEG = 2;
+    public static final int CAMERA_CODEC_Y4M = 3;
+
+    /***********************************************************************
+     *                    CAMERA IMAGE FORMAT CONSTANTS                    *
+     ***********************************************************************
+     * File formats for use with `captureStill`.
+     ***********************************************************************/
+    public static final int CAMERA_IMAGE_FORMAT_JPEG = 0;
+    public static final int CAMERA_IMAGE_FORMAT_PNG = 1;
+    public static final int CAMERA_IMAGE_FORMAT_BMP = 2;
+    public static final int CAMERA_IMAGE_FORMAT_PPM = 3;
+
+    /***********************************************************************
+     *                   CAMERA OVERLAY CORNER CONSTANTS                   *
+     ***********************************************************************
+     * Corners the timestamp is drawn in, for use with `setCameraOverlay`.
+     ***********************************************************************/
+    public static final int CAMERA_OVERLAY_TOP_LEFT = 0;
+    public static final int CAMERA_OVERLAY_TOP_RIGHT = 1;
+    public static final int CAMERA_OVERLAY_BOTTOM_LEFT = 2;
+    public static final int CAMERA_OVERLAY_BOTTOM_RIGHT = 3;
+
+    /***********************************************************************
+     *                  RECORDING TIMING INDEX CONSTANTS                   *
+     ***********************************************************************
+     * Positions in the arrays returned by `getCameraVideoTiming` and
+     * `getBackgroundRecordingTiming`, telling whether a recording kept up
+     * with the camera. Frame deadlines fall every frame interval at the rate
+     * the file plays back at; a recording that misses them has fewer frames
+     * than it should and plays back too fast. Times are in microseconds.
+     * `TIMING_ACTUAL_MILLIFPS` is the frame rate the recording achieved, in
+     * thousandths of a frame per second, to compare with the rate it was
+     * recorded at.
+     ***********************************************************************/
+    public static final int TIMING_FRAMES_CAPTURED = 0;
+    public static final int TIMING_FRAMES_WRITTEN = 1;
+    public static final int TIMING_AVERAGE_PROCESSING_MICROS = 2;
+    public static final int TIMING_MAX_PROCESSING_MICROS = 3;
+    public static final int TIMING_MISSED_DEADLINES = 4;
+    public static final int TIMING_ACTUAL_MILLIFPS = 5;
+
     /***********************************************************************
      *                     MOTOR CONTROL METHODS                           *
      ***********************************************************************
@@ -204,10 +303,1592 @@ public class SwiftBot {
     /**
      * Captures an image from the SwiftBot's camera.
      *
-     * @return A byte array containing the captured image data in JPEG format.
-     *         Returns `null` if an error occurs.
+     * @return A byte array containing the captured image data in JPEG format,
+     *         or raw pixels in the mode set by `setCameraColorMode`. Returns
+     *         `null` if an error occurs.
      */
     public byte[] captureImage() {
         return NativeBindings.captureImage();
     }
+
+    /**
+     * Captures a single still from the SwiftBot's camera as a JPEG. The
+     * camera is opened just for the snapshot and closed afterwards, so this
+     * fails if images are already being captured; call `stopCamera` first.
+     * The JPEG carries EXIF metadata with the time it was taken, its size,
+     * and the camera's exposure and gain.
+     *
+     * @param quality JPEG quality from 1 (smallest) to 100 (best).
+     * @return A byte array containing the JPEG image data, or `null` if an
+     *         error occurs.
+     */
+    public byte[] captureSnapshot(int quality) {
+        return NativeBindings.captureSnapshot(quality);
+    }
+
+    /**
+     * Captures a single still from the SwiftBot's camera as a JPEG, at the
+     * quality set by `setCameraJpegQuality`.
+     *
+     * @return A byte array containing the JPEG image data, or `null` if an
+     *         error occurs.
+     */
+    public byte[] captureSnapshot() {
+        return NativeBindings.captureSnapshot(NativeBindings.getCameraJpegQuality());
+    }
+
+    /**
+     * Captures a single still from the SwiftBot's camera and saves it
+     * losslessly as a PNG file, e.g. for marker detection where JPEG
+     * artifacts get in the way. If images are already being captured the
+     * next frame is saved, which fails in `CAMERA_COLOR_MODE_JPEG`. The
+     * file can be read as soon as this returns.
+     *
+     * @param path Path of the PNG file to write.
+     */
+    public void captureSnapshotPng(String path) {
+        NativeBindings.captureSnapshotPng(path);
+    }
+
+    /**
+     * Captures a single still from the SwiftBot's camera and saves it to a
+     * file. Unlike `captureSnapshot` this also works while images are being
+     * captured, taking the next frame and leaving the camera running, except
+     * that lossless stills can't be taken while streaming in JPEG mode. The
+     * format is never guessed from the file name. JPEG stills carry EXIF
+     * metadata like those from `captureSnapshot`. The file can be read as
+     * soon as this returns.
+     *
+     * @param path    Path of the file to write.
+     * @param format  `CAMERA_IMAGE_FORMAT_JPEG`, or the lossless
+     *                `CAMERA_IMAGE_FORMAT_PNG`, `CAMERA_IMAGE_FORMAT_BMP` or
+     *                `CAMERA_IMAGE_FORMAT_PPM`.
+     * @param quality JPEG quality from 1 (smallest) to 100 (best), ignored
+     *                for the lossless formats.
+     */
+    public void captureStill(String path, int format, int quality) {
+        NativeBindings.captureStill(path, format, quality);
+    }
+
+    /**
+     * Captures a single still from the SwiftBot's camera and saves it as a
+     * JPEG file, like `captureStill(path, CAMERA_IMAGE_FORMAT_JPEG, quality)`.
+     *
+     * @param path    Path of the JPEG file to write.
+     * @param quality JPEG quality from 1 (smallest) to 100 (best).
+     */
+    public void captureStill(String path, int quality) {
+        NativeBindings.captureStill(path, CAMERA_IMAGE_FORMAT_JPEG, quality);
+    }
+
+    /**
+     * Captures a single still from the SwiftBot's camera and saves it as a
+     * JPEG file, at the quality set by `setCameraJpegQuality`.
+     *
+     * @param path Path of the JPEG file to write.
+     */
+    public void captureStill(String path) {
+        NativeBindings.captureStill(path, CAMERA_IMAGE_FORMAT_JPEG, NativeBindings.getCameraJpegQuality());
+    }
+
+    /**
+     * Saves the last frame the running camera captured as a JPEG file,
+     * without capturing a new one. The slow encoding happens after the
+     * camera is released, so a background recording or the MJPEG server
+     * keeps streaming while the file is written.
+     *
+     * @param path    Path of the JPEG file to write.
+     * @param quality JPEG quality from 1 (smallest) to 100 (best).
+     * @return The ID of the frame saved, as returned by `getCameraFrameId`.
+     * @throws IllegalStateException If the camera isn't running or hasn't
+     *                               captured a frame yet.
+     */
+    public long snapshotFromStream(String path, int quality) {
+        return NativeBindings.snapshotFromStream(path, quality);
+    }
+
+    /**
+     * Saves one frame exactly as the camera delivered it, for reporting color
+     * or distortion problems. The file holds the unconverted YUYV bytes, and
+     * a sidecar file with `.toml` added to its name gives the frame's size
+     * and pixel format. This fails if the camera is busy, e.g. recording.
+     *
+     * @param path Path of the file to write.
+     */
+    public void captureRawFrame(String path) {
+        NativeBindings.captureRawFrame(path);
+    }
+
+    /**
+     * Captures frames back to back, as fast as the camera delivers them, and
+     * saves them as `frame_0001.jpg`, `frame_0002.jpg` and so on, e.g. to
+     * study motion blur. Like `captureVideo` this leaves the camera running.
+     *
+     * @param dir     Directory to save the frames in, created if needed.
+     * @param count   Number of frames to capture.
+     * @param quality JPEG quality from 1 (smallest) to 100 (best).
+     * @return The number of frames saved, always `count`, or -1 if an error
+     *         occurs. If the burst stops part way through, the exception's
+     *         message says how many frames were saved, and those are kept.
+     */
+    public int captureBurst(String dir, int count, int quality) {
+        return NativeBindings.captureBurst(dir, count, quality);
+    }
+
+    /**
+     * Captures frames back to back, as fast as the camera delivers them, and
+     * returns them as RGB pixels, e.g. to average them into one image with
+     * less noise. Each frame takes width * height * 3 bytes, so at most 32
+     * frames can be captured at once, about 29 MB at 640x480. In
+     * `CAMERA_COLOR_MODE_JPEG` the camera must be stopped first.
+     *
+     * @param count Number of frames to capture, from 1 to 32.
+     * @return `count` arrays, one per frame, of packed RGB pixels, row by
+     *         row, or `null` if an error occurs. If the burst stops part way
+     *         through, the exception's message says how many frames had been
+     *         captured.
+     */
+    public byte[][] captureBurstToMemory(int count) {
+        return NativeBindings.captureBurstToMemory(count);
+    }
+
+    /**
+     * Saves every captured frame as a JPEG file of its own, named by a
+     * printf-style pattern, e.g. `run3_000001.jpg` onwards for
+     * `run3_%06d.jpg`, to collect training data. This blocks until the
+     * recording ends, so call `stopRecording` from another thread to end it
+     * early. Like `captureVideo` this leaves the camera running.
+     *
+     * @param dir             Directory to save the frames in, created if
+     *                        needed.
+     * @param pattern         File name with exactly one `%d` placeholder,
+     *                        optionally with a width such as `%06d`.
+     * @param durationSeconds How long to record for, or 0 for no time limit.
+     * @param maxFrames       How many files to write at most, or 0 for no
+     *                        limit. At least one limit is needed.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best).
+     * @return The number of files written, or -1 if an error occurs, e.g.
+     *         when the disk fills up. The error message says how many files
+     *         had been written.
+     */
+    public int captureFrameSequence(String dir, String pattern, int durationSeconds, int maxFrames, int quality) {
+        return NativeBindings.captureFrameSequence(dir, pattern, durationSeconds, maxFrames, quality);
+    }
+
+    /**
+     * Saves every frame captured for `durationSeconds` as a JPEG file of
+     * its own, named `frame_0001.jpg`, `frame_0002.jpg` and so on, for
+     * post-processing. This is `captureFrameSequence` with the
+     * `frame_%04d.jpg` pattern and no frame limit.
+     *
+     * @param dir             Directory to save the frames in, created if
+     *                        needed.
+     * @param durationSeconds How long to record for.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best).
+     * @return The number of files written, or -1 if an error occurs.
+     */
+    public int captureFrames(String dir, int durationSeconds, int quality) {
+        return captureFrameSequence(dir, "frame_%04d.jpg", durationSeconds, 0, quality);
+    }
+
+    /**
+     * Takes a photo every `intervalMs` milliseconds and saves them as
+     * `frame_0001.jpg`, `frame_0002.jpg` and so on, e.g. to document a
+     * patrol. This blocks until the timelapse ends, so call `stopRecording`
+     * from another thread to end it early. Photos already saved are kept if
+     * it fails part way through.
+     *
+     * @param dir        Directory to save the photos in, created if needed.
+     * @param intervalMs Time between photos in milliseconds.
+     * @param frames     Number of photos to take.
+     * @param quality    JPEG quality from 1 (smallest) to 100 (best).
+     * @param powerDown  Whether to close the camera between photos, which
+     *                   saves power over long intervals.
+     * @return The number of photos taken, or -1 if an error occurs.
+     */
+    public int captureTimelapse(String dir, int intervalMs, int frames, int quality, boolean powerDown) {
+        return NativeBindings.captureTimelapse(dir, intervalMs, frames, quality, powerDown);
+    }
+
+    /**
+     * Records video from the SwiftBot's camera to a Motion JPEG AVI file,
+     * which plays back at the frame rate set by `setCameraFrameRate`. This
+     * blocks until the recording ends, so call `stopRecording` from another
+     * thread to end it early.
+     *
+     * @param path            Path of the file to write.
+     * @param durationSeconds Length of the recording in seconds.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best).
+     *                        Values around 60-75 keep long recordings small.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int captureVideo(String path, int durationSeconds, int quality) {
+        return captureVideo(path, durationSeconds, quality, CAMERA_CODEC_MJPEG, 0);
+    }
+
+    /**
+     * Records video from the SwiftBot's camera with the given codec.
+     * `CAMERA_CODEC_H264` records a much smaller MP4 file using the Pi's
+     * hardware encoder, and falls back to Motion JPEG AVI if there is none
+     * (see `isCameraH264Available`). In the JPEG and grayscale color modes
+     * the camera must be stopped before recording H.264.
+     * `CAMERA_CODEC_RAW_MJPEG` writes the JPEG frames back to back with no
+     * container, as older versions of this library did. It also writes an
+     * index to `path + ".csv"`, with the header
+     * `frame,offset,length,timestamp_us` and a line per frame giving its
+     * byte offset and length in the recording and its capture time in
+     * microseconds since the Unix epoch, so a frame can be found without
+     * scanning the file. The index is kept up to date as frames are written.
+     * `CAMERA_CODEC_Y4M` writes the frames uncompressed, exactly as the
+     * camera captured them, to a YUV4MPEG2 (`.y4m`) stream with 4:2:2
+     * chroma, for tools such as ffmpeg. No JPEG encoding is done, so the
+     * overlay, cropping and mirroring don't apply, and the files are large:
+     * about 18 MB per second at 640x480 and 30 fps. In the JPEG color mode
+     * the camera must be stopped before recording.
+     *
+     * @param path            Path of the file to write.
+     * @param durationSeconds Length of the recording in seconds.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best),
+     *                        used for Motion JPEG recordings.
+     * @param codec           One of the `CAMERA_CODEC_*` constants.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int captureVideo(String path, int durationSeconds, int quality, int codec) {
+        return captureVideo(path, durationSeconds, quality, codec, 0);
+    }
+
+    /**
+     * Records video from the SwiftBot's camera with the given codec at a
+     * lower frame rate than the camera runs at, e.g. 5 fps from a 30 fps
+     * stream to save space when logging. The camera keeps capturing at its
+     * own rate, so `captureImage` still gets fresh frames, and only some
+     * of them are written. The file plays back at `outputFps`, so playback
+     * runs at the right speed. A rate above the camera's own is lowered to
+     * it.
+     *
+     * @param path            Path of the file to write.
+     * @param durationSeconds Length of the recording in seconds.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best),
+     *                        used for Motion JPEG recordings.
+     * @param codec           One of the `CAMERA_CODEC_*` constants.
+     * @param outputFps       Frames per second to record, or 0 to record
+     *                        every frame.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int captureVideo(String path, int durationSeconds, int quality, int codec, int outputFps) {
+        return NativeBindings.captureVideo(path, durationSeconds, quality, codec, outputFps);
+    }
+
+    /**
+     * Records exactly `frameCount` frames from the SwiftBot's camera, e.g.
+     * for tests and scripted captures that need a known number of frames.
+     * Works like `captureVideo` with the same codecs, but has no time
+     * limit. Calling `cancelCapture` from another thread cancels the
+     * recording within a frame, and the file is finished with the frames
+     * written so far.
+     *
+     * @param path       Path of the file to write.
+     * @param frameCount Number of frames to write.
+     * @param quality    JPEG quality from 1 (smallest) to 100 (best),
+     *                   used for Motion JPEG recordings.
+     * @param codec      One of the `CAMERA_CODEC_*` constants.
+     * @param outputFps  Frames per second to record, or 0 to record
+     *                   every frame.
+     * @return The number of frames written, fewer than `frameCount` if the
+     *         recording was cancelled, or -1 if an error occurs.
+     */
+    public int captureVideoFrames(String path, int frameCount, int quality, int codec, int outputFps) {
+        return NativeBindings.captureVideoFrames(path, frameCount, quality, codec, outputFps);
+    }
+
+    /**
+     * Records a Motion JPEG AVI clip into memory and returns it, e.g. to
+     * upload it without writing to the SD card. This blocks until the
+     * recording ends, so call `stopRecording` from another thread to end it
+     * early. If the next frame would take the clip over `maxBytes`, the
+     * recording ends there and the clip recorded so far is returned.
+     *
+     * @param durationSeconds Length of the recording in seconds.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best).
+     * @param maxBytes        Largest size the clip may grow to, e.g.
+     *                        64000000 for 64 MB.
+     * @param outputFps       Frames per second to record, or 0 to record
+     *                        every frame.
+     * @param truncated       If not `null`, its first element is set to
+     *                        `true` if the size limit ended the recording.
+     * @return The AVI file, or `null` if an error occurs.
+     */
+    public byte[] captureVideoToMemory(int durationSeconds, int quality, int maxBytes, int outputFps,
+            boolean[] truncated) {
+        return NativeBindings.captureVideoToMemory(durationSeconds, quality, maxBytes, outputFps, truncated);
+    }
+
+    /**
+     * Records a Motion JPEG AVI clip of every frame into memory and returns
+     * it, see `captureVideoToMemory(int, int, int, int, boolean[])`.
+     *
+     * @param durationSeconds Length of the recording in seconds.
+     * @param quality         JPEG quality from 1 (smallest) to 100 (best).
+     * @param maxBytes        Largest size the clip may grow to.
+     * @return The AVI file, or `null` if an error occurs.
+     */
+    public byte[] captureVideoToMemory(int durationSeconds, int quality, int maxBytes) {
+        return captureVideoToMemory(durationSeconds, quality, maxBytes, 0, null);
+    }
+
+    /**
+     * Records raw H.264 video from the SwiftBot's camera using the Pi's
+     * hardware encoder. The file holds the bare H.264 stream rather than an
+     * MP4, so name it e.g. `clip.h264`. Unlike `captureVideo` there is no
+     * fallback, so this fails if the encoder is missing (see
+     * `isCameraH264Available`). In the JPEG and grayscale color modes the
+     * camera must be stopped first. This blocks until the recording ends, so
+     * call `stopRecording` from another thread to end it early.
+     *
+     * @param path            Path of the file to write.
+     * @param durationSeconds Length of the recording in seconds.
+     * @param bitrate         Bits per second to encode at, e.g. 5000000.
+     * @return The number of frames recorded, or -1 if an error occurs.
+     */
+    public int captureVideoH264(String path, int durationSeconds, int bitrate) {
+        return NativeBindings.captureVideoH264(path, durationSeconds, bitrate);
+    }
+
+    /**
+     * Records a short clip from the SwiftBot's camera into an animated GIF
+     * that loops forever, e.g. to share over a chat where video files don't
+     * play inline. Each frame is shrunk by `scale`, as a full size GIF is
+     * very large, and reduced to 256 colours. The clip plays back in real
+     * time even if the camera couldn't keep up with `fps`. In
+     * `CAMERA_COLOR_MODE_JPEG` the camera must be stopped first. This blocks
+     * until the recording ends, so call `stopRecording` from another thread
+     * to end it early.
+     *
+     * @param path       Path of the GIF file to write.
+     * @param durationMs Length of the clip in milliseconds, at most 10000.
+     * @param fps        Frames per second to take, from 1 to 50.
+     * @param scale      How many times smaller each side of the frames
+     *                   becomes, e.g. 2 for 320x240 from 640x480, or 1 to
+     *                   keep their size.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int captureGif(String path, int durationMs, int fps, int scale) {
+        return NativeBindings.captureGif(path, durationMs, fps, scale);
+    }
+
+    /**
+     * Checks whether the SwiftBot has a hardware H.264 encoder for
+     * `captureVideo` to use.
+     *
+     * @return `true` if H.264 recordings are available.
+     */
+    public boolean isCameraH264Available() {
+        return NativeBindings.isCameraH264Available();
+    }
+
+    /**
+     * Records video from the SwiftBot's camera to a Motion JPEG AVI file, at
+     * the quality set by `setCameraJpegQuality`.
+     *
+     * @param path            Path of the file to write.
+     * @param durationSeconds Length of the recording in seconds.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int captureVideo(String path, int durationSeconds) {
+        return captureVideo(path, durationSeconds, NativeBindings.getCameraJpegQuality(),
+                CAMERA_CODEC_MJPEG, 0);
+    }
+
+    /**
+     * Stops a recording started by `captureVideo`, a timelapse started by
+     * `captureTimelapse` or a frame sequence started by
+     * `captureFrameSequence`. The file is finished and the method returns
+     * shortly afterwards.
+     */
+    public void stopRecording() {
+        NativeBindings.stopRecording();
+    }
+
+    /**
+     * Cancels a recording in progress in `captureVideo` or
+     * `captureVideoFrames` from another thread. The recording stops within
+     * one frame, its file is finished with the frames written so far, and it
+     * returns their number. This does the same as `stopRecording`.
+     */
+    public void cancelCapture() {
+        NativeBindings.cancelCapture();
+    }
+
+    /**
+     * Returns how the last recording made by `captureVideo`,
+     * `captureVideoToMemory` or `captureVideoH264` kept up with the camera,
+     * including one that failed part way, e.g. to check that a loaded robot
+     * managed the full frame rate.
+     *
+     * @return The timing, indexed by the `TIMING_` constants, or `null`
+     *         before the first recording.
+     */
+    public long[] getCameraVideoTiming() {
+        return NativeBindings.getCameraVideoTiming();
+    }
+
+    /**
+     * Starts recording video from the SwiftBot's camera to a Motion JPEG AVI
+     * file in the background and returns straight away, so the program can
+     * keep driving the robot. `captureImage` and the other camera methods
+     * keep working during the recording. Call `stopBackgroundRecording` to
+     * finish the file.
+     *
+     * @param path    Path of the AVI file to write.
+     * @param quality JPEG quality from 1 (smallest) to 100 (best).
+     */
+    public void startBackgroundRecording(String path, int quality) {
+        startBackgroundRecording(path, quality, 0, 0);
+    }
+
+    /**
+     * Starts a background recording like `startBackgroundRecording(path,
+     * quality)`, splitting it into numbered files so a long recording isn't
+     * lost to one damaged file. For `capture.avi` the files are
+     * `capture_0001.avi`, `capture_0002.avi` and so on, each playable on its
+     * own. A file is finished before the next frame would take it past a
+     * limit. `getBackgroundRecordingSegments` lists the files.
+     *
+     * @param path         Path the numbered file names are based on.
+     * @param quality      JPEG quality from 1 (smallest) to 100 (best).
+     * @param maxMegabytes Size in megabytes (1,000,000 bytes) of each file,
+     *                     or 0 for no limit.
+     * @param maxSeconds   Seconds of recording in each file, or 0 for no
+     *                     limit.
+     */
+    public void startBackgroundRecording(String path, int quality, int maxMegabytes, int maxSeconds) {
+        startBackgroundRecording(path, quality, maxMegabytes, maxSeconds, 1);
+    }
+
+    /**
+     * Starts a background recording like `startBackgroundRecording(path,
+     * quality, maxMegabytes, maxSeconds)`, recording smaller frames than the
+     * camera captures, e.g. 320x240 while the camera runs at 640x480 where
+     * its auto-exposure works best. `captureImage` and the other camera
+     * methods keep getting full size images. Frames can't be shrunk in
+     * `CAMERA_COLOR_MODE_JPEG`.
+     *
+     * @param path         Path of the AVI file, or the numbered file names
+     *                     are based on.
+     * @param quality      JPEG quality from 1 (smallest) to 100 (best).
+     * @param maxMegabytes Size in megabytes (1,000,000 bytes) of each file,
+     *                     or 0 for no limit.
+     * @param maxSeconds   Seconds of recording in each file, or 0 for no
+     *                     limit.
+     * @param scale        How many times smaller each side of the recorded
+     *                     frames is: 1, 2 or 4.
+     */
+    public void startBackgroundRecording(String path, int quality, int maxMegabytes, int maxSeconds, int scale) {
+        NativeBindings.startBackgroundRecording(path, quality, maxMegabytes, maxSeconds, scale);
+    }
+
+    /**
+     * Stops the recording started by `startBackgroundRecording` and waits for
+     * the file to be finished.
+     *
+     * @return An array holding the number of frames written, the length of
+     *         the recording in milliseconds and the number of files written,
+     *         followed from index 3 by the timing as in
+     *         `getBackgroundRecordingTiming`, or `null` if an error occurs.
+     */
+    public long[] stopBackgroundRecording() {
+        return NativeBindings.stopBackgroundRecording();
+    }
+
+    /**
+     * Checks whether a recording started by `startBackgroundRecording` is
+     * running.
+     *
+     * @return `true` until `stopBackgroundRecording` is called.
+     */
+    public boolean isBackgroundRecording() {
+        return NativeBindings.isBackgroundRecording();
+    }
+
+    /**
+     * Returns how the recording started by `startBackgroundRecording` is
+     * keeping up with the camera so far, e.g. to warn the operator while it
+     * runs that the robot is too busy to record at the full frame rate.
+     *
+     * @return The timing, indexed by the `TIMING_` constants, or `null` if no
+     *         background recording is running.
+     */
+    public long[] getBackgroundRecordingTiming() {
+        return NativeBindings.getBackgroundRecordingTiming();
+    }
+
+    /**
+     * Lists the files written by the background recording in progress, or
+     * by the last one once it has stopped.
+     *
+     * @return The file paths in recording order, the last still being
+     *         written while the recording runs. Empty before the first
+     *         recording.
+     */
+    public String[] getBackgroundRecordingSegments() {
+        return NativeBindings.getBackgroundRecordingSegments();
+    }
+
+    /**
+     * Pauses the recording started by `startBackgroundRecording`, e.g. while
+     * the robot is standing still. The file stays open and playback carries
+     * straight on when the recording is resumed. Does nothing if it is
+     * already paused.
+     */
+    public void pauseBackgroundRecording() {
+        NativeBindings.pauseBackgroundRecording();
+    }
+
+    /**
+     * Resumes a recording paused by `pauseBackgroundRecording`, appending to
+     * the same file.
+     */
+    public void resumeBackgroundRecording() {
+        NativeBindings.resumeBackgroundRecording();
+    }
+
+    /**
+     * Checks whether the recording started by `startBackgroundRecording` is
+     * paused.
+     *
+     * @return `true` if it is paused.
+     */
+    public boolean isBackgroundRecordingPaused() {
+        return NativeBindings.isBackgroundRecordingPaused();
+    }
+
+    /**
+     * Calls `listener` as background recordings get on, with the recording
+     * time so far, not counting pauses, and the frames and bytes written
+     * across every file, replacing any listener already set. The listener
+     * runs on the recording thread and holds it up, so it should return
+     * quickly; an exception it throws is printed and the recording carries
+     * on.
+     *
+     * @param listener    Called with the progress, or null to stop calling
+     *                    the listener already set.
+     * @param everyFrames How many frames to write between calls, or 0 to
+     *                    call it once a second of recording time instead.
+     * @throws IllegalArgumentException If `everyFrames` is negative.
+     */
+    public void setRecordingProgressListener(RecordingProgressListener listener, int everyFrames) {
+        NativeBindings.registerRecordingProgressListener(listener, everyFrames);
+    }
+
+    /**
+     * Starts keeping the last few seconds of camera frames in memory and
+     * returns straight away, so the moments before an event, such as a
+     * button press or an obstacle, can be saved with `dumpCameraPrebuffer`
+     * once it has happened. The oldest frames are dropped as soon as either
+     * limit is passed. Call `stopCameraPrebuffer` to free the frames.
+     *
+     * @param seconds      Seconds of frames to keep.
+     * @param maxMegabytes Size in megabytes (1,000,000 bytes) the frames are
+     *                     kept under.
+     * @param quality      JPEG quality from 1 (smallest) to 100 (best).
+     */
+    public void startCameraPrebuffer(int seconds, int maxMegabytes, int quality) {
+        NativeBindings.startCameraPrebuffer(seconds, maxMegabytes, quality);
+    }
+
+    /**
+     * Saves the frames kept by `startCameraPrebuffer` to a Motion JPEG AVI
+     * file, followed by the frames captured in the next `afterSeconds`, and
+     * waits for the file to be finished. The buffer keeps running, so it can
+     * be saved again for a later event. Calls made from other threads while
+     * a file is being saved wait for it to finish.
+     *
+     * @param path         Path of the AVI file to write.
+     * @param afterSeconds Seconds to keep recording after the buffered
+     *                     frames, or 0 for none.
+     * @return The number of frames written, or -1 if an error occurs.
+     */
+    public int dumpCameraPrebuffer(String path, int afterSeconds) {
+        return NativeBindings.dumpCameraPrebuffer(path, afterSeconds);
+    }
+
+    /**
+     * Stops the pre-record buffer started by `startCameraPrebuffer` and
+     * frees its frames.
+     */
+    public void stopCameraPrebuffer() {
+        NativeBindings.stopCameraPrebuffer();
+    }
+
+    /**
+     * Checks whether a pre-record buffer started by `startCameraPrebuffer` is
+     * running.
+     *
+     * @return `true` until `stopCameraPrebuffer` is called.
+     */
+    public boolean isCameraPrebuffering() {
+        return NativeBindings.isCameraPrebuffering();
+    }
+
+    /**
+     * Starts serving the camera as a live Motion JPEG stream, viewable in a
+     * browser at `http://<robot address>:<port>/`. Frames are only captured
+     * while someone is watching, and `captureImage` and the other camera
+     * methods keep working alongside the stream.
+     *
+     * @param port TCP port to listen on, e.g. 8080.
+     */
+    public void startMjpegServer(int port) {
+        NativeBindings.startMjpegServer(port);
+    }
+
+    /**
+     * Stops the stream started by `startMjpegServer`.
+     */
+    public void stopMjpegServer() {
+        NativeBindings.stopMjpegServer();
+    }
+
+    /**
+     * Sets the quality that images are JPEG encoded at by this library,
+     * used by `captureVideo` and `captureSnapshot` when no quality is
+     * given. Images the camera
+     * delivers as JPEG itself are returned unchanged.
+     *
+     * @param quality JPEG quality from 1 (smallest) to 100 (best). The
+     *                default is 90.
+     */
+    public void setCameraJpegQuality(int quality) {
+        NativeBindings.setCameraJpegQuality(quality);
+    }
+
+    /**
+     * Returns the quality that images are JPEG encoded at by this library.
+     *
+     * @return The JPEG quality, from 1 to 100.
+     */
+    public int getCameraJpegQuality() {
+        return NativeBindings.getCameraJpegQuality();
+    }
+
+    /**
+     * Stops the camera and releases the device. It is reopened on the next
+     * call to `captureImage`.
+     */
+    public void stopCamera() {
+        NativeBindings.stopCamera();
+    }
+
+    /**
+     * Sets how the camera is reopened if it disappears while capturing,
+     * e.g. because its USB cable was pulled. Captures wait for it to come
+     * back before failing. The default is 10 attempts one second apart.
+     *
+     * @param attempts   Number of times to try reopening the camera, or 0 to
+     *                   fail straight away.
+     * @param intervalMs Wait before each attempt, in milliseconds.
+     */
+    public void setCameraReconnect(int attempts, int intervalMs) {
+        NativeBindings.setCameraReconnect(attempts, intervalMs);
+    }
+
+    /**
+     * Checks whether a capture is waiting for a disconnected camera to be
+     * plugged back in. Can be called while another thread is blocked in
+     * `captureImage` or `captureVideo`.
+     *
+     * @return `true` while the camera is reconnecting.
+     */
+    public boolean isCameraReconnecting() {
+        return NativeBindings.isCameraReconnecting();
+    }
+
+    /**
+     * Loads the camera configuration from a settings file, so each robot
+     * can keep its camera setup in a file instead of calling setters on
+     * every boot. Settings the file doesn't mention are reset to their
+     * defaults, so call other camera setters afterwards to override it. A
+     * file can also be loaded at startup by naming it in the
+     * `SWIFTBOT_CAMERA_CONFIG` environment variable.
+     *
+     * @param path Path of the settings file.
+     */
+    public void loadCameraConfig(String path) {
+        NativeBindings.loadCameraConfig(path);
+    }
+
+    /**
+     * Sets the resolution used for camera captures. The camera is reopened
+     * with the new resolution on the next capture.
+     *
+     * @param width  Frame width in pixels.
+     * @param height Frame height in pixels.
+     */
+    public void setCameraResolution(int width, int height) {
+        NativeBindings.setCameraResolution(width, height);
+    }
+
+    /**
+     * Changes the camera resolution and frame rate together. A running
+     * camera is restarted straight away instead of on the next capture, and
+     * the previous settings are restored if the camera refuses the new ones.
+     *
+     * @param width  Frame width in pixels.
+     * @param height Frame height in pixels.
+     * @param fps    Target frames per second.
+     */
+    public void reconfigureCamera(int width, int height, int fps) {
+        NativeBindings.reconfigureCamera(width, height, fps);
+    }
+
+    /**
+     * Selects the camera device to capture from by path.
+     *
+     * @param path Path of the V4L2 device node, e.g. "/dev/video2".
+     */
+    public void setCameraDevice(String path) {
+        NativeBindings.setCameraDevice(path);
+    }
+
+    /**
+     * Selects the camera device to capture from by index.
+     *
+     * @param index Index of the V4L2 device node, e.g. 2 for "/dev/video2".
+     */
+    public void setCameraDeviceIndex(int index) {
+        NativeBindings.setCameraDeviceIndex(index);
+    }
+
+    /**
+     * Sets the target camera frame rate. If the camera driver does not
+     * support the rate, captures are paced in software instead.
+     *
+     * @param fps Target frames per second.
+     */
+    public void setCameraFrameRate(int fps) {
+        NativeBindings.setCameraFrameRate(fps);
+    }
+
+    /**
+     * Sets the number of buffers the camera driver captures into. Fewer
+     * buffers save memory at high resolutions; more buffers make dropped
+     * frames less likely at high frame rates.
+     *
+     * @param buffers Number of capture buffers, at least 2.
+     */
+    public void setCameraBufferCount(int buffers) {
+        NativeBindings.setCameraBufferCount(buffers);
+    }
+
+    /**
+     * Sets how many frames are thrown away each time the camera is opened.
+     * The first frames are often dark or badly coloured while the sensor's
+     * auto exposure and white balance settle, so skipping a few gives
+     * calibration steps a stable first frame. Takes effect the next time
+     * the camera is opened.
+     *
+     * @param frames Number of frames to discard, 0 to keep every frame.
+     */
+    public void setCameraWarmupFrames(int frames) {
+        NativeBindings.setCameraWarmupFrames(frames);
+    }
+
+    /**
+     * Gets how many frames were thrown away when the camera was last opened,
+     * e.g. for logging.
+     *
+     * @return The number of discarded warm-up frames.
+     */
+    public int getCameraWarmupFramesDiscarded() {
+        return NativeBindings.getCameraWarmupFramesDiscarded();
+    }
+
+    /**
+     * Sets the layout of the image data returned by `captureImage`.
+     * Grayscale frames hold one luma byte per pixel, row by row, which is
+     * much cheaper to process than decoding JPEG. RGB and BGR frames hold
+     * three bytes per pixel; BGR can be handed to OpenCV directly.
+     *
+     * @param mode One of the `CAMERA_COLOR_MODE_*` constants.
+     */
+    public void setCameraColorMode(int mode) {
+        NativeBindings.setCameraColorMode(mode);
+    }
+
+    /**
+     * Gets the layout of the image data returned by `captureImage`.
+     * Grayscale frames have one channel, RGB and BGR frames three.
+     *
+     * @return One of the `CAMERA_COLOR_MODE_*` constants.
+     */
+    public int getCameraColorMode() {
+        return NativeBindings.getCameraColorMode();
+    }
+
+    /**
+     * Mirrors captured frames, e.g. for a camera mounted upside down. The
+     * camera's own flip controls are used where available; otherwise frames
+     * are flipped in software, which only applies to the raw color modes.
+     *
+     * @param horizontal `true` to mirror each frame left to right.
+     * @param vertical   `true` to mirror each frame top to bottom.
+     */
+    public void setCameraFlip(boolean horizontal, boolean vertical) {
+        NativeBindings.setCameraFlip(horizontal, vertical);
+    }
+
+    /**
+     * Crops captured frames to a region of the image. The camera crops at
+     * the sensor where supported, scaling the region to the configured
+     * resolution; otherwise the region is cut out in software, which only
+     * applies to the raw color modes and makes frames the size of the region.
+     * The region must lie inside the sensor area, or inside the frame when
+     * cropping in software, e.g. `setCameraCrop(0, 320, 640, 160)` keeps the
+     * bottom third of a 640x480 frame for line following.
+     *
+     * @param x      Left edge of the region in pixels.
+     * @param y      Top edge of the region in pixels.
+     * @param width  Width of the region in pixels.
+     * @param height Height of the region in pixels.
+     * @return `true` if the camera crops in hardware, `false` if frames are
+     *         cropped in software.
+     */
+    public boolean setCameraCrop(int x, int y, int width, int height) {
+        return NativeBindings.setCameraCrop(x, y, width, height);
+    }
+
+    /**
+     * Restores full-frame capture after `setCameraCrop`.
+     */
+    public void resetCameraCrop() {
+        NativeBindings.resetCameraCrop();
+    }
+
+    /**
+     * Scales captured frames down, e.g. to process 320x240 frames from a
+     * camera running at 640x480 without changing its format. Each output
+     * pixel is copied from the nearest captured one, after any crop. Frames
+     * are never enlarged, and only the raw color modes are scaled.
+     * `getCameraFrameWidth` and `getCameraFrameHeight` report the scaled
+     * size.
+     *
+     * @param width  Width to scale frames to in pixels.
+     * @param height Height to scale frames to in pixels.
+     */
+    public void setCameraOutputSize(int width, int height) {
+        NativeBindings.setCameraOutputSize(width, height);
+    }
+
+    /**
+     * Stops scaling frames after `setCameraOutputSize`, so they come out at
+     * their captured size again.
+     */
+    public void resetCameraOutputSize() {
+        NativeBindings.setCameraOutputSize(0, 0);
+    }
+
+    /**
+     * Burns the current date and time into every captured image, e.g. so
+     * recordings can be reviewed after an incident. It appears the same way
+     * in recordings, stills, the MJPEG server and captured images, but not
+     * in `CAMERA_COLOR_MODE_JPEG`, where the camera's images are passed on
+     * as they are.
+     *
+     * @param corner       One of the `CAMERA_OVERLAY_*` constants.
+     * @param frameCounter Whether to show the frame number after the time.
+     */
+    public void setCameraOverlay(int corner, boolean frameCounter) {
+        NativeBindings.setCameraOverlay(true, corner, frameCounter);
+    }
+
+    /**
+     * Stops drawing the timestamp set by `setCameraOverlay`.
+     */
+    public void clearCameraOverlay() {
+        NativeBindings.setCameraOverlay(false, CAMERA_OVERLAY_BOTTOM_RIGHT, false);
+    }
+
+    /**
+     * Turns motion detection on or off. While it is on, every captured frame
+     * is compared with the one before it, keeping only the brightness of the
+     * previous frame. Turning it off also removes any motion listener.
+     *
+     * @param enabled Whether to compare frames.
+     */
+    public void setCameraMotionDetection(boolean enabled) {
+        NativeBindings.setCameraMotionDetection(enabled);
+    }
+
+    /**
+     * Returns how much the last captured frame differed from the one before
+     * it, e.g. for a security camera that saves stills when the scene
+     * changes.
+     *
+     * @return The mean brightness difference from 0.0 (no change) to 1.0
+     *         (black to white everywhere), or -1.0 while motion detection is
+     *         off, before two frames have been compared, and in
+     *         `CAMERA_COLOR_MODE_JPEG`.
+     */
+    public double getCameraMotionScore() {
+        return NativeBindings.getCameraMotionScore();
+    }
+
+    /**
+     * Turns motion detection on and calls `listener` for every captured frame
+     * whose motion score is at least `threshold`, replacing any listener
+     * already set. The listener runs on a thread of its own and can use the
+     * camera; frames that score while it is still busy are skipped. Motion
+     * is only seen while frames are being captured.
+     *
+     * @param listener  Called with the score and ID of each frame.
+     * @param threshold Lowest score that counts as motion, from 0.0 to 1.0.
+     */
+    public void setMotionListener(MotionListener listener, double threshold) {
+        NativeBindings.registerMotionListener(listener, threshold);
+    }
+
+    /**
+     * Removes the listener set by `setMotionListener`, leaving motion
+     * detection on.
+     */
+    public void clearMotionListener() {
+        NativeBindings.unregisterMotionListener();
+    }
+
+    /**
+     * Returns the brightness histogram of the last captured frame, e.g. for
+     * experimenting with exposure. It is kept up to date as images are
+     * captured, so this doesn't process the frame again.
+     *
+     * @return 256 pixel counts, from black at index 0 to white at index 255,
+     *         or null before the first image and in
+     *         `CAMERA_COLOR_MODE_JPEG`.
+     */
+    public int[] getCameraHistogram() {
+        return NativeBindings.getCameraHistogram();
+    }
+
+    /**
+     * Returns the average brightness of the last captured frame.
+     *
+     * @return The mean brightness from 0.0 (black) to 255.0 (white), or -1.0
+     *         before the first image and in `CAMERA_COLOR_MODE_JPEG`.
+     */
+    public double getCameraAverageBrightness() {
+        return NativeBindings.getCameraAverageBrightness();
+    }
+
+    /**
+     * Turns edge detection on or off, e.g. to follow the walls of a maze.
+     * While it is on, every captured image is run through a Sobel filter
+     * into an edge map, see `getCameraEdgeMap`. It is off by default.
+     *
+     * @param enabled Whether to filter images.
+     */
+    public void setCameraEdgeDetection(boolean enabled) {
+        NativeBindings.setCameraEdgeDetection(enabled);
+    }
+
+    /**
+     * Blurs captured images before edges are detected and QR codes are
+     * read, so sensor noise doesn't show up as edges. The images themselves
+     * are left as they are. They aren't blurred by default.
+     *
+     * @param radius How far the blur reaches either side of a pixel, from 1
+     *               to 32 pixels, or 0 for no blur.
+     * @throws IllegalArgumentException If the radius is out of range.
+     */
+    public void setCameraBlur(int radius) {
+        NativeBindings.setCameraBlur(radius);
+    }
+
+    /**
+     * Returns the edge map of the last captured image.
+     *
+     * @return One byte per pixel, row by row, bright along edges and dark in
+     *         flat areas, at the size reported by `getCameraFrameWidth` and
+     *         `getCameraFrameHeight`; or null while edge detection is off,
+     *         before the first image and in `CAMERA_COLOR_MODE_JPEG`.
+     */
+    public byte[] getCameraEdgeMap() {
+        return NativeBindings.getCameraEdgeMap();
+    }
+
+    /**
+     * Starts picking out one colour in every captured image, e.g. to track a
+     * ball, see `getCameraColorMask`. Each pixel is converted to hue,
+     * saturation and value, and matches if all three lie within their
+     * ranges. This only works in `CAMERA_COLOR_MODE_RGB` and
+     * `CAMERA_COLOR_MODE_BGR`.
+     *
+     * @param hueMin The lowest hue, in degrees from 0 to 359.
+     * @param hueMax The highest hue, in degrees from 0 to 359. If it is below
+     *               `hueMin` the range wraps around through 0, e.g. 340 to
+     *               20 for reds.
+     * @param saturationMin The lowest saturation, from 0 (gray) to 255.
+     * @param saturationMax The highest saturation, from 0 to 255.
+     * @param valueMin The lowest value, from 0 (black) to 255.
+     * @param valueMax The highest value, from 0 to 255.
+     * @throws IllegalArgumentException If a value is out of range, or a
+     *                                  saturation or value range has its
+     *                                  minimum above its maximum.
+     */
+    public void setCameraColorFilter(int hueMin, int hueMax, int saturationMin, int saturationMax, int valueMin,
+            int valueMax) {
+        NativeBindings.setCameraColorFilter(hueMin, hueMax, saturationMin, saturationMax, valueMin, valueMax);
+    }
+
+    /**
+     * Stops picking out a colour, see `setCameraColorFilter`.
+     */
+    public void clearCameraColorFilter() {
+        NativeBindings.clearCameraColorFilter();
+    }
+
+    /**
+     * Returns the colour mask of the last captured image.
+     *
+     * @return One byte per pixel, row by row, that is -1 (255 unsigned) where
+     *         the colour matches and 0 elsewhere, at the size reported by
+     *         `getCameraFrameWidth` and `getCameraFrameHeight`; or null
+     *         without a filter, before the first image and in the modes
+     *         without colour.
+     */
+    public byte[] getCameraColorMask() {
+        return NativeBindings.getCameraColorMask();
+    }
+
+    /**
+     * Returns where the colour picked out by `setCameraColorFilter` is in
+     * the last captured image, as the largest patch of matching pixels, so
+     * the robot can steer towards it without copying the mask.
+     *
+     * @return An array holding the column and row of the patch's centre, its
+     *         area in pixels, and the left column, top row, width and height
+     *         of the rectangle around it; or null if no pixel matched, and
+     *         when `getCameraColorMask` would return null.
+     */
+    public int[] getCameraLargestBlob() {
+        return NativeBindings.getCameraLargestBlob();
+    }
+
+    /**
+     * Captures an image and reads every QR code in it, e.g. to find
+     * navigation waypoints. Decoding is slow next to capturing, so it only
+     * happens when this is called.
+     *
+     * In JPEG mode the camera must be stopped, and is opened in a raw mode
+     * just for the image. Otherwise the running stream is used.
+     *
+     * @return The payload of each code found, empty if there are none. Their
+     *         corners are returned by `getCameraQrCorners`.
+     */
+    public String[] decodeCameraQr() {
+        return NativeBindings.decodeCameraQr();
+    }
+
+    /**
+     * Returns the corners of the QR codes read by the last call to
+     * `decodeCameraQr`, e.g. to work out how far away and at what angle a
+     * code is.
+     *
+     * @return Eight values per code, in the order their payloads were
+     *         returned: the x and y coordinates in pixels of its top left,
+     *         top right, bottom right and bottom left corners, as the code
+     *         reads. Empty before the first decode.
+     */
+    public double[] getCameraQrCorners() {
+        return NativeBindings.getCameraQrCorners();
+    }
+
+    /**
+     * Finds the tag36h11 AprilTags in the last image the running camera
+     * captured, e.g. to work out exactly where the robot is from tags placed
+     * at known positions. Detection is slow next to capturing, so it only
+     * happens when this is called. Only tag IDs 0 to 33 are recognised so
+     * far, and `CAMERA_COLOR_MODE_JPEG` isn't supported.
+     *
+     * @return One array per tag found, empty if there are none. Each holds
+     *         the tag's ID, the x and y coordinates in pixels of its centre,
+     *         then those of its top left, top right, bottom right and bottom
+     *         left corners, as the tag reads.
+     */
+    public double[][] detectAprilTags() {
+        return NativeBindings.detectCameraAprilTags();
+    }
+
+    /**
+     * Returns the frame rate negotiated with the camera driver.
+     *
+     * @return The frame rate in frames per second, or -1.0 if the camera
+     *         could not be opened.
+     */
+    public double getCameraFrameRate() {
+        return NativeBindings.getCameraFrameRate();
+    }
+
+    /**
+     * Returns the ID of the most recently captured camera frame. IDs
+     * increase by one with every capture, so comparing them tells whether a
+     * frame has already been processed.
+     *
+     * @return The frame ID, or 0 if no frame has been captured yet.
+     */
+    public long getCameraFrameId() {
+        return NativeBindings.getCameraFrameId();
+    }
+
+    /**
+     * Waits until the camera captures a new frame, e.g. for a background
+     * recording or the MJPEG server, so a processing loop can follow the
+     * stream without polling `getCameraFrameId`. Only frames captured after
+     * the call count.
+     *
+     * @param timeoutMs Longest time to wait, in milliseconds.
+     * @return The ID of the newest frame, or -1 if none was captured in time.
+     */
+    public long waitForCameraFrame(long timeoutMs) {
+        return NativeBindings.waitForCameraFrame(timeoutMs);
+    }
+
+    /**
+     * Returns the last frame captured from the running stream as a read
+     * only direct buffer over native memory, without copying it into the
+     * Java heap.
+     *
+     * Every buffer acquired must be handed to `releaseCameraFrameBuffer`
+     * once it has been read. Until then its memory stays valid, even if the
+     * camera captures newer frames or stops. It must not be read after it
+     * has been released. Acquiring the same frame again returns a buffer
+     * over the same memory, which needs its own release.
+     *
+     * @return The frame as it was captured: JPEG data in JPEG mode, or raw
+     *         pixels laid out as `getCameraFrameBufferInfo` describes.
+     * @throws IllegalStateException If the camera isn't running or hasn't
+     *         captured a frame yet.
+     */
+    public ByteBuffer acquireCameraFrameBuffer() {
+        return NativeBindings.acquireCameraFrameBuffer().asReadOnlyBuffer();
+    }
+
+    /**
+     * Gives back a buffer returned by `acquireCameraFrameBuffer`. Its
+     * memory is freed once no other buffer over the same frame is held.
+     *
+     * @param buffer The buffer to release. It must not be read afterwards.
+     * @throws IllegalArgumentException If the buffer isn't held, e.g.
+     *         because it has already been released.
+     */
+    public void releaseCameraFrameBuffer(ByteBuffer buffer) {
+        NativeBindings.releaseCameraFrameBuffer(buffer);
+    }
+
+    /**
+     * Describes the frame held by a buffer from `acquireCameraFrameBuffer`.
+     *
+     * @param buffer A buffer that has been acquired and not yet released.
+     * @return The frame's ID, its width and height in pixels, and the color
+     *         mode it was captured in, numbered as `getCameraColorMode`
+     *         numbers them.
+     * @throws IllegalArgumentException If the buffer isn't held.
+     */
+    public long[] getCameraFrameBufferInfo(ByteBuffer buffer) {
+        return NativeBindings.getCameraFrameBufferInfo(buffer);
+    }
+
+    /**
+     * Returns the width of the images `captureImage` returns in the raw
+     * color modes, following the resolution and any crop set.
+     *
+     * @return The image width in pixels.
+     */
+    public int getCameraFrameWidth() {
+        return NativeBindings.getCameraFrameWidth();
+    }
+
+    /**
+     * Returns the height of the images `captureImage` returns in the raw
+     * color modes, following the resolution and any crop set.
+     *
+     * @return The image height in pixels.
+     */
+    public int getCameraFrameHeight() {
+        return NativeBindings.getCameraFrameHeight();
+    }
+
+    /**
+     * Returns the number of bytes per pixel in the images `captureImage`
+     * returns, so raw images can be interpreted without hardcoding a
+     * layout.
+     *
+     * @return 1 in grayscale mode, 3 in RGB and BGR mode, or 0 in JPEG mode.
+     */
+    public int getCameraFrameChannels() {
+        return NativeBindings.getCameraFrameChannels();
+    }
+
+    /**
+     * Describes the configured camera for diagnostics, including its card,
+     * driver and bus names, driver version, capability flags and current
+     * capture format. Fields that could not be read are replaced by the
+     * error that occurred.
+     *
+     * @return A description of the camera.
+     */
+    public String getCameraInfo() {
+        return NativeBindings.getCameraInfo();
+    }
+
+    /**
+     * Lists the camera devices attached to the system.
+     *
+     * @return One description per video device node, containing its index,
+     *         path, card and driver names, whether it supports capture, and
+     *         the formats and resolutions it supports. Nodes that could not
+     *         be queried are listed with the error that occurred.
+     */
+    public String[] listCameras() {
+        return NativeBindings.listCameras();
+    }
+
+    /**
+     * Lists the pixel formats and frame sizes supported by the selected
+     * camera device.
+     *
+     * @return One description per pixel format, containing its FourCC,
+     *         description and supported frame sizes.
+     */
+    public String[] getCameraFormats() {
+        return NativeBindings.getCameraFormats();
+    }
+
+    /**
+     * Lists the frame rates the selected camera device supports for a pixel
+     * format and frame size. Many cameras only reach their full frame rate at
+     * lower resolutions.
+     *
+     * @param format FourCC of the pixel format, as listed by
+     *               `getCameraFormats`, e.g. "MJPG" or "YUYV".
+     * @param width  Frame width in pixels.
+     * @param height Frame height in pixels.
+     * @return The supported frame rates in frames per second, fastest first.
+     *         For cameras with a continuous range, the fastest and slowest
+     *         rates of that range.
+     */
+    public double[] getCameraFrameRates(String format, int width, int height) {
+        return NativeBindings.getCameraFrameRates(format, width, height);
+    }
+
+    /**
+     * Enables or disables automatic exposure on the camera.
+     *
+     * @param enabled `true` for automatic exposure, `false` for manual.
+     */
+    public void setCameraExposureAuto(boolean enabled) {
+        NativeBindings.setCameraExposureAuto(enabled);
+    }
+
+    /**
+     * Enables or disables automatic exposure on the camera, like
+     * `setCameraExposureAuto`. Works whether or not the camera is streaming.
+     *
+     * @param enabled `true` for automatic exposure, `false` for manual.
+     * @throws UnsupportedOperationException If the camera has no exposure
+     *                                       mode control.
+     */
+    public void setCameraAutoExposure(boolean enabled) {
+        NativeBindings.setCameraAutoExposure(enabled);
+    }
+
+    /**
+     * Checks whether the camera is using automatic exposure.
+     *
+     * @return `true` if exposure is controlled automatically.
+     */
+    public boolean isCameraExposureAuto() {
+        return NativeBindings.isCameraExposureAuto();
+    }
+
+    /**
+     * Sets the manual exposure time of the camera. Most cameras ignore this
+     * unless automatic exposure is disabled.
+     *
+     * @param value Exposure time in units of 100 microseconds.
+     */
+    public void setCameraExposure(int value) {
+        NativeBindings.setCameraExposure(value);
+    }
+
+    /**
+     * Reads the current exposure time of the camera.
+     *
+     * @return The exposure time in units of 100 microseconds, or -1 if it
+     *         could not be read.
+     */
+    public int getCameraExposure() {
+        return NativeBindings.getCameraExposure();
+    }
+
+    /**
+     * Enables or disables automatic white balance on the camera.
+     *
+     * @param enabled `true` for automatic white balance, `false` for manual.
+     */
+    public void setCameraWhiteBalanceAuto(boolean enabled) {
+        NativeBindings.setCameraWhiteBalanceAuto(enabled);
+    }
+
+    /**
+     * Enables or disables automatic white balance on the camera, like
+     * `setCameraWhiteBalanceAuto`, e.g. to lock it before calibrating colors.
+     *
+     * @param enabled `true` for automatic white balance, `false` for manual.
+     * @throws UnsupportedOperationException If the camera has no automatic
+     *                                       white balance.
+     */
+    public void setCameraAutoWhiteBalance(boolean enabled) {
+        NativeBindings.setCameraAutoWhiteBalance(enabled);
+    }
+
+    /**
+     * Checks whether the camera is using automatic white balance.
+     *
+     * @return `true` if white balance is controlled automatically.
+     */
+    public boolean isCameraWhiteBalanceAuto() {
+        return NativeBindings.isCameraWhiteBalanceAuto();
+    }
+
+    /**
+     * Sets the manual white balance temperature of the camera. Most cameras
+     * ignore this unless automatic white balance is disabled.
+     *
+     * @param kelvin White balance temperature in Kelvin.
+     * @return The temperature accepted by the camera, which may be rounded
+     *         to a coarser step, or -1 if it was rejected.
+     */
+    public int setCameraWhiteBalanceTemperature(int kelvin) {
+        return NativeBindings.setCameraWhiteBalanceTemperature(kelvin);
+    }
+
+    /**
+     * Reads the current white balance temperature of the camera.
+     *
+     * @return The temperature in Kelvin, or -1 if it could not be read.
+     */
+    public int getCameraWhiteBalanceTemperature() {
+        return NativeBindings.getCameraWhiteBalanceTemperature();
+    }
+
+    /**
+     * Sets a V4L2 control on the camera. The value is clamped to the range
+     * the control supports.
+     *
+     * @param controlId The V4L2 control ID (e.g., CAMERA_CONTROL_BRIGHTNESS).
+     * @param value     The value to set.
+     * @return The value that was applied after clamping, or -1 if the
+     *         control could not be set.
+     */
+    public int setCameraControl(int controlId, int value) {
+        return NativeBindings.setCameraControl(controlId, value);
+    }
+
+    /**
+     * Reads the current value of a V4L2 control on the camera.
+     *
+     * @param controlId The V4L2 control ID (e.g., CAMERA_CONTROL_BRIGHTNESS).
+     * @return The current value, or -1 if it could not be read.
+     */
+    public int getCameraControl(int controlId) {
+        return NativeBindings.getCameraControl(controlId);
+    }
+
+    /**
+     * Reads the valid range of a V4L2 control on the camera, e.g. to build
+     * a slider for it. Booleans are reported as a 0 to 1 range and menus as
+     * the range of their item indices.
+     *
+     * @param controlId The V4L2 control ID (e.g., CAMERA_CONTROL_EXPOSURE).
+     * @return The minimum, maximum, step, default and current value, then 1
+     *         if the control is enabled and 1 if it is read-only, indexed by
+     *         the CAMERA_RANGE constants. Returns `null` if the camera
+     *         doesn't implement the control.
+     */
+    public int[] getCameraControlRange(int controlId) {
+        return NativeBindings.getCameraControlRange(controlId);
+    }
+
+    /**
+     * Reads the valid range of a V4L2 control on the camera like
+     * `getCameraControlRange`, but throws for controls the camera doesn't
+     * implement instead of returning `null`.
+     *
+     * @param controlId The V4L2 control ID (e.g., CAMERA_CONTROL_BRIGHTNESS).
+     * @return The minimum, maximum, step, default and current value, then 1
+     *         if the control is enabled and 1 if it is read-only, indexed by
+     *         the CAMERA_RANGE constants.
+     * @throws UnsupportedOperationException If the camera doesn't implement
+     *                                       the control.
+     */
+    public int[] queryCameraControlRange(int controlId) {
+        return NativeBindings.queryCameraControlRange(controlId);
+    }
+
+    /**
+     * Sets the picture brightness, clamped to the range the camera supports.
+     *
+     * @param value The brightness to set.
+     * @return The brightness that was applied.
+     * @throws UnsupportedOperationException If the camera has no brightness
+     *                                       control.
+     */
+    public int setCameraBrightness(int value) {
+        return NativeBindings.setCameraBrightness(value);
+    }
+
+    /**
+     * Reads the current picture brightness.
+     *
+     * @return The current brightness.
+     */
+    public int getCameraBrightness() {
+        return getCameraControl(CAMERA_CONTROL_BRIGHTNESS);
+    }
+
+    /**
+     * Sets the picture contrast, clamped to the range the camera supports.
+     *
+     * @param value The contrast to set.
+     * @return The contrast that was applied.
+     * @throws UnsupportedOperationException If the camera has no contrast
+     *                                       control.
+     */
+    public int setCameraContrast(int value) {
+        return NativeBindings.setCameraContrast(value);
+    }
+
+    /**
+     * Reads the current picture contrast.
+     *
+     * @return The current contrast.
+     */
+    public int getCameraContrast() {
+        return getCameraControl(CAMERA_CONTROL_CONTRAST);
+    }
+
+    /**
+     * Sets the picture saturation, clamped to the range the camera supports.
+     *
+     * @param value The saturation to set.
+     * @return The saturation that was applied.
+     * @throws UnsupportedOperationException If the camera has no saturation
+     *                                       control.
+     */
+    public int setCameraSaturation(int value) {
+        return NativeBindings.setCameraSaturation(value);
+    }
+
+    /**
+     * Reads the current picture saturation.
+     *
+     * @return The current saturation.
+     */
+    public int getCameraSaturation() {
+        return getCameraControl(CAMERA_CONTROL_SATURATION);
+    }
+
+    /**
+     * Sets the sensor gain of the camera, clamped to the range it supports.
+     * Raising the gain brightens frames in low light at the cost of noise.
+     *
+     * @param value The gain to set.
+     * @return The gain that was applied.
+     * @throws UnsupportedOperationException If the camera has no gain
+     *                                       control.
+     */
+    public int setCameraGain(int value) {
+        return NativeBindings.setCameraGain(value);
+    }
+
+    /**
+     * Reads the current sensor gain of the camera.
+     *
+     * @return The current gain.
+     * @throws UnsupportedOperationException If the camera has no gain
+     *                                       control.
+     */
+    public int getCameraGain() {
+        return NativeBindings.getCameraGain();
+    }
+
+    /**
+     * Sets the mains frequency the camera compensates for, to avoid banding
+     * under artificial lighting. Many cameras only offer some settings.
+     *
+     * @param frequency One of the `CAMERA_POWER_LINE_*` constants.
+     * @return `true` if the camera accepted the setting, `false` if it
+     *         doesn't offer it.
+     * @throws UnsupportedOperationException If the camera has no
+     *                                       anti-flicker control.
+     */
+    public boolean setCameraPowerLineFrequency(int frequency) {
+        return NativeBindings.setCameraPowerLineFrequency(frequency);
+    }
+
+    /**
+     * Reads the anti-flicker setting of the camera.
+     *
+     * @return One of the `CAMERA_POWER_LINE_*` constants.
+     * @throws UnsupportedOperationException If the camera has no
+     *                                       anti-flicker control.
+     */
+    public int getCameraPowerLineFrequency() {
+        return NativeBindings.getCameraPowerLineFrequency();
+    }
+
+    /**
+     * Enables or disables continuous autofocus. Disable it to lock focus
+     * once the robot is in position.
+     *
+     * @param enabled `true` for autofocus, `false` to hold the current lens
+     *                position.
+     * @throws UnsupportedOperationException If the camera has no autofocus.
+     */
+    public void setCameraAutofocus(boolean enabled) {
+        NativeBindings.setCameraAutofocus(enabled);
+    }
+
+    /**
+     * Checks whether the camera is focusing automatically.
+     *
+     * @return `true` if autofocus is enabled, `false` otherwise.
+     * @throws UnsupportedOperationException If the camera has no autofocus.
+     */
+    public boolean isCameraAutofocus() {
+        return NativeBindings.isCameraAutofocus();
+    }
+
+    /**
+     * Sets the lens position of the camera, clamped to the range it
+     * supports. Most cameras ignore this unless autofocus is disabled.
+     *
+     * @param value The lens position to set.
+     * @return The lens position that was applied.
+     * @throws UnsupportedOperationException If the camera has no focus
+     *                                       control.
+     */
+    public int setCameraFocus(int value) {
+        return NativeBindings.setCameraFocus(value);
+    }
+
+    /**
+     * Reads the current lens position of the camera.
+     *
+     * @return The current lens position.
+     * @throws UnsupportedOperationException If the camera has no focus
+     *                                       control.
+     */
+    public int getCameraFocus() {
+        return NativeBindings.getCameraFocus();
+    }
 }
\ No newline at end of file
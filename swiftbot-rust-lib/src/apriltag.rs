@@ -0,0 +1,414 @@
+use crate::qr::{Bitmap, Point, Transform};
+
+//Codewords of the tag36h11 family by tag ID, from the AprilTag library's tag36h11.c. The family has
+//587 tags; this table holds the first 34, and tags with higher IDs are not reported until the rest
+//of the table is added. Every code differs from every other one, and from its own rotations, in at
+//least 11 bits, which the tests check.
+const TAG36H11_CODES: [u64; 34] = [
+    0xd7e00984b, 0xdda664ca7, 0xdc4a1c821, 0xe17b470e9, 0xef91d01b1, 0xf429cdd73, 0x05da29225, 0x1106cba43,
+    0x223bed79d, 0x21f51213c, 0x33eb19ca6, 0x3f76eb0f8, 0x469a97414, 0x45dcfe0b0, 0x4a6465f72, 0x51801db96,
+    0x5eb946b4e, 0x68a7cc2ec, 0x6f0ba2652, 0x78765559d, 0x87b83d129, 0x86cc4a5c5, 0x8b64df90f, 0x9c577b611,
+    0xa3810f2f5, 0xaf4d75b83, 0xb59a03fef, 0xbb1096f85, 0xd1b92fc76, 0xd0dd509d2, 0xe2cfda160, 0x2ff497c63,
+    0x47240671b, 0x5047a2e55,
+];
+
+//Cell of each code bit, most significant first, counted from the top left cell of the black border.
+//A set bit is a white cell. The bits run in rings, so turning a tag a quarter turn rotates its code
+//by 9 bits
+const BIT_X: [usize; 36] = [
+    1, 2, 3, 4, 5, 2, 3, 4, 3, 6, 6, 6, 6, 6, 5, 5, 5, 4, 6, 5, 4, 3, 2, 5, 4, 3, 4, 1, 1, 1, 1, 1, 2, 2, 2, 3,
+];
+const BIT_Y: [usize; 36] = [
+    1, 1, 1, 1, 1, 2, 2, 2, 3, 1, 2, 3, 4, 5, 2, 3, 4, 3, 6, 6, 6, 6, 6, 5, 5, 5, 4, 6, 5, 4, 3, 2, 5, 4, 3, 4,
+];
+
+//Cells across the black border, which is one cell wide around the 6 by 6 data cells
+const BORDER_CELLS: usize = 8;
+
+//Most bit errors corrected when matching a code. The codes are 11 bits apart, so this leaves a wide
+//margin against reading something that isn't a tag as one
+const MAX_CORRECTED_BITS: u32 = 2;
+
+//Smallest side of a tag in pixels, below which its cells are too small to sample
+const MIN_SIDE: f32 = 16.0;
+
+/// An AprilTag found in a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagDetection {
+    pub id: u32,                  //The tag's ID in the tag36h11 family
+    pub corners: [(f32, f32); 4], //Outer corners of the border in pixels, clockwise from the top left as the tag reads
+    pub center: (f32, f32),       //Centre of the tag in pixels
+}
+
+/// Finds every tag36h11 AprilTag in a grayscale image.
+///
+/// Tags may be rotated and seen at an angle, as long as the whole of the black border and a white
+/// margin around it are in view. Up to two misread cells are corrected.
+///
+/// # Arguments
+///
+/// * `pixels` - The luma of each pixel, row by row.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+///
+/// # Returns
+///
+/// A `Vec<TagDetection>` with one entry per tag found, empty if there are none.
+pub fn detect(pixels: &[u8], width: u32, height: u32) -> Vec<TagDetection> {
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 || pixels.len() < width * height {
+        return Vec::new();
+    }
+
+    let bitmap = Bitmap::new(pixels, width, height);
+    dark_outlines(&bitmap)
+        .iter()
+        .filter_map(|outline| fit_quad(outline))
+        .filter_map(|quad| decode_quad(&bitmap, quad))
+        .collect()
+}
+
+/// Returns the outline of every dark region that doesn't touch the edge of the image, as the corners
+/// of the pixels at the end of each of its rows and columns.
+///
+/// A tag's black border and the black cells touching it make up one region, whose outline is the
+/// outside of the border.
+fn dark_outlines(bitmap: &Bitmap) -> Vec<Vec<Point>> {
+    let (width, height) = (bitmap.width, bitmap.height);
+    let mut seen = vec![false; width * height];
+    let mut outlines = Vec::new();
+    let mut stack = Vec::new();
+    let mut pixels = Vec::new();
+
+    for start in 0..width * height {
+        if seen[start] || !bitmap.dark[start] {
+            continue;
+        }
+        //Flood fill the region, noting if it reaches the edge
+        pixels.clear();
+        stack.push(start);
+        seen[start] = true;
+        let mut at_edge = false;
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            pixels.push((x, y));
+            at_edge |= x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                if !seen[neighbour] && bitmap.dark[neighbour] {
+                    seen[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        if at_edge || pixels.len() < 4 * MIN_SIDE as usize {
+            continue;
+        }
+
+        //The first and last pixel of every row and column, by their outer corners
+        let (left, top) = pixels.iter().fold((width, height), |(l, t), &(x, y)| (l.min(x), t.min(y)));
+        let (right, bottom) = pixels.iter().fold((0, 0), |(r, b), &(x, y)| (r.max(x), b.max(y)));
+        if ((right - left + 1) as f32) < MIN_SIDE || ((bottom - top + 1) as f32) < MIN_SIDE {
+            continue;
+        }
+        let mut rows = vec![(usize::MAX, 0); bottom - top + 1];
+        let mut columns = vec![(usize::MAX, 0); right - left + 1];
+        for &(x, y) in &pixels {
+            let row = &mut rows[y - top];
+            *row = (row.0.min(x), row.1.max(x));
+            let column = &mut columns[x - left];
+            *column = (column.0.min(y), column.1.max(y));
+        }
+        let mut outline = Vec::with_capacity((rows.len() + columns.len()) * 4);
+        for (y, &(first, last)) in (top..).zip(&rows) {
+            let (y, first, last) = (y as f32, first as f32, last as f32 + 1.0);
+            outline.extend([(first, y), (first, y + 1.0), (last, y), (last, y + 1.0)]);
+        }
+        for (x, &(first, last)) in (left..).zip(&columns) {
+            let (x, first, last) = (x as f32, first as f32, last as f32 + 1.0);
+            outline.extend([(x, first), (x + 1.0, first), (x, last), (x + 1.0, last)]);
+        }
+        outlines.push(outline);
+    }
+    outlines
+}
+
+/// Fits a quadrilateral around an outline, returning its corners clockwise on screen, or `None` if
+/// the outline is too small or not convex enough to be a tag.
+///
+/// The corners are picked from the convex hull of the outline, then each side is refitted to the
+/// outline points along it, so the corners land between pixels rather than on them.
+fn fit_quad(outline: &[Point]) -> Option<[Point; 4]> {
+    let hull = convex_hull(outline);
+    if hull.len() < 4 {
+        return None;
+    }
+
+    //The two points furthest apart lie on a diagonal, and the points furthest from it either side
+    //are the other two corners
+    let mut diagonal = (0, 0, 0.0);
+    for (i, a) in hull.iter().enumerate() {
+        for (j, b) in hull.iter().enumerate().skip(i + 1) {
+            let length = distance(*a, *b);
+            if length > diagonal.2 {
+                diagonal = (i, j, length);
+            }
+        }
+    }
+    let (a, b) = (hull[diagonal.0], hull[diagonal.1]);
+    let side = |p: Point| (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    let (c, d) = hull.iter().fold(((a, 0.0), (a, 0.0)), |(c, d), &p| {
+        let s = side(p);
+        (if s > c.1 { (p, s) } else { c }, if s < d.1 { (p, s) } else { d })
+    });
+    //Clockwise on screen, where y points down
+    let corners = [a, d.0, b, c.0];
+    if !(0..4).all(|i| distance(corners[i], corners[(i + 1) % 4]) >= MIN_SIDE) {
+        return None;
+    }
+
+    //Refit each side to the outline points along its middle
+    let mut lines = [(a, (0.0, 0.0)); 4];
+    for (i, line) in lines.iter_mut().enumerate() {
+        let (start, end) = (corners[i], corners[(i + 1) % 4]);
+        let length = distance(start, end);
+        let direction = ((end.0 - start.0) / length, (end.1 - start.1) / length);
+        let tolerance = (length / BORDER_CELLS as f32 * 0.3).max(1.0);
+        let along: Vec<Point> = outline
+            .iter()
+            .copied()
+            .filter(|&p| {
+                let (dx, dy) = (p.0 - start.0, p.1 - start.1);
+                let t = (dx * direction.0 + dy * direction.1) / length;
+                (0.1..=0.9).contains(&t) && (dx * direction.1 - dy * direction.0).abs() <= tolerance
+            })
+            .collect();
+        *line = fit_line(&along).unwrap_or((start, direction));
+    }
+    let mut refined = [(0.0, 0.0); 4];
+    for (i, corner) in refined.iter_mut().enumerate() {
+        *corner = intersect(lines[(i + 3) % 4], lines[i])
+            .filter(|&p| distance(p, corners[i]) < distance(corners[i], corners[(i + 1) % 4]) / BORDER_CELLS as f32)
+            .unwrap_or(corners[i]);
+    }
+
+    //Every turn must go the same way
+    let convex = (0..4).all(|i| {
+        let (p, q, r) = (refined[i], refined[(i + 1) % 4], refined[(i + 2) % 4]);
+        (q.0 - p.0) * (r.1 - q.1) - (q.1 - p.1) * (r.0 - q.0) > 0.0
+    });
+    convex.then_some(refined)
+}
+
+/// Reads the cells inside a quadrilateral and matches them against the tag codes in each of the
+/// four ways the tag could be turned.
+fn decode_quad(bitmap: &Bitmap, corners: [Point; 4]) -> Option<TagDetection> {
+    let size = BORDER_CELLS as f32;
+    let transform = Transform::from_points(&[
+        ((0.0, 0.0), corners[0]),
+        ((size, 0.0), corners[1]),
+        ((size, size), corners[2]),
+        ((0.0, size), corners[3]),
+    ])?;
+
+    //Whether each cell is dark, sampled at its centre, row by row
+    let mut cells = [false; BORDER_CELLS * BORDER_CELLS];
+    for (index, cell) in cells.iter_mut().enumerate() {
+        let (x, y) = (index % BORDER_CELLS, index / BORDER_CELLS);
+        *cell = bitmap.sample(transform.map(x as f32 + 0.5, y as f32 + 0.5));
+    }
+
+    //The border must be dark but for a cell or two that glare or blur got to
+    let last = BORDER_CELLS - 1;
+    let light_border = (0..BORDER_CELLS * BORDER_CELLS)
+        .filter(|&index| {
+            let (x, y) = (index % BORDER_CELLS, index / BORDER_CELLS);
+            (x == 0 || y == 0 || x == last || y == last) && !cells[index]
+        })
+        .count();
+    if light_border > 2 {
+        return None;
+    }
+
+    let (id, turns, _) = (0..4)
+        .flat_map(|turns| {
+            let code = read_code(&cells, turns);
+            TAG36H11_CODES.iter().enumerate().map(move |(id, &c)| (id, turns, (code ^ c).count_ones()))
+        })
+        .filter(|&(_, _, errors)| errors <= MAX_CORRECTED_BITS)
+        .min_by_key(|&(_, _, errors)| errors)?;
+
+    Some(TagDetection {
+        id: id as u32,
+        corners: std::array::from_fn(|i| corners[(i + turns) % 4]),
+        center: transform.map(size / 2.0, size / 2.0),
+    })
+}
+
+/// Reads the code of a tag whose top left corner is `turns` corners clockwise from the first corner
+/// the cells were sampled from.
+fn read_code(cells: &[bool; BORDER_CELLS * BORDER_CELLS], turns: usize) -> u64 {
+    let last = BORDER_CELLS - 1;
+    BIT_X.iter().zip(&BIT_Y).fold(0, |code, (&x, &y)| {
+        //Turn the tag's cell back into the sampled one, a quarter turn at a time
+        let (x, y) = (0..turns).fold((x, y), |(x, y), _| (last - y, x));
+        code << 1 | !cells[y * BORDER_CELLS + x] as u64
+    })
+}
+
+/// Returns the convex hull of a set of points in order around it, by Andrew's monotone chain.
+fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    //One half of the hull left to right and the other back again, each without its last point
+    let mut hull = half_hull(sorted.iter().copied());
+    hull.extend(half_hull(sorted.iter().rev().copied()));
+    hull
+}
+
+/// Returns the points of one half of a convex hull, given points sorted along it, leaving out the
+/// last one as it starts the other half.
+fn half_hull(points: impl Iterator<Item = Point>) -> Vec<Point> {
+    let cross = |o: Point, a: Point, b: Point| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let mut half: Vec<Point> = Vec::new();
+    for p in points {
+        while half.len() >= 2 && cross(half[half.len() - 2], half[half.len() - 1], p) <= 0.0 {
+            half.pop();
+        }
+        half.push(p);
+    }
+    half.pop();
+    half
+}
+
+/// Fits a line through points by least squares, as a point on it and its direction, or `None` if
+/// there are too few points to go on.
+fn fit_line(points: &[Point]) -> Option<(Point, Point)> {
+    if points.len() < 4 {
+        return None;
+    }
+    let count = points.len() as f32;
+    let mean = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p.0 / count, y + p.1 / count));
+    let (xx, xy, yy) = points.iter().fold((0.0, 0.0, 0.0), |(xx, xy, yy), p| {
+        let (dx, dy) = (p.0 - mean.0, p.1 - mean.1);
+        (xx + dx * dx, xy + dx * dy, yy + dy * dy)
+    });
+    //The direction of greatest spread
+    let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+    Some((mean, (angle.cos(), angle.sin())))
+}
+
+/// Returns the point where two lines cross, or `None` if they are parallel.
+fn intersect((p, d): (Point, Point), (q, e): (Point, Point)) -> Option<Point> {
+    let det = d.0 * e.1 - d.1 * e.0;
+    if det.abs() < 1e-6 {
+        return None;
+    }
+    let t = ((q.0 - p.0) * e.1 - (q.1 - p.1) * e.0) / det;
+    Some((p.0 + t * d.0, p.1 + t * d.1))
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws a tag with its outer corners at the given pixels, clockwise from its top left, on a
+    /// light grey background.
+    fn render(id: usize, corners: [Point; 4], width: usize, height: usize) -> Vec<u8> {
+        let size = BORDER_CELLS as f32;
+        let to_tag = Transform::from_points(&[
+            (corners[0], (0.0, 0.0)),
+            (corners[1], (size, 0.0)),
+            (corners[2], (size, size)),
+            (corners[3], (0.0, size)),
+        ])
+        .unwrap();
+        let code = TAG36H11_CODES[id];
+        let mut white = [[true; BORDER_CELLS]; BORDER_CELLS];
+        for row in white.iter_mut() {
+            row[0] = false;
+            row[BORDER_CELLS - 1] = false;
+        }
+        white[0] = [false; BORDER_CELLS];
+        white[BORDER_CELLS - 1] = [false; BORDER_CELLS];
+        for (bit, (&x, &y)) in BIT_X.iter().zip(&BIT_Y).enumerate() {
+            white[y][x] = code >> (35 - bit) & 1 == 1;
+        }
+
+        let mut pixels = vec![200; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let (u, v) = to_tag.map(x as f32 + 0.5, y as f32 + 0.5);
+                if (0.0..size).contains(&u) && (0.0..size).contains(&v) {
+                    pixels[y * width + x] = if white[v as usize][u as usize] { 240 } else { 20 };
+                }
+            }
+        }
+        pixels
+    }
+
+    fn assert_near(actual: Point, expected: Point) {
+        assert!(distance(actual, expected) < 1.5, "{:?} is not near {:?}", actual, expected);
+    }
+
+    #[test]
+    fn codes_are_at_least_eleven_bits_from_each_other_and_their_rotations() {
+        let rotate = |code: u64| (code << 9 | code >> 27) & 0xf_ffff_ffff;
+        for (i, &code) in TAG36H11_CODES.iter().enumerate() {
+            let turned = rotate(code);
+            let rotations = [turned, rotate(turned), rotate(rotate(turned))];
+            assert!(rotations.iter().all(|&r| (code ^ r).count_ones() >= 11), "Tag {} is too symmetric", i);
+            for &other in &TAG36H11_CODES[..i] {
+                let mut r = other;
+                for _ in 0..4 {
+                    assert!((code ^ r).count_ones() >= 11, "Tag {} is too close to {:#x}", i, other);
+                    r = rotate(r);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_an_upright_tag() {
+        let corners = [(40.0, 40.0), (200.0, 40.0), (200.0, 200.0), (40.0, 200.0)];
+        let tags = detect(&render(7, corners, 240, 240), 240, 240);
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].id, 7);
+        for (&actual, &expected) in tags[0].corners.iter().zip(&corners) {
+            assert_near(actual, expected);
+        }
+        assert_near(tags[0].center, (120.0, 120.0));
+    }
+
+    #[test]
+    fn finds_a_turned_tag_seen_at_an_angle() {
+        //The top left corner of the tag is at the bottom right of the image
+        let corners = [(215.0, 190.0), (30.0, 205.0), (50.0, 30.0), (190.0, 55.0)];
+        let tags = detect(&render(21, corners, 256, 240), 256, 240);
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].id, 21);
+        for (&actual, &expected) in tags[0].corners.iter().zip(&corners) {
+            assert_near(actual, expected);
+        }
+    }
+
+    #[test]
+    fn finds_nothing_in_a_blank_image() {
+        assert!(detect(&[128; 64 * 48], 64, 48).is_empty());
+        assert!(detect(&[], 0, 0).is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+//Bytes from the start of the file to the first frame chunk, the headers are always the same size
+const HEADER_SIZE: u32 = 224;
+
+//Main header flag saying the file ends with an `idx1` index
+const AVIF_HASINDEX: u32 = 0x10;
+
+//Index entry flag marking a frame that can be decoded on its own, which every JPEG frame can
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+/// Writes JPEG frames into a Motion JPEG AVI file with a single video stream.
+///
+/// Space for the headers is reserved when the writer is created, and they are filled in by
+/// [`Writer::finish`] once the frame count and size are known. A file that isn't finished has no
+/// valid headers. RIFF limits the file to 4 GiB.
+pub struct Writer<W: Write + Seek> {
+    inner: W,
+    start: u64, //Position of the RIFF header in `inner`
+    fps: u32,
+    width: u32,
+    height: u32,
+    movi_size: u32,         //Bytes in the `movi` list so far, including its FourCC
+    max_frame: u32,         //Size of the largest frame, which players use to size their buffers
+    index: Vec<(u32, u32)>, //Offset from the `movi` FourCC and size of each frame chunk
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Starts an AVI file at the current position of `inner`, played back at `fps` frames per second.
+    pub fn new(mut inner: W, fps: u32) -> io::Result<Self> {
+        let start = inner.stream_position()?;
+        inner.write_all(&[0; HEADER_SIZE as usize])?;
+        Ok(Writer {
+            inner,
+            start,
+            fps: fps.max(1),
+            width: 0,
+            height: 0,
+            movi_size: 4,
+            max_frame: 0,
+            index: Vec::new(),
+        })
+    }
+
+    /// Appends one JPEG frame. The first frame sets the video size stored in the headers.
+    pub fn write_frame(&mut self, jpeg: &[u8], width: u32, height: u32) -> io::Result<()> {
+        //Chunks are padded to an even length
+        let padded = jpeg.len() as u64 + (jpeg.len() as u64 & 1);
+        let movi_size = self.movi_size as u64 + 8 + padded;
+
+        //The headers, frames and index all have to fit in the 32-bit RIFF size
+        let frames = self.index.len() as u64 + 1;
+        if HEADER_SIZE as u64 + movi_size + 8 + 16 * frames > u32::MAX as u64 {
+            return Err(io::Error::other("Recording is too large for an AVI file"));
+        }
+        let size = jpeg.len() as u32;
+
+        if self.index.is_empty() {
+            self.width = width;
+            self.height = height;
+        }
+
+        self.inner.write_all(b"00dc")?;
+        self.inner.write_all(&size.to_le_bytes())?;
+        self.inner.write_all(jpeg)?;
+        if size & 1 != 0 {
+            self.inner.write_all(&[0])?;
+        }
+
+        self.index.push((self.movi_size, size));
+        self.movi_size = movi_size as u32;
+        self.max_frame = self.max_frame.max(size);
+        Ok(())
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> u32 {
+        self.index.len() as u32
+    }
+
+    /// Returns the number of bytes written so far, the headers and frames without the index that
+    /// [`Writer::finish`] adds.
+    pub fn size(&self) -> u64 {
+        (HEADER_SIZE - 4) as u64 + self.movi_size as u64
+    }
+
+    /// Returns the size the file would have if it were finished after appending a frame of `length`
+    /// bytes, so a recording can move on to a new file before this one gets too large.
+    pub fn size_with_frame(&self, length: usize) -> u64 {
+        let padded = length as u64 + (length as u64 & 1);
+        let frames = self.index.len() as u64 + 1;
+        (HEADER_SIZE - 4) as u64 + self.movi_size as u64 + 8 + padded + 8 + 16 * frames
+    }
+
+    /// Writes the index and headers, leaving `inner` positioned at the end of the file.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut index = Vec::with_capacity(self.index.len() * 16);
+        for &(offset, size) in &self.index {
+            index.extend_from_slice(b"00dc");
+            index.extend_from_slice(&AVIIF_KEYFRAME.to_le_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&size.to_le_bytes());
+        }
+        chunk(&mut self.inner, b"idx1", &index)?;
+
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(self.start))?;
+        let header = self.header();
+        self.inner.write_all(&header)?;
+        self.inner.seek(SeekFrom::Start(end))?;
+        Ok(self.inner)
+    }
+
+    /// Builds the RIFF, `hdrl` and `movi` headers that precede the first frame.
+    fn header(&self) -> Vec<u8> {
+        let frames = self.frame_count();
+        //Everything after the RIFF size field: the headers up to the `movi` list, the list and the index
+        let riff_size = (HEADER_SIZE - 12) + self.movi_size + (8 + 16 * frames);
+
+        //Microseconds per frame, maximum data rate, padding granularity, flags, frame count, initial
+        //frames, stream count, suggested buffer size, width, height and four reserved fields
+        let mut main = Vec::with_capacity(56);
+        for value in [
+            1_000_000 / self.fps,
+            self.max_frame.saturating_mul(self.fps),
+            0,
+            AVIF_HASINDEX,
+            frames,
+            0,
+            1,
+            self.max_frame,
+            self.width,
+            self.height,
+            0,
+            0,
+            0,
+            0,
+        ] {
+            main.extend_from_slice(&value.to_le_bytes());
+        }
+
+        //Type, handler, flags, priority and language, initial frames, scale, rate, start, length,
+        //suggested buffer size, quality (-1 for the default), sample size (0 as frames vary in size)
+        //and the frame rectangle
+        let mut stream = Vec::with_capacity(56);
+        stream.extend_from_slice(b"vids");
+        stream.extend_from_slice(b"MJPG");
+        for value in [0, 0, 0, 1, self.fps, 0, frames, self.max_frame, u32::MAX, 0] {
+            stream.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [0, 0, self.width as u16, self.height as u16] {
+            stream.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut format = Vec::with_capacity(40);
+        format.extend_from_slice(&40u32.to_le_bytes());
+        format.extend_from_slice(&self.width.to_le_bytes());
+        format.extend_from_slice(&self.height.to_le_bytes());
+        format.extend_from_slice(&1u16.to_le_bytes()); //Planes
+        format.extend_from_slice(&24u16.to_le_bytes()); //Bits per pixel once decoded
+        format.extend_from_slice(b"MJPG");
+        format.extend_from_slice(&self.width.saturating_mul(self.height).saturating_mul(3).to_le_bytes());
+        format.extend_from_slice(&[0; 16]);
+
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&riff_size.to_le_bytes());
+        header.extend_from_slice(b"AVI ");
+        header.extend_from_slice(b"LIST");
+        header.extend_from_slice(&192u32.to_le_bytes());
+        header.extend_from_slice(b"hdrl");
+        push_chunk(&mut header, b"avih", &main);
+        header.extend_from_slice(b"LIST");
+        header.extend_from_slice(&116u32.to_le_bytes());
+        header.extend_from_slice(b"strl");
+        push_chunk(&mut header, b"strh", &stream);
+        push_chunk(&mut header, b"strf", &format);
+        header.extend_from_slice(b"LIST");
+        header.extend_from_slice(&self.movi_size.to_le_bytes());
+        header.extend_from_slice(b"movi");
+        header
+    }
+}
+
+/// Appends a chunk with the given FourCC to a buffer. `data` must be of even length.
+fn push_chunk(buffer: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    buffer.extend_from_slice(id);
+    buffer.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(data);
+}
+
+/// Writes a chunk with the given FourCC. `data` must be of even length.
+fn chunk(out: &mut impl Write, id: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(id)?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u32_at(file: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Writes three frames, the first of odd length so it gets padded, at 25 fps.
+    fn finished_file() -> Vec<u8> {
+        let mut video = Writer::new(Cursor::new(Vec::new()), 25).unwrap();
+        for frame in [&[1u8; 5][..], &[2; 8], &[3; 6]] {
+            video.write_frame(frame, 320, 240).unwrap();
+        }
+        assert_eq!(video.frame_count(), 3);
+        video.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn header_lists_have_fixed_sizes() {
+        let file = finished_file();
+        assert_eq!(&file[0..4], b"RIFF");
+        assert_eq!(u32_at(&file, 4) as usize, file.len() - 8);
+        assert_eq!(&file[8..12], b"AVI ");
+
+        assert_eq!(&file[12..16], b"LIST");
+        assert_eq!(u32_at(&file, 16), 192);
+        assert_eq!(&file[20..24], b"hdrl");
+        assert_eq!(&file[88..92], b"LIST");
+        assert_eq!(u32_at(&file, 92), 116);
+        assert_eq!(&file[96..100], b"strl");
+        assert_eq!(&file[212..216], b"LIST");
+        assert_eq!(&file[220..224], b"movi");
+    }
+
+    #[test]
+    fn headers_give_rate_and_frame_count() {
+        let file = finished_file();
+        assert_eq!(&file[24..28], b"avih");
+        assert_eq!(u32_at(&file, 32), 40_000); //Microseconds per frame
+        assert_eq!(u32_at(&file, 44), AVIF_HASINDEX);
+        assert_eq!(u32_at(&file, 48), 3); //Frames
+        assert_eq!(u32_at(&file, 64), 320);
+        assert_eq!(u32_at(&file, 68), 240);
+
+        assert_eq!(&file[100..104], b"strh");
+        assert_eq!(&file[108..116], b"vidsMJPG");
+        assert_eq!(u32_at(&file, 128), 1); //Scale
+        assert_eq!(u32_at(&file, 132), 25); //Rate
+        assert_eq!(u32_at(&file, 140), 3); //Length
+    }
+
+    #[test]
+    fn movi_size_and_index_offsets_match_the_frames() {
+        let file = finished_file();
+
+        //The FourCC, then each frame's chunk header and data padded to an even length
+        let movi_size = 4 + (8 + 6) + (8 + 8) + (8 + 6);
+        assert_eq!(u32_at(&file, 216), movi_size);
+        assert_eq!(&file[224..228], b"00dc");
+        assert_eq!(u32_at(&file, 228), 5);
+        assert_eq!(&file[232..237], &[1; 5]);
+        assert_eq!(file[237], 0); //Padding
+
+        let index = 220 + movi_size as usize;
+        assert_eq!(&file[index..index + 4], b"idx1");
+        assert_eq!(u32_at(&file, index + 4), 3 * 16);
+        assert_eq!(file.len(), index + 8 + 3 * 16);
+        for (entry, (offset, size)) in [(4, 5), (18, 8), (34, 6)].into_iter().enumerate() {
+            let at = index + 8 + entry * 16;
+            assert_eq!(&file[at..at + 4], b"00dc");
+            assert_eq!(u32_at(&file, at + 4), AVIIF_KEYFRAME);
+            assert_eq!(u32_at(&file, at + 8), offset);
+            assert_eq!(u32_at(&file, at + 12), size);
+
+            //Offsets count from the `movi` FourCC and point at the frame's chunk
+            assert_eq!(&file[220 + offset as usize..224 + offset as usize], b"00dc");
+        }
+    }
+}
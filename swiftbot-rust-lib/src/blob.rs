@@ -0,0 +1,95 @@
+use crate::convert::Rect;
+
+/// A connected patch of set pixels in a mask.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blob {
+    pub cx: f32,    //Column of the centroid, the mean of the pixels' columns
+    pub cy: f32,    //Row of the centroid
+    pub area: u32,  //Number of pixels in the blob
+    pub bbox: Rect, //Smallest rectangle holding every pixel of the blob
+}
+
+/// Finds blobs in masks such as the one [`convert::hsv_mask`](crate::convert::hsv_mask) makes.
+///
+/// The buffers used to label pixels are kept between calls, so tracking a colour every frame
+/// doesn't allocate once the frame size settles.
+#[derive(Default)]
+pub struct Finder {
+    visited: Vec<bool>, //Pixels already counted into a blob
+    stack: Vec<usize>,  //Pixels of the current blob still to be looked at
+}
+
+impl Finder {
+    /// Returns the largest blob in a mask, or `None` if no pixel is set.
+    ///
+    /// Pixels belong to the same blob if they touch along an edge, not just at a corner. Of blobs
+    /// of the same size the first one found, scanning row by row from the top, is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - One byte per pixel, row by row, that is non-zero where the pixel is set.
+    /// * `width` - The width of the mask in pixels.
+    /// * `height` - The height of the mask in pixels.
+    pub fn largest(&mut self, mask: &[u8], width: u32, height: u32) -> Option<Blob> {
+        let (width, height) = (width as usize, height as usize);
+        let count = (width * height).min(mask.len());
+        self.visited.clear();
+        self.visited.resize(count, false);
+
+        let mut largest: Option<Blob> = None;
+        for start in 0..count {
+            if mask[start] == 0 || self.visited[start] {
+                continue;
+            }
+            let blob = self.fill(mask, width, height, start);
+            if largest.is_none_or(|largest| blob.area > largest.area) {
+                largest = Some(blob);
+            }
+        }
+        largest
+    }
+
+    /// Marks every pixel of the blob holding `start` as visited and measures it.
+    fn fill(&mut self, mask: &[u8], width: usize, height: usize, start: usize) -> Blob {
+        let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+        let (mut sum_x, mut sum_y, mut area) = (0u64, 0u64, 0u32);
+
+        self.visited[start] = true;
+        self.stack.push(start);
+        while let Some(index) = self.stack.pop() {
+            let (x, y) = (index % width, index / width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+            sum_x += x as u64;
+            sum_y += y as u64;
+            area += 1;
+
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                if neighbour < self.visited.len() && mask[neighbour] != 0 && !self.visited[neighbour] {
+                    self.visited[neighbour] = true;
+                    self.stack.push(neighbour);
+                }
+            }
+        }
+
+        Blob {
+            cx: (sum_x as f64 / area as f64) as f32,
+            cy: (sum_y as f64 / area as f64) as f32,
+            area,
+            bbox: Rect {
+                x: left as u32,
+                y: top as u32,
+                width: (right - left + 1) as u32,
+                height: (bottom - top + 1) as u32,
+            },
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::convert::Channels;
+
+//Bytes in the file header and the BITMAPINFOHEADER that follows it
+const FILE_HEADER_SIZE: u32 = 14;
+const INFO_HEADER_SIZE: u32 = 40;
+
+/// Encodes an image as an uncompressed BMP.
+///
+/// RGB images are stored as 24-bit pixels and grayscale ones as 8-bit indices into a grayscale
+/// palette, so the file holds exactly the captured values. BMP stores rows bottom-up, in BGR order
+/// and padded to a multiple of 4 bytes, which is all handled here. If `pixels` holds fewer than
+/// `width * height` pixels the missing ones are stored as black.
+///
+/// # Arguments
+///
+/// * `pixels` - The image data, row by row from the top.
+/// * `width` - Image width in pixels, greater than zero.
+/// * `height` - Image height in pixels, greater than zero.
+/// * `channels` - Channel layout of `pixels`.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the BMP file.
+pub fn encode(pixels: &[u8], width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let (bytes_per_pixel, palette_size) = match channels {
+        Channels::Gray => (1, 256 * 4),
+        Channels::Rgb => (3, 0),
+    };
+    let row_bytes = width as usize * bytes_per_pixel;
+    let padded_row = row_bytes.next_multiple_of(4);
+    let data_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + palette_size;
+    let image_size = (padded_row * height as usize) as u32;
+
+    let mut out = Vec::with_capacity(data_offset as usize + image_size as usize);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(data_offset + image_size).to_le_bytes());
+    out.extend_from_slice(&[0; 4]); //Reserved
+    out.extend_from_slice(&data_offset.to_le_bytes());
+
+    //Header size, width, height (positive for bottom-up rows), planes, bits per pixel, compression
+    //(none), image size, resolution of 72 dpi in pixels per metre, and palette entries used and important
+    out.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(bytes_per_pixel as u16 * 8).to_le_bytes());
+    for value in [0, image_size, 2835, 2835, palette_size / 4, 0] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    //Palette entries are blue, green, red and a reserved byte
+    if channels == Channels::Gray {
+        for level in 0..=255u8 {
+            out.extend_from_slice(&[level, level, level, 0]);
+        }
+    }
+
+    for row in (0..height as usize).rev() {
+        let start = (row * row_bytes).min(pixels.len());
+        let end = (start + row_bytes).min(pixels.len());
+        let row_start = out.len();
+        match channels {
+            Channels::Gray => out.extend_from_slice(&pixels[start..end]),
+            Channels::Rgb => {
+                for pixel in pixels[start..end].chunks_exact(3) {
+                    out.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+                }
+            }
+        }
+        out.resize(row_start + padded_row, 0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bmp: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bmp[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn rgb_gradient_is_stored_bottom_up_in_bgr_with_padded_rows() {
+        //3x2 gradient, red rising to the right and green downwards
+        let pixels = [0, 0, 50, 100, 0, 50, 200, 0, 50, 0, 100, 50, 100, 100, 50, 200, 100, 50];
+        let bmp = encode(&pixels, 3, 2, Channels::Rgb);
+
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(u32_at(&bmp, 2), 54 + 24); //File size
+        assert_eq!(u32_at(&bmp, 10), 54); //Pixel data offset, no palette
+        assert_eq!(u32_at(&bmp, 18), 3);
+        assert_eq!(u32_at(&bmp, 22), 2);
+        assert_eq!(u16::from_le_bytes([bmp[28], bmp[29]]), 24);
+        assert_eq!(u32_at(&bmp, 34), 24); //Image size
+
+        //Each 9-byte row is padded to 12, and the bottom row comes first
+        #[rustfmt::skip]
+        let expected = [
+            50, 100, 0, 50, 100, 100, 50, 100, 200, 0, 0, 0,
+            50, 0, 0, 50, 0, 100, 50, 0, 200, 0, 0, 0,
+        ];
+        assert_eq!(&bmp[54..], &expected);
+    }
+
+    #[test]
+    fn gray_gradient_uses_a_gray_palette() {
+        let pixels = [0, 85, 170, 255];
+        let bmp = encode(&pixels, 2, 2, Channels::Gray);
+
+        assert_eq!(u32_at(&bmp, 10), 54 + 1024);
+        assert_eq!(u16::from_le_bytes([bmp[28], bmp[29]]), 8);
+        assert_eq!(u32_at(&bmp, 46), 256); //Palette entries used
+        for level in 0..256 {
+            let entry = &bmp[54 + level * 4..58 + level * 4];
+            assert_eq!(entry, &[level as u8, level as u8, level as u8, 0]);
+        }
+
+        //Rows of 2 bytes are padded to 4, bottom row first
+        assert_eq!(&bmp[1078..], &[170, 255, 0, 0, 0, 85, 0, 0]);
+    }
+
+    #[test]
+    fn missing_pixels_are_black() {
+        let bmp = encode(&[255, 255, 255], 2, 1, Channels::Rgb);
+        assert_eq!(&bmp[54..], &[255, 255, 255, 0, 0, 0, 0, 0]);
+    }
+}
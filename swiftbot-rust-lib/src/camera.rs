@@ -1,44 +1,3963 @@
-use rscam::{Camera, Config};
+use crate::apriltag;
+use crate::avi;
+use crate::blob;
+use crate::bmp;
+use crate::convert::{self, FrameFormat, Transform, YuvLayout};
+use crate::exif;
+use crate::gif;
+use crate::h264;
+use crate::jpeg;
+use crate::motion;
+use crate::mp4;
+use crate::png;
+use crate::ppm;
+use crate::qr;
+use crate::settings::{self, Entry, Value};
+use crate::utils::clamp;
+use crate::v4l2;
+use crate::y4m;
+use rscam::{Camera, Config, Control, CtrlData, Frame, IntervalInfo, ResolutionInfo};
 use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Cursor, Seek, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//Types the controller's API takes and returns, so Rust programs using this module can name them
+pub use crate::apriltag::TagDetection;
+pub use crate::blob::Blob;
+pub use crate::convert::{Channels, ColorFilter, Flip, Mirror, PreFilter, Rect};
+pub use crate::frame_buffer::FrameBuffer;
+pub use crate::jpeg::Encoder as JpegEncoder;
+pub use crate::overlay::{Corner, Overlay};
+pub use crate::qr::QrResult;
+pub use crate::v4l2::{Capability, PixFormat};
+
+/// Environment variable naming a settings file the camera configuration is loaded from at startup.
+pub const CONFIG_ENV_VAR: &str = "SWIFTBOT_CAMERA_CONFIG";
+
+//Set by `stop_recording` to end a recording or timelapse in progress. Kept outside the controller so it
+//can be set while the recording holds the camera lock
+static STOP_RECORDING: AtomicBool = AtomicBool::new(false);
+
+//Set while a capture waits for a disconnected camera to come back, readable without the camera lock
+static RECONNECTING: AtomicBool = AtomicBool::new(false);
+
+//ID of the last frame captured, announced to `wait_for_frame` without the camera lock, which the capturing
+//thread holds
+static FRAME_READY: FrameSignal = FrameSignal::new();
+
+//Pixel format frames are handed to the H.264 encoder in
+const H264_INPUT: &[u8; 4] = b"RGB3";
+
+//How often a timelapse waiting for its next shot checks whether it has been stopped
+const TIMELAPSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Most frames [`CameraController::capture_burst_to_memory`] captures at once, about 29 MB of RGB
+/// pixels at 640x480.
+pub const MAX_MEMORY_BURST_FRAMES: usize = 32;
+
+/// Longest clip [`CameraController::capture_gif`] records. GIF compresses frames far less than video
+/// does, so longer clips make files too large to share.
+pub const MAX_GIF_DURATION: Duration = Duration::from_secs(10);
+
+/// Highest frame rate [`CameraController::capture_gif`] records at. GIF frame delays are in
+/// hundredths of a second, and viewers slow down frames shown for less than two.
+pub const MAX_GIF_FPS: u32 = 50;
+
+/// Largest radius [`CameraController::set_prefilter`] accepts for a blur, in pixels.
+pub const MAX_BLUR_RADIUS: u32 = 32;
+
+/// Settings applied when the camera device is opened.
+#[derive(Clone, Debug)]
+pub struct CameraConfig {
+    pub device: String, //Path of the V4L2 device node
+    pub width: u32,     //Frame width in pixels
+    pub height: u32,    //Frame height in pixels
+    pub fps: u32,       //Target frames per second
+    pub buffers: u32,   //Number of mmap capture buffers requested from the driver
+    pub color_mode: ColorMode, //Layout of the captured frame data
+    pub formats: Vec<[u8; 4]>, //Pixel formats to try before the color mode's defaults
+    pub flip: Flip,            //Mirroring applied to captured frames
+    pub crop: Option<Rect>,    //Region of the frame to keep, or the full frame if `None`
+    pub output_size: Option<(u32, u32)>, //Size raw frames are shrunk to, or `None` to keep their size
+    pub overlay: Option<Overlay>,        //Timestamp drawn into raw frames, or `None` for none
+    pub controls: Vec<(u32, i64)>, //V4L2 control values applied whenever the device is opened
+    pub jpeg_quality: u8,          //Quality, from 1 to 100, frames are encoded at when the driver doesn't
+    pub reconnect_attempts: u32,   //Times to try reopening a camera that disappears mid-stream
+    pub reconnect_interval: Duration, //Wait before each of those attempts
+    pub warmup_frames: u32,           //Frames thrown away after opening while exposure settles
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            device: String::from("/dev/video0"),
+            width: 640,
+            height: 480,
+            fps: 30,
+            buffers: 2,
+            color_mode: ColorMode::Jpeg,
+            formats: Vec::new(),
+            flip: Flip::default(),
+            crop: None,
+            output_size: None,
+            overlay: None,
+            controls: Vec::new(),
+            jpeg_quality: 90,
+            reconnect_attempts: 10,
+            reconnect_interval: Duration::from_secs(1),
+            warmup_frames: 0,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// Reads a configuration from a settings file, starting from the defaults.
+    ///
+    /// The file is a subset of TOML. Top-level keys are `device`, `width`, `height`, `fps`,
+    /// `buffers`, `color_mode` (`"jpeg"`, `"grayscale"`, `"rgb"` or `"bgr"`), `formats` (FourCCs
+    /// to try first, e.g. `["UYVY"]`), `jpeg_quality`, `flip` (`"none"`, `"horizontal"`,
+    /// `"vertical"` or `"both"`), `flip_horizontal`, `flip_vertical`,
+    /// `reconnect_attempts`, `reconnect_interval_ms` and `warmup_frames`. A `[controls]` table
+    /// sets V4L2 controls by name, e.g. `exposure_auto = false` and `exposure = 250`, or by numeric
+    /// ID, and they are applied in the order they are listed.
+    pub fn from_file(path: &str) -> Result<CameraConfig, CameraError> {
+        let error = |message: String| CameraError::Config(format!("{}: {}", path, message));
+
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let mut config = CameraConfig::default();
+        for entry in settings::parse(&text).map_err(error)? {
+            config
+                .apply_setting(&entry)
+                .map_err(|message| error(format!("line {}: {}", entry.line, message)))?;
+        }
+
+        config.validate().map_err(|e| error(e.to_string()))?;
+        Ok(config)
+    }
+
+    /// Checks that the configuration describes something the camera could be opened with.
+    fn validate(&self) -> Result<(), CameraError> {
+        if self.device.is_empty() {
+            return Err(CameraError::InvalidArgument("Device path must not be empty".into()));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(CameraError::InvalidArgument("Resolution must be non-zero".into()));
+        }
+        if self.fps == 0 {
+            return Err(CameraError::InvalidArgument("Frame rate must be non-zero".into()));
+        }
+        if self.buffers < 2 {
+            return Err(CameraError::InvalidArgument("At least 2 capture buffers are required".into()));
+        }
+        CameraController::validate_quality(self.jpeg_quality)?;
+
+        for format in &self.formats {
+            if !self.color_mode.formats().contains(&format) {
+                return Err(CameraError::InvalidArgument(format!(
+                    "The {} pixel format can't be used for {:?} capture",
+                    String::from_utf8_lossy(format),
+                    self.color_mode
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies one entry of a settings file.
+    fn apply_setting(&mut self, entry: &Entry) -> Result<(), String> {
+        let (key, value) = (entry.key.as_str(), &entry.value);
+        let unsigned = |max: i64| setting_integer(key, value, 0, max);
+
+        match (entry.table.as_str(), key) {
+            ("", "device") => self.device = setting_string(key, value)?,
+            ("", "width") => self.width = unsigned(u32::MAX as i64)? as u32,
+            ("", "height") => self.height = unsigned(u32::MAX as i64)? as u32,
+            ("", "fps") => self.fps = unsigned(u32::MAX as i64)? as u32,
+            ("", "buffers") => self.buffers = unsigned(u32::MAX as i64)? as u32,
+            ("", "jpeg_quality") => self.jpeg_quality = setting_integer(key, value, 1, 100)? as u8,
+            ("", "flip_horizontal") => {
+                self.flip = Mirror { horizontal: setting_bool(key, value)?, ..self.flip.into() }.into()
+            }
+            ("", "flip_vertical") => {
+                self.flip = Mirror { vertical: setting_bool(key, value)?, ..self.flip.into() }.into()
+            }
+            ("", "flip") => {
+                self.flip = match setting_string(key, value)?.to_ascii_lowercase().as_str() {
+                    "none" => Flip::None,
+                    "horizontal" => Flip::Horizontal,
+                    "vertical" => Flip::Vertical,
+                    "both" => Flip::Both,
+                    other => return Err(format!("unknown flip `{}`, expected none, horizontal, vertical or both", other)),
+                }
+            }
+            ("", "reconnect_attempts") => self.reconnect_attempts = unsigned(u32::MAX as i64)? as u32,
+            ("", "warmup_frames") => self.warmup_frames = unsigned(u32::MAX as i64)? as u32,
+            ("", "reconnect_interval_ms") => {
+                self.reconnect_interval = Duration::from_millis(unsigned(u32::MAX as i64)? as u64)
+            }
+            ("", "color_mode") => {
+                self.color_mode = match setting_string(key, value)?.to_ascii_lowercase().as_str() {
+                    "jpeg" => ColorMode::Jpeg,
+                    "grayscale" => ColorMode::Grayscale,
+                    "rgb" => ColorMode::Rgb,
+                    "bgr" => ColorMode::Bgr,
+                    other => return Err(format!("unknown color mode `{}`, expected jpeg, grayscale, rgb or bgr", other)),
+                }
+            }
+            ("", "formats") => {
+                let formats = match value {
+                    Value::Array(formats) => formats,
+                    other => return Err(format!("expected an array of FourCCs for `formats`, found {}", other.type_name())),
+                };
+                self.formats = formats
+                    .iter()
+                    .map(|format| {
+                        let format = setting_string(key, format)?;
+                        format
+                            .as_bytes()
+                            .try_into()
+                            .map_err(|_| format!("pixel format `{}` is not a four character code", format))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            ("controls", name) => {
+                let (id, value) = control_setting(name, value)?;
+                self.controls.retain(|&(control, _)| control != id);
+                self.controls.push((id, value));
+            }
+            ("", _) => return Err(format!("unknown setting `{}`", key)),
+            (table, _) => return Err(format!("unknown table `[{}]`", table)),
+        }
+        Ok(())
+    }
+}
+
+/// Reads a settings file value that must be a string.
+fn setting_string(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        other => Err(format!("expected a string for `{}`, found {}", key, other.type_name())),
+    }
+}
+
+/// Reads a settings file value that must be an integer within a range.
+fn setting_integer(key: &str, value: &Value, min: i64, max: i64) -> Result<i64, String> {
+    match value {
+        Value::Integer(integer) if (min..=max).contains(integer) => Ok(*integer),
+        Value::Integer(integer) => Err(format!("`{}` must be between {} and {}, found {}", key, min, max, integer)),
+        other => Err(format!("expected an integer for `{}`, found {}", key, other.type_name())),
+    }
+}
+
+/// Reads a settings file value that must be a boolean.
+fn setting_bool(key: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(boolean) => Ok(*boolean),
+        other => Err(format!("expected a boolean for `{}`, found {}", key, other.type_name())),
+    }
+}
+
+/// Maps an entry of a settings file's `[controls]` table to a V4L2 control ID and value.
+fn control_setting(name: &str, value: &Value) -> Result<(u32, i64), String> {
+    let id = match name {
+        "brightness" => rscam::CID_BRIGHTNESS,
+        "contrast" => rscam::CID_CONTRAST,
+        "saturation" => rscam::CID_SATURATION,
+        "hue" => rscam::CID_HUE,
+        "gamma" => rscam::CID_GAMMA,
+        "sharpness" => rscam::CID_SHARPNESS,
+        "gain" => rscam::CID_GAIN,
+        "exposure_auto" => rscam::CID_EXPOSURE_AUTO,
+        "exposure" => rscam::CID_EXPOSURE_ABSOLUTE,
+        "white_balance_auto" => rscam::CID_AUTO_WHITE_BALANCE,
+        "white_balance_temperature" => rscam::CID_WHITE_BALANCE_TEMPERATURE,
+        "focus_auto" => rscam::CID_FOCUS_AUTO,
+        "focus" => rscam::CID_FOCUS_ABSOLUTE,
+        "power_line_frequency" => rscam::CID_POWER_LINE_FREQUENCY,
+        //Any other control can be given by its numeric ID, e.g. 0x009a0901
+        _ => match settings::parse_integer(name) {
+            Ok(id) if id > 0 && id <= u32::MAX as i64 => id as u32,
+            _ => return Err(format!("unknown control `{}`", name)),
+        },
+    };
+
+    let value = match value {
+        Value::Integer(value) => *value,
+        //Aperture priority is the automatic exposure mode UVC webcams implement
+        Value::Boolean(true) if id == rscam::CID_EXPOSURE_AUTO => rscam::EXPOSURE_APERTURE_PRIORITY as i64,
+        Value::Boolean(false) if id == rscam::CID_EXPOSURE_AUTO => rscam::EXPOSURE_MANUAL as i64,
+        Value::Boolean(enabled) => *enabled as i64,
+        other => return Err(format!("expected an integer or boolean for `{}`, found {}", name, other.type_name())),
+    };
+    Ok((id, value))
+}
+
+/// The valid range and current state of a V4L2 control, as reported by the driver.
+#[derive(Clone, Debug)]
+pub struct ControlRange {
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: i64,
+    pub default: i64,
+    pub value: i64,
+    pub enabled: bool,   //False while the control is disabled or inactive, e.g. exposure under auto exposure
+    pub read_only: bool,
+}
+
+/// Called with the data and ID of every captured frame, see [`CameraController::set_frame_callback`].
+pub type FrameCallback = Box<dyn FnMut(&[u8], u64) + Send>;
+
+/// Called with the motion score and frame ID of every frame that scores at or above the threshold,
+/// see [`CameraController::set_motion_callback`].
+pub type MotionCallback = Box<dyn FnMut(f64, u64) + Send>;
+
+/// A captured image along with its dimensions.
+#[derive(Default)]
+pub struct CapturedFrame {
+    pub data: Vec<u8>, //JPEG data or raw pixels, depending on the color mode
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Mains frequency the camera compensates for to avoid banding under artificial lighting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerLineFrequency {
+    Disabled = 0,
+    Hz50 = 1,
+    Hz60 = 2,
+    Auto = 3,
+}
+
+impl PowerLineFrequency {
+    /// Maps a V4L2 power line frequency menu value to the matching setting.
+    pub fn from_value(value: i64) -> Option<PowerLineFrequency> {
+        match value {
+            0 => Some(PowerLineFrequency::Disabled),
+            1 => Some(PowerLineFrequency::Hz50),
+            2 => Some(PowerLineFrequency::Hz60),
+            3 => Some(PowerLineFrequency::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Signature shared by the YUV pixel conversions in [`convert`].
+type Conversion = fn(&[u8], &FrameFormat, &Transform, &mut Vec<u8>) -> (u32, u32);
+
+/// Layout of the image data returned by a capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    Jpeg,      //Compressed MJPEG frames as delivered by the camera
+    Grayscale, //One luma byte per pixel, taken from YUYV frames
+    Rgb,       //Three bytes per pixel in red, green, blue order
+    Bgr,       //Three bytes per pixel in blue, green, red order, as used by OpenCV
+}
+
+impl ColorMode {
+    /// Returns the V4L2 pixel formats this mode can be captured in, most preferred first.
+    fn formats(self) -> &'static [&'static [u8; 4]] {
+        match self {
+            ColorMode::Jpeg => &[b"MJPG", b"JPEG"],
+            ColorMode::Grayscale | ColorMode::Rgb | ColorMode::Bgr => &[b"YUYV", b"UYVY"],
+        }
+    }
+}
+
+/// File format a still is saved in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg, //Smaller files, at the chosen quality
+    Png,  //Lossless, for image processing that JPEG artifacts get in the way of
+    Bmp,  //Uncompressed 24-bit or 8-bit grayscale, for tools that don't read PNG
+    Ppm,  //Uncompressed binary PPM, or PGM for grayscale, the simplest format to parse
+}
+
+impl ImageFormat {
+    /// Encodes captured pixels losslessly in this format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] for JPEG, which is lossy.
+    fn encode_lossless(self, frame: &CapturedFrame, channels: Channels) -> Result<Vec<u8>, CameraError> {
+        match self {
+            ImageFormat::Bmp => Ok(bmp::encode(&frame.data, frame.width, frame.height, channels)),
+            ImageFormat::Ppm => Ok(ppm::encode(&frame.data, frame.width, frame.height, channels)),
+            ImageFormat::Png => Ok(png::encode(&frame.data, frame.width, frame.height, channels)),
+            ImageFormat::Jpeg => Err(CameraError::InvalidArgument(String::from("JPEG is not a lossless format"))),
+        }
+    }
+}
+
+/// Codec a recording is encoded with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoCodec {
+    Mjpeg,    //JPEG frames in an AVI file, which works with any camera
+    H264,     //H.264 in an MP4 file, using the device's hardware encoder
+    RawMjpeg, //JPEG frames back to back with no container, as recordings were originally written
+    Y4m,      //Uncompressed YUV 4:2:2 in a YUV4MPEG2 stream, exactly as the camera captured it
+}
+
+/// What [`CameraController::capture_video`] recorded.
+///
+/// A [`VideoCodec::RawMjpeg`] recording has no container to seek with, so it gets a frame index
+/// next to it, named after the recording with `.csv` appended. The index is a CSV file with the
+/// header `frame,offset,length,timestamp_us` and one line per frame: the frame number counting
+/// from 0, the byte offset of its JPEG data in the recording, its length in bytes, and when it was
+/// captured in microseconds since the Unix epoch. Each line is flushed as soon as its frame is in
+/// the recording, so the index matches what was written even if the recording is interrupted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoStats {
+    pub frames: u32,             //Frames written to the file
+    pub fps: u32,                //Frame rate the file plays back at, the rate `timing` was aiming for
+    pub warning: Option<String>, //Why the requested output frame rate was lowered, if it was
+    pub index: Option<String>,   //Path of the frame index of a raw MJPEG recording
+    pub stopped: bool,           //Whether `stop_recording` ended the recording early
+    pub timing: FrameTiming,
+}
+
+/// How well a recording kept up with the camera.
+///
+/// A deadline falls every frame interval at the rate the file plays back at. When the robot is too
+/// loaded to capture, encode and write every frame in time, the frames that should have filled the
+/// missed deadlines are simply absent, and the file plays back faster than real time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameTiming {
+    pub captured: u32,              //Frames captured from the camera, including those skipped to lower the rate
+    pub written: u32,               //Frames written to the recording
+    pub total_processing: Duration, //Time spent encoding and writing frames, not waiting for the camera
+    pub max_processing: Duration,   //Longest time spent encoding and writing one frame
+    pub missed_deadlines: u32,      //Frame intervals that passed without a frame being written
+    pub intervals: u32,             //Gaps between frames written one after the other, leaving out pauses
+    pub interval_time: Duration,    //Total length of those gaps, measured from when the frames were captured
+}
+
+impl FrameTiming {
+    /// Returns the average time spent encoding and writing a frame, or zero before the first one.
+    pub fn average_processing(&self) -> Duration {
+        self.total_processing / self.written.max(1)
+    }
+
+    /// Returns the frame rate the recording actually achieved, from when the frames written were
+    /// captured, or zero before the second frame. Comparing it with the rate the file plays back at
+    /// tells whether the camera or CPU kept up.
+    pub fn actual_fps(&self) -> f64 {
+        if self.interval_time.is_zero() {
+            return 0.0;
+        }
+        self.intervals as f64 / self.interval_time.as_secs_f64()
+    }
+}
+
+/// A recording held in memory, see [`CameraController::capture_video_to_memory`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryVideo {
+    pub data: Vec<u8>, //The finished AVI file
+    pub stats: VideoStats,
+    pub truncated: bool, //The recording was cut short because the next frame would have gone over the cap
+}
+
+/// A copy of the last frame captured while running, in a [`FrameBuffer`] that can be moved to
+/// another thread or handed to code outside Rust.
+pub struct BufferedFrame {
+    pub id: u64,         //ID of the frame, as returned by `frame_id`
+    pub mode: ColorMode, //Color mode the frame was captured in, which decides how its data is laid out
+    pub width: u32,
+    pub height: u32,
+    pub data: FrameBuffer,
+}
+
+/// Errors returned by camera operations.
+#[derive(Debug)]
+pub enum CameraError {
+    DeviceOpen(String, io::Error), //The device node could not be opened
+    Busy,                          //Another process is streaming from the device
+    StreamRunning,                 //The operation needs the camera to be stopped first
+    RecordingRunning,              //A background recording is already in progress
+    NotRecording,                  //There is no background recording to stop
+    NotStarted,                    //The operation needs the camera to be streaming
+    NoFrame,                       //The camera is streaming but hasn't captured a frame yet
+    Timeout,                       //No frame was captured in the time allowed
+    Config(String),                //A settings file could not be read or is invalid
+    Disconnected(String),          //The device disappeared mid-stream and could not be reopened
+    NotCaptureDevice(String),      //The device node doesn't support video capture
+    FormatUnsupported(String),     //The camera can't stream the required pixel format
+    ResolutionUnsupported(String), //The camera can't stream the requested frame size
+    ControlUnsupported(u32),       //The camera doesn't implement a V4L2 control
+    ControlRejected(String),       //The driver refused a control value
+    AllocFailed(String),           //The driver couldn't provide the requested capture buffers
+    InvalidArgument(String),       //A setting was out of range before reaching the driver
+    Interrupted(u32, Box<CameraError>), //A burst failed after capturing this many frames
+    Io(io::Error),                 //Any other device I/O failure
+    Other(String),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CameraError::DeviceOpen(device, e) => write!(f, "Failed to open camera {}: {}", device, e),
+            CameraError::Busy => write!(f, "Camera is in use by another process"),
+            CameraError::StreamRunning => write!(f, "Camera is already streaming, stop it first"),
+            CameraError::RecordingRunning => write!(f, "A recording is already in progress"),
+            CameraError::NotRecording => write!(f, "No recording is in progress"),
+            CameraError::NotStarted => write!(f, "Camera is not running"),
+            CameraError::NoFrame => write!(f, "Camera has not captured a frame yet"),
+            CameraError::Timeout => write!(f, "Timed out waiting for a frame"),
+            CameraError::Config(message) => write!(f, "Invalid camera settings file {}", message),
+            CameraError::Disconnected(device) => write!(f, "Camera {} was disconnected", device),
+            CameraError::NotCaptureDevice(device) => write!(f, "{} does not support video capture", device),
+            CameraError::ControlUnsupported(id) => write!(f, "Control {:#x} is not supported by this camera", id),
+            CameraError::Io(e) => write!(f, "Camera I/O error: {}", e),
+            CameraError::Interrupted(captured, e) => write!(f, "Burst stopped after {} frames: {}", captured, e),
+            CameraError::FormatUnsupported(message)
+            | CameraError::ResolutionUnsupported(message)
+            | CameraError::ControlRejected(message)
+            | CameraError::AllocFailed(message)
+            | CameraError::InvalidArgument(message)
+            | CameraError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for CameraError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CameraError::DeviceOpen(_, e) | CameraError::Io(e) => Some(e),
+            CameraError::Interrupted(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CameraError {
+    fn from(e: io::Error) -> Self {
+        match e.raw_os_error() {
+            Some(libc::EBUSY) => CameraError::Busy,
+            _ => CameraError::Io(e),
+        }
+    }
+}
+
+impl From<rscam::Error> for CameraError {
+    fn from(e: rscam::Error) -> Self {
+        match e {
+            rscam::Error::Io(e) => e.into(),
+            rscam::Error::BadFormat => CameraError::FormatUnsupported(String::from("Unsupported pixel format")),
+            rscam::Error::BadResolution => CameraError::ResolutionUnsupported(String::from("Unsupported resolution")),
+            rscam::Error::BadInterval => CameraError::Other(String::from("Unsupported frame interval")),
+            rscam::Error::BadField => CameraError::Other(String::from("Unsupported field order")),
+        }
+    }
+}
+
+impl From<String> for CameraError {
+    fn from(message: String) -> Self {
+        CameraError::Other(message)
+    }
+}
+
+impl From<&str> for CameraError {
+    fn from(message: &str) -> Self {
+        CameraError::Other(message.to_string())
+    }
+}
+
+/// A pixel format advertised by a camera, along with the frame sizes it supports.
+pub struct FormatDescription {
+    pub fourcc: String,
+    pub description: String,
+    pub resolutions: ResolutionInfo,
+}
+
+impl fmt::Display for FormatDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}): ", self.fourcc, self.description)?;
+        match &self.resolutions {
+            ResolutionInfo::Discretes(sizes) => {
+                let sizes: Vec<String> = sizes.iter().map(|(w, h)| format!("{}x{}", w, h)).collect();
+                write!(f, "{}", sizes.join(", "))
+            }
+            ResolutionInfo::Stepwise { min, max, step } => write!(
+                f,
+                "{}x{} to {}x{} in steps of {}x{}",
+                min.0, min.1, max.0, max.1, step.0, step.1
+            ),
+        }
+    }
+}
+
+/// A V4L2 device node found by [`list_devices`].
+pub struct DeviceInfo {
+    pub index: u32,
+    pub path: String,
+    pub driver: String,
+    pub card: String,
+    pub bus_info: String,
+    pub capture: bool,   //Supports video capture
+    pub streaming: bool, //Supports streaming I/O
+    pub formats: Vec<FormatDescription>,
+    pub error: Option<String>, //Why the node could not be queried, if it couldn't
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(error) = &self.error {
+            return write!(f, "{} {}: {}", self.index, self.path, error);
+        }
+
+        write!(
+            f,
+            "{} {} ({}, driver {}, bus {}), capture: {}, streaming: {}",
+            self.index, self.path, self.card, self.driver, self.bus_info, self.capture, self.streaming
+        )?;
+        for format in &self.formats {
+            write!(f, "; {}", format)?;
+        }
+        Ok(())
+    }
+}
+
+/// Driver details and current format of the configured camera, for diagnostics.
+///
+/// Each part is read independently, so whatever could be read is reported even if other queries fail.
+pub struct CameraInfo {
+    pub path: String,
+    pub capability: Option<v4l2::Capability>,
+    pub format: Option<v4l2::PixFormat>,
+    pub errors: Vec<String>, //Queries that failed
+}
+
+impl fmt::Display for CameraInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(capability) = &self.capability {
+            let version = capability.version;
+            write!(
+                f,
+                ": {} (driver {} {}.{}.{}, bus {}), capabilities {:#010x}, device capabilities {:#010x}",
+                capability.card,
+                capability.driver,
+                version >> 16,
+                (version >> 8) & 0xff,
+                version & 0xff,
+                capability.bus_info,
+                capability.capabilities,
+                capability.device_caps
+            )?;
+        }
+        if let Some(format) = &self.format {
+            write!(
+                f,
+                "; format {}x{} {}, {} bytes per line",
+                format.width, format.height, String::from_utf8_lossy(&format.fourcc), format.bytes_per_line
+            )?;
+        }
+        for error in &self.errors {
+            write!(f, "; {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans `/dev/video*` and reports the capabilities of each node, along with the formats and
+/// resolutions advertised by the ones that support video capture.
+///
+/// Nodes that cannot be opened or queried are still listed, with the error that occurred. Nodes are
+/// only opened for queries, so a camera that is already streaming is not disturbed.
+pub fn list_devices() -> Result<Vec<DeviceInfo>, CameraError> {
+    let devices = v4l2::device_indices()?
+        .into_iter()
+        .map(|index| {
+            let path = format!("/dev/video{}", index);
+            describe_device(index, &path).unwrap_or_else(|e| DeviceInfo {
+                index,
+                path,
+                driver: String::new(),
+                card: String::new(),
+                bus_info: String::new(),
+                capture: false,
+                streaming: false,
+                formats: Vec::new(),
+                error: Some(e.to_string()),
+            })
+        })
+        .collect();
+
+    Ok(devices)
+}
+
+/// Reads the capabilities of a device node and, if it can capture video, its supported formats.
+fn describe_device(index: u32, path: &str) -> Result<DeviceInfo, CameraError> {
+    let capability = v4l2::query_capability(&v4l2::open(path)?)?;
+    let capture = capability.supports(v4l2::CAP_VIDEO_CAPTURE);
+    let streaming = capability.supports(v4l2::CAP_STREAMING);
+
+    let formats = if capture {
+        describe_formats(&Camera::new(path)?)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(DeviceInfo {
+        index,
+        path: path.to_string(),
+        driver: capability.driver,
+        card: capability.card,
+        bus_info: capability.bus_info,
+        capture,
+        streaming,
+        formats,
+        error: None,
+    })
+}
+
+/// Walks the pixel formats a camera advertises and the frame sizes supported for each.
+fn describe_formats(camera: &Camera) -> Result<Vec<FormatDescription>, CameraError> {
+    let mut formats = Vec::new();
+    for info in camera.formats() {
+        let info = info?;
+        formats.push(FormatDescription {
+            fourcc: String::from_utf8_lossy(&info.format).into_owned(),
+            description: info.description,
+            resolutions: camera.resolutions(&info.format)?,
+        });
+    }
+    Ok(formats)
+}
+
+/// Asks a recording, timelapse or frame sequence in progress to stop after the frame it is currently
+/// capturing.
+///
+/// Has no effect if nothing is being recorded.
+pub fn stop_recording() {
+    STOP_RECORDING.store(true, Ordering::SeqCst);
+}
+
+/// Returns `true` if [`stop_recording`] has been called since the last recording started.
+fn recording_stopped() -> bool {
+    STOP_RECORDING.load(Ordering::SeqCst)
+}
+
+/// Returns `true` while a capture is waiting for a disconnected camera to be plugged back in.
+pub fn is_reconnecting() -> bool {
+    RECONNECTING.load(Ordering::SeqCst)
+}
+
+/// Blocks until the camera captures a new frame and returns its ID, or fails with
+/// [`CameraError::Timeout`] if none is captured within `timeout`.
+///
+/// This doesn't take the camera lock or capture anything itself, so it suits a processing thread
+/// following frames another thread captures, e.g. a background recording or the MJPEG server,
+/// without polling [`CameraController::current_frame_id`]. The frame can then be read with
+/// [`CameraController::latest_frame_rgb`]. Only frames captured after the call count, and if
+/// several are captured before the thread wakes up the ID of the newest is returned.
+pub fn wait_for_frame(timeout: Duration) -> Result<u64, CameraError> {
+    FRAME_READY.wait(timeout).ok_or(CameraError::Timeout)
+}
+
+/// The ID of the last frame captured, and a condition variable woken whenever it changes.
+struct FrameSignal {
+    id: Mutex<u64>,
+    published: Condvar,
+}
+
+impl FrameSignal {
+    const fn new() -> Self {
+        FrameSignal { id: Mutex::new(0), published: Condvar::new() }
+    }
+
+    /// Records that the frame with the given ID has been captured and wakes every waiting thread.
+    fn publish(&self, id: u64) {
+        *self.id.lock().unwrap_or_else(PoisonError::into_inner) = id;
+        self.published.notify_all();
+    }
+
+    /// Waits up to `timeout` for a frame to be published, returning its ID or `None` on timeout.
+    fn wait(&self, timeout: Duration) -> Option<u64> {
+        let id = self.id.lock().unwrap_or_else(PoisonError::into_inner);
+        let seen = *id;
+        let (id, _) = self
+            .published
+            .wait_timeout_while(id, timeout, |id| *id == seen)
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(*id).filter(|&id| id != seen)
+    }
+}
+
+/// Returns `true` if the device has a hardware H.264 encoder that recordings can use.
+pub fn h264_available() -> bool {
+    h264::find_encoder(H264_INPUT).is_some()
+}
+
+/// Runs `capture` for frames 1 to `count` of a burst, stopping at the first failure.
+///
+/// A failure on the first frame is returned as it is, and a later one wrapped in
+/// [`CameraError::Interrupted`] with the number of frames captured before it.
+fn run_burst(count: u32, mut capture: impl FnMut(u32) -> Result<(), CameraError>) -> Result<u32, CameraError> {
+    for number in 1..=count {
+        if let Err(e) = capture(number) {
+            return Err(match number {
+                1 => e,
+                _ => CameraError::Interrupted(number - 1, Box::new(e)),
+            });
+        }
+    }
+    Ok(count)
+}
+
+/// Flushes a buffered file and syncs it to disk. If the flush fails, what did reach the file is still
+/// synced and the flush error returned.
+fn sync_buffered(writer: BufWriter<fs::File>) -> Result<(), CameraError> {
+    match writer.into_inner() {
+        Ok(file) => Ok(file.sync_all()?),
+        Err(e) => {
+            let (error, writer) = e.into_parts();
+            let _ = writer.get_ref().sync_all();
+            Err(CameraError::Io(error))
+        }
+    }
+}
+
+/// Swaps the first and third byte of every 3-byte pixel, converting between RGB and BGR.
+pub(crate) fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(3) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Runs grayscale pixels through a prefilter into `filtered`, returning them as they are without one.
+fn prefilter<'a>(
+    prefilter: PreFilter,
+    gray: &'a [u8],
+    (width, height): (u32, u32),
+    filtered: &'a mut Vec<u8>,
+    scratch: &mut Vec<u8>,
+) -> &'a [u8] {
+    match prefilter {
+        PreFilter::None => gray,
+        PreFilter::GaussianBlur { radius } => {
+            convert::gaussian_blur(gray, width, height, radius, scratch, filtered);
+            filtered
+        }
+    }
+}
+
+/// Converts a `(numerator, denominator)` frame interval into frames per second.
+pub fn interval_rate((numerator, denominator): (u32, u32)) -> f64 {
+    denominator as f64 / numerator.max(1) as f64
+}
+
+/// Picks which captured frames a recording keeps when it is written at a lower frame rate than the
+/// camera captures at, spreading them evenly.
+struct Decimator {
+    ratio: f64,  //Share of the captured frames that are kept
+    credit: f64, //Grows by `ratio` with every frame, a frame is kept each time it reaches 1
+}
+
+impl Decimator {
+    fn new(ratio: f64) -> Self {
+        Decimator { ratio, credit: 1.0 }
+    }
+
+    /// Returns `true` if the frame just captured should be written.
+    fn keep(&mut self) -> bool {
+        //Allow for rounding, so a ratio of 1/6 keeps exactly every 6th frame
+        let keep = self.credit >= 1.0 - 1e-6;
+        if keep {
+            self.credit -= 1.0;
+        }
+        self.credit += self.ratio;
+        keep
+    }
+}
+
+/// When a recording ends, if [`stop_recording`] isn't called first.
+#[derive(Clone, Copy, Debug)]
+struct RecordLimit {
+    deadline: Option<Instant>,
+    max_frames: Option<u32>, //Frames written to the file
+}
+
+impl RecordLimit {
+    /// Ends the recording `duration` from now.
+    fn duration(duration: Duration) -> Self {
+        RecordLimit { deadline: Some(Instant::now() + duration), max_frames: None }
+    }
+
+    /// Returns `true` once a recording that has written `written` frames should stop.
+    fn reached(&self, written: u32) -> bool {
+        self.max_frames.is_some_and(|max| written >= max)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || STOP_RECORDING.load(Ordering::SeqCst)
+    }
+}
+
+/// Collects the [`FrameTiming`] of a recording as its frames are captured and written.
+pub(crate) struct FrameTimer {
+    timing: FrameTiming,
+    interval: Duration,            //Time between frames at the rate the recording plays back at
+    last_written: Option<Instant>, //When the last frame written was captured
+}
+
+impl FrameTimer {
+    pub(crate) fn new(fps: u32) -> Self {
+        FrameTimer {
+            timing: FrameTiming::default(),
+            interval: Duration::from_secs(1) / fps.max(1),
+            last_written: None,
+        }
+    }
+
+    /// Counts a frame that has just been captured, returning when its processing started.
+    pub(crate) fn captured(&mut self) -> Instant {
+        self.timing.captured += 1;
+        Instant::now()
+    }
+
+    /// Counts a frame that has been written, `start` being what [`FrameTimer::captured`] returned.
+    pub(crate) fn written(&mut self, start: Instant) {
+        let processing = start.elapsed();
+        self.timing.written += 1;
+        self.timing.total_processing += processing;
+        self.timing.max_processing = self.timing.max_processing.max(processing);
+
+        //Allow for jitter, and for the uneven spacing of frames kept from a faster stream
+        if let Some(last) = self.last_written.filter(|&last| start - last > self.interval * 3 / 2) {
+            let intervals = (start - last).as_secs_f64() / self.interval.as_secs_f64();
+            self.timing.missed_deadlines += intervals.round() as u32 - 1;
+        }
+        if let Some(last) = self.last_written {
+            self.timing.intervals += 1;
+            self.timing.interval_time += start - last;
+        }
+        self.last_written = Some(start);
+    }
+
+    /// Forgets when the last frame was written, so a pause isn't counted as missed deadlines or in
+    /// the frame rate achieved.
+    pub(crate) fn pause(&mut self) {
+        self.last_written = None;
+    }
+
+    pub(crate) fn timing(&self) -> FrameTiming {
+        self.timing
+    }
+}
+
+/// A printf-style file name pattern with a single integer placeholder, such as `run3_%06d.jpg`.
+struct FramePattern {
+    prefix: String, //Text before the placeholder, with `%%` turned into `%`
+    suffix: String, //Text after the placeholder, likewise
+    width: usize,   //Minimum number of digits
+    zero_pad: bool, //Pad to the width with zeros rather than spaces
+}
+
+impl FramePattern {
+    /// Parses a pattern holding exactly one `%d`, `%i` or `%u` placeholder, optionally with a width
+    /// and a `0` flag as in `%06d`. `%%` stands for a literal `%`, and no other conversions are
+    /// allowed. The pattern names files within a directory, so it can't contain a `/`.
+    fn parse(pattern: &str) -> Result<FramePattern, CameraError> {
+        let invalid = |reason: &str| {
+            CameraError::InvalidArgument(format!("Invalid file pattern {:?}: {}", pattern, reason))
+        };
+        if pattern.contains('/') {
+            return Err(invalid("it must be a file name, not a path"));
+        }
+
+        let mut parts = [String::new(), String::new()];
+        let mut placeholder = None;
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                parts[placeholder.is_some() as usize].push(c);
+                continue;
+            }
+            if chars.next_if_eq(&'%').is_some() {
+                parts[placeholder.is_some() as usize].push('%');
+                continue;
+            }
+
+            let zero_pad = chars.next_if_eq(&'0').is_some();
+            let mut width = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                width.push(digit);
+            }
+            match chars.next() {
+                Some('d' | 'i' | 'u') => {}
+                _ => return Err(invalid("only %d, %i and %u placeholders are supported")),
+            }
+            if placeholder.is_some() {
+                return Err(invalid("it has more than one placeholder"));
+            }
+            placeholder = Some((zero_pad, width.parse().unwrap_or(0)));
+        }
+
+        let (zero_pad, width) = match placeholder {
+            Some(placeholder) => placeholder,
+            None => return Err(invalid("it needs an integer placeholder such as %06d")),
+        };
+        let [prefix, suffix] = parts;
+        Ok(FramePattern { prefix, suffix, width, zero_pad })
+    }
+
+    /// Returns the file name for frame `number`.
+    fn format(&self, number: u32) -> String {
+        match self.zero_pad {
+            true => format!("{}{:0width$}{}", self.prefix, number, self.suffix, width = self.width),
+            false => format!("{}{:width$}{}", self.prefix, number, self.suffix, width = self.width),
+        }
+    }
+}
 
 pub struct CameraController {
-    camera: Camera,
+    camera: Option<Camera>,
+    config: CameraConfig,
+    frame_rate: f64, //Frame rate negotiated with the driver
+    interval: (u32, u32), //Seconds per frame negotiated with the driver, as (numerator, denominator)
+    frame_pacing: Option<Duration>, //Software pacing used when the driver refuses the target rate
+    last_capture: Option<Instant>,
+    software: Transform, //Cropping and mirroring the driver can't do, applied during conversion instead
+    stride: u32,         //Bytes per row of the running stream, including driver padding
+    frame_id: u64,       //Number of frames captured since the controller was created
+    warmup_discarded: u32, //Frames thrown away when the camera was last opened
+    config_error: Option<String>, //Why the startup settings file couldn't be loaded, until a new config replaces it
+    frame_callback: Option<FrameCallback>,
+    motion: Option<motion::Detector>, //Compares each frame with the last while motion detection is on
+    motion_callback: Option<(f64, MotionCallback)>, //Threshold and callback for frames that change enough
+    histogram: Option<[u32; 256]>, //Luma histogram of the last raw frame captured
+    edges: Option<CapturedFrame>,  //Sobel edge map of the last raw frame while edge detection is on
+    edge_luma: Vec<u8>,            //Luma of colour frames, converted for the edge detector
+    prefilter: PreFilter,          //Filtering of grayscale frames before edge detection and QR decoding
+    prefiltered: Vec<u8>,          //The last frame run through the prefilter
+    prefilter_scratch: Vec<u8>,    //Frame filtered along its rows only, for the separable blur
+    color_filter: Option<(ColorFilter, CapturedFrame)>, //Colour range to pick out, and the last mask of it
+    latest: Option<(u64, ColorMode, CapturedFrame)>, //ID, color mode and copy of the last frame captured while running
+    shared: Option<Arc<BufferedFrame>>, //Last frame handed out by acquire_frame_buffer
+    blob_finder: blob::Finder,
+    blob: Option<Blob>, //Largest blob in the last colour mask
+    video_timing: Option<FrameTiming>, //How the last recording kept up, kept even if it failed
+    qr_codes: Vec<QrResult>,           //Codes read by the last QR decode
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CameraController {
-    /// Initializes the camera with the specified resolution and format.
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let mut camera = Camera::new("/dev/video0")?;
+    /// Creates a controller with the default configuration.
+    ///
+    /// The device is opened lazily on the first capture, so the configuration can be changed beforehand.
+    /// If the [`CONFIG_ENV_VAR`] environment variable names a settings file, the configuration is
+    /// loaded from it. Errors in the file are reported by the first capture.
+    pub fn new() -> Self {
+        let mut controller = CameraController {
+            camera: None,
+            config: CameraConfig::default(),
+            frame_rate: 0.0,
+            interval: (1, 0),
+            frame_pacing: None,
+            last_capture: None,
+            software: Transform::default(),
+            stride: 0,
+            frame_id: 0,
+            warmup_discarded: 0,
+            config_error: None,
+            frame_callback: None,
+            motion: None,
+            motion_callback: None,
+            histogram: None,
+            edges: None,
+            edge_luma: Vec::new(),
+            prefilter: PreFilter::None,
+            prefiltered: Vec::new(),
+            prefilter_scratch: Vec::new(),
+            color_filter: None,
+            latest: None,
+            shared: None,
+            blob_finder: blob::Finder::default(),
+            blob: None,
+            video_timing: None,
+            qr_codes: Vec::new(),
+        };
 
-        //Configure the camera (adjust resolution and format as needed)
-        camera.start(&Config {
-            interval: (1, 30), // 30 fps
-            resolution: (640, 480),
-            format: b"MJPG",
-            ..Default::default()
-        })?;
+        //Deployments can point the library at a settings file instead of calling setters on every boot
+        if let Ok(path) = std::env::var(CONFIG_ENV_VAR) {
+            match CameraConfig::from_file(&path) {
+                Ok(config) => controller.config = config,
+                Err(CameraError::Config(message)) => controller.config_error = Some(message),
+                Err(e) => controller.config_error = Some(format!("{}: {}", path, e)),
+            }
+        }
+        controller
+    }
 
-        Ok(CameraController { camera })
+    /// Loads the configuration from a settings file, see [`CameraConfig::from_file`].
+    ///
+    /// Values the file doesn't set are reset to their defaults, so setters should be called after
+    /// loading it. If the camera is running it is restarted with the new settings.
+    pub fn load_config(&mut self, path: &str) -> Result<(), CameraError> {
+        self.reconfigure(CameraConfig::from_file(path)?)
     }
 
-    /// Captures a single image from the camera.
+    /// Returns the current camera configuration.
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    /// Applies a new configuration in one step.
     ///
-    /// # Returns
+    /// If the camera is running it is restarted with the new settings straight away, so only the
+    /// frames in flight during the switch are lost. If the camera refuses the new settings the
+    /// previous configuration is restored and the camera is restarted with it.
+    pub fn reconfigure(&mut self, config: CameraConfig) -> Result<(), CameraError> {
+        let was_running = self.camera.is_some();
+        let previous = self.config.clone();
+        self.replace_config(config)?;
+        self.config_error = None;
+
+        if was_running {
+            if let Err(e) = self.ensure_started() {
+                self.config = previous;
+                let _ = self.ensure_started();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the capture resolution.
+    ///
+    /// If the camera is already running it is stopped and reopened with the new size on the next capture.
+    pub fn set_resolution(&mut self, width: u32, height: u32) -> Result<(), CameraError> {
+        self.replace_config(CameraConfig { width, height, ..self.config.clone() })
+    }
+
+    /// Selects the V4L2 device to capture from by path, e.g. `/dev/video2`.
     ///
-    /// A `Vec<u8>` containing the JPEG image data.
-    pub fn capture_image(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
-        //Capture a frame
-        let frame = self.camera.capture()?;
+    /// If a camera is already running it is stopped and the new device is opened on the next capture.
+    pub fn set_device(&mut self, path: &str) -> Result<(), CameraError> {
+        self.replace_config(CameraConfig { device: path.to_string(), ..self.config.clone() })
+    }
 
-        //The frame data is in MJPEG format (JPEG)
-        let image_data = frame.to_vec();
+    /// Selects the V4L2 device to capture from by index, i.e. `/dev/video<index>`.
+    pub fn set_device_index(&mut self, index: u32) -> Result<(), CameraError> {
+        self.set_device(&format!("/dev/video{}", index))
+    }
 
-        Ok(image_data)
+    /// Sets the target frame rate.
+    ///
+    /// The closest rate the driver supports is programmed when the camera is opened. If that is faster
+    /// than the target, captures are paced in software.
+    pub fn set_frame_rate(&mut self, fps: u32) -> Result<(), CameraError> {
+        self.replace_config(CameraConfig { fps, ..self.config.clone() })
     }
 
-    /// Stops the camera and releases resources.
-    pub fn stop(&mut self) -> Result<(), Box<dyn Error>> {
-        self.camera.stop()?;
+    /// Sets the number of mmap buffers the driver is asked to capture into.
+    ///
+    /// Fewer buffers save memory at high resolutions, more buffers make dropped frames less likely at
+    /// high frame rates. At least two are needed so one can be filled while the other is read.
+    pub fn set_buffer_count(&mut self, buffers: u32) -> Result<(), CameraError> {
+        self.replace_config(CameraConfig { buffers, ..self.config.clone() })
+    }
+
+    /// Sets the layout of captured image data.
+    ///
+    /// If the camera is already running it is stopped and restarted in the matching pixel format
+    /// on the next capture.
+    pub fn set_color_mode(&mut self, mode: ColorMode) -> Result<(), CameraError> {
+        self.replace_config(CameraConfig { color_mode: mode, ..self.config.clone() })
+    }
+
+    /// Sets how many frames are thrown away each time the camera is opened, so the first frame that
+    /// is returned or recorded has settled exposure and white balance. Takes effect the next time the
+    /// camera is opened.
+    pub fn set_warmup_frames(&mut self, frames: u32) {
+        self.config.warmup_frames = frames;
+    }
+
+    /// Returns how many frames were thrown away when the camera was last opened.
+    pub fn warmup_frames_discarded(&self) -> u32 {
+        self.warmup_discarded
+    }
+
+    /// Returns the layout of captured image data.
+    pub fn color_mode(&self) -> ColorMode {
+        self.config.color_mode
+    }
+
+    /// Sets the quality, from 1 to 100, that frames are JPEG encoded at by this library.
+    ///
+    /// It is the default for [`capture_video`](Self::capture_video) and
+    /// [`capture_snapshot`](Self::capture_snapshot), and takes effect without restarting the camera.
+    /// Frames the driver delivers as MJPEG are passed through as they are.
+    pub fn set_jpeg_quality(&mut self, quality: u8) -> Result<(), CameraError> {
+        Self::validate_quality(quality)?;
+        self.config.jpeg_quality = quality;
+        Ok(())
+    }
+
+    /// Returns the configured JPEG quality.
+    pub fn jpeg_quality(&self) -> u8 {
+        self.config.jpeg_quality
+    }
+
+    /// Checks that a JPEG quality is within the range the encoder accepts.
+    fn validate_quality(quality: u8) -> Result<(), CameraError> {
+        if !(1..=100).contains(&quality) {
+            return Err(CameraError::InvalidArgument(format!(
+                "JPEG quality must be between 1 and 100, got {}",
+                quality
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validates a configuration, then stops the camera so it is reopened with it on the next capture.
+    fn replace_config(&mut self, config: CameraConfig) -> Result<(), CameraError> {
+        config.validate()?;
+
+        self.stop()?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Mirrors captured frames horizontally and/or vertically, e.g. for a camera mounted upside down.
+    ///
+    /// This is [`set_flip_mode`](Self::set_flip_mode) with the two directions given separately.
+    pub fn set_flip(&mut self, horizontal: bool, vertical: bool) -> Result<(), CameraError> {
+        self.set_flip_mode(Mirror { horizontal, vertical }.into())
+    }
+
+    /// Flips captured frames, e.g. with [`Flip::Both`] for a camera mounted upside down.
+    ///
+    /// Setting this before the first capture, or in [`CameraConfig::flip`], selects it when the
+    /// camera starts, so every frame, still and recording is flipped. The camera's flip controls
+    /// are used where the driver supports them. Otherwise frames are flipped in software while they
+    /// are converted, which is only possible in the raw color modes. The setting is reapplied
+    /// whenever the camera is reopened.
+    pub fn set_flip_mode(&mut self, flip: Flip) -> Result<(), CameraError> {
+        self.config.flip = flip;
+        if let Some(camera) = &self.camera {
+            self.software.flip = Self::apply_flip(camera, self.config.flip)?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Crops captured frames to a region, or restores the full frame if `crop` is `None`.
+    ///
+    /// The driver's selection API is used where supported, which crops at the sensor and scales the
+    /// region to the configured resolution. Otherwise the region is cut out in software while frames
+    /// are converted, which is only possible in the raw color modes and makes frames the size of
+    /// the region. Takes effect without restarting the camera and is reapplied whenever it is
+    /// reopened.
+    ///
+    /// The region must lie inside the area the driver can crop from, or when cropping in software,
+    /// inside the frame at the configured resolution.
+    ///
+    /// Returns `true` if the driver crops in hardware.
+    pub fn set_crop(&mut self, crop: Option<Rect>) -> Result<bool, CameraError> {
+        if let Some(rect) = crop {
+            if rect.width == 0 || rect.height == 0 {
+                return Err(CameraError::InvalidArgument("Crop region must be non-empty".into()));
+            }
+            //Drivers without the selection API report no bounds, the frame check below covers them
+            if let Ok(bounds) = v4l2::open(&self.config.device).and_then(|file| v4l2::crop_bounds(&file)) {
+                Self::check_crop(rect, bounds, "sensor area")?;
+            }
+        }
+
+        let software = Self::apply_crop(&self.config.device, crop);
+        if let Some(rect) = software {
+            let frame = Rect { x: 0, y: 0, width: self.config.width, height: self.config.height };
+            Self::check_crop(rect, frame, "frame")?;
+        }
+        self.config.crop = crop;
+        self.software.crop = software;
+        Ok(self.software.crop.is_none())
+    }
+
+    /// Fails with [`CameraError::InvalidArgument`] unless the crop region `rect` lies inside `bounds`.
+    fn check_crop(rect: Rect, bounds: Rect, what: &str) -> Result<(), CameraError> {
+        if bounds.contains(rect) {
+            return Ok(());
+        }
+        Err(CameraError::InvalidArgument(format!(
+            "Crop region {}x{} at ({}, {}) is outside the {}x{} {} at ({}, {})",
+            rect.width, rect.height, rect.x, rect.y, bounds.width, bounds.height, what, bounds.x, bounds.y
+        )))
+    }
+
+    /// Scales raw frames down to `size` while they are converted, or restores the captured size if it
+    /// is `None`, e.g. to process 320x240 frames from a camera running at 640x480.
+    ///
+    /// Each output pixel is copied from the nearest captured one, after any software crop, so
+    /// processing gets cheaper without changing the camera's format. Frames are never enlarged, so a
+    /// size larger than the frame leaves it as it is in that direction. JPEG frames are passed
+    /// through unscaled. Takes effect from the next capture.
+    pub fn set_output_size(&mut self, size: Option<(u32, u32)>) -> Result<(), CameraError> {
+        if let Some((width, height)) = size {
+            if width == 0 || height == 0 {
+                return Err(CameraError::InvalidArgument("Output size must be non-zero".into()));
+            }
+        }
+        self.config.output_size = size;
+        Ok(())
+    }
+
+    /// Burns a timestamp into every raw frame from now on, or stops if `overlay` is `None`.
+    ///
+    /// The overlay is drawn while the frame is captured, so it appears the same way in recordings,
+    /// stills, the MJPEG server and frames returned to callers. JPEG frames aren't decoded, so in
+    /// JPEG mode frames are left as the camera delivered them. Motion detection, the histogram and
+    /// the edge map are all measured before the overlay is drawn.
+    pub fn set_overlay(&mut self, overlay: Option<Overlay>) {
+        self.config.overlay = overlay;
+    }
+
+    /// Returns the frame rate negotiated with the driver, opening the camera if needed.
+    pub fn frame_rate(&mut self) -> Result<f64, CameraError> {
+        self.ensure_started()?;
+        Ok(self.frame_rate)
+    }
+
+    /// Reads the driver details and current format of the configured device.
+    ///
+    /// The device is queried through its own handle, so this works whether or not the camera is running.
+    pub fn info(&self) -> CameraInfo {
+        let mut info = CameraInfo {
+            path: self.config.device.clone(),
+            capability: None,
+            format: None,
+            errors: Vec::new(),
+        };
+
+        let device = match v4l2::open(&self.config.device) {
+            Ok(device) => device,
+            Err(e) => {
+                info.errors.push(format!("Failed to open device: {}", e));
+                return info;
+            }
+        };
+        match v4l2::query_capability(&device) {
+            Ok(capability) => info.capability = Some(capability),
+            Err(e) => info.errors.push(format!("Failed to query capabilities: {}", e)),
+        }
+        match v4l2::query_format(&device) {
+            Ok(format) => info.format = Some(format),
+            Err(e) => info.errors.push(format!("Failed to query format: {}", e)),
+        }
+
+        info
+    }
+
+    /// Returns the pixel formats and frame sizes supported by the configured device.
+    ///
+    /// Uses the running camera if there is one, otherwise the device is opened just for the query.
+    pub fn supported_formats(&self) -> Result<Vec<FormatDescription>, CameraError> {
+        self.with_device(describe_formats)
+    }
+
+    /// Returns the frame intervals the configured device supports for a pixel format and frame size.
+    ///
+    /// Intervals are `(numerator, denominator)` fractions of a second per frame. Uses the running
+    /// camera if there is one, otherwise the device is opened just for the query.
+    pub fn frame_intervals(&self, fourcc: &str, width: u32, height: u32) -> Result<IntervalInfo, CameraError> {
+        let format: [u8; 4] = match fourcc.as_bytes().try_into() {
+            Ok(format) => format,
+            Err(_) => return Err(CameraError::InvalidArgument(format!("Invalid pixel format {:?}", fourcc))),
+        };
+        self.with_device(|camera| Ok(camera.intervals(&format, (width, height))?))
+    }
+
+    /// Opens and starts the camera with the current configuration if it isn't running already.
+    fn ensure_started(&mut self) -> Result<(), CameraError> {
+        if self.camera.is_some() {
+            return Ok(());
+        }
+        if let Some(message) = &self.config_error {
+            return Err(CameraError::Config(message.clone()));
+        }
+
+        let mut camera = Camera::new(&self.config.device)
+            .map_err(|e| CameraError::DeviceOpen(self.config.device.clone(), e))?;
+
+        Self::validate_buffer_count(&self.config.device, self.config.buffers)?;
+
+        let format = &Self::select_format(&camera, &self.config)?;
+
+        let resolution = (self.config.width, self.config.height);
+        Self::validate_resolution(&camera, format, resolution)?;
+
+        let device = &self.config.device;
+        let fps = self.config.fps;
+        let resolution_error = |e: rscam::Error| match e {
+            rscam::Error::BadResolution => CameraError::ResolutionUnsupported(format!(
+                "Camera adjusted the requested resolution {}x{}{}",
+                resolution.0, resolution.1, Self::describe_chosen_format(device)
+            )),
+            rscam::Error::BadFormat => CameraError::FormatUnsupported(format!(
+                "Camera did not accept the {} pixel format{}",
+                String::from_utf8_lossy(format), Self::describe_chosen_format(device)
+            )),
+            rscam::Error::BadInterval => CameraError::Other(format!(
+                "Camera did not accept a frame rate near {} fps at {}x{}",
+                fps, resolution.0, resolution.1
+            )),
+            e => CameraError::from(e),
+        };
+
+        //Program the supported interval closest to the target. Drivers that don't enumerate their
+        //intervals are asked for the target directly
+        let interval = match camera.intervals(format, resolution) {
+            Ok(intervals) => Self::select_interval(&intervals, fps)?,
+            Err(_) => (1, fps),
+        };
+        Self::start_camera(&mut camera, format, resolution, interval, self.config.buffers)
+            .map_err(resolution_error)?;
+
+        //If the driver can't run as slowly as the target, run faster and pace captures in software
+        self.frame_rate = interval_rate(interval);
+        self.interval = interval;
+        self.frame_pacing = if self.frame_rate > fps as f64 {
+            Some(Duration::from_secs_f64(1.0 / fps as f64))
+        } else {
+            None
+        };
+
+        //Some drivers pad rows, so convert using the stride the driver actually chose
+        self.stride = v4l2::query_format(&v4l2::open(device)?)?.bytes_per_line;
+
+        //Reapply controls set before the camera was (re)opened
+        for &(id, value) in &self.config.controls {
+            Self::write_control(&camera, id, value)?;
+        }
+        self.software.flip = Self::apply_flip(&camera, self.config.flip)?;
+        self.software.crop = Self::apply_crop(device, self.config.crop);
+
+        //The first frames are often dark or off-colour while auto exposure and white balance settle
+        for _ in 0..self.config.warmup_frames {
+            camera.capture()?;
+        }
+        self.warmup_discarded = self.config.warmup_frames;
+
+        self.camera = Some(camera);
+        self.last_capture = None;
+        Ok(())
+    }
+
+    /// Starts streaming frames in the given pixel format, resolution and frame interval.
+    fn start_camera(
+        camera: &mut Camera,
+        format: &[u8],
+        resolution: (u32, u32),
+        interval: (u32, u32),
+        buffers: u32,
+    ) -> Result<(), rscam::Error> {
+        camera.start(&Config {
+            interval,
+            resolution,
+            format,
+            nbuffers: buffers,
+            ..Default::default()
+        })
+    }
+
+    /// Describes the format the driver chose instead of the requested one, for error messages.
+    fn describe_chosen_format(device: &str) -> String {
+        match v4l2::open(device).and_then(|file| v4l2::query_format(&file)) {
+            Ok(chosen) => format!(
+                ", it chose {}x{} {} with {} bytes per line",
+                chosen.width, chosen.height, String::from_utf8_lossy(&chosen.fourcc), chosen.bytes_per_line
+            ),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Checks that the driver will grant the requested number of capture buffers.
+    fn validate_buffer_count(device: &str, buffers: u32) -> Result<(), CameraError> {
+        let granted = v4l2::probe_buffer_count(&v4l2::open(device)?, buffers)?;
+        if granted < buffers {
+            return Err(CameraError::AllocFailed(format!(
+                "Driver granted only {} of the {} requested capture buffers", granted, buffers
+            )));
+        }
+        Ok(())
+    }
+
+    /// Picks the slowest supported frame interval that is still at least as fast as `fps`,
+    /// or the fastest one if none are.
+    fn select_interval(intervals: &IntervalInfo, fps: u32) -> Result<(u32, u32), CameraError> {
+        let intervals = match intervals {
+            IntervalInfo::Discretes(intervals) => intervals.clone(),
+            //Ask for the target itself if it lies within a stepwise range, otherwise the nearer end
+            IntervalInfo::Stepwise { min, max, .. } => {
+                let target = fps as f64;
+                if target > interval_rate(*min) {
+                    vec![*min]
+                } else if target < interval_rate(*max) {
+                    vec![*max]
+                } else {
+                    vec![(1, fps)]
+                }
+            }
+        };
+
+        let faster = intervals
+            .iter()
+            .copied()
+            .filter(|&interval| interval_rate(interval) >= fps as f64)
+            .min_by(|&a, &b| interval_rate(a).total_cmp(&interval_rate(b)));
+        let fastest = intervals
+            .iter()
+            .copied()
+            .max_by(|&a, &b| interval_rate(a).total_cmp(&interval_rate(b)));
+
+        faster
+            .or(fastest)
+            .ok_or_else(|| "Camera does not report any frame intervals".into())
+    }
+
+    /// Programs the flip controls, returning the mirroring the driver couldn't do itself.
+    fn apply_flip(camera: &Camera, flip: Flip) -> Result<Mirror, CameraError> {
+        let flip = Mirror::from(flip);
+        let mut software = Mirror::default();
+
+        for (id, enabled, fallback) in [
+            (rscam::CID_HFLIP, flip.horizontal, &mut software.horizontal),
+            (rscam::CID_VFLIP, flip.vertical, &mut software.vertical),
+        ] {
+            match Self::write_control(camera, id, enabled as i64) {
+                Ok(()) => {}
+                Err(CameraError::ControlUnsupported(_)) => *fallback = enabled,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(software)
+    }
+
+    /// Programs the driver's crop region, returning the region to crop in software if it can't.
+    fn apply_crop(device: &str, crop: Option<Rect>) -> Option<Rect> {
+        match crop {
+            Some(rect) => match v4l2::open(device).and_then(|file| v4l2::set_crop(&file, rect)) {
+                Ok(_) => None,
+                Err(_) => Some(rect),
+            },
+            None => {
+                //Drivers without the selection API always capture the full frame
+                let _ = v4l2::open(device).and_then(|file| v4l2::reset_crop(&file));
+                None
+            }
+        }
+    }
+
+    /// Sleeps until the next frame is due when captures are paced in software.
+    fn pace_capture(&mut self) {
+        if let (Some(pacing), Some(last)) = (self.frame_pacing, self.last_capture) {
+            let elapsed = last.elapsed();
+            if elapsed < pacing {
+                thread::sleep(pacing - elapsed);
+            }
+        }
+        self.last_capture = Some(Instant::now());
+    }
+
+    /// Picks the first of the preferred pixel formats, then of the color mode's priority list, that the
+    /// device advertises.
+    fn select_format(camera: &Camera, config: &CameraConfig) -> Result<[u8; 4], CameraError> {
+        let (device, mode) = (&config.device, config.color_mode);
+        let formats = camera.formats().collect::<Result<Vec<_>, _>>()?;
+
+        if formats.is_empty() {
+            return Err(CameraError::NotCaptureDevice(device.to_string()));
+        }
+
+        let mut candidates = config.formats.clone();
+        candidates.extend(mode.formats().iter().copied().filter(|format| !config.formats.contains(format)));
+        for &candidate in &candidates {
+            if formats.iter().any(|info| info.format == candidate) {
+                return Ok(candidate);
+            }
+        }
+
+        let fourccs = |formats: Vec<&[u8]>| formats.into_iter()
+            .map(|format| String::from_utf8_lossy(format).into_owned())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(CameraError::FormatUnsupported(format!(
+            "{} does not support any of the formats needed for {:?} capture ({}). Offered formats: {}",
+            device,
+            mode,
+            fourccs(candidates.iter().map(|format| &format[..]).collect()),
+            fourccs(formats.iter().map(|info| &info.format[..]).collect()),
+        )))
+    }
+
+    /// Checks that the camera advertises the given resolution for a pixel format.
+    fn validate_resolution(camera: &Camera, format: &[u8], resolution: (u32, u32)) -> Result<(), CameraError> {
+        let (width, height) = resolution;
+
+        match camera.resolutions(format)? {
+            ResolutionInfo::Discretes(sizes) => {
+                if !sizes.contains(&resolution) {
+                    let supported: Vec<String> = sizes.iter().map(|(w, h)| format!("{}x{}", w, h)).collect();
+                    return Err(CameraError::ResolutionUnsupported(format!(
+                        "Unsupported resolution {}x{}. Supported: {}", width, height, supported.join(", ")
+                    )));
+                }
+            }
+            ResolutionInfo::Stepwise { min, max, step } => {
+                let in_range = |value: u32, min: u32, max: u32, step: u32| {
+                    (min..=max).contains(&value) && (value - min).is_multiple_of(step.max(1))
+                };
+                if !in_range(width, min.0, max.0, step.0) || !in_range(height, min.1, max.1, step.1) {
+                    return Err(CameraError::ResolutionUnsupported(format!(
+                        "Unsupported resolution {}x{}. Supported: {}x{} to {}x{} in steps of {}x{}",
+                        width, height, min.0, min.1, max.0, max.1, step.0, step.1
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Enables or disables automatic exposure.
+    ///
+    /// Many UVC cameras only offer aperture priority as their automatic mode, so that is used when
+    /// fully automatic exposure is rejected.
+    pub fn set_exposure_auto(&mut self, enabled: bool) -> Result<(), CameraError> {
+        if !enabled {
+            return self.apply_control(rscam::CID_EXPOSURE_AUTO, rscam::EXPOSURE_MANUAL as i64);
+        }
+
+        self.apply_control(rscam::CID_EXPOSURE_AUTO, rscam::EXPOSURE_AUTO as i64)
+            .or_else(|_| self.apply_control(rscam::CID_EXPOSURE_AUTO, rscam::EXPOSURE_APERTURE_PRIORITY as i64))
+    }
+
+    /// Returns `true` if exposure is controlled automatically.
+    pub fn exposure_auto(&self) -> Result<bool, CameraError> {
+        Ok(self.control(rscam::CID_EXPOSURE_AUTO)? != rscam::EXPOSURE_MANUAL as i64)
+    }
+
+    /// Sets the manual exposure time, in units of 100 µs.
+    ///
+    /// Most cameras ignore this unless automatic exposure is disabled.
+    pub fn set_exposure_absolute(&mut self, value: i64) -> Result<(), CameraError> {
+        self.apply_control(rscam::CID_EXPOSURE_ABSOLUTE, value)
+    }
+
+    /// Returns the current exposure time, in units of 100 µs.
+    pub fn exposure_absolute(&self) -> Result<i64, CameraError> {
+        self.control(rscam::CID_EXPOSURE_ABSOLUTE)
+    }
+
+    /// Sets the manual exposure time, like [`set_exposure_absolute`](Self::set_exposure_absolute).
+    ///
+    /// Fails with [`CameraError::ControlUnsupported`] if the camera has no exposure control. Uses the
+    /// running stream if there is one, otherwise the device is opened just for the change.
+    pub fn set_exposure(&mut self, value: i64) -> Result<(), CameraError> {
+        self.set_exposure_absolute(value)
+    }
+
+    /// Enables or disables automatic exposure, like [`set_exposure_auto`](Self::set_exposure_auto).
+    ///
+    /// Fails with [`CameraError::ControlUnsupported`] if the camera has no exposure mode control.
+    pub fn set_auto_exposure(&mut self, enabled: bool) -> Result<(), CameraError> {
+        self.set_exposure_auto(enabled)
+    }
+
+    /// Enables or disables automatic white balance.
+    pub fn set_white_balance_auto(&mut self, enabled: bool) -> Result<(), CameraError> {
+        self.apply_control(rscam::CID_AUTO_WHITE_BALANCE, enabled as i64)
+    }
+
+    /// Enables or disables automatic white balance, like
+    /// [`set_white_balance_auto`](Self::set_white_balance_auto).
+    ///
+    /// Fails with [`CameraError::ControlUnsupported`] if the camera has no automatic white balance,
+    /// e.g. to lock it before a colour calibration.
+    pub fn set_auto_white_balance(&mut self, enabled: bool) -> Result<(), CameraError> {
+        self.set_white_balance_auto(enabled)
+    }
+
+    /// Returns `true` if white balance is controlled automatically.
+    pub fn white_balance_auto(&self) -> Result<bool, CameraError> {
+        Ok(self.control(rscam::CID_AUTO_WHITE_BALANCE)? != 0)
+    }
+
+    /// Sets the manual white balance temperature in Kelvin.
+    ///
+    /// Many cameras round the temperature to a coarse step, so the value accepted by the driver is
+    /// read back and returned.
+    pub fn set_white_balance_temperature(&mut self, kelvin: i64) -> Result<i64, CameraError> {
+        self.apply_control(rscam::CID_WHITE_BALANCE_TEMPERATURE, kelvin)?;
+        self.white_balance_temperature()
+    }
+
+    /// Returns the current white balance temperature in Kelvin.
+    pub fn white_balance_temperature(&self) -> Result<i64, CameraError> {
+        self.control(rscam::CID_WHITE_BALANCE_TEMPERATURE)
+    }
+
+    /// Enables or disables continuous autofocus.
+    pub fn set_autofocus(&mut self, enabled: bool) -> Result<(), CameraError> {
+        self.apply_control(rscam::CID_FOCUS_AUTO, enabled as i64)
+    }
+
+    /// Returns `true` if the camera is focusing automatically.
+    pub fn autofocus(&self) -> Result<bool, CameraError> {
+        Ok(self.control(rscam::CID_FOCUS_AUTO)? != 0)
+    }
+
+    /// Sets the lens position, clamped to the range the camera supports.
+    ///
+    /// Most cameras ignore this unless autofocus is disabled. Returns the value that was applied.
+    pub fn set_focus_absolute(&mut self, value: i64) -> Result<i64, CameraError> {
+        self.set_control(rscam::CID_FOCUS_ABSOLUTE, value)
+    }
+
+    /// Returns the current lens position.
+    pub fn focus_absolute(&self) -> Result<i64, CameraError> {
+        self.control(rscam::CID_FOCUS_ABSOLUTE)
+    }
+
+    /// Sets the power line frequency used for anti-flicker compensation.
+    ///
+    /// Many cameras only offer some of the settings, so this returns `false` without changing
+    /// anything if the camera doesn't accept the requested one.
+    pub fn set_power_line_frequency(&mut self, frequency: PowerLineFrequency) -> Result<bool, CameraError> {
+        let value = frequency as i64;
+        let offered = self.with_device(|camera| {
+            match Self::query_control(camera, rscam::CID_POWER_LINE_FREQUENCY)?.data {
+                CtrlData::Menu { items, .. } => Ok(items.iter().any(|item| item.index as i64 == value)),
+                _ => Ok(true),
+            }
+        })?;
+        if !offered {
+            return Ok(false);
+        }
+
+        match self.apply_control(rscam::CID_POWER_LINE_FREQUENCY, value) {
+            Ok(()) => Ok(true),
+            Err(CameraError::ControlRejected(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the current power line frequency setting.
+    pub fn power_line_frequency(&self) -> Result<PowerLineFrequency, CameraError> {
+        let value = self.control(rscam::CID_POWER_LINE_FREQUENCY)?;
+        PowerLineFrequency::from_value(value)
+            .ok_or_else(|| format!("Unknown power line frequency setting {}", value).into())
+    }
+
+    /// Sets the sensor gain, clamped to the range the camera supports.
+    ///
+    /// Uses the user gain control where available and falls back to the analogue gain control.
+    /// Returns the value that was applied, or [`CameraError::ControlUnsupported`] if the camera has neither.
+    pub fn set_gain(&mut self, value: i64) -> Result<i64, CameraError> {
+        let id = self.gain_control()?;
+        self.set_control(id, value)
+    }
+
+    /// Returns the current sensor gain.
+    pub fn gain(&self) -> Result<i64, CameraError> {
+        self.control(self.gain_control()?)
+    }
+
+    /// Picks the gain control the camera implements.
+    fn gain_control(&self) -> Result<u32, CameraError> {
+        self.with_device(|camera| {
+            for id in [rscam::CID_GAIN, rscam::CID_ANALOGUE_GAIN] {
+                match Self::query_control(camera, id) {
+                    Ok(_) => return Ok(id),
+                    Err(CameraError::ControlUnsupported(_)) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(CameraError::ControlUnsupported(rscam::CID_GAIN))
+        })
+    }
+
+    /// Sets any V4L2 control by ID, clamping the value to the range the control reports.
+    ///
+    /// Returns the value that was applied. The value is reapplied whenever the camera is reopened.
+    pub fn set_control(&mut self, id: u32, value: i64) -> Result<i64, CameraError> {
+        let clamped = self.with_device(|camera| {
+            let control = Self::query_control(camera, id)?;
+
+            //Snap onto the control's step so the driver doesn't round the value itself
+            let snap = |value: i64, min: i64, max: i64, step: i64| {
+                let value = clamp(value, min, max);
+                min + (value - min) / step.max(1) * step.max(1)
+            };
+
+            Ok(match control.data {
+                CtrlData::Integer { minimum, maximum, step, .. } => {
+                    snap(value, minimum as i64, maximum as i64, step as i64)
+                }
+                CtrlData::Integer64 { minimum, maximum, step, .. } => snap(value, minimum, maximum, step),
+                CtrlData::Boolean { .. } => clamp(value, 0, 1),
+                _ => value,
+            })
+        })?;
+
+        self.apply_control(id, clamped)?;
+        Ok(clamped)
+    }
+
+    /// Sets a V4L2 control and remembers it so it is reapplied whenever the camera is reopened.
+    ///
+    /// If the camera isn't running the control is set through a temporary handle to the device.
+    fn apply_control(&mut self, id: u32, value: i64) -> Result<(), CameraError> {
+        self.with_device(|camera| Self::write_control(camera, id, value))?;
+
+        self.config.controls.retain(|&(control, _)| control != id);
+        self.config.controls.push((id, value));
+        Ok(())
+    }
+
+    /// Reads the current value of a V4L2 control.
+    pub fn control(&self, id: u32) -> Result<i64, CameraError> {
+        self.with_device(|camera| {
+            let control = Self::query_control(camera, id)?;
+
+            match control.data {
+                CtrlData::Integer { value, .. } => Ok(value as i64),
+                CtrlData::Boolean { value, .. } => Ok(value as i64),
+                CtrlData::Menu { value, .. } => Ok(value as i64),
+                CtrlData::Integer64 { value, .. } => Ok(value),
+                CtrlData::Bitmask { value, .. } => Ok(value as i64),
+                CtrlData::IntegerMenu { value, .. } => Ok(value as i64),
+                _ => Err(format!("Control {} does not hold a numeric value", control.name).into()),
+            }
+        })
+    }
+
+    /// Returns the valid range, default and current value of a V4L2 control, e.g. to build a slider.
+    ///
+    /// Booleans are reported as a 0 to 1 range and menus as the range of their item indices. Uses the
+    /// running camera if there is one, otherwise the device is opened just for the query.
+    ///
+    /// Returns `None` if the camera doesn't implement the control.
+    pub fn control_range(&self, id: u32) -> Result<Option<ControlRange>, CameraError> {
+        let control = match self.with_device(|camera| Self::query_control(camera, id)) {
+            Ok(control) => control,
+            Err(CameraError::ControlUnsupported(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let (minimum, maximum, step, default, value) = match &control.data {
+            CtrlData::Integer { value, default, minimum, maximum, step } => {
+                (*minimum as i64, *maximum as i64, *step as i64, *default as i64, *value as i64)
+            }
+            CtrlData::Integer64 { value, default, minimum, maximum, step } => {
+                (*minimum, *maximum, *step, *default, *value)
+            }
+            CtrlData::Boolean { value, default } => (0, 1, 1, *default as i64, *value as i64),
+            CtrlData::Menu { value, default, items } => {
+                let indices = items.iter().map(|item| item.index as i64);
+                (indices.clone().min().unwrap_or(0), indices.max().unwrap_or(0), 1, *default as i64, *value as i64)
+            }
+            CtrlData::IntegerMenu { value, default, items } => {
+                let indices = items.iter().map(|item| item.index as i64);
+                (indices.clone().min().unwrap_or(0), indices.max().unwrap_or(0), 1, *default as i64, *value as i64)
+            }
+            CtrlData::Bitmask { value, default, maximum } => (0, *maximum as i64, 1, *default as i64, *value as i64),
+            _ => return Err(format!("Control {} does not hold a numeric value", control.name).into()),
+        };
+
+        Ok(Some(ControlRange {
+            minimum,
+            maximum,
+            step,
+            default,
+            value,
+            enabled: control.flags & (rscam::FLAG_DISABLED | rscam::FLAG_INACTIVE) == 0,
+            read_only: control.flags & rscam::FLAG_READ_ONLY != 0,
+        }))
+    }
+
+    /// Returns the valid range, default and current value of a V4L2 control like
+    /// [`control_range`](Self::control_range), but fails with [`CameraError::ControlUnsupported`]
+    /// if the camera doesn't implement the control.
+    pub fn query_control_range(&self, id: u32) -> Result<ControlRange, CameraError> {
+        self.control_range(id)?.ok_or(CameraError::ControlUnsupported(id))
+    }
+
+    /// Sets the picture brightness, clamped to the range the camera supports, see
+    /// [`query_control_range`](Self::query_control_range) with [`rscam::CID_BRIGHTNESS`].
+    ///
+    /// Returns the value that was applied, or [`CameraError::ControlUnsupported`] if the camera has
+    /// no brightness control. Takes effect on the running stream without restarting it.
+    pub fn set_brightness(&mut self, value: i64) -> Result<i64, CameraError> {
+        self.set_control(rscam::CID_BRIGHTNESS, value)
+    }
+
+    /// Sets the picture contrast, clamped to the range the camera supports.
+    ///
+    /// Returns the value that was applied, or [`CameraError::ControlUnsupported`] if the camera has
+    /// no contrast control.
+    pub fn set_contrast(&mut self, value: i64) -> Result<i64, CameraError> {
+        self.set_control(rscam::CID_CONTRAST, value)
+    }
+
+    /// Sets the colour saturation, clamped to the range the camera supports.
+    ///
+    /// Returns the value that was applied, or [`CameraError::ControlUnsupported`] if the camera has
+    /// no saturation control.
+    pub fn set_saturation(&mut self, value: i64) -> Result<i64, CameraError> {
+        self.set_control(rscam::CID_SATURATION, value)
+    }
+
+    /// Runs `f` against the running camera, or a temporary handle to the configured device.
+    fn with_device<T>(&self, f: impl FnOnce(&Camera) -> Result<T, CameraError>) -> Result<T, CameraError> {
+        match &self.camera {
+            Some(camera) => f(camera),
+            None => {
+                let camera = Camera::new(&self.config.device)
+                    .map_err(|e| CameraError::DeviceOpen(self.config.device.clone(), e))?;
+                f(&camera)
+            }
+        }
+    }
+
+    /// Queries a control, returning [`CameraError::ControlUnsupported`] if the camera doesn't implement it.
+    fn query_control(camera: &Camera, id: u32) -> Result<Control, CameraError> {
+        camera.get_control(id).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => CameraError::ControlUnsupported(id),
+            _ => format!("Failed to query control {:#x}: {}", id, e).into(),
+        })
+    }
+
+    /// Writes a control value, describing the control and the values it accepts if the driver rejects it.
+    fn write_control(camera: &Camera, id: u32, value: i64) -> Result<(), CameraError> {
+        let error = match camera.set_control(id, &value) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        let control = Self::query_control(camera, id)?;
+
+        let accepted = match control.data {
+            CtrlData::Integer { minimum, maximum, .. } => format!("{} to {}", minimum, maximum),
+            CtrlData::Integer64 { minimum, maximum, .. } => format!("{} to {}", minimum, maximum),
+            CtrlData::Boolean { .. } => String::from("0 or 1"),
+            CtrlData::Menu { items, .. } => items.iter()
+                .map(|item| format!("{} ({})", item.index, item.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            CtrlData::IntegerMenu { items, .. } => items.iter()
+                .map(|item| format!("{} ({})", item.index, item.value))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::from("unknown"),
+        };
+
+        Err(CameraError::ControlRejected(format!(
+            "Failed to set {} to {}: {}. Accepted values: {}", control.name, value, error, accepted
+        )))
+    }
+
+    /// Captures a single image from the camera.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<u8>` containing the JPEG image data, or raw pixels laid out as set by [`ColorMode`].
+    pub fn capture_image(&mut self) -> Result<Vec<u8>, CameraError> {
+        Ok(self.capture_frame()?.data)
+    }
+
+    /// Captures a single image from the camera along with its dimensions.
+    pub fn capture_frame(&mut self) -> Result<CapturedFrame, CameraError> {
+        let mut captured = CapturedFrame::default();
+        self.capture_frame_into(&mut captured)?;
+        Ok(captured)
+    }
+
+    /// Captures a single image into an existing frame, reusing its buffer.
+    pub fn capture_frame_into(&mut self, captured: &mut CapturedFrame) -> Result<(), CameraError> {
+        let frame = self.capture_driver_frame()?;
+
+        match self.config.color_mode {
+            //The frame data is in MJPEG format (JPEG)
+            ColorMode::Jpeg => {
+                captured.data.clear();
+                captured.data.extend_from_slice(&frame);
+                (captured.width, captured.height) = frame.resolution;
+            }
+            ColorMode::Grayscale => self.convert_frame(&frame, convert::yuv422_to_gray, captured)?,
+            ColorMode::Rgb => self.convert_frame(&frame, convert::yuv422_to_rgb24, captured)?,
+            ColorMode::Bgr => self.convert_frame(&frame, convert::yuv422_to_bgr24, captured)?,
+        }
+
+        self.frame_id += 1;
+
+        //Measure the frame before the overlay is drawn, so the changing time doesn't count as motion
+        self.update_histogram(captured);
+        self.detect_motion(captured);
+        self.detect_edges(captured);
+        self.filter_colors(captured);
+        self.draw_overlay(captured);
+        self.keep_latest(captured);
+        FRAME_READY.publish(self.frame_id);
+
+        if let Some(callback) = self.frame_callback.as_mut() {
+            callback(&captured.data, self.frame_id);
+        }
+        Ok(())
+    }
+
+    /// Dequeues the next frame from the driver as it was delivered, opening the camera if needed,
+    /// pacing the capture and reopening the camera if it was unplugged.
+    fn capture_driver_frame(&mut self) -> Result<Frame, CameraError> {
+        self.ensure_started()?;
+        self.pace_capture();
+
+        match self.camera.as_ref().ok_or("Camera is not running")?.capture() {
+            Ok(frame) => Ok(frame),
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENODEV) | Some(libc::EIO)) => {
+                self.reconnect()?;
+                Ok(self.camera.as_ref().ok_or("Camera is not running")?.capture()?)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Captures a single still and encodes it as a JPEG.
+    ///
+    /// The camera is opened just for the snapshot and closed again afterwards, so this fails with
+    /// [`CameraError::StreamRunning`] if the camera is already streaming. Grayscale mode produces a
+    /// grayscale JPEG, every other mode a colour one. `quality` overrides the configured JPEG
+    /// quality for this snapshot, e.g. to keep timelapse stills smaller than other captures. The
+    /// JPEG carries EXIF metadata with the time it was taken, its size, and the exposure and gain.
+    pub fn capture_snapshot(&mut self, quality: Option<u8>) -> Result<Vec<u8>, CameraError> {
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+        let (frame, channels) = self.capture_still_pixels()?;
+        let jpeg = jpeg::encode(&frame.data, frame.width, frame.height, channels, quality);
+        Ok(self.add_exif(&jpeg, frame.width, frame.height))
+    }
+
+    /// Adds EXIF metadata to a JPEG still of the given size that has just been taken.
+    ///
+    /// The metadata records the current time, the size, and the exposure and gain controls where the
+    /// camera implements them and they are in effect, i.e. not while automatic exposure sets them.
+    /// Reading the controls opens the device briefly if the camera isn't running.
+    fn add_exif(&self, jpeg: &[u8], width: u32, height: u32) -> Vec<u8> {
+        exif::insert(jpeg, &self.exif_metadata(width, height))
+    }
+
+    /// Collects the EXIF metadata [`add_exif`](Self::add_exif) records for a still of the given size.
+    fn exif_metadata(&self, width: u32, height: u32) -> exif::Metadata {
+        let control = |id| match self.control_range(id) {
+            Ok(Some(range)) if range.enabled => Some(range.value),
+            _ => None,
+        };
+        exif::Metadata {
+            time: SystemTime::now(),
+            width,
+            height,
+            exposure: control(rscam::CID_EXPOSURE_ABSOLUTE),
+            gain: control(rscam::CID_GAIN),
+        }
+    }
+
+    /// Captures a single still and saves it losslessly as a PNG file.
+    ///
+    /// This is [`capture_still`](Self::capture_still) with [`ImageFormat::Png`], so it also works
+    /// while the camera is streaming in a raw color mode, and fails with
+    /// [`CameraError::FormatUnsupported`] while it streams JPEG. The file is written and synced to
+    /// disk before this returns, so it can be read back straight away.
+    pub fn capture_snapshot_png(&mut self, path: &str) -> Result<(), CameraError> {
+        self.capture_still(path, ImageFormat::Png, None)
+    }
+
+    /// Captures a single still and saves it to a file in the given format.
+    ///
+    /// JPEG stills are encoded at `quality`, or the configured JPEG quality if it is `None`; PNG, BMP
+    /// and PPM stills are lossless and ignore it. The format is never guessed from the file name.
+    ///
+    /// Unlike [`capture_snapshot`](Self::capture_snapshot) this also works while the camera is
+    /// streaming, taking the next frame from the running stream and leaving it running. Otherwise the
+    /// camera is opened just for the still and closed again. In JPEG mode a streamed frame is saved as
+    /// the camera delivered it, and can't be saved in a lossless format. The file is written and synced to disk
+    /// before this returns.
+    pub fn capture_still(
+        &mut self,
+        path: &str,
+        format: ImageFormat,
+        quality: Option<u8>,
+    ) -> Result<(), CameraError> {
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        if format == ImageFormat::Jpeg {
+            Self::validate_quality(quality)?;
+        }
+
+        let image = if self.camera.is_some() {
+            let mut frame = CapturedFrame::default();
+            self.capture_frame_into(&mut frame)?;
+            let mode = self.config.color_mode;
+            match format {
+                ImageFormat::Jpeg => {
+                    let (width, height) = (frame.width, frame.height);
+                    let mut encoded = Vec::new();
+                    let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut jpeg::Encoder::new(), &mut encoded);
+                    self.add_exif(jpeg, width, height)
+                }
+                ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Ppm => {
+                    let channels = match mode {
+                        ColorMode::Jpeg => {
+                            return Err(CameraError::FormatUnsupported(String::from(
+                                "Lossless stills need a raw color mode while the camera is streaming",
+                            )))
+                        }
+                        ColorMode::Grayscale => Channels::Gray,
+                        ColorMode::Rgb => Channels::Rgb,
+                        ColorMode::Bgr => {
+                            swap_red_blue(&mut frame.data);
+                            Channels::Rgb
+                        }
+                    };
+                    format.encode_lossless(&frame, channels)?
+                }
+            }
+        } else {
+            match format {
+                ImageFormat::Jpeg => self.capture_snapshot(Some(quality))?,
+                ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Ppm => {
+                    let (frame, channels) = self.capture_still_pixels()?;
+                    format.encode_lossless(&frame, channels)?
+                }
+            }
+        };
+
+        let mut file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        file.write_all(&image)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Captures one frame exactly as the driver delivered it and writes it to `path`, to tell camera
+    /// and driver problems apart from conversion bugs.
+    ///
+    /// Nothing is cropped, flipped or converted, so the file holds YUYV bytes, or the camera's own
+    /// JPEG data in JPEG mode. A sidecar file named `path` with `.toml` appended records the width,
+    /// height, pixel format, bytes per row and bytes used of the frame. Uses the running stream if
+    /// there is one, otherwise the camera is opened just for the frame and closed again.
+    pub fn capture_raw_frame(&mut self, path: &str) -> Result<(), CameraError> {
+        let running = self.camera.is_some();
+        self.ensure_started()?;
+        let result = self.write_raw_frame(path);
+        if !running {
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Dequeues one frame from the running stream and writes it and its sidecar file.
+    fn write_raw_frame(&mut self, path: &str) -> Result<(), CameraError> {
+        let frame = self.camera.as_ref().ok_or("Camera is not running")?.capture()?;
+
+        let mut file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        file.write_all(&frame)?;
+        file.sync_all()?;
+
+        let (width, height) = frame.resolution;
+        let sidecar = format!(
+            "width = {}\nheight = {}\nfourcc = \"{}\"\nbytes_per_line = {}\nbytes_used = {}\n",
+            width,
+            height,
+            String::from_utf8_lossy(&frame.format),
+            self.stride,
+            frame.len()
+        );
+        fs::write(format!("{}.toml", path), sidecar)?;
+        Ok(())
+    }
+
+    /// Opens the camera, captures one frame of raw pixels for encoding, and closes it again.
+    ///
+    /// Grayscale mode captures grayscale pixels, every other mode RGB.
+    fn capture_still_pixels(&mut self) -> Result<(CapturedFrame, Channels), CameraError> {
+        if self.camera.is_some() {
+            return Err(CameraError::StreamRunning);
+        }
+
+        let mode = self.config.color_mode;
+        let (capture_mode, channels) = match mode {
+            ColorMode::Grayscale => (ColorMode::Grayscale, Channels::Gray),
+            _ => (ColorMode::Rgb, Channels::Rgb),
+        };
+        self.config.color_mode = capture_mode;
+        let frame = self.capture_frame();
+        self.config.color_mode = mode;
+        self.stop()?;
+
+        let frame = frame?;
+        if frame.width == 0 || frame.height == 0 {
+            return Err(CameraError::Other("Camera returned an empty frame".into()));
+        }
+        Ok((frame, channels))
+    }
+
+    /// Records frames to a video file for `duration_seconds`, or until [`stop_recording`] is called.
+    ///
+    /// With [`VideoCodec::H264`] the frames are encoded by the device's hardware H.264 encoder into
+    /// an MP4 file, falling back to Motion JPEG if there is no encoder. The encoder needs raw
+    /// pixels, so in the JPEG and grayscale modes the camera must be stopped first, and it is opened
+    /// in RGB mode and stopped again afterwards.
+    ///
+    /// With [`VideoCodec::Mjpeg`] the frames are stored in an AVI file, and with
+    /// [`VideoCodec::RawMjpeg`] written back to back with no container, for tools that expect the
+    /// older format, with a frame index next to it as described on [`VideoStats`]. In the raw color
+    /// modes they are encoded at `quality`, or the configured JPEG quality if it is `None`.
+    ///
+    /// With [`VideoCodec::Y4m`] the YUV 4:2:2 frames the camera delivers are written to a YUV4MPEG2
+    /// stream without any JPEG encoding, split into planes but otherwise exactly as captured, so
+    /// cropping, mirroring, scaling and the overlay don't apply and `quality` is ignored. The header
+    /// gives the exact rate frames are captured at unless `output_fps` is set. A 640x480 stream
+    /// takes about 18 MB per second at 30 fps. As for H.264 the camera must be stopped first in
+    /// JPEG mode, and it is opened in RGB mode and stopped again afterwards.
+    ///
+    /// Container headers give the configured frame rate, or `output_fps` if it is set. The camera
+    /// keeps capturing at its own rate, so the latest frame stays fresh for other users, and only
+    /// every so often a frame is encoded and written, e.g. every 6th to record at 5 fps from a
+    /// 30 fps stream. An `output_fps` above the rate the camera captures at is lowered to that rate,
+    /// with a warning in the returned stats.
+    ///
+    /// Otherwise uses the running stream if there is one, or opens the camera and leaves it running.
+    /// The file is finished, flushed and synced to disk before this returns, even if a capture fails
+    /// part way through.
+    ///
+    /// Returns the number of frames written, which may be fewer than the duration implies if the
+    /// recording was stopped early, the frame rate the file plays back at, whether [`stop_recording`]
+    /// stopped it, and how well the recording kept up with the camera, so a file that will play back
+    /// too fast can be spotted.
+    pub fn capture_video(
+        &mut self,
+        path: &str,
+        duration_seconds: u32,
+        quality: Option<u8>,
+        codec: VideoCodec,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        if duration_seconds == 0 {
+            return Err(CameraError::InvalidArgument("Recording duration must be non-zero".into()));
+        }
+        if output_fps == Some(0) {
+            return Err(CameraError::InvalidArgument("Output frame rate must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let limit = RecordLimit::duration(Duration::from_secs(duration_seconds as u64));
+        self.record_video(path, limit, quality, codec, output_fps)
+    }
+
+    /// Records exactly `frame_count` frames to a video file, or fewer if [`stop_recording`] is
+    /// called, e.g. for tests and scripted captures that need a known number of frames.
+    ///
+    /// Works like [`capture_video`](Self::capture_video) otherwise, with no time limit. With an
+    /// `output_fps` only the frames written count, so the recording takes `frame_count` frame
+    /// intervals at that rate. A capture failure still ends the recording early, with the error
+    /// returned once the file is finished.
+    pub fn capture_video_frames(
+        &mut self,
+        path: &str,
+        frame_count: u32,
+        quality: Option<u8>,
+        codec: VideoCodec,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        if frame_count == 0 {
+            return Err(CameraError::InvalidArgument("Recording frame count must be non-zero".into()));
+        }
+        if output_fps == Some(0) {
+            return Err(CameraError::InvalidArgument("Output frame rate must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let limit = RecordLimit { deadline: None, max_frames: Some(frame_count) };
+        self.record_video(path, limit, quality, codec, output_fps)
+    }
+
+    /// Does the recording for [`capture_video`](Self::capture_video) and
+    /// [`capture_video_frames`](Self::capture_video_frames) with `codec` until `limit` is reached.
+    fn record_video(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        quality: u8,
+        codec: VideoCodec,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        let encoder = match codec {
+            VideoCodec::H264 => h264::find_encoder(H264_INPUT),
+            VideoCodec::RawMjpeg => return self.record_raw_mjpeg(path, limit, quality, output_fps),
+            VideoCodec::Y4m => return self.record_y4m(path, limit, output_fps),
+            VideoCodec::Mjpeg => None,
+        };
+        match encoder {
+            Some(encoder) => self.record_h264(path, limit, &encoder, h264::BITRATE, true, output_fps),
+            None => self.record_mjpeg(path, limit, quality, output_fps),
+        }
+    }
+
+    /// Returns how the last recording made by [`capture_video`](Self::capture_video),
+    /// [`capture_video_to_memory`](Self::capture_video_to_memory) or
+    /// [`capture_video_h264`](Self::capture_video_h264) kept up with the camera, or `None` before the
+    /// first. Unlike the stats a recording returns, this is kept if the recording fails part way.
+    pub fn last_video_timing(&self) -> Option<FrameTiming> {
+        self.video_timing
+    }
+
+    /// Works out the frame rate a recording started on the running camera is written at, and which
+    /// captured frames it keeps to get there. Without `output_fps` every frame is kept and the file
+    /// plays at the configured rate.
+    fn recording_rate(&self, output_fps: Option<u32>) -> (u32, Decimator, Option<String>) {
+        //Paced captures arrive at the configured rate, others at the rate negotiated with the driver
+        let capture_fps = self.frame_rate.min(self.config.fps as f64);
+        let fps = match output_fps {
+            Some(fps) if capture_fps > 0.0 => fps,
+            _ => return (self.config.fps, Decimator::new(1.0), None),
+        };
+
+        let clamped = (capture_fps.round() as u32).max(1);
+        if fps >= clamped {
+            let warning = (fps > clamped).then(|| {
+                format!(
+                    "Output frame rate {} fps is above the {:.1} fps the camera captures at, recording at {} fps",
+                    fps, capture_fps, clamped
+                )
+            });
+            return (clamped, Decimator::new(1.0), warning);
+        }
+        (fps, Decimator::new(fps as f64 / capture_fps), None)
+    }
+
+    /// Captures `count` consecutive frames into numbered JPEG files in `dir`, named `frame_0001.jpg`
+    /// onwards, e.g. to study motion blur.
+    ///
+    /// Frames are taken as fast as the camera delivers them, without the software pacing used to
+    /// hold lower frame rates. Raw frames are encoded at `quality`, or the configured JPEG quality if
+    /// it is `None`. `dir` is created if needed. Like [`capture_video`](Self::capture_video) this uses
+    /// the running stream if there is one, or opens the camera and leaves it running.
+    ///
+    /// Returns `count` once every frame is saved.
+    ///
+    /// # Errors
+    ///
+    /// A failure on the first frame is returned as it is. If capturing or writing fails later on,
+    /// the files already written are kept and [`CameraError::Interrupted`] holds how many there are
+    /// along with the error, so a camera unplugged mid-burst doesn't pass for a short burst.
+    pub fn capture_burst(&mut self, dir: &str, count: u32, quality: Option<u8>) -> Result<u32, CameraError> {
+        if count == 0 {
+            return Err(CameraError::InvalidArgument("Burst frame count must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+        fs::create_dir_all(dir).map_err(|e| CameraError::Other(format!("Failed to create {}: {}", dir, e)))?;
+
+        let pacing = self.frame_pacing.take();
+        let result = self.capture_burst_frames(Path::new(dir), count, quality);
+        self.frame_pacing = pacing;
+        result
+    }
+
+    /// Does the capturing for [`CameraController::capture_burst`] with pacing turned off.
+    fn capture_burst_frames(&mut self, dir: &Path, count: u32, quality: u8) -> Result<u32, CameraError> {
+        //Reuse the frame, encoder and JPEG buffers so a burst doesn't allocate per frame
+        let mut frame = CapturedFrame::default();
+        let mut encoder = jpeg::Encoder::new();
+        let mut encoded = Vec::new();
+
+        run_burst(count, |number| {
+            self.capture_frame_into(&mut frame)?;
+            let mode = self.config.color_mode;
+            let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut encoder, &mut encoded);
+
+            let path = dir.join(format!("frame_{:04}.jpg", number));
+            fs::write(&path, jpeg).map_err(|e| CameraError::Other(format!("Failed to write {}: {}", path.display(), e)))
+        })
+    }
+
+    /// Captures `count` consecutive frames into memory as packed RGB pixels, e.g. to stack them to
+    /// take out sensor noise.
+    ///
+    /// Frames are taken as fast as the camera delivers them, like [`capture_burst`](Self::capture_burst)
+    /// does. Grayscale frames are widened to RGB and BGR ones swapped round. Uses the running stream
+    /// if there is one, or opens the camera and leaves it running. JPEG frames aren't decoded, so in
+    /// JPEG mode the camera must be stopped first, and it is opened in RGB mode and stopped again
+    /// afterwards.
+    ///
+    /// Every frame takes `width * height * 3` bytes, at the size [`frame_size`](Self::frame_size)
+    /// reports in RGB mode, so a burst of [`MAX_MEMORY_BURST_FRAMES`] at 640x480 holds about 29 MB.
+    ///
+    /// Returns all `count` frames.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] if `count` is zero or above
+    /// [`MAX_MEMORY_BURST_FRAMES`], and [`CameraError::FormatUnsupported`] in JPEG mode if the camera
+    /// is running. A capture failure on the first frame is returned as it is, and a later one as
+    /// [`CameraError::Interrupted`] with the number of frames captured before it.
+    pub fn capture_burst_to_memory(&mut self, count: usize) -> Result<Vec<Vec<u8>>, CameraError> {
+        if count == 0 || count > MAX_MEMORY_BURST_FRAMES {
+            return Err(CameraError::InvalidArgument(format!(
+                "Burst frame count must be between 1 and {}, got {}",
+                MAX_MEMORY_BURST_FRAMES, count
+            )));
+        }
+        let mode = self.config.color_mode;
+        if mode == ColorMode::Jpeg {
+            if self.camera.is_some() {
+                return Err(CameraError::FormatUnsupported(String::from(
+                    "Capturing a burst to memory needs a raw color mode while the camera is streaming",
+                )));
+            }
+            self.config.color_mode = ColorMode::Rgb;
+        }
+
+        let pacing = self.frame_pacing.take();
+        let result = self.capture_memory_burst_frames(count);
+        self.frame_pacing = pacing;
+        if mode == ColorMode::Jpeg {
+            self.config.color_mode = mode;
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Does the capturing for [`CameraController::capture_burst_to_memory`] once pacing is off and
+    /// the color mode is set.
+    fn capture_memory_burst_frames(&mut self, count: usize) -> Result<Vec<Vec<u8>>, CameraError> {
+        let mut frame = CapturedFrame::default();
+        let mut frames = Vec::with_capacity(count);
+        run_burst(count as u32, |_| {
+            self.capture_frame_into(&mut frame)?;
+            let rgb = match self.config.color_mode {
+                ColorMode::Grayscale => frame.data.iter().flat_map(|&luma| [luma; 3]).collect(),
+                ColorMode::Bgr => {
+                    swap_red_blue(&mut frame.data);
+                    frame.data.clone()
+                }
+                _ => frame.data.clone(),
+            };
+            frames.push(rgb);
+            Ok(())
+        })?;
+        Ok(frames)
+    }
+
+    /// Takes a photo every `interval` into numbered JPEG files in `dir`, named `frame_0001.jpg`
+    /// onwards, until `frames` have been taken or [`stop_recording`] is called.
+    ///
+    /// Raw frames are encoded at `quality`, or the configured JPEG quality if it is `None`. `dir` is
+    /// created if needed. With `power_down` the camera is closed between shots to save power and CPU,
+    /// which suits long intervals; otherwise the running stream is used, or the camera is opened and
+    /// left running. Each file is synced to disk as it is written, so the photos already taken survive
+    /// if the timelapse fails or the robot loses power.
+    ///
+    /// Returns the number of photos taken, fewer than `frames` if it was stopped early. If a capture
+    /// or write fails, the error says how many photos had been saved.
+    pub fn capture_timelapse(
+        &mut self,
+        dir: &str,
+        interval: Duration,
+        frames: u32,
+        quality: Option<u8>,
+        power_down: bool,
+    ) -> Result<u32, CameraError> {
+        if frames == 0 {
+            return Err(CameraError::InvalidArgument("Timelapse frame count must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+        fs::create_dir_all(dir).map_err(|e| CameraError::Other(format!("Failed to create {}: {}", dir, e)))?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        if power_down {
+            self.stop()?;
+        }
+
+        let mut frame = CapturedFrame::default();
+        let mut encoder = jpeg::Encoder::new();
+        let mut encoded = Vec::new();
+        let mut next_shot = Instant::now();
+
+        for number in 1..=frames {
+            //Sleep in short steps so a stop request doesn't wait for the rest of the interval
+            while Instant::now() < next_shot {
+                if STOP_RECORDING.load(Ordering::SeqCst) {
+                    return Ok(number - 1);
+                }
+                thread::sleep(TIMELAPSE_POLL_INTERVAL.min(next_shot - Instant::now()));
+            }
+            if STOP_RECORDING.load(Ordering::SeqCst) {
+                return Ok(number - 1);
+            }
+            next_shot += interval;
+
+            let path = Path::new(dir).join(format!("frame_{:04}.jpg", number));
+            let shot = if power_down {
+                self.capture_snapshot(Some(quality)).and_then(|jpeg| Self::write_synced(&path, &jpeg))
+            } else {
+                self.capture_frame_into(&mut frame).and_then(|()| {
+                    let (mode, width, height) = (self.config.color_mode, frame.width, frame.height);
+                    let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut encoder, &mut encoded);
+                    Self::write_synced(&path, &self.add_exif(jpeg, width, height))
+                })
+            };
+            if let Err(e) = shot {
+                return Err(CameraError::Other(format!(
+                    "Timelapse stopped at frame {} of {} with {} saved: {}",
+                    number, frames, number - 1, e
+                )));
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Records every captured frame as a JPEG file of its own in `dir`, named by `pattern`, e.g.
+    /// `run3_000001.jpg` onwards for `run3_%06d.jpg`, to collect training data.
+    ///
+    /// The pattern must hold exactly one printf-style integer placeholder, `%d`, `%i` or `%u` with an
+    /// optional width and `0` flag, and `%%` for a literal `%`. Frames are numbered from 1. The recording ends after `duration`, after `max_frames` files, or
+    /// when [`stop_recording`] is called, whichever comes first; at least one limit must be given.
+    /// Raw frames are encoded at `quality`, or the configured JPEG quality if it is `None`. `dir` is
+    /// created if needed. Like [`capture_video`](Self::capture_video) this uses the running stream if
+    /// there is one, or opens the camera and leaves it running.
+    ///
+    /// Each file goes through a buffered writer and is flushed before the next frame, but not synced,
+    /// to keep up with the frame rate. A file that can't be written completely, e.g. because the disk
+    /// filled up, is removed, and the error says how many files had been written.
+    ///
+    /// Returns the number of files written.
+    pub fn capture_frame_sequence(
+        &mut self,
+        dir: &str,
+        pattern: &str,
+        duration: Option<Duration>,
+        max_frames: Option<u32>,
+        quality: Option<u8>,
+    ) -> Result<u32, CameraError> {
+        let pattern = FramePattern::parse(pattern)?;
+        if duration.is_none() && max_frames.is_none() {
+            return Err(CameraError::InvalidArgument("A frame sequence needs a duration or a frame limit".into()));
+        }
+        if duration == Some(Duration::ZERO) || max_frames == Some(0) {
+            return Err(CameraError::InvalidArgument("Frame sequence limits must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+        fs::create_dir_all(dir).map_err(|e| CameraError::Other(format!("Failed to create {}: {}", dir, e)))?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let deadline = duration.map(|duration| Instant::now() + duration);
+
+        let mut frame = CapturedFrame::default();
+        let mut encoder = jpeg::Encoder::new();
+        let mut encoded = Vec::new();
+        let mut written = 0;
+
+        while max_frames.is_none_or(|max| written < max)
+            && deadline.is_none_or(|deadline| Instant::now() < deadline)
+            && !STOP_RECORDING.load(Ordering::SeqCst)
+        {
+            let path = Path::new(dir).join(pattern.format(written + 1));
+            let saved = self.capture_frame_into(&mut frame).and_then(|()| {
+                let mode = self.config.color_mode;
+                let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut encoder, &mut encoded);
+                Self::write_buffered(&path, jpeg).inspect_err(|_| {
+                    let _ = fs::remove_file(&path);
+                })
+            });
+            if let Err(e) = saved {
+                return Err(CameraError::Other(format!(
+                    "Frame sequence stopped at {} with {} files written: {}",
+                    path.display(),
+                    written,
+                    e
+                )));
+            }
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Records every captured frame for `duration_seconds` as a JPEG file of its own in `dir`, named
+    /// `frame_0001.jpg` onwards, for post-processing.
+    ///
+    /// This is [`capture_frame_sequence`](Self::capture_frame_sequence) with the `frame_%04d.jpg`
+    /// pattern and no frame limit; numbers past 9999 just grow wider. Raw frames are encoded at
+    /// `quality`. Returns the number of files written.
+    pub fn capture_frames(&mut self, dir: &str, duration_seconds: u32, quality: u8) -> Result<u32, CameraError> {
+        if duration_seconds == 0 {
+            return Err(CameraError::InvalidArgument("Recording duration must be non-zero".into()));
+        }
+        let duration = Duration::from_secs(duration_seconds as u64);
+        self.capture_frame_sequence(dir, "frame_%04d.jpg", Some(duration), None, Some(quality))
+    }
+
+    /// Writes a file through a buffer, flushing it so write errors are reported rather than lost.
+    fn write_buffered(path: &Path, data: &[u8]) -> Result<(), CameraError> {
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path.display(), e)))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a file and syncs it to disk.
+    fn write_synced(path: &Path, data: &[u8]) -> Result<(), CameraError> {
+        let mut file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path.display(), e)))?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Records JPEG frames into an AVI file until `limit` is reached, see
+    /// [`CameraController::capture_video`].
+    fn record_mjpeg(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        quality: u8,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        self.ensure_started()?;
+        let (fps, mut decimator, warning) = self.recording_rate(output_fps);
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        let mut video = avi::Writer::new(BufWriter::new(file), fps)?;
+        let mut timer = FrameTimer::new(fps);
+
+        let recorded = self.record_avi_frames(&mut video, limit, quality, &mut decimator, &mut timer, None);
+        self.video_timing = Some(timer.timing());
+        let (_, result) = recorded?;
+
+        let frames = video.frame_count();
+        let file = video.finish()?.into_inner().map_err(|e| CameraError::Io(e.into_error()))?;
+        file.sync_all()?;
+        let stopped = recording_stopped();
+        result.map(|_| VideoStats { frames, fps, warning, index: None, stopped, timing: timer.timing() })
+    }
+
+    /// Records a Motion JPEG AVI clip into memory instead of a file, e.g. to send it over the network
+    /// without wearing the SD card.
+    ///
+    /// Records like [`capture_video`](Self::capture_video) with [`VideoCodec::Mjpeg`] for
+    /// `duration_seconds`, or until [`stop_recording`] is called. The clip is kept under `max_bytes`.
+    /// If the next frame would take it over, the recording stops there and the finished clip is
+    /// returned marked as truncated. A capture failure part way through returns the error, and the
+    /// frames recorded until then are lost with it.
+    pub fn capture_video_to_memory(
+        &mut self,
+        duration_seconds: u32,
+        quality: Option<u8>,
+        max_bytes: usize,
+        output_fps: Option<u32>,
+    ) -> Result<MemoryVideo, CameraError> {
+        if duration_seconds == 0 {
+            return Err(CameraError::InvalidArgument("Recording duration must be non-zero".into()));
+        }
+        if output_fps == Some(0) {
+            return Err(CameraError::InvalidArgument("Output frame rate must be non-zero".into()));
+        }
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let limit = RecordLimit::duration(Duration::from_secs(duration_seconds as u64));
+        self.ensure_started()?;
+        let (fps, mut decimator, warning) = self.recording_rate(output_fps);
+        let mut video = avi::Writer::new(Cursor::new(Vec::new()), fps)?;
+        let mut timer = FrameTimer::new(fps);
+
+        let max_bytes = Some(max_bytes as u64);
+        let recorded = self.record_avi_frames(&mut video, limit, quality, &mut decimator, &mut timer, max_bytes);
+        self.video_timing = Some(timer.timing());
+        let (truncated, result) = recorded?;
+        result?;
+
+        let frames = video.frame_count();
+        let data = video.finish()?.into_inner();
+        let stopped = recording_stopped();
+        let stats = VideoStats { frames, fps, warning, index: None, stopped, timing: timer.timing() };
+        Ok(MemoryVideo { data, stats, truncated })
+    }
+
+    /// Writes the frames `decimator` keeps into `video` until `limit` is reached, or until the next
+    /// frame would make the finished file larger than `max_bytes`.
+    ///
+    /// Returns whether the size limit ended the recording, along with the capture or write error that
+    /// ended it early, if any, so what was recorded so far can still be finished.
+    fn record_avi_frames<W: Write + Seek>(
+        &mut self,
+        video: &mut avi::Writer<W>,
+        limit: RecordLimit,
+        quality: u8,
+        decimator: &mut Decimator,
+        timer: &mut FrameTimer,
+        max_bytes: Option<u64>,
+    ) -> Result<(bool, Result<(), CameraError>), CameraError> {
+        //Reuse the frame, encoder and JPEG buffers so recording doesn't allocate per frame
+        let mut frame = CapturedFrame::default();
+        let mut encoder = jpeg::Encoder::new();
+        let mut encoded = Vec::new();
+
+        while !limit.reached(timer.timing().written) {
+            //Keep what was recorded so far playable if the camera fails
+            if let Err(e) = self.capture_frame_into(&mut frame) {
+                return Ok((false, Err(e)));
+            }
+            let start = timer.captured();
+            if !decimator.keep() {
+                continue;
+            }
+            let (mode, width, height) = (self.config.color_mode, frame.width, frame.height);
+            let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut encoder, &mut encoded);
+            if max_bytes.is_some_and(|max| video.size_with_frame(jpeg.len()) > max) {
+                return Ok((true, Ok(())));
+            }
+            //Likewise if the disk fills up, so the headers and index still get written
+            if let Err(e) = video.write_frame(jpeg, width, height) {
+                return Ok((false, Err(e.into())));
+            }
+            timer.written(start);
+        }
+        Ok((false, Ok(())))
+    }
+
+    /// Records JPEG frames back to back into a file until `limit` is reached, without a container.
+    fn record_raw_mjpeg(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        quality: u8,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        self.ensure_started()?;
+        let (fps, mut decimator, warning) = self.recording_rate(output_fps);
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        let mut writer = BufWriter::new(file);
+
+        //The index format is described on `VideoStats`
+        let index_path = format!("{}.csv", path);
+        let index_file = fs::File::create(&index_path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", index_path, e)))?;
+        let mut index = BufWriter::new(index_file);
+        index.write_all(b"frame,offset,length,timestamp_us\n")?;
+
+        let mut timer = FrameTimer::new(fps);
+        let written = self.record_raw_frames(&mut writer, &mut index, limit, quality, &mut decimator, &mut timer);
+        self.video_timing = Some(timer.timing());
+        let result = written?;
+
+        //Flush and sync both files even if the recording failed, so the frames indexed stay readable
+        let data = sync_buffered(writer);
+        let indexed = sync_buffered(index);
+        let frames = timer.timing().written;
+        let stopped = recording_stopped();
+        let stats = VideoStats { frames, fps, warning, index: Some(index_path), stopped, timing: timer.timing() };
+        result.and(data).and(indexed).map(|_| stats)
+    }
+
+    /// Writes the frames `decimator` keeps back to back into `writer` until `limit` is reached,
+    /// listing each in `index`, see [`CameraController::record_raw_mjpeg`].
+    ///
+    /// Returns the capture or write error that ended the recording early, if any, so what was recorded
+    /// so far can still be finished.
+    fn record_raw_frames(
+        &mut self,
+        writer: &mut impl Write,
+        index: &mut impl Write,
+        limit: RecordLimit,
+        quality: u8,
+        decimator: &mut Decimator,
+        timer: &mut FrameTimer,
+    ) -> Result<Result<(), CameraError>, CameraError> {
+        let mut frame = CapturedFrame::default();
+        let mut encoder = jpeg::Encoder::new();
+        let mut encoded = Vec::new();
+
+        let mut offset = 0;
+        while !limit.reached(timer.timing().written) {
+            if let Err(e) = self.capture_frame_into(&mut frame) {
+                return Ok(Err(e));
+            }
+            let captured = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let start = timer.captured();
+            if !decimator.keep() {
+                continue;
+            }
+            let mode = self.config.color_mode;
+            let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut encoder, &mut encoded);
+            let written = (|| {
+                writer.write_all(jpeg)?;
+
+                //Only list frames that have reached the file, so an interrupted index is still right
+                writer.flush()?;
+                writeln!(index, "{},{},{},{}", timer.timing().written, offset, jpeg.len(), captured.as_micros())?;
+                index.flush()
+            })();
+            //Likewise if the disk fills up, so both files are still synced
+            if let Err(e) = written {
+                return Ok(Err(e.into()));
+            }
+            timer.written(start);
+            offset += jpeg.len() as u64;
+        }
+        Ok(Ok(()))
+    }
+
+    /// Records the frames `decimator` keeps into a YUV4MPEG2 stream at `path` until `limit` is
+    /// reached, see [`CameraController::capture_video`]. Captures in RGB mode if the camera is in
+    /// JPEG mode, as the driver only delivers YUV frames in the raw modes.
+    fn record_y4m(&mut self, path: &str, limit: RecordLimit, output_fps: Option<u32>) -> Result<VideoStats, CameraError> {
+        let mode = self.config.color_mode;
+        let jpeg = mode == ColorMode::Jpeg;
+        if jpeg {
+            if self.camera.is_some() {
+                return Err(CameraError::StreamRunning);
+            }
+            self.config.color_mode = ColorMode::Rgb;
+        }
+
+        let result = self.record_y4m_frames(path, limit, output_fps);
+        if jpeg {
+            self.config.color_mode = mode;
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Does the recording for [`CameraController::record_y4m`] once the color mode is set.
+    fn record_y4m_frames(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+
+        //The stream is set up for the size and byte order of the first frame
+        let mut frame = self.capture_driver_frame()?;
+        let layout = YuvLayout::from_fourcc(&frame.format).ok_or_else(|| CameraError::FormatUnsupported(format!(
+            "Cannot record {} frames as YUV", String::from_utf8_lossy(&frame.format)
+        )))?;
+        let format = FrameFormat {
+            layout,
+            width: frame.resolution.0,
+            height: frame.resolution.1,
+            stride: self.stride,
+        };
+
+        //Without an output rate the header carries the exact rate frames arrive at, e.g. 30000:1001,
+        //which is the configured rate for paced captures and the one negotiated with the driver otherwise
+        let (fps, mut decimator, warning) = self.recording_rate(output_fps);
+        let (rate, fps) = match (output_fps, self.frame_pacing) {
+            (None, None) => {
+                let (numerator, denominator) = self.interval;
+                ((denominator, numerator), (interval_rate(self.interval).round() as u32).max(1))
+            }
+            _ => ((fps, 1), fps),
+        };
+        let mut video = y4m::Writer::new(BufWriter::new(file), format.width, format.height, rate)?;
+        let mut timer = FrameTimer::new(fps);
+
+        let mut planes = Vec::new();
+        let mut result = Ok(());
+        let mut start = timer.captured();
+        loop {
+            self.frame_id += 1;
+            if decimator.keep() {
+                convert::yuv422_to_planar(&frame, &format, &mut planes);
+                if let Err(e) = video.write_frame(&planes) {
+                    result = Err(e.into());
+                    break;
+                }
+                timer.written(start);
+            }
+            if limit.reached(timer.timing().written) {
+                break;
+            }
+            frame = match self.capture_driver_frame() {
+                Ok(frame) => frame,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            start = timer.captured();
+        }
+        self.video_timing = Some(timer.timing());
+
+        let frames = video.frame_count();
+        let file = video.finish()?.into_inner().map_err(|e| CameraError::Io(e.into_error()))?;
+        file.sync_all()?;
+        let stopped = recording_stopped();
+        result.map(|_| VideoStats { frames, fps, warning, index: None, stopped, timing: timer.timing() })
+    }
+
+    /// Records raw H.264 to a file for `duration_seconds`, or until [`stop_recording`] is called,
+    /// using the device's hardware encoder.
+    ///
+    /// Unlike [`capture_video`](Self::capture_video) with [`VideoCodec::H264`], the file holds the
+    /// bare Annex B stream rather than an MP4, which tools such as ffmpeg read directly, it is
+    /// encoded at `bitrate` bits per second, or 5 Mbit/s if it is `None`, and there is no
+    /// fallback to Motion JPEG. The camera is used as for `capture_video`, and the file is flushed
+    /// and synced to disk before this returns.
+    ///
+    /// Fails with [`CameraError::FormatUnsupported`] if there is no hardware H.264 encoder, as on
+    /// anything other than a Raspberry Pi. Returns the number of frames recorded.
+    pub fn capture_video_h264(
+        &mut self,
+        path: &str,
+        duration_seconds: u32,
+        bitrate: Option<u32>,
+    ) -> Result<u32, CameraError> {
+        if duration_seconds == 0 {
+            return Err(CameraError::InvalidArgument("Recording duration must be non-zero".into()));
+        }
+        let bitrate = bitrate.unwrap_or(h264::BITRATE);
+        if bitrate == 0 {
+            return Err(CameraError::InvalidArgument("H.264 bitrate must be non-zero".into()));
+        }
+        let encoder = h264::find_encoder(H264_INPUT).ok_or_else(|| {
+            CameraError::FormatUnsupported(String::from(
+                "No hardware H.264 encoder found, recording H.264 needs a Raspberry Pi's video codec",
+            ))
+        })?;
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let limit = RecordLimit::duration(Duration::from_secs(duration_seconds as u64));
+        self.record_h264(path, limit, &encoder, bitrate, false, None).map(|stats| stats.frames)
+    }
+
+    /// Records a short clip into an animated GIF file that loops forever, e.g. to share in a chat
+    /// where a video file wouldn't play inline.
+    ///
+    /// Frames are taken at up to `fps` frames per second for `duration`, or until [`stop_recording`]
+    /// is called, each shrunk by `scale`, e.g. from 640x480 to 320x240 with a scale of 2, and reduced
+    /// to a palette of 256 colours. Each frame is shown for the time that passed until the next one
+    /// was captured, so the clip plays back in real time even if the camera couldn't keep up.
+    ///
+    /// GIF needs raw pixels, so in JPEG mode the camera must be stopped first, and it is opened in
+    /// RGB mode and stopped again afterwards. Otherwise uses the running stream if there is one, or
+    /// opens the camera and leaves it running. Grayscale frames stay gray. The file is finished and
+    /// synced to disk before this returns, even if a capture or write fails part way through, in
+    /// which case the error is returned and the file holds the frames written until then.
+    ///
+    /// Returns the number of frames written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] if `duration` is zero or above [`MAX_GIF_DURATION`],
+    /// `fps` is zero or above [`MAX_GIF_FPS`], or `scale` is zero, and
+    /// [`CameraError::StreamRunning`] in JPEG mode if the camera is running.
+    pub fn capture_gif(&mut self, path: &str, duration: Duration, fps: u32, scale: u32) -> Result<u32, CameraError> {
+        if duration.is_zero() || duration > MAX_GIF_DURATION {
+            return Err(CameraError::InvalidArgument(format!(
+                "GIF clips must be between 1 ms and {} seconds long",
+                MAX_GIF_DURATION.as_secs()
+            )));
+        }
+        if !(1..=MAX_GIF_FPS).contains(&fps) {
+            return Err(CameraError::InvalidArgument(format!(
+                "GIF frame rate must be between 1 and {} fps, got {}",
+                MAX_GIF_FPS, fps
+            )));
+        }
+        if scale == 0 {
+            return Err(CameraError::InvalidArgument("GIF scale must be non-zero".into()));
+        }
+
+        let mode = self.config.color_mode;
+        if mode == ColorMode::Jpeg {
+            if self.camera.is_some() {
+                return Err(CameraError::StreamRunning);
+            }
+            self.config.color_mode = ColorMode::Rgb;
+        }
+
+        STOP_RECORDING.store(false, Ordering::SeqCst);
+        let result = self.record_gif(path, duration, fps, scale);
+        if mode == ColorMode::Jpeg {
+            self.config.color_mode = mode;
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Does the recording for [`CameraController::capture_gif`] once the color mode is set.
+    fn record_gif(&mut self, path: &str, duration: Duration, fps: u32, scale: u32) -> Result<u32, CameraError> {
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        let channels = match self.config.color_mode {
+            ColorMode::Grayscale => Channels::Gray,
+            _ => Channels::Rgb,
+        };
+
+        //The file is set up for the size of the first frame, which is held until the next one is
+        //captured and its delay known
+        let mut frame = CapturedFrame::default();
+        let mut pending = Vec::new();
+        self.capture_frame_into(&mut frame)?;
+        let start = Instant::now();
+        let (width, height) = convert::shrink(&frame.data, frame.width, frame.height, channels, scale, &mut pending);
+        let mut gif = gif::Writer::new(BufWriter::new(file), width, height)?;
+
+        let centiseconds = |elapsed: Duration| (elapsed.as_millis() as u64 + 5) / 10;
+        let interval = Duration::from_secs(1) / fps;
+        let mut shown_at = 0;
+        let mut due = start + interval;
+        let mut result = Ok(());
+        while Instant::now() < start + duration && !STOP_RECORDING.load(Ordering::SeqCst) {
+            //Keep what was recorded so far playable if the camera fails
+            if let Err(e) = self.capture_frame_into(&mut frame) {
+                result = Err(e);
+                break;
+            }
+            let now = Instant::now();
+            if now < due {
+                continue;
+            }
+            //Skip the deadlines missed while the camera was slow rather than catching up in a burst
+            due = (due + interval).max(now);
+
+            if self.config.color_mode == ColorMode::Bgr {
+                swap_red_blue(&mut pending);
+            }
+            let at = centiseconds(now - start);
+            //Likewise if the disk fills up, so the trailer still gets written. The frame that failed
+            //isn't tried again
+            if let Err(e) = gif.write_frame(&pending, channels, (at - shown_at).clamp(1, u16::MAX as u64) as u16) {
+                result = Err(e.into());
+                pending.clear();
+                break;
+            }
+            shown_at = at;
+            convert::shrink(&frame.data, frame.width, frame.height, channels, scale, &mut pending);
+        }
+
+        if !pending.is_empty() {
+            if self.config.color_mode == ColorMode::Bgr {
+                swap_red_blue(&mut pending);
+            }
+            if let Err(e) = gif.write_frame(&pending, channels, centiseconds(interval).max(1) as u16) {
+                result = result.and(Err(e.into()));
+            }
+        }
+        let frames = gif.frame_count();
+        let finished = gif.finish().map_err(CameraError::from).and_then(sync_buffered);
+        result.and(finished).map(|_| frames)
+    }
+
+    /// Records frames through the H.264 encoder at `encoder` until `limit` is reached, into an MP4
+    /// file if `container` is set and a raw H.264 file otherwise. Captures in RGB mode if the current
+    /// mode doesn't deliver 24-bit pixels.
+    fn record_h264(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        encoder: &str,
+        bitrate: u32,
+        container: bool,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        let mode = self.config.color_mode;
+        let raw = matches!(mode, ColorMode::Rgb | ColorMode::Bgr);
+        if !raw {
+            if self.camera.is_some() {
+                return Err(CameraError::StreamRunning);
+            }
+            self.config.color_mode = ColorMode::Rgb;
+        }
+
+        let result = self.record_h264_frames(path, limit, encoder, bitrate, container, output_fps);
+        if !raw {
+            self.config.color_mode = mode;
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Does the recording for [`CameraController::record_h264`] once the color mode is set.
+    fn record_h264_frames(
+        &mut self,
+        path: &str,
+        limit: RecordLimit,
+        encoder: &str,
+        bitrate: u32,
+        container: bool,
+        output_fps: Option<u32>,
+    ) -> Result<VideoStats, CameraError> {
+        let file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+
+        //The encoder and file are set up for the size of the first frame
+        let mut frame = CapturedFrame::default();
+        self.capture_frame_into(&mut frame)?;
+        let (fps, mut decimator, warning) = self.recording_rate(output_fps);
+        let encoder = h264::Encoder::new(encoder, frame.width, frame.height, H264_INPUT, fps, bitrate)?;
+        let mut timer = FrameTimer::new(fps);
+
+        let (frames, result, file) = if container {
+            let mut video = mp4::Writer::new(BufWriter::new(file), fps, frame.width, frame.height)?;
+            let mut sink = |data: &[u8]| video.write_frame(data);
+            let encoded = self.encode_h264(encoder, &mut frame, limit, &mut decimator, &mut timer, &mut sink);
+            self.video_timing = Some(timer.timing());
+            let (_, result) = encoded?;
+            let frames = video.frame_count();
+            (frames, result, video.finish()?.into_inner())
+        } else {
+            let mut writer = BufWriter::new(file);
+            let mut sink = |data: &[u8]| writer.write_all(data);
+            let encoded = self.encode_h264(encoder, &mut frame, limit, &mut decimator, &mut timer, &mut sink);
+            self.video_timing = Some(timer.timing());
+            let (frames, result) = encoded?;
+            (frames, result, writer.into_inner())
+        };
+
+        let file = file.map_err(|e| CameraError::Io(e.into_error()))?;
+        file.sync_all()?;
+        let stopped = recording_stopped();
+        result.map(|_| VideoStats { frames, fps, warning, index: None, stopped, timing: timer.timing() })
+    }
+
+    /// Feeds the frames `decimator` keeps to `encoder` until `limit` is reached, starting with the
+    /// one already in `frame`, then flushes it into `sink`.
+    ///
+    /// Returns the number of frames encoded, along with the capture, encoding or write error that
+    /// ended the recording early, if any, so what was recorded so far can still be finished.
+    fn encode_h264(
+        &mut self,
+        mut encoder: h264::Encoder,
+        frame: &mut CapturedFrame,
+        limit: RecordLimit,
+        decimator: &mut Decimator,
+        timer: &mut FrameTimer,
+        sink: &mut impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(u32, Result<(), CameraError>), CameraError> {
+        let mut frames = 0;
+        let mut result = Ok(());
+        let mut start = timer.captured();
+        loop {
+            if decimator.keep() {
+                if self.config.color_mode == ColorMode::Bgr {
+                    swap_red_blue(&mut frame.data);
+                }
+                //Keep what was recorded so far playable if the file can't be written
+                if let Err(e) = encoder.encode(&frame.data, sink) {
+                    result = Err(e.into());
+                    break;
+                }
+                frames += 1;
+                timer.written(start);
+            }
+
+            if limit.reached(frames) {
+                break;
+            }
+            //Keep what was recorded so far playable if the camera fails
+            if let Err(e) = self.capture_frame_into(frame) {
+                result = Err(e);
+                break;
+            }
+            start = timer.captured();
+        }
+        //Flush what the encoder holds even after an error, reporting the error that came first
+        if let Err(e) = encoder.finish(sink) {
+            if result.is_ok() {
+                result = Err(e.into());
+            }
+        }
+        Ok((frames, result))
+    }
+
+    /// Captures a frame from the stream as JPEG data, encoding raw frames at `quality`, or the
+    /// configured quality if it is `None`.
+    ///
+    /// Unlike [`capture_snapshot`](Self::capture_snapshot) this uses the running stream, opening the
+    /// camera and leaving it running if needed. `frame`, `encoder` and `encoded` are reused between
+    /// calls so a stream of frames doesn't allocate per frame.
+    pub fn capture_jpeg_into<'a>(
+        &mut self,
+        frame: &'a mut CapturedFrame,
+        encoder: &mut jpeg::Encoder,
+        encoded: &'a mut Vec<u8>,
+        quality: Option<u8>,
+    ) -> Result<&'a [u8], CameraError> {
+        let quality = quality.unwrap_or(self.config.jpeg_quality);
+        Self::validate_quality(quality)?;
+        self.capture_frame_into(frame)?;
+        Ok(Self::encode_frame(self.config.color_mode, frame, quality, encoder, encoded))
+    }
+
+    /// Returns a captured frame as JPEG data, encoding it into `encoded` if the camera delivered raw pixels.
+    fn encode_frame<'a>(
+        mode: ColorMode,
+        frame: &'a mut CapturedFrame,
+        quality: u8,
+        encoder: &mut jpeg::Encoder,
+        encoded: &'a mut Vec<u8>,
+    ) -> &'a [u8] {
+        let channels = match mode {
+            ColorMode::Jpeg => return &frame.data,
+            ColorMode::Grayscale => Channels::Gray,
+            ColorMode::Rgb => Channels::Rgb,
+            ColorMode::Bgr => {
+                //The encoder expects red first
+                swap_red_blue(&mut frame.data);
+                Channels::Rgb
+            }
+        };
+        encoder.encode_into(&frame.data, frame.width, frame.height, channels, quality, encoded);
+        encoded
+    }
+
+    /// Registers a callback that is given every frame as soon as it has been captured and converted,
+    /// replacing any callback already registered.
+    ///
+    /// The callback receives the frame data in the current color mode, e.g. packed RGB pixels in RGB
+    /// mode, and the frame's ID. It runs on the thread capturing the frame while the controller is
+    /// borrowed, so it must be fast, and can't use the controller itself; copy the data out if it
+    /// needs more work.
+    pub fn set_frame_callback(&mut self, callback: FrameCallback) {
+        self.frame_callback = Some(callback);
+    }
+
+    /// Unregisters the callback set by [`set_frame_callback`](Self::set_frame_callback).
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback = None;
+    }
+
+    /// Turns motion detection on or off.
+    ///
+    /// While it is on, every captured frame is compared with the one before it, see
+    /// [`motion_score`](Self::motion_score). Only the luma of the previous frame is kept. Turning it
+    /// off frees that buffer and unregisters the callback set by
+    /// [`set_motion_callback`](Self::set_motion_callback).
+    pub fn set_motion_detection(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.motion.get_or_insert_with(motion::Detector::default);
+            }
+            false => {
+                self.motion = None;
+                self.motion_callback = None;
+            }
+        }
+    }
+
+    /// Returns how much the last captured frame differed from the one before it, as the mean
+    /// absolute luma difference from 0.0 (no change) to 1.0 (black to white everywhere).
+    ///
+    /// Returns `None` while motion detection is off, before two frames of the same size have been
+    /// captured, and in JPEG mode, where frames aren't decoded.
+    pub fn motion_score(&self) -> Option<f64> {
+        self.motion.as_ref().and_then(motion::Detector::score)
+    }
+
+    /// Registers a callback that is given the motion score and ID of every frame scoring at least
+    /// `threshold`, replacing any callback already registered, and turns motion detection on.
+    ///
+    /// Like the frame callback it runs on the thread capturing the frame while the controller is
+    /// borrowed, so it must be fast and can't use the controller itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] if `threshold` is not between 0.0 and 1.0.
+    pub fn set_motion_callback(&mut self, threshold: f64, callback: MotionCallback) -> Result<(), CameraError> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(CameraError::InvalidArgument(format!(
+                "Motion threshold must be between 0.0 and 1.0, got {}",
+                threshold
+            )));
+        }
+        self.set_motion_detection(true);
+        self.motion_callback = Some((threshold, callback));
+        Ok(())
+    }
+
+    /// Unregisters the callback set by [`set_motion_callback`](Self::set_motion_callback), leaving
+    /// motion detection on.
+    pub fn clear_motion_callback(&mut self) {
+        self.motion_callback = None;
+    }
+
+    /// Returns how many pixels of the last captured frame have each luma value, from black at index 0
+    /// to white at index 255.
+    ///
+    /// The histogram is kept up to date as frames are captured, so reading it costs nothing extra.
+    /// Colour pixels are measured as `(r + 2g + b) / 4`. Returns `None` before the first frame and in
+    /// JPEG mode, where frames aren't decoded.
+    pub fn frame_histogram(&self) -> Option<[u32; 256]> {
+        self.histogram
+    }
+
+    /// Returns the mean luma of the last captured frame, from 0.0 for black to 255.0 for white.
+    ///
+    /// Like [`frame_histogram`](Self::frame_histogram) this returns `None` before the first frame
+    /// and in JPEG mode.
+    pub fn average_brightness(&self) -> Option<f32> {
+        let histogram = self.histogram.as_ref()?;
+        let pixels: u64 = histogram.iter().map(|&count| count as u64).sum();
+        let total: u64 = histogram.iter().zip(0u64..).map(|(&count, luma)| count as u64 * luma).sum();
+        (pixels > 0).then(|| (total as f64 / pixels as f64) as f32)
+    }
+
+    /// Counts the luma values of a captured frame into the histogram.
+    fn update_histogram(&mut self, captured: &CapturedFrame) {
+        let channels = match self.config.color_mode {
+            ColorMode::Jpeg => {
+                self.histogram = None;
+                return;
+            }
+            ColorMode::Grayscale => Channels::Gray,
+            ColorMode::Rgb | ColorMode::Bgr => Channels::Rgb,
+        };
+
+        let histogram = self.histogram.insert([0; 256]);
+        for luma in convert::luma(&captured.data, channels) {
+            histogram[luma as usize] += 1;
+        }
+    }
+
+    /// Turns edge detection on or off. It is off by default.
+    ///
+    /// While it is on, every raw frame captured is run through a Sobel filter, see
+    /// [`edge_map`](Self::edge_map). Grayscale frames are filtered as they are, colour frames are
+    /// reduced to luma first. Turning it off frees the edge map.
+    pub fn set_edge_detection(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.edges.get_or_insert_with(CapturedFrame::default);
+            }
+            false => {
+                self.edges = None;
+                self.edge_luma = Vec::new();
+            }
+        }
+    }
+
+    /// Returns the Sobel edge map of the last captured frame, one byte per pixel that is bright along
+    /// edges and dark in flat areas, at the same size as the frame.
+    ///
+    /// Returns `None` while edge detection is off, before the first frame and in JPEG mode, where
+    /// frames aren't decoded.
+    pub fn edge_map(&self) -> Option<&CapturedFrame> {
+        self.edges.as_ref().filter(|edges| edges.width > 0 && edges.height > 0)
+    }
+
+    /// Filters a captured frame into the edge map if edge detection is on.
+    fn detect_edges(&mut self, captured: &CapturedFrame) {
+        let edges = match self.edges.as_mut() {
+            Some(edges) => edges,
+            None => return,
+        };
+        let gray = match self.config.color_mode {
+            ColorMode::Jpeg => {
+                (edges.width, edges.height) = (0, 0);
+                return;
+            }
+            ColorMode::Grayscale => &captured.data,
+            ColorMode::Rgb | ColorMode::Bgr => {
+                self.edge_luma.clear();
+                self.edge_luma.extend(convert::luma(&captured.data, Channels::Rgb));
+                &self.edge_luma
+            }
+        };
+
+        let gray = prefilter(
+            self.prefilter,
+            gray,
+            (captured.width, captured.height),
+            &mut self.prefiltered,
+            &mut self.prefilter_scratch,
+        );
+        convert::sobel(gray, captured.width, captured.height, &mut edges.data);
+        (edges.width, edges.height) = (captured.width, captured.height);
+    }
+
+    /// Sets the filtering applied to grayscale frames before edges are detected and QR codes are read,
+    /// e.g. a blur to keep sensor noise out of the edge map. It is [`PreFilter::None`] by default. The
+    /// frames themselves, and everything else measured from them, are left as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] if a blur radius is 0 or above [`MAX_BLUR_RADIUS`].
+    pub fn set_prefilter(&mut self, prefilter: PreFilter) -> Result<(), CameraError> {
+        if let PreFilter::GaussianBlur { radius } = prefilter {
+            if !(1..=MAX_BLUR_RADIUS).contains(&radius) {
+                return Err(CameraError::InvalidArgument(format!(
+                    "Blur radius must be between 1 and {}, got {}",
+                    MAX_BLUR_RADIUS, radius
+                )));
+            }
+        }
+        self.prefilter = prefilter;
+        if prefilter == PreFilter::None {
+            self.prefiltered = Vec::new();
+            self.prefilter_scratch = Vec::new();
+        }
+        Ok(())
+    }
+
+    /// Returns the filtering set with [`set_prefilter`](Self::set_prefilter).
+    pub fn prefilter(&self) -> PreFilter {
+        self.prefilter
+    }
+
+    /// Marks the pixels of every colour frame captured whose colour lies within `filter`, or stops if
+    /// it is `None`, e.g. to find a ball of a known colour. See [`mask_buffer`](Self::mask_buffer).
+    ///
+    /// Each pixel is converted to HSV and compared with the filter, so the mask doesn't depend on how
+    /// brightly lit the object is beyond the value range given. Only the RGB and BGR modes have the
+    /// colours to filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::InvalidArgument`] if a hue is above 359, or a saturation or value
+    /// range has its minimum above its maximum.
+    pub fn set_color_filter(&mut self, filter: Option<ColorFilter>) -> Result<(), CameraError> {
+        let filter = match filter {
+            Some(filter) => filter,
+            None => {
+                self.color_filter = None;
+                self.blob = None;
+                return Ok(());
+            }
+        };
+        if filter.hue.0 > 359 || filter.hue.1 > 359 {
+            return Err(CameraError::InvalidArgument("Hues must be between 0 and 359 degrees".into()));
+        }
+        if filter.saturation.0 > filter.saturation.1 || filter.value.0 > filter.value.1 {
+            return Err(CameraError::InvalidArgument(
+                "Saturation and value ranges must have their minimum at or below their maximum".into(),
+            ));
+        }
+        self.color_filter = Some((filter, CapturedFrame::default()));
+        self.blob = None;
+        Ok(())
+    }
+
+    /// Returns the mask of the last captured frame made by the filter set with
+    /// [`set_color_filter`](Self::set_color_filter), one byte per pixel that is 255 where the colour
+    /// matches and 0 elsewhere, at the same size as the frame.
+    ///
+    /// Returns `None` without a filter, before the first frame and in the modes without colour.
+    pub fn mask_buffer(&self) -> Option<&CapturedFrame> {
+        let (_, mask) = self.color_filter.as_ref()?;
+        (mask.width > 0 && mask.height > 0).then_some(mask)
+    }
+
+    /// Returns the largest patch of matching pixels in the last mask made by the filter set with
+    /// [`set_color_filter`](Self::set_color_filter), e.g. to steer towards a tracked object, or
+    /// `None` if no pixel matched or there is no mask, as for [`mask_buffer`](Self::mask_buffer).
+    ///
+    /// Pixels belong to the same patch if they touch along an edge. The blob is found as each frame
+    /// is captured, so this doesn't look at the mask again.
+    pub fn largest_blob(&self) -> Option<Blob> {
+        self.blob
+    }
+
+    /// Masks a captured frame with the colour filter if one is set, and finds its largest blob.
+    fn filter_colors(&mut self, captured: &CapturedFrame) {
+        let (filter, mask) = match self.color_filter.as_mut() {
+            Some((filter, mask)) => (filter, mask),
+            None => return,
+        };
+        let bgr = match self.config.color_mode {
+            ColorMode::Jpeg | ColorMode::Grayscale => {
+                (mask.width, mask.height) = (0, 0);
+                self.blob = None;
+                return;
+            }
+            ColorMode::Rgb => false,
+            ColorMode::Bgr => true,
+        };
+
+        convert::hsv_mask(&captured.data, bgr, filter, &mut mask.data);
+        (mask.width, mask.height) = (captured.width, captured.height);
+        self.blob = self.blob_finder.largest(&mask.data, mask.width, mask.height);
+    }
+
+    /// Captures a frame and reads every QR code in it, e.g. to find navigation waypoints.
+    ///
+    /// Decoding takes a lot longer than capturing, so it only happens when this is called rather
+    /// than for every frame. Colour frames are reduced to luma first. In JPEG mode the camera must be
+    /// stopped, and is opened in a raw mode just for the frame and closed again, as this library
+    /// doesn't decode JPEG. Otherwise uses the running stream, or opens the camera and leaves it
+    /// running.
+    ///
+    /// Returns the payload of each code found with its corners in the frame, from which its
+    /// distance and angle can be worked out, or an empty `Vec` if there are none. The codes are also
+    /// kept until the next decode, see [`last_qr_codes`](Self::last_qr_codes).
+    pub fn decode_qr(&mut self) -> Result<Vec<QrResult>, CameraError> {
+        self.qr_codes.clear();
+        let (frame, channels) = match self.config.color_mode {
+            ColorMode::Jpeg if self.camera.is_some() => {
+                return Err(CameraError::FormatUnsupported(String::from(
+                    "Reading QR codes needs a raw color mode while the camera is streaming",
+                )))
+            }
+            ColorMode::Jpeg => self.capture_still_pixels()?,
+            ColorMode::Grayscale => (self.capture_frame()?, Channels::Gray),
+            ColorMode::Rgb | ColorMode::Bgr => (self.capture_frame()?, Channels::Rgb),
+        };
+
+        let luma: Vec<u8> = match channels {
+            Channels::Gray => frame.data,
+            Channels::Rgb => convert::luma(&frame.data, Channels::Rgb).collect(),
+        };
+        let luma = prefilter(
+            self.prefilter,
+            &luma,
+            (frame.width, frame.height),
+            &mut self.prefiltered,
+            &mut self.prefilter_scratch,
+        );
+        self.qr_codes = qr::decode(luma, frame.width, frame.height);
+        Ok(self.qr_codes.clone())
+    }
+
+    /// Returns the codes read by the last call to [`decode_qr`](Self::decode_qr), empty before the
+    /// first or if it failed.
+    pub fn last_qr_codes(&self) -> &[QrResult] {
+        &self.qr_codes
+    }
+
+    /// Draws the overlay into a captured frame if one is set.
+    fn draw_overlay(&self, captured: &mut CapturedFrame) {
+        let overlay = match self.config.overlay {
+            Some(overlay) => overlay,
+            None => return,
+        };
+        let channels = match self.config.color_mode {
+            ColorMode::Jpeg => return,
+            ColorMode::Grayscale => Channels::Gray,
+            ColorMode::Rgb | ColorMode::Bgr => Channels::Rgb,
+        };
+        overlay.draw(&mut captured.data, captured.width, captured.height, channels, self.frame_id);
+    }
+
+    /// Compares a captured frame with the previous one if motion detection is on, calling the motion
+    /// callback if it changed enough.
+    fn detect_motion(&mut self, captured: &CapturedFrame) {
+        let detector = match self.motion.as_mut() {
+            Some(detector) => detector,
+            None => return,
+        };
+        let channels = match self.config.color_mode {
+            ColorMode::Jpeg => {
+                detector.reset();
+                return;
+            }
+            ColorMode::Grayscale => Channels::Gray,
+            ColorMode::Rgb | ColorMode::Bgr => Channels::Rgb,
+        };
+
+        let score = detector.update(&captured.data, captured.width, captured.height, channels);
+        if let (Some(score), Some((threshold, callback))) = (score, self.motion_callback.as_mut()) {
+            if score >= *threshold {
+                callback(score, self.frame_id);
+            }
+        }
+    }
+
+    /// Returns the width and height of the frames captures return with the current settings.
+    ///
+    /// This is the configured resolution, or the size of the crop region where it is cut out in
+    /// software, shrunk to the output size if one is set. The driver must grant the configured
+    /// resolution exactly, so this holds whether or not the camera is running.
+    pub fn frame_size(&self) -> (u32, u32) {
+        let (width, height) = (self.config.width, self.config.height);
+
+        //JPEG frames are passed through without being cropped or scaled
+        if self.config.color_mode == ColorMode::Jpeg {
+            return (width, height);
+        }
+        let (width, height) = match self.software.crop {
+            Some(crop) => {
+                let region = crop.clip(width, height);
+                (region.width, region.height)
+            }
+            None => (width & !1, height),
+        };
+        match self.config.output_size {
+            Some((out_width, out_height)) => (out_width.min(width), out_height.min(height)),
+            None => (width, height),
+        }
+    }
+
+    /// Returns the number of bytes per pixel in captured frames: 1 in grayscale mode, 3 in RGB and
+    /// BGR mode, and 0 in JPEG mode, where frames are compressed.
+    pub fn frame_channels(&self) -> u32 {
+        match self.config.color_mode {
+            ColorMode::Jpeg => 0,
+            ColorMode::Grayscale => 1,
+            ColorMode::Rgb | ColorMode::Bgr => 3,
+        }
+    }
+
+    /// Returns the ID of the most recently captured frame, or `0` if nothing has been captured yet.
+    ///
+    /// IDs increase by one with every capture and keep counting across camera restarts, so
+    /// consumers can compare them to skip frames they have already processed.
+    pub fn current_frame_id(&self) -> u64 {
+        self.frame_id
+    }
+
+    /// Returns a copy of the last frame captured from the running stream as packed RGB pixels, along
+    /// with its ID, without waiting for a new one.
+    ///
+    /// This is for Rust programs using the library directly rather than through JNI. Grayscale and
+    /// BGR frames are converted to RGB. Calling this again before the next capture returns the same
+    /// frame with the same ID, so the ID tells whether a frame has been seen before. Returns `None`
+    /// if the camera isn't running or hasn't captured a frame since it was opened, and for frames
+    /// captured in JPEG mode, as this library doesn't decode JPEG.
+    pub fn latest_frame_rgb(&self) -> Option<(u64, CapturedFrame)> {
+        let (id, mode, latest) = self.latest.as_ref().filter(|_| self.camera.is_some())?;
+        let data = match mode {
+            ColorMode::Jpeg => return None,
+            ColorMode::Grayscale => latest.data.iter().flat_map(|&luma| [luma; 3]).collect(),
+            ColorMode::Rgb => latest.data.clone(),
+            ColorMode::Bgr => {
+                let mut data = latest.data.clone();
+                swap_red_blue(&mut data);
+                data
+            }
+        };
+        Some((*id, CapturedFrame { data, width: latest.width, height: latest.height }))
+    }
+
+    /// Saves the last frame captured from the running stream as a JPEG file without capturing a new
+    /// one, and returns the frame's ID.
+    ///
+    /// This takes the controller's mutex rather than the controller, so it can copy the frame while
+    /// holding the lock and release it before the slow encode and write. A capture thread sharing
+    /// the controller, like a background recording, only waits for the copy. `quality` overrides the
+    /// configured JPEG quality, and frames captured in JPEG mode are saved as the camera delivered
+    /// them. The JPEG carries the same EXIF metadata as [`capture_snapshot`](Self::capture_snapshot),
+    /// and the file is synced to disk before this returns.
+    ///
+    /// Fails with [`CameraError::NotStarted`] if the camera isn't running, and with
+    /// [`CameraError::NoFrame`] if it hasn't captured a frame since it was opened.
+    pub fn snapshot_from_stream(
+        camera: &Mutex<CameraController>,
+        path: &str,
+        quality: Option<u8>,
+    ) -> Result<u64, CameraError> {
+        let (id, mode, mut frame, quality, metadata) = {
+            let controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+            if controller.camera.is_none() {
+                return Err(CameraError::NotStarted);
+            }
+            let (id, mode, latest) = controller.latest.as_ref().ok_or(CameraError::NoFrame)?;
+            let quality = quality.unwrap_or(controller.config.jpeg_quality);
+            Self::validate_quality(quality)?;
+            let frame = CapturedFrame { data: latest.data.clone(), width: latest.width, height: latest.height };
+            let metadata = controller.exif_metadata(frame.width, frame.height);
+            (*id, *mode, frame, quality, metadata)
+        };
+
+        let mut encoded = Vec::new();
+        let jpeg = Self::encode_frame(mode, &mut frame, quality, &mut jpeg::Encoder::new(), &mut encoded);
+        let image = exif::insert(jpeg, &metadata);
+
+        let mut file = fs::File::create(path)
+            .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+        file.write_all(&image)?;
+        file.sync_all()?;
+        Ok(id)
+    }
+
+    /// Finds the tag36h11 AprilTags in the last frame captured from the running stream, e.g. to
+    /// work out exactly where the robot is from tags at known places.
+    ///
+    /// Detection takes a lot longer than capturing, so it only happens when this is called, and like
+    /// [`snapshot_from_stream`](Self::snapshot_from_stream) it takes the controller's mutex so the
+    /// frame can be copied as luma under the lock and searched after releasing it. No new frame is
+    /// captured.
+    ///
+    /// Returns the ID of each tag found with its corners and centre in the frame, or an empty `Vec`
+    /// if there are none. Only IDs 0 to 33 are recognised so far, as the vendored codebook holds the
+    /// first 34 of the family's 587 codes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::NotStarted`] if the camera isn't running, [`CameraError::NoFrame`] if
+    /// it hasn't captured a frame since it was opened, and [`CameraError::FormatUnsupported`] in
+    /// JPEG mode, as this library doesn't decode JPEG.
+    pub fn detect_apriltags(camera: &Mutex<CameraController>) -> Result<Vec<TagDetection>, CameraError> {
+        let (luma, width, height) = {
+            let controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+            if controller.camera.is_none() {
+                return Err(CameraError::NotStarted);
+            }
+            let (_, mode, latest) = controller.latest.as_ref().ok_or(CameraError::NoFrame)?;
+            let luma: Vec<u8> = match mode {
+                ColorMode::Jpeg => {
+                    return Err(CameraError::FormatUnsupported(String::from(
+                        "Detecting AprilTags needs a raw color mode",
+                    )))
+                }
+                ColorMode::Grayscale => latest.data.clone(),
+                ColorMode::Rgb | ColorMode::Bgr => convert::luma(&latest.data, Channels::Rgb).collect(),
+            };
+            (luma, latest.width, latest.height)
+        };
+        Ok(apriltag::detect(&luma, width, height))
+    }
+
+    /// Copies the last frame captured from the running stream into a [`FrameBuffer`] without
+    /// capturing a new one, e.g. to hand it to a processing thread.
+    ///
+    /// The data is left as it was captured: JPEG data in JPEG mode, or raw pixels laid out as the
+    /// frame's color mode says.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::NotStarted`] if the camera isn't running, and [`CameraError::NoFrame`]
+    /// if it hasn't captured a frame since it was opened.
+    pub fn latest_frame_buffer(&self) -> Result<BufferedFrame, CameraError> {
+        let (id, mode, latest) = self.latest_frame()?;
+        Ok(BufferedFrame {
+            id,
+            mode,
+            width: latest.width,
+            height: latest.height,
+            data: FrameBuffer::from_slice(&latest.data),
+        })
+    }
+
+    /// Shares the last frame captured from the running stream without copying it again for every caller.
+    ///
+    /// The frame is copied into a [`FrameBuffer`] the first time it is acquired. Later calls for the
+    /// same frame return another reference to that buffer, so the call is cheap to repeat and every
+    /// holder sees the same memory. The memory is freed only once the controller has moved on to a
+    /// newer frame, or stopped, and every returned reference has been dropped. Holding a reference is
+    /// what keeps the address from [`FrameBuffer::as_ptr`] valid, so code outside Rust that reads
+    /// through it must keep one until it is done.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CameraError::NotStarted`] if the camera isn't running, and [`CameraError::NoFrame`]
+    /// if it hasn't captured a frame since it was opened.
+    pub fn acquire_frame_buffer(&mut self) -> Result<Arc<BufferedFrame>, CameraError> {
+        let id = self.latest_frame()?.0;
+        match &self.shared {
+            Some(shared) if shared.id == id => Ok(Arc::clone(shared)),
+            _ => {
+                let shared = Arc::new(self.latest_frame_buffer()?);
+                self.shared = Some(Arc::clone(&shared));
+                Ok(shared)
+            }
+        }
+    }
+
+    /// Returns the last frame kept from the running stream with its ID and color mode.
+    fn latest_frame(&self) -> Result<(u64, ColorMode, &CapturedFrame), CameraError> {
+        match &self.latest {
+            Some((id, mode, latest)) if self.camera.is_some() => Ok((*id, *mode, latest)),
+            Some(_) => Err(CameraError::NotStarted),
+            None if self.camera.is_some() => Err(CameraError::NoFrame),
+            None => Err(CameraError::NotStarted),
+        }
+    }
+
+    /// Copies a frame that has just been captured for [`latest_frame_rgb`](Self::latest_frame_rgb)
+    /// and [`snapshot_from_stream`](Self::snapshot_from_stream), reusing the buffer of the previous copy.
+    fn keep_latest(&mut self, captured: &CapturedFrame) {
+        let (id, mode, latest) = self.latest.get_or_insert_with(|| (0, ColorMode::Jpeg, CapturedFrame::default()));
+        *id = self.frame_id;
+        *mode = self.config.color_mode;
+        latest.data.clear();
+        latest.data.extend_from_slice(&captured.data);
+        (latest.width, latest.height) = (captured.width, captured.height);
+    }
+
+    /// Runs a YUV frame through a pixel conversion into `captured`, applying any software crop and flip.
+    fn convert_frame(
+        &self,
+        frame: &Frame,
+        conversion: Conversion,
+        captured: &mut CapturedFrame,
+    ) -> Result<(), CameraError> {
+        let layout = YuvLayout::from_fourcc(&frame.format).ok_or_else(|| CameraError::FormatUnsupported(format!(
+            "Cannot convert {} frames", String::from_utf8_lossy(&frame.format)
+        )))?;
+
+        let format = FrameFormat {
+            layout,
+            width: frame.resolution.0,
+            height: frame.resolution.1,
+            stride: self.stride,
+        };
+
+        let transform = Transform { scale: self.config.output_size, ..self.software };
+        (captured.width, captured.height) = conversion(frame, &format, &transform, &mut captured.data);
+        Ok(())
+    }
+
+    /// Sets how a camera that disappears mid-stream, e.g. because its USB cable was pulled, is
+    /// reopened. Captures wait for up to `attempts` tries, `interval` apart, before failing with
+    /// [`CameraError::Disconnected`].
+    pub fn set_reconnect_policy(&mut self, attempts: u32, interval: Duration) {
+        self.config.reconnect_attempts = attempts;
+        self.config.reconnect_interval = interval;
+    }
+
+    /// Drops a camera that disappeared mid-stream and tries to reopen it with the current settings.
+    fn reconnect(&mut self) -> Result<(), CameraError> {
+        //The device is gone, so there is nothing left to stop cleanly
+        self.camera = None;
+        RECONNECTING.store(true, Ordering::SeqCst);
+
+        let mut result = Err(CameraError::Disconnected(self.config.device.clone()));
+        for _ in 0..self.config.reconnect_attempts {
+            thread::sleep(self.config.reconnect_interval);
+            if self.ensure_started().is_ok() {
+                result = Ok(());
+                break;
+            }
+        }
+
+        RECONNECTING.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Stops the camera and releases resources.
+    pub fn stop(&mut self) -> Result<(), CameraError> {
+        self.latest = None;
+        self.shared = None;
+        if let Some(mut camera) = self.camera.take() {
+            camera.stop()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn frame_wait_times_out_without_a_new_frame() {
+        let signal = FrameSignal::new();
+        signal.publish(7);
+
+        let started = Instant::now();
+        assert_eq!(signal.wait(Duration::from_millis(50)), None);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn frame_wait_returns_the_published_id() {
+        let signal = Arc::new(FrameSignal::new());
+        signal.publish(7);
+
+        let waiter = {
+            let signal = Arc::clone(&signal);
+            thread::spawn(move || signal.wait(Duration::from_secs(10)))
+        };
+        //The waiter may not be waiting yet when an ID is published, so keep publishing new ones until it wakes
+        let mut id = 7;
+        while !waiter.is_finished() {
+            id += 1;
+            signal.publish(id);
+            thread::sleep(Duration::from_millis(5));
+        }
+        let woken = waiter.join().unwrap().expect("the waiter timed out");
+        assert!((8..=id).contains(&woken));
+    }
+
+    #[test]
+    fn burst_failure_keeps_the_error_and_the_frames_captured() {
+        let mut captured = 0;
+        let result = run_burst(5, |number| match number {
+            3 => Err(CameraError::Disconnected(String::from("/dev/video0"))),
+            _ => {
+                captured += 1;
+                Ok(())
+            }
+        });
+        match result {
+            Err(CameraError::Interrupted(2, e)) => assert!(matches!(*e, CameraError::Disconnected(_))),
+            other => panic!("Expected an interrupted burst, got {:?}", other),
+        }
+        assert_eq!(captured, 2);
+
+        assert!(matches!(run_burst(5, |_| Err(CameraError::Timeout)), Err(CameraError::Timeout)));
+        assert_eq!(run_burst(5, |_| Ok(())).unwrap(), 5);
+    }
+
+    #[test]
+    fn jpeg_is_rejected_as_a_lossless_format() {
+        let frame = CapturedFrame { data: vec![0; 12], width: 2, height: 2 };
+        assert!(matches!(
+            ImageFormat::Jpeg.encode_lossless(&frame, Channels::Rgb),
+            Err(CameraError::InvalidArgument(_))
+        ));
+        assert!(ImageFormat::Png.encode_lossless(&frame, Channels::Rgb).is_ok());
+    }
+
+    #[test]
+    fn snapshot_from_stream_needs_a_running_camera() {
+        let camera = Mutex::new(CameraController::new());
+        let path = std::env::temp_dir().join("swiftbot_snapshot_from_stream_test.jpg");
+        let result = CameraController::snapshot_from_stream(&camera, path.to_str().unwrap(), None);
+        assert!(matches!(result, Err(CameraError::NotStarted)));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn apriltag_detection_needs_a_running_camera() {
+        let camera = Mutex::new(CameraController::new());
+        assert!(matches!(CameraController::detect_apriltags(&camera), Err(CameraError::NotStarted)));
+    }
+
+    #[test]
+    fn latest_frame_buffer_needs_a_running_camera() {
+        let mut controller = CameraController::new();
+        assert!(matches!(controller.latest_frame_buffer(), Err(CameraError::NotStarted)));
+        //A frame left over from before the camera stopped isn't handed out either
+        controller.latest = Some((3, ColorMode::Grayscale, CapturedFrame { data: vec![9; 4], width: 2, height: 2 }));
+        assert!(matches!(controller.latest_frame_buffer(), Err(CameraError::NotStarted)));
+    }
+
+    #[test]
+    fn acquired_frame_outlives_the_controller_reference() {
+        let mut controller = CameraController::new();
+        assert!(matches!(controller.acquire_frame_buffer(), Err(CameraError::NotStarted)));
+        //Stopping drops the controller's reference, but a holder keeps the memory alive
+        let frame = FrameBuffer::from_slice(&[4, 5, 6]);
+        let held = Arc::new(BufferedFrame { id: 7, mode: ColorMode::Grayscale, width: 3, height: 1, data: frame });
+        controller.shared = Some(Arc::clone(&held));
+        controller.stop().unwrap();
+        assert!(controller.shared.is_none());
+        assert_eq!(Arc::strong_count(&held), 1);
+        assert_eq!(held.data.as_slice(), &[4, 5, 6]);
+    }
+}
@@ -0,0 +1,746 @@
+/// Mirroring applied to a frame while it is converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mirror {
+    pub horizontal: bool, //Mirror each row left to right
+    pub vertical: bool,   //Mirror the rows top to bottom
+}
+
+/// Which way captured frames are flipped, e.g. [`Flip::Both`] for a camera mounted upside down,
+/// which is the same as rotating frames by 180°.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Flip {
+    #[default]
+    None,
+    Horizontal, //Left to right
+    Vertical,   //Top to bottom
+    Both,
+}
+
+impl From<Flip> for Mirror {
+    fn from(flip: Flip) -> Self {
+        Mirror {
+            horizontal: matches!(flip, Flip::Horizontal | Flip::Both),
+            vertical: matches!(flip, Flip::Vertical | Flip::Both),
+        }
+    }
+}
+
+impl From<Mirror> for Flip {
+    fn from(mirror: Mirror) -> Self {
+        match (mirror.horizontal, mirror.vertical) {
+            (false, false) => Flip::None,
+            (true, false) => Flip::Horizontal,
+            (false, true) => Flip::Vertical,
+            (true, true) => Flip::Both,
+        }
+    }
+}
+
+/// A rectangular region of a frame, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    /// Returns `true` if `other` lies entirely inside this region.
+    pub fn contains(&self, other: Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x as u64 + other.width as u64 <= self.x as u64 + self.width as u64
+            && other.y as u64 + other.height as u64 <= self.y as u64 + self.height as u64
+    }
+
+    /// Shrinks the region to fit inside a frame, keeping its left edge and width on pixel pairs.
+    pub fn clip(self, width: u32, height: u32) -> Rect {
+        let x = self.x.min(width) & !1;
+        let y = self.y.min(height);
+        Rect {
+            x,
+            y,
+            width: self.width.min(width - x) & !1,
+            height: self.height.min(height - y),
+        }
+    }
+}
+
+/// Filtering applied to grayscale frames before edges are detected or QR codes are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreFilter {
+    #[default]
+    None,
+    GaussianBlur { radius: u32 }, //Smooths out sensor noise, see [`gaussian_blur`]
+}
+
+/// A range of colours in HSV space, for picking out objects of one colour.
+///
+/// Hue is in degrees from 0 to 359, saturation and value from 0 to 255. A hue range whose minimum is
+/// above its maximum wraps around through 0, e.g. 340 to 20 for reds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorFilter {
+    pub hue: (u16, u16),
+    pub saturation: (u8, u8),
+    pub value: (u8, u8),
+}
+
+impl ColorFilter {
+    /// Returns `true` if a colour with the given hue, saturation and value lies in the range.
+    pub fn contains(&self, hue: u16, saturation: u8, value: u8) -> bool {
+        let (hue_min, hue_max) = self.hue;
+        let hue_matches = match hue_min <= hue_max {
+            true => (hue_min..=hue_max).contains(&hue),
+            false => hue >= hue_min || hue <= hue_max,
+        };
+        hue_matches
+            && (self.saturation.0..=self.saturation.1).contains(&saturation)
+            && (self.value.0..=self.value.1).contains(&value)
+    }
+}
+
+/// Changes applied to a frame while it is converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub flip: Mirror,
+    pub crop: Option<Rect>,        //Region to keep, in the coordinates of the unflipped frame
+    pub scale: Option<(u32, u32)>, //Size to shrink the region to, it is never enlarged
+}
+
+/// Channel layout of an 8-bit image, as passed to the encoders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channels {
+    Gray, //One luma byte per pixel
+    Rgb,  //Three bytes per pixel in red, green, blue order
+}
+
+/// Byte order of a packed YUV 4:2:2 frame, where each 4-byte group holds two pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvLayout {
+    Yuyv, //Y0 U Y1 V
+    Uyvy, //U Y0 V Y1
+}
+
+impl YuvLayout {
+    /// Returns the layout for a V4L2 pixel format, or `None` if it isn't packed YUV 4:2:2.
+    pub fn from_fourcc(fourcc: &[u8]) -> Option<YuvLayout> {
+        match fourcc {
+            b"YUYV" => Some(YuvLayout::Yuyv),
+            b"UYVY" => Some(YuvLayout::Uyvy),
+            _ => None,
+        }
+    }
+
+    /// Returns the offsets of Y0, U, Y1 and V within a 4-byte group.
+    fn offsets(self) -> [usize; 4] {
+        match self {
+            YuvLayout::Yuyv => [0, 1, 2, 3],
+            YuvLayout::Uyvy => [1, 0, 3, 2],
+        }
+    }
+}
+
+/// Layout of a packed YUV 4:2:2 frame in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub layout: YuvLayout,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32, //Bytes from the start of one row to the next, at least `width * 2`
+}
+
+/// Extracts the luma (Y) plane from a packed YUV 4:2:2 frame.
+///
+/// Each 4-byte group holds two pixels, so the output is half the size of the input.
+///
+/// # Arguments
+///
+/// * `src` - The YUV frame data.
+/// * `format` - Byte order, size and row stride of the frame data.
+/// * `transform` - Cropping, mirroring and scaling to apply while converting.
+/// * `dst` - The buffer to write one grayscale byte per pixel into, resized to fit.
+///
+/// # Returns
+///
+/// The width and height of the converted image, which differ from the frame's if it was cropped or
+/// scaled.
+pub fn yuv422_to_gray(src: &[u8], format: &FrameFormat, transform: &Transform, dst: &mut Vec<u8>) -> (u32, u32) {
+    convert_yuv422(src, format, transform, dst, 1, |out, y, _, _| out[0] = y)
+}
+
+/// Converts a packed YUV 4:2:2 frame into 24-bit RGB, three bytes per pixel.
+///
+/// # Arguments
+///
+/// * `src` - The YUV frame data.
+/// * `format` - Byte order, size and row stride of the frame data.
+/// * `transform` - Cropping, mirroring and scaling to apply while converting.
+/// * `dst` - The buffer to write the RGB pixels into, resized to fit.
+///
+/// # Returns
+///
+/// The width and height of the converted image, which differ from the frame's if it was cropped or
+/// scaled.
+pub fn yuv422_to_rgb24(src: &[u8], format: &FrameFormat, transform: &Transform, dst: &mut Vec<u8>) -> (u32, u32) {
+    convert_yuv422(src, format, transform, dst, 3, |out, y, u, v| store_rgb(out, y, u, v, [0, 1, 2]))
+}
+
+/// Converts a packed YUV 4:2:2 frame into 24-bit BGR, the byte order OpenCV expects.
+///
+/// # Arguments
+///
+/// * `src` - The YUV frame data.
+/// * `format` - Byte order, size and row stride of the frame data.
+/// * `transform` - Cropping, mirroring and scaling to apply while converting.
+/// * `dst` - The buffer to write the BGR pixels into, resized to fit.
+///
+/// # Returns
+///
+/// The width and height of the converted image, which differ from the frame's if it was cropped or
+/// scaled.
+pub fn yuv422_to_bgr24(src: &[u8], format: &FrameFormat, transform: &Transform, dst: &mut Vec<u8>) -> (u32, u32) {
+    convert_yuv422(src, format, transform, dst, 3, |out, y, u, v| store_rgb(out, y, u, v, [2, 1, 0]))
+}
+
+/// Splits a packed YUV 4:2:2 frame into planar 4:2:2, the full size Y plane followed by the half
+/// width U and V planes, without converting or resampling any samples.
+///
+/// Rows missing from a short frame are filled with black, so every frame comes out the same size.
+///
+/// # Arguments
+///
+/// * `src` - The YUV frame data.
+/// * `format` - Byte order, size and row stride of the frame data.
+/// * `dst` - The buffer to write the three planes into, resized to fit.
+pub fn yuv422_to_planar(src: &[u8], format: &FrameFormat, dst: &mut Vec<u8>) {
+    let [y0, u, y1, v] = format.layout.offsets();
+    let pairs = format.width as usize / 2;
+    let height = format.height as usize;
+    let stride = (format.stride as usize).max(pairs * 4);
+
+    dst.clear();
+    dst.resize(pairs * 2 * height, 16);
+    dst.resize(pairs * 4 * height, 128);
+    let (luma, chroma) = dst.split_at_mut(pairs * 2 * height);
+    let (blue, red) = chroma.split_at_mut(pairs * height);
+
+    let rows = src.chunks(stride).take(height).filter(|line| line.len() >= pairs * 4);
+    for (row, line) in rows.enumerate() {
+        let luma = &mut luma[row * pairs * 2..(row + 1) * pairs * 2];
+        for (pair, pixels) in line[..pairs * 4].chunks_exact(4).enumerate() {
+            luma[pair * 2] = pixels[y0];
+            luma[pair * 2 + 1] = pixels[y1];
+            blue[row * pairs + pair] = pixels[u];
+            red[row * pairs + pair] = pixels[v];
+        }
+    }
+}
+
+/// Iterates over the brightness of each pixel of an 8-bit image.
+///
+/// Grayscale pixels are already luma. Colour pixels are reduced to `(r + 2g + b) / 4`, which is close
+/// enough to luma for measuring a frame and the same whichever way round red and blue are.
+pub fn luma(pixels: &[u8], channels: Channels) -> impl Iterator<Item = u8> + '_ {
+    let step = match channels {
+        Channels::Gray => 1,
+        Channels::Rgb => 3,
+    };
+    pixels.chunks_exact(step).map(|pixel| match pixel {
+        [r, g, b] => ((*r as u16 + 2 * *g as u16 + *b as u16) / 4) as u8,
+        _ => pixel[0],
+    })
+}
+
+/// Shrinks an image by a whole factor, averaging each `factor` by `factor` block of pixels into one.
+///
+/// Pixels left over at the right and bottom edges that don't fill a block are dropped. A factor of
+/// 1 copies the image as it is.
+///
+/// # Arguments
+///
+/// * `src` - The pixels, row by row.
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `channels` - Channel layout of `src`, which `dst` keeps.
+/// * `factor` - How many times smaller each side becomes, at least 1.
+/// * `dst` - The buffer to write the shrunk pixels into, resized to fit.
+///
+/// # Returns
+///
+/// The width and height of the shrunk image, at least one pixel each if the image isn't empty.
+pub fn shrink(src: &[u8], width: u32, height: u32, channels: Channels, factor: u32, dst: &mut Vec<u8>) -> (u32, u32) {
+    let bytes_per_pixel = match channels {
+        Channels::Gray => 1,
+        Channels::Rgb => 3,
+    };
+    let factor = factor.clamp(1, width.min(height).max(1)) as usize;
+    let (width, height) = (width as usize, height as usize);
+    let (out_width, out_height) = (width / factor, height / factor);
+    dst.clear();
+    dst.resize(out_width * out_height * bytes_per_pixel, 0);
+    if out_width == 0 || out_height == 0 || src.len() < width * height * bytes_per_pixel {
+        return (out_width as u32, out_height as u32);
+    }
+
+    let area = (factor * factor) as u32;
+    let row_bytes = width * bytes_per_pixel;
+    for (y, out) in dst.chunks_exact_mut(out_width * bytes_per_pixel).enumerate() {
+        for (x, pixel) in out.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            for (channel, value) in pixel.iter_mut().enumerate() {
+                let sum: u32 = (0..factor)
+                    .flat_map(|dy| (0..factor).map(move |dx| (y * factor + dy, x * factor + dx)))
+                    .map(|(row, column)| src[row * row_bytes + column * bytes_per_pixel + channel] as u32)
+                    .sum();
+                *value = ((sum + area / 2) / area) as u8;
+            }
+        }
+    }
+    (out_width as u32, out_height as u32)
+}
+
+/// Blurs a grayscale image with a Gaussian kernel reaching `radius` pixels either side of each
+/// pixel, with a standard deviation of half the radius.
+///
+/// The kernel is applied along the rows into `scratch` and then down the columns into `dst`, which
+/// takes `4 * radius + 2` multiplications per pixel rather than the square of the kernel width.
+/// Pixels past the border of the image are taken to repeat the nearest one on the border.
+///
+/// # Arguments
+///
+/// * `src` - The grayscale pixels, one byte per pixel, row by row.
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `radius` - How far the kernel reaches in pixels. 0 copies the image as it is.
+/// * `scratch` - A buffer for the image blurred along its rows, resized to fit.
+/// * `dst` - The buffer to write the blurred pixels into, resized to fit.
+pub fn gaussian_blur(src: &[u8], width: u32, height: u32, radius: u32, scratch: &mut Vec<u8>, dst: &mut Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    dst.clear();
+    dst.resize(width * height, 0);
+    if width == 0 || height == 0 || src.len() < width * height {
+        return;
+    }
+    if radius == 0 {
+        dst.copy_from_slice(&src[..width * height]);
+        return;
+    }
+
+    //Fixed-point weights with 12 bits for the centre, divided by their actual total so flat areas stay flat
+    let sigma = radius as f32 / 2.0;
+    let reach = radius as usize;
+    let weights: Vec<u32> = (0..=2 * reach)
+        .map(|i| (i as f32 - reach as f32).powi(2) / (2.0 * sigma * sigma))
+        .map(|exponent| ((4096.0 * (-exponent).exp()).round() as u32).max(1))
+        .collect();
+
+    scratch.clear();
+    scratch.resize(width * height, 0);
+    for (row, out) in src.chunks_exact(width).zip(scratch.chunks_exact_mut(width)) {
+        for (x, pixel) in out.iter_mut().enumerate() {
+            *pixel = convolve(&weights, |i| row[(x + i).saturating_sub(reach).min(width - 1)]);
+        }
+    }
+    for (y, out) in dst.chunks_exact_mut(width).enumerate() {
+        for (x, pixel) in out.iter_mut().enumerate() {
+            *pixel = convolve(&weights, |i| scratch[(y + i).saturating_sub(reach).min(height - 1) * width + x]);
+        }
+    }
+}
+
+/// Sums `sample(i) * weights[i]` over the kernel and divides by the total weight, rounding.
+fn convolve(weights: &[u32], sample: impl Fn(usize) -> u8) -> u8 {
+    let (sum, total) = weights
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(sum, total), (i, &weight)| (sum + sample(i) as u32 * weight, total + weight));
+    ((sum + total / 2) / total) as u8
+}
+
+/// Computes the Sobel gradient magnitude of a grayscale image, which is bright along edges and dark
+/// in flat areas.
+///
+/// The magnitude is divided by 4, so a sharp step from black to white across a vertical or
+/// horizontal edge comes out as 255, and clamped to 255 for diagonal ones. Pixels on the border of
+/// the image have no full neighbourhood and are left at 0.
+///
+/// # Arguments
+///
+/// * `src` - The grayscale pixels, one byte per pixel, row by row.
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `dst` - The buffer to write one magnitude byte per pixel into, resized to fit.
+pub fn sobel(src: &[u8], width: u32, height: u32, dst: &mut Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    dst.clear();
+    dst.resize(width * height, 0);
+    if width < 3 || height < 3 || src.len() < width * height {
+        return;
+    }
+
+    let pixel = |line: &[u8], x: usize| line[x] as i32;
+    for y in 1..height - 1 {
+        let (above, row, below) = (&src[(y - 1) * width..], &src[y * width..], &src[(y + 1) * width..]);
+        for x in 1..width - 1 {
+            let gx = pixel(above, x + 1) + 2 * pixel(row, x + 1) + pixel(below, x + 1)
+                - pixel(above, x - 1)
+                - 2 * pixel(row, x - 1)
+                - pixel(below, x - 1);
+            let gy = pixel(below, x - 1) + 2 * pixel(below, x) + pixel(below, x + 1)
+                - pixel(above, x - 1)
+                - 2 * pixel(above, x)
+                - pixel(above, x + 1);
+            let magnitude = ((gx * gx + gy * gy) as f32).sqrt() / 4.0;
+            dst[y * width + x] = magnitude.min(255.0) as u8;
+        }
+    }
+}
+
+/// Marks the pixels of a colour image whose colour lies within `filter`.
+///
+/// # Arguments
+///
+/// * `src` - The pixels, three bytes each, row by row.
+/// * `bgr` - Whether the pixels are in blue, green, red order rather than red, green, blue.
+/// * `filter` - The range of colours to mark.
+/// * `dst` - The buffer to write the mask into, one byte per pixel that is 255 where the colour
+///   matches and 0 elsewhere, resized to fit.
+pub fn hsv_mask(src: &[u8], bgr: bool, filter: &ColorFilter, dst: &mut Vec<u8>) {
+    dst.clear();
+    dst.extend(src.chunks_exact(3).map(|pixel| {
+        let (r, g, b) = match bgr {
+            true => (pixel[2], pixel[1], pixel[0]),
+            false => (pixel[0], pixel[1], pixel[2]),
+        };
+        let (hue, saturation, value) = rgb_to_hsv(r, g, b);
+        if filter.contains(hue, saturation, value) {
+            255
+        } else {
+            0
+        }
+    }));
+}
+
+/// Converts an RGB colour to its hue in degrees, and its saturation and value from 0 to 255, using
+/// integer arithmetic. Grays have a hue of 0.
+fn rgb_to_hsv(r: u8, g: u8, b: u8) -> (u16, u8, u8) {
+    let max = r.max(g).max(b) as i32;
+    let min = r.min(g).min(b) as i32;
+    let delta = max - min;
+    if delta == 0 {
+        return (0, 0, max as u8);
+    }
+
+    let saturation = (delta * 255 / max) as u8;
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let hue = if max == r {
+        60 * (g - b) / delta
+    } else if max == g {
+        120 + 60 * (b - r) / delta
+    } else {
+        240 + 60 * (r - g) / delta
+    };
+    (hue.rem_euclid(360) as u16, saturation, max as u8)
+}
+
+/// Walks a packed YUV 4:2:2 frame in output order, handing each pixel's Y, U and V to `store`.
+///
+/// Cropping, flipping and scaling are done by choosing which source row and pixel pair to read, so
+/// the frame is only copied once. Scaling picks the nearest source pixel, which is fast and good
+/// enough for image processing.
+fn convert_yuv422(
+    src: &[u8],
+    format: &FrameFormat,
+    transform: &Transform,
+    dst: &mut Vec<u8>,
+    bytes_per_pixel: usize,
+    store: impl Fn(&mut [u8], u8, u8, u8),
+) -> (u32, u32) {
+    let [y0, u, y1, v] = format.layout.offsets();
+    let flip = transform.flip;
+    let full = Rect { x: 0, y: 0, width: format.width, height: format.height };
+    let region = transform.crop.unwrap_or(full).clip(format.width, format.height);
+
+    let offset = region.x as usize * 2;
+    let pairs = region.width as usize / 2;
+    let row_bytes = pairs * 4;
+    let stride = (format.stride as usize).max(format.width as usize / 2 * 4);
+
+    dst.clear();
+    if pairs == 0 {
+        return (0, 0);
+    }
+
+    //Only convert complete rows in case the driver delivered a short frame
+    let complete_rows = (src.len() + stride).saturating_sub(offset + row_bytes) / stride;
+    let rows = (region.height as usize).min(complete_rows.saturating_sub(region.y as usize));
+
+    let width = pairs * 2;
+    let (out_width, out_height) = match transform.scale {
+        Some((out_width, out_height)) => ((out_width as usize).min(width), (out_height as usize).min(rows)),
+        None => (width, rows),
+    };
+    if out_width < width || out_height < rows {
+        if out_width == 0 || out_height == 0 {
+            return (0, 0);
+        }
+        dst.resize(out_width * out_height * bytes_per_pixel, 0);
+
+        for (row, out_row) in dst.chunks_exact_mut(out_width * bytes_per_pixel).enumerate() {
+            let scaled_row = row * rows / out_height;
+            let src_row = region.y as usize + if flip.vertical { rows - 1 - scaled_row } else { scaled_row };
+            let start = src_row * stride + offset;
+            let line = &src[start..start + row_bytes];
+
+            for (column, out) in out_row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+                let scaled_column = column * width / out_width;
+                let pixel = if flip.horizontal { width - 1 - scaled_column } else { scaled_column };
+                let pixels = &line[pixel / 2 * 4..pixel / 2 * 4 + 4];
+                let luma = if pixel % 2 == 0 { pixels[y0] } else { pixels[y1] };
+                store(out, luma, pixels[u], pixels[v]);
+            }
+        }
+        return (out_width as u32, out_height as u32);
+    }
+
+    dst.resize(rows * pairs * 2 * bytes_per_pixel, 0);
+
+    for (row, out_row) in dst.chunks_exact_mut(pairs * 2 * bytes_per_pixel).enumerate() {
+        let src_row = region.y as usize + if flip.vertical { rows - 1 - row } else { row };
+        let start = src_row * stride + offset;
+        let line = &src[start..start + row_bytes];
+
+        for (pair, out) in out_row.chunks_exact_mut(2 * bytes_per_pixel).enumerate() {
+            let src_pair = if flip.horizontal { pairs - 1 - pair } else { pair };
+            let pixels = &line[src_pair * 4..src_pair * 4 + 4];
+
+            //Mirroring a pair swaps its two luma samples, the chroma is shared
+            let (first, second) = if flip.horizontal {
+                (pixels[y1], pixels[y0])
+            } else {
+                (pixels[y0], pixels[y1])
+            };
+
+            let (left, right) = out.split_at_mut(bytes_per_pixel);
+            store(left, first, pixels[u], pixels[v]);
+            store(right, second, pixels[u], pixels[v]);
+        }
+    }
+
+    (pairs as u32 * 2, rows as u32)
+}
+
+/// Writes one pixel's red, green and blue bytes at the given offsets.
+fn store_rgb(out: &mut [u8], y: u8, u: u8, v: u8, order: [usize; 3]) {
+    let (r, g, b) = yuv_to_rgb(y, u, v);
+    out[order[0]] = r;
+    out[order[1]] = g;
+    out[order[2]] = b;
+}
+
+//Index of a channel value of 0 in `YuvTables::saturate`. Sums range from -258 to 534 once shifted
+const SATURATE_OFFSET: i32 = 384;
+
+//Built at compile time, so converting a pixel costs a few table reads and additions
+static YUV_TABLES: YuvTables = YuvTables::new();
+
+/// The terms of the BT.601 limited-range YUV to RGB conversion for every sample value, scaled by 256.
+struct YuvTables {
+    luma: [i32; 256],     //298 * (Y - 16) plus 128 to round, with Y below 16 treated as black
+    red_v: [i32; 256],    //409 * (V - 128)
+    green_u: [i32; 256],  //-100 * (U - 128)
+    green_v: [i32; 256],  //-208 * (V - 128)
+    blue_u: [i32; 256],   //516 * (U - 128)
+    saturate: [u8; 1024], //A sum shifted down by 8 and clamped to 0-255, from `SATURATE_OFFSET` on
+}
+
+impl YuvTables {
+    const fn new() -> Self {
+        let mut tables = YuvTables {
+            luma: [0; 256],
+            red_v: [0; 256],
+            green_u: [0; 256],
+            green_v: [0; 256],
+            blue_u: [0; 256],
+            saturate: [0; 1024],
+        };
+
+        let mut i = 0;
+        while i < 256 {
+            let (luma, chroma) = (i as i32 - 16, i as i32 - 128);
+            tables.luma[i] = if luma > 0 { luma * 298 } else { 0 } + 128;
+            tables.red_v[i] = 409 * chroma;
+            tables.green_u[i] = -100 * chroma;
+            tables.green_v[i] = -208 * chroma;
+            tables.blue_u[i] = 516 * chroma;
+            i += 1;
+        }
+
+        let mut i = 0;
+        while i < tables.saturate.len() {
+            let value = i as i32 - SATURATE_OFFSET;
+            tables.saturate[i] = if value < 0 { 0 } else if value > 255 { 255 } else { value as u8 };
+            i += 1;
+        }
+        tables
+    }
+}
+
+/// Converts one BT.601 limited-range YUV sample to RGB using fixed-point lookup tables.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+    let tables = &YUV_TABLES;
+    let (c, u, v) = (tables.luma[y as usize], u as usize, v as usize);
+
+    let channel = |value: i32| tables.saturate[((value >> 8) + SATURATE_OFFSET) as usize];
+    (
+        channel(c + tables.red_v[v]),
+        channel(c + tables.green_u[u] + tables.green_v[v]),
+        channel(c + tables.blue_u[u]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flips_convert_to_and_from_mirroring() {
+        for flip in [Flip::None, Flip::Horizontal, Flip::Vertical, Flip::Both] {
+            assert_eq!(Flip::from(Mirror::from(flip)), flip);
+        }
+        assert_eq!(Mirror::from(Flip::Both), Mirror { horizontal: true, vertical: true });
+        assert_eq!(Mirror::from(Flip::Vertical), Mirror { horizontal: false, vertical: true });
+    }
+
+    /// BT.601 limited-range conversion in floating point, rounded, that the fixed-point one follows.
+    fn float_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+        let c = 1.164 * (y as f32 - 16.0);
+        let (d, e) = (u as f32 - 128.0, v as f32 - 128.0);
+        let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+        [channel(c + 1.596 * e), channel(c - 0.392 * d - 0.813 * e), channel(c + 2.017 * d)]
+    }
+
+    fn assert_near(actual: &[u8], expected: [u8; 3]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(actual.abs_diff(expected) <= 1, "{:?} is not within 1 of {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn yuyv_block_converts_within_one_of_floating_point() {
+        //Black, white, gray and saturated samples, two pixels per group
+        #[rustfmt::skip]
+        let src = [
+            16, 128, 235, 128,
+            126, 128, 82, 240,
+            145, 54, 41, 34,
+            210, 16, 170, 146,
+        ];
+        let format = FrameFormat { layout: YuvLayout::Yuyv, width: 8, height: 1, stride: 16 };
+        let mut rgb = Vec::new();
+        assert_eq!(yuv422_to_rgb24(&src, &format, &Transform::default(), &mut rgb), (8, 1));
+
+        for (group, pixels) in src.chunks_exact(4).zip(rgb.chunks_exact(6)) {
+            assert_near(&pixels[..3], float_rgb(group[0], group[1], group[3]));
+            assert_near(&pixels[3..], float_rgb(group[2], group[1], group[3]));
+        }
+        assert_eq!(&rgb[..6], &[0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn conversion_stays_within_one_of_floating_point_across_the_range() {
+        for y in (16..=235).step_by(3) {
+            for u in (16..=240).step_by(4) {
+                for v in (16..=240).step_by(4) {
+                    let (r, g, b) = yuv_to_rgb(y, u, v);
+                    assert_near(&[r, g, b], float_rgb(y, u, v));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_tables_match_the_fixed_point_arithmetic() {
+        let arithmetic = |y: u8, u: u8, v: u8| {
+            let c = (y as i32 - 16).max(0) * 298;
+            let (d, e) = (u as i32 - 128, v as i32 - 128);
+            let channel = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+            (channel(c + 409 * e), channel(c - 100 * d - 208 * e), channel(c + 516 * d))
+        };
+        for y in 0..=255 {
+            for u in 0..=255 {
+                for v in 0..=255 {
+                    assert_eq!(yuv_to_rgb(y, u, v), arithmetic(y, u, v), "for {}, {}, {}", y, u, v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn both_pixels_of_a_2x1_frame_are_converted() {
+        let src = [81, 90, 145, 240]; //Red, then green, sharing one pair of chroma samples
+        let format = FrameFormat { layout: YuvLayout::Yuyv, width: 2, height: 1, stride: 4 };
+
+        let mut rgb = vec![7; 6];
+        assert_eq!(yuv422_to_rgb24(&src, &format, &Transform::default(), &mut rgb), (2, 1));
+        let (first, second) = (yuv_to_rgb(81, 90, 240), yuv_to_rgb(145, 90, 240));
+        assert_eq!(rgb, [first.0, first.1, first.2, second.0, second.1, second.2]);
+
+        let mut gray = Vec::new();
+        assert_eq!(yuv422_to_gray(&src, &format, &Transform::default(), &mut gray), (2, 1));
+        assert_eq!(gray, [81, 145]);
+    }
+
+    #[test]
+    fn sobel_marks_a_vertical_step_at_the_boundary() {
+        //8x5 image, dark on the left four columns and bright on the right four
+        let (width, height) = (8, 5);
+        let src: Vec<u8> = (0..width * height).map(|i| if i % width < 4 { 0 } else { 200 }).collect();
+        let mut edges = Vec::new();
+        sobel(&src, width as u32, height as u32, &mut edges);
+
+        assert_eq!(edges.len(), width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let inside = y > 0 && y < height - 1 && x > 0 && x < width - 1;
+                let expected = if inside && (x == 3 || x == 4) { 200 } else { 0 };
+                assert_eq!(edges[y * width + x], expected, "at {},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn sobel_of_a_horizontal_step_is_along_the_boundary_rows() {
+        let (width, height) = (5, 6);
+        let src: Vec<u8> = (0..width * height).map(|i| if i / width < 3 { 100 } else { 20 }).collect();
+        let mut edges = Vec::new();
+        sobel(&src, width as u32, height as u32, &mut edges);
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let expected = if y == 2 || y == 3 { 80 } else { 0 };
+                assert_eq!(edges[y * width + x], expected, "at {},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn sobel_of_a_gradient_is_uniform() {
+        //Brightness rises by 10 per column, so every inner pixel sees the same slope
+        let (width, height) = (6, 4);
+        let src: Vec<u8> = (0..width * height).map(|i| (i % width * 10) as u8).collect();
+        let mut edges = Vec::new();
+        sobel(&src, width as u32, height as u32, &mut edges);
+
+        for y in 1..height - 1 {
+            assert!(edges[y * width + 1..(y + 1) * width - 1].iter().all(|&edge| edge == 20));
+        }
+    }
+
+    #[test]
+    fn sobel_leaves_tiny_images_black() {
+        let mut edges = Vec::new();
+        sobel(&[0, 255, 0, 255], 2, 2, &mut edges);
+        assert_eq!(edges, [0; 4]);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::utils::local_time;
+use std::time::SystemTime;
+
+//Tags used, IFD0 ones first and then those of the Exif IFD, each directory in ascending order
+const IMAGE_WIDTH: u16 = 0x0100;
+const IMAGE_LENGTH: u16 = 0x0101;
+const DATE_TIME: u16 = 0x0132;
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const EXPOSURE_TIME: u16 = 0x829a;
+const EXIF_VERSION: u16 = 0x9000;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const USER_COMMENT: u16 = 0x9286;
+const PIXEL_X_DIMENSION: u16 = 0xa002;
+const PIXEL_Y_DIMENSION: u16 = 0xa003;
+
+//Field types
+const ASCII: u16 = 2;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const UNDEFINED: u16 = 7;
+
+//Start of the APP1 payload, before the TIFF header that offsets are counted from
+const EXIF_HEADER: [u8; 6] = *b"Exif\0\0";
+
+/// What is recorded about a photo.
+pub struct Metadata {
+    pub time: SystemTime, //When the photo was taken
+    pub width: u32,
+    pub height: u32,
+    pub exposure: Option<i64>, //V4L2 absolute exposure, in units of 100 µs
+    pub gain: Option<i64>,     //V4L2 gain, in the driver's own units
+}
+
+/// One directory entry, with its value already encoded.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: Vec<u8>,
+}
+
+impl Entry {
+    fn long(tag: u16, value: u32) -> Entry {
+        Entry { tag, kind: LONG, count: 1, value: value.to_be_bytes().to_vec() }
+    }
+
+    fn ascii(tag: u16, text: &str) -> Entry {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        Entry { tag, kind: ASCII, count: value.len() as u32, value }
+    }
+
+    fn undefined(tag: u16, value: Vec<u8>) -> Entry {
+        Entry { tag, kind: UNDEFINED, count: value.len() as u32, value }
+    }
+
+    fn rational(tag: u16, numerator: u32, denominator: u32) -> Entry {
+        let value = [numerator.to_be_bytes(), denominator.to_be_bytes()].concat();
+        Entry { tag, kind: RATIONAL, count: 1, value }
+    }
+}
+
+/// Inserts an EXIF segment describing a photo into a JPEG file.
+///
+/// The segment holds the time the photo was taken as `DateTime` and `DateTimeOriginal` in local
+/// time, its size, and the exposure and gain where they are known: the exposure as `ExposureTime`
+/// and both raw values in the `UserComment`, as EXIF has no tag for a sensor's gain setting. It goes
+/// straight after the SOI marker, or after the JFIF header if there is one, which keeps the file a
+/// valid JFIF as well. Data that doesn't start with an SOI marker is returned unchanged.
+///
+/// # Arguments
+///
+/// * `jpeg` - The JPEG file.
+/// * `metadata` - What to record about the photo.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the JPEG file with the EXIF segment.
+pub fn insert(jpeg: &[u8], metadata: &Metadata) -> Vec<u8> {
+    if !jpeg.starts_with(&[0xff, 0xd8]) {
+        return jpeg.to_vec();
+    }
+
+    //Skip an APP0 segment right after the SOI marker
+    let mut position = 2;
+    if jpeg.len() >= 6 && jpeg[2..4] == [0xff, 0xe0] {
+        let length = u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        position = (4 + length).min(jpeg.len());
+    }
+
+    let segment = segment(metadata);
+    let mut out = Vec::with_capacity(jpeg.len() + segment.len());
+    out.extend_from_slice(&jpeg[..position]);
+    out.extend_from_slice(&segment);
+    out.extend_from_slice(&jpeg[position..]);
+    out
+}
+
+/// Builds the APP1 segment, with its marker and length.
+fn segment(metadata: &Metadata) -> Vec<u8> {
+    let time = format_time(metadata.time);
+
+    let mut exif = Vec::new();
+    if let Some(exposure) = metadata.exposure.filter(|&exposure| exposure > 0) {
+        exif.push(Entry::rational(EXPOSURE_TIME, exposure.min(u32::MAX as i64) as u32, 10_000));
+    }
+    exif.push(Entry::undefined(EXIF_VERSION, b"0230".to_vec()));
+    exif.push(Entry::ascii(DATE_TIME_ORIGINAL, &time));
+    let comment: Vec<String> = [("exposure", metadata.exposure), ("gain", metadata.gain)]
+        .iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{}={}", name, value)))
+        .collect();
+    if !comment.is_empty() {
+        exif.push(Entry::undefined(USER_COMMENT, [b"ASCII\0\0\0", comment.join(" ").as_bytes()].concat()));
+    }
+    exif.push(Entry::long(PIXEL_X_DIMENSION, metadata.width));
+    exif.push(Entry::long(PIXEL_Y_DIMENSION, metadata.height));
+
+    let mut ifd0 = vec![
+        Entry::long(IMAGE_WIDTH, metadata.width),
+        Entry::long(IMAGE_LENGTH, metadata.height),
+        Entry::ascii(DATE_TIME, &time),
+        Entry::long(EXIF_IFD_POINTER, 0),
+    ];
+
+    //Offsets count from the TIFF header, which is followed by IFD0 and then the Exif IFD
+    let exif_start = 8 + directory(&ifd0, 8).len() as u32;
+    ifd0[3] = Entry::long(EXIF_IFD_POINTER, exif_start);
+
+    let mut tiff = vec![b'M', b'M', 0, 42, 0, 0, 0, 8];
+    tiff.extend(directory(&ifd0, 8));
+    tiff.extend(directory(&exif, exif_start));
+
+    let length = (2 + EXIF_HEADER.len() + tiff.len()) as u16;
+    let mut out = vec![0xff, 0xe1];
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&EXIF_HEADER);
+    out.extend(tiff);
+    out
+}
+
+/// Encodes a directory starting `start` bytes into the TIFF data, followed by the values too large
+/// to fit in their entries. There is never a next directory.
+fn directory(entries: &[Entry], start: u32) -> Vec<u8> {
+    let data_start = start + 2 + 12 * entries.len() as u32 + 4;
+    let mut out = (entries.len() as u16).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    for entry in entries {
+        out.extend_from_slice(&entry.tag.to_be_bytes());
+        out.extend_from_slice(&entry.kind.to_be_bytes());
+        out.extend_from_slice(&entry.count.to_be_bytes());
+        if entry.value.len() <= 4 {
+            let mut value = [0; 4];
+            value[..entry.value.len()].copy_from_slice(&entry.value);
+            out.extend_from_slice(&value);
+        } else {
+            out.extend_from_slice(&(data_start + data.len() as u32).to_be_bytes());
+            data.extend_from_slice(&entry.value);
+            //Values start on a word boundary
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+    out.extend_from_slice(&[0; 4]);
+    out.extend(data);
+    out
+}
+
+/// Formats a time as EXIF expects, `YYYY:MM:DD HH:MM:SS` in local time.
+fn format_time(time: SystemTime) -> String {
+    let local = local_time(time);
+    format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        local.tm_year + 1900,
+        local.tm_mon + 1,
+        local.tm_mday,
+        local.tm_hour,
+        local.tm_min,
+        local.tm_sec
+    )
+}
@@ -0,0 +1,92 @@
+use std::ptr;
+use std::slice;
+
+/// A heap buffer of frame data, held as a raw pointer and length so it can be handed to code outside
+/// Rust, such as a Java direct buffer, without copying.
+///
+/// The buffer owns its memory and frees it when dropped. All access from Rust goes through
+/// [`as_slice`](Self::as_slice) and [`as_mut_slice`](Self::as_mut_slice), which cover exactly the
+/// allocated length, so indexing past the end panics rather than writing over other memory.
+pub struct FrameBuffer(*mut u8, usize);
+
+//The pointer is owned by the buffer alone, so moving the buffer to another thread moves the memory
+//with it. Shared references only ever read through it, so they can be shared between threads too
+unsafe impl Send for FrameBuffer {}
+unsafe impl Sync for FrameBuffer {}
+
+impl FrameBuffer {
+    /// Allocates a zeroed buffer of `length` bytes.
+    pub fn new(length: usize) -> FrameBuffer {
+        FrameBuffer::from_vec(vec![0; length])
+    }
+
+    /// Allocates a buffer holding a copy of `data`.
+    pub fn from_slice(data: &[u8]) -> FrameBuffer {
+        FrameBuffer::from_vec(data.to_vec())
+    }
+
+    fn from_vec(data: Vec<u8>) -> FrameBuffer {
+        let length = data.len();
+        FrameBuffer(Box::into_raw(data.into_boxed_slice()) as *mut u8, length)
+    }
+
+    /// Returns the length of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.1
+    }
+
+    /// Returns `true` if the buffer holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.1 == 0
+    }
+
+    /// Returns the address of the first byte, which stays the same for the life of the buffer.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.0
+    }
+
+    /// Returns the whole buffer as a slice.
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.0, self.1) }
+    }
+
+    /// Returns the whole buffer as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.0, self.1) }
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        //Rebuild the box the memory came from, so it is freed with the layout it was allocated with
+        unsafe {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(self.0, self.1)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn slices_cover_exactly_the_buffer() {
+        let mut buffer = FrameBuffer::new(6);
+        assert_eq!(buffer.as_slice(), &[0; 6]);
+        buffer.as_mut_slice().copy_from_slice(b"frame!");
+        assert_eq!(buffer.as_slice(), b"frame!");
+        assert_eq!(buffer.as_slice().get(6), None);
+        assert!(FrameBuffer::new(0).is_empty());
+    }
+
+    #[test]
+    fn buffer_moves_to_another_thread() {
+        let buffer = FrameBuffer::from_slice(&[1, 2, 3]);
+        let address = buffer.as_ptr() as usize;
+        let (sum, moved) = thread::spawn(move || (buffer.as_slice().iter().sum::<u8>(), buffer.as_ptr() as usize))
+            .join()
+            .unwrap();
+        assert_eq!((sum, moved), (6, address));
+    }
+}
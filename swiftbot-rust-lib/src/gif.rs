@@ -0,0 +1,313 @@
+use crate::convert::Channels;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::ops::Range;
+
+//Bits per palette index, enough for 256 colours, which is also the smallest LZW code size used
+const INDEX_BITS: u32 = 8;
+
+//Widest LZW code GIF allows, which caps the code table at 4096 entries
+const MAX_CODE_BITS: u32 = 12;
+
+//Colours are counted at 5 bits per channel while a palette is picked
+const HISTOGRAM_SIZE: usize = 1 << 15;
+
+/// Writes frames into an animated GIF file that loops forever.
+///
+/// Each frame gets a palette of its own, so a clip whose colours change doesn't wash out towards
+/// the end. Colour frames are reduced to 256 colours by median cut, without dithering, and grayscale
+/// frames keep all 256 levels of gray. The buffers used to quantize and compress a frame are kept
+/// between frames.
+pub struct Writer<W: Write> {
+    inner: W,
+    width: u16,
+    height: u16,
+    frames: u32,
+    histogram: Vec<[u32; 4]>,       //Pixels of each 15-bit colour in the frame, and their red, green and blue sums
+    colors: Vec<(u16, u32)>,        //15-bit colours found in the frame, with their pixel counts
+    lookup: Vec<u8>,                //Palette index of each 15-bit colour
+    indices: Vec<u8>,               //The frame as palette indices
+    codes: HashMap<(u16, u8), u16>, //LZW code of each string, keyed by the code of its prefix and its last index
+    compressed: Vec<u8>,            //The frame as LZW codes, before it is split into sub-blocks
+}
+
+/// A box of colours in the histogram that median cut may split further.
+struct ColorBox {
+    colors: Range<usize>, //The colours in the box, as a range of `Writer::colors`
+    channel: u32,         //Channel the colours are spread furthest along, 0 for red to 2 for blue
+    spread: u16,          //Difference between the largest and smallest value of that channel
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts a GIF file of frames `width` by `height` pixels, writing the header into `inner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if a side is 0 or above 65535, the
+    /// largest GIF can store, or the error from writing the header.
+    pub fn new(mut inner: W, width: u32, height: u32) -> io::Result<Self> {
+        let size = |side: u32| u16::try_from(side).ok().filter(|&side| side > 0);
+        let (width, height) = match (size(width), size(height)) {
+            (Some(width), Some(height)) => (width, height),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("GIF frames must be between 1 and 65535 pixels a side, got {}x{}", width, height),
+                ))
+            }
+        };
+
+        inner.write_all(b"GIF89a")?;
+        inner.write_all(&width.to_le_bytes())?;
+        inner.write_all(&height.to_le_bytes())?;
+        //No global palette, as every frame has its own, with 8 bits of colour resolution
+        inner.write_all(&[0x70, 0, 0])?;
+        //Application extension that makes viewers loop the clip forever
+        inner.write_all(&[0x21, 0xFF, 0x0B])?;
+        inner.write_all(b"NETSCAPE2.0")?;
+        inner.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Writer {
+            inner,
+            width,
+            height,
+            frames: 0,
+            histogram: Vec::new(),
+            colors: Vec::new(),
+            lookup: Vec::new(),
+            indices: Vec::new(),
+            codes: HashMap::new(),
+            compressed: Vec::new(),
+        })
+    }
+
+    /// Appends a frame that is shown for `delay` hundredths of a second.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - The frame, row by row at the size given to [`Writer::new`]. Missing pixels are
+    ///   stored as black.
+    /// * `channels` - Channel layout of `pixels`, with colour pixels in red, green, blue order.
+    /// * `delay` - How long the frame is shown, in hundredths of a second.
+    pub fn write_frame(&mut self, pixels: &[u8], channels: Channels, delay: u16) -> io::Result<()> {
+        let palette = match channels {
+            Channels::Gray => {
+                self.indices.clear();
+                self.indices.extend_from_slice(&pixels[..pixels.len().min(self.pixel_count())]);
+                std::array::from_fn(|level| [level as u8; 3])
+            }
+            Channels::Rgb => self.quantize(pixels),
+        };
+        self.indices.resize(self.pixel_count(), 0);
+
+        //Graphic control extension giving the delay, with no transparency
+        self.inner.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.inner.write_all(&delay.to_le_bytes())?;
+        self.inner.write_all(&[0x00, 0x00])?;
+
+        //Image descriptor covering the whole canvas, followed by its 256-colour palette
+        self.inner.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.inner.write_all(&self.width.to_le_bytes())?;
+        self.inner.write_all(&self.height.to_le_bytes())?;
+        self.inner.write_all(&[0x80 | (INDEX_BITS as u8 - 1)])?;
+        for color in &palette {
+            self.inner.write_all(color)?;
+        }
+
+        self.compress();
+        self.inner.write_all(&[INDEX_BITS as u8])?;
+        for block in self.compressed.chunks(255) {
+            self.inner.write_all(&[block.len() as u8])?;
+            self.inner.write_all(block)?;
+        }
+        self.inner.write_all(&[0])?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> u32 {
+        self.frames
+    }
+
+    /// Ends the file and returns the writer it was written into.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(&[0x3B])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Picks a palette for RGB pixels by median cut and maps the pixels to it in `indices`.
+    ///
+    /// Starting with one box holding every colour in the frame, the box spread furthest along one
+    /// channel is split in two at the median pixel along that channel, until there are 256 boxes
+    /// or no box holds more than one colour. Each box becomes the mean colour of its pixels.
+    fn quantize(&mut self, pixels: &[u8]) -> [[u8; 3]; 256] {
+        let pixels = &pixels[..(pixels.len() / 3).min(self.pixel_count()) * 3];
+        self.histogram.clear();
+        self.histogram.resize(HISTOGRAM_SIZE, [0; 4]);
+        for pixel in pixels.chunks_exact(3) {
+            let [count, red, green, blue] = &mut self.histogram[color_key(pixel)];
+            *count += 1;
+            (*red, *green, *blue) = (*red + pixel[0] as u32, *green + pixel[1] as u32, *blue + pixel[2] as u32);
+        }
+        self.colors.clear();
+        let counts = (0..HISTOGRAM_SIZE as u16).zip(&self.histogram);
+        self.colors.extend(counts.filter(|(_, slot)| slot[0] > 0).map(|(key, slot)| (key, slot[0])));
+
+        let mut boxes = vec![self.color_box(0..self.colors.len())];
+        while boxes.len() < 256 {
+            let splittable = boxes.iter().enumerate().filter(|(_, color_box)| color_box.spread > 0);
+            let widest = match splittable.max_by_key(|(_, color_box)| color_box.spread) {
+                Some((widest, _)) => widest,
+                None => break,
+            };
+            let (first, second) = self.split(&boxes[widest]);
+            boxes[widest] = first;
+            boxes.push(second);
+        }
+
+        let mut palette = [[0; 3]; 256];
+        self.lookup.clear();
+        self.lookup.resize(HISTOGRAM_SIZE, 0);
+        for (index, color_box) in boxes.iter().enumerate() {
+            let mut sums = [0u64; 3];
+            let mut total = 0u64;
+            for &(key, count) in &self.colors[color_box.colors.clone()] {
+                for (sum, &slot_sum) in sums.iter_mut().zip(&self.histogram[key as usize][1..]) {
+                    *sum += slot_sum as u64;
+                }
+                total += count as u64;
+                self.lookup[key as usize] = index as u8;
+            }
+            palette[index] = sums.map(|sum| ((sum + total / 2) / total.max(1)) as u8);
+        }
+
+        self.indices.clear();
+        self.indices.extend(pixels.chunks_exact(3).map(|pixel| self.lookup[color_key(pixel)]));
+        palette
+    }
+
+    /// Measures which channel the colours in `colors` are spread furthest along.
+    fn color_box(&self, colors: Range<usize>) -> ColorBox {
+        let (channel, spread) = (0..3)
+            .map(|channel| {
+                let values = self.colors[colors.clone()].iter().map(|&(key, _)| channel_value(key, channel));
+                let (min, max) = values.fold((u16::MAX, 0), |(min, max), value| (min.min(value), max.max(value)));
+                (channel, max.saturating_sub(min))
+            })
+            .max_by_key(|&(_, spread)| spread)
+            .unwrap_or((0, 0));
+        ColorBox { colors, channel, spread }
+    }
+
+    /// Splits a box holding at least two colours at the median pixel along its widest channel.
+    fn split(&mut self, color_box: &ColorBox) -> (ColorBox, ColorBox) {
+        let Range { start, end } = color_box.colors;
+        let colors = &mut self.colors[start..end];
+        colors.sort_unstable_by_key(|&(key, _)| channel_value(key, color_box.channel));
+
+        let half = colors.iter().map(|&(_, count)| count as u64).sum::<u64>() / 2;
+        let mut seen = 0;
+        let median = colors.iter().position(|&(_, count)| {
+            seen += count as u64;
+            seen > half
+        });
+        //Both halves keep at least one colour
+        let middle = start + median.unwrap_or(0).clamp(1, end - start - 1);
+        (self.color_box(start..middle), self.color_box(middle..end))
+    }
+
+    /// Compresses `indices` into `compressed` with the variable-width LZW coding GIF uses.
+    ///
+    /// Codes start 9 bits wide and grow as the table fills. Once the table holds 4096 strings it is
+    /// cleared and compression starts over.
+    fn compress(&mut self) {
+        let clear = 1u16 << INDEX_BITS;
+        let end = clear + 1;
+        let mut bits = BitWriter { out: &mut self.compressed, buffer: 0, count: 0 };
+        bits.out.clear();
+        self.codes.clear();
+        let mut width = INDEX_BITS + 1;
+        let mut next = end + 1;
+        bits.write(clear, width);
+
+        let mut indices = self.indices.iter();
+        let mut prefix = match indices.next() {
+            Some(&index) => index as u16,
+            None => {
+                bits.write(end, width);
+                bits.flush();
+                return;
+            }
+        };
+        for &index in indices {
+            if let Some(&code) = self.codes.get(&(prefix, index)) {
+                prefix = code;
+                continue;
+            }
+            bits.write(prefix, width);
+            if next < 1 << MAX_CODE_BITS {
+                self.codes.insert((prefix, index), next);
+                next += 1;
+                //The decoder adds each string one code later, so it widens its codes one code later too
+                if next > 1 << width && width < MAX_CODE_BITS {
+                    width += 1;
+                }
+            } else {
+                bits.write(clear, width);
+                self.codes.clear();
+                width = INDEX_BITS + 1;
+                next = end + 1;
+            }
+            prefix = index as u16;
+        }
+        bits.write(prefix, width);
+        //The decoder adds a string for the last code too, which may widen the end code
+        if next == 1 << width && width < MAX_CODE_BITS {
+            width += 1;
+        }
+        bits.write(end, width);
+        bits.flush();
+    }
+}
+
+/// Packs codes into bytes least significant bit first, as GIF stores them.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32, //Bits not yet written, in the low `count` bits
+    count: u32,
+}
+
+impl BitWriter<'_> {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += width;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+            (self.buffer, self.count) = (0, 0);
+        }
+    }
+}
+
+/// Returns the histogram slot of an RGB pixel, keeping the top 5 bits of each channel.
+fn color_key(pixel: &[u8]) -> usize {
+    (pixel[0] as usize >> 3) << 10 | (pixel[1] as usize >> 3) << 5 | pixel[2] as usize >> 3
+}
+
+/// Returns one 5-bit channel of a histogram slot, 0 for red to 2 for blue.
+fn channel_value(key: u16, channel: u32) -> u16 {
+    (key >> (10 - 5 * channel)) & 31
+}
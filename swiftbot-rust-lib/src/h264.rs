@@ -0,0 +1,270 @@
+use crate::v4l2::{self, M2mQueue};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+
+//Encoder controls
+const CID_MPEG_VIDEO_BITRATE: u32 = 0x0099_09CF;
+const CID_MPEG_VIDEO_REPEAT_SEQ_HEADER: u32 = 0x0099_09E2;
+const CID_MPEG_VIDEO_H264_I_PERIOD: u32 = 0x0099_0A66;
+
+//Buffers allocated on each queue, enough to keep the encoder busy while a frame is being captured
+const BUFFER_COUNT: u32 = 4;
+
+//How long to wait for the encoder before deciding it has stopped responding
+const TIMEOUT_MS: i32 = 2000;
+
+/// Bitrate recordings are encoded at unless another is asked for, in bits per second.
+pub const BITRATE: u32 = 5_000_000;
+
+/// Finds a hardware H.264 encoder, such as the Raspberry Pi's `bcm2835-codec`, that accepts frames
+/// in the given pixel format.
+///
+/// Returns the path of its device node, or `None` if there isn't one.
+pub fn find_encoder(input: &[u8; 4]) -> Option<String> {
+    let indices = v4l2::device_indices().ok()?;
+    indices.into_iter().map(|index| format!("/dev/video{}", index)).find(|path| {
+        let device = match v4l2::open(path) {
+            Ok(device) => device,
+            Err(_) => return false,
+        };
+        let memory_to_memory = v4l2::query_capability(&device)
+            .map(|capability| {
+                capability.supports(v4l2::CAP_VIDEO_M2M_MPLANE) && capability.supports(v4l2::CAP_STREAMING)
+            })
+            .unwrap_or(false);
+
+        memory_to_memory
+            && v4l2::supports_m2m_format(&device, M2mQueue::Capture, b"H264")
+            && v4l2::supports_m2m_format(&device, M2mQueue::Output, input)
+    })
+}
+
+/// A buffer shared with the driver through `mmap`.
+struct Mapping {
+    data: *mut u8,
+    length: usize,
+}
+
+impl Mapping {
+    fn new(device: &File, queue: M2mQueue, index: u32) -> io::Result<Mapping> {
+        let (offset, length) = v4l2::query_m2m_buffer(device, queue, index)?;
+        let data = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                length as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                device.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+        if data == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { data: data as *mut u8, length: length as usize })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data, self.length) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.data, self.length) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.data as *mut libc::c_void, self.length);
+        }
+    }
+}
+
+/// Encodes packed 24-bit frames to H.264 on a V4L2 memory-to-memory encoder.
+///
+/// Frames go in through the output queue and the encoded data comes back through the capture queue,
+/// one buffer per frame, after a short delay. Every buffer is handed to the sink passed to
+/// [`Encoder::encode`] or [`Encoder::finish`] as Annex B NAL units, starting with the SPS and PPS.
+pub struct Encoder {
+    output: Vec<Mapping>,  //Raw frame buffers
+    capture: Vec<Mapping>, //Encoded data buffers
+    free: Vec<u32>,        //Output buffers not currently held by the driver
+    row_bytes: usize,      //Bytes in one row of the frames passed to `encode`
+    bytes_per_line: usize, //Row length of the output buffers, including the driver's padding
+    height: usize,
+    frame_size: u32,       //Bytes of an output buffer that are filled with pixels
+    finished: bool,        //The driver has returned its last encoded buffer
+    device: File,
+}
+
+impl Encoder {
+    /// Opens the encoder at `path` for frames of the given size and pixel format, encoding at
+    /// `bitrate` bits per second.
+    ///
+    /// A keyframe is emitted every `fps` frames, so recordings can be seeked to the second.
+    pub fn new(
+        path: &str,
+        width: u32,
+        height: u32,
+        input: &[u8; 4],
+        fps: u32,
+        bitrate: u32,
+    ) -> io::Result<Encoder> {
+        let device = v4l2::open_nonblocking(path)?;
+
+        //The coded format has to be set before the raw one, leaving room for a keyframe of a noisy scene
+        let encoded_size = (width * height).max(512 * 1024);
+        v4l2::set_m2m_format(&device, M2mQueue::Capture, width, height, b"H264", encoded_size)?;
+        let raw = v4l2::set_m2m_format(&device, M2mQueue::Output, width, height, input, 0)?;
+        if raw.width != width || raw.height < height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The H.264 encoder does not support {}x{} frames", width, height),
+            ));
+        }
+
+        //Not every driver supports these, the encoder's defaults are used for the rest
+        let _ = v4l2::set_control(&device, CID_MPEG_VIDEO_BITRATE, bitrate.min(i32::MAX as u32) as i32);
+        let _ = v4l2::set_control(&device, CID_MPEG_VIDEO_H264_I_PERIOD, fps.max(1) as i32);
+        let _ = v4l2::set_control(&device, CID_MPEG_VIDEO_REPEAT_SEQ_HEADER, 1);
+
+        let output = Self::map_buffers(&device, M2mQueue::Output)?;
+        let capture = Self::map_buffers(&device, M2mQueue::Capture)?;
+        for index in 0..capture.len() as u32 {
+            v4l2::queue_m2m_buffer(&device, M2mQueue::Capture, index, 0)?;
+        }
+        v4l2::stream_on(&device, M2mQueue::Output)?;
+        v4l2::stream_on(&device, M2mQueue::Capture)?;
+
+        Ok(Encoder {
+            free: (0..output.len() as u32).rev().collect(),
+            output,
+            capture,
+            row_bytes: width as usize * 3,
+            bytes_per_line: raw.bytes_per_line as usize,
+            height: height as usize,
+            frame_size: raw.size_image,
+            finished: false,
+            device,
+        })
+    }
+
+    /// Allocates and maps the buffers of one queue.
+    fn map_buffers(device: &File, queue: M2mQueue) -> io::Result<Vec<Mapping>> {
+        let count = v4l2::request_m2m_buffers(device, queue, BUFFER_COUNT)?;
+        (0..count).map(|index| Mapping::new(device, queue, index)).collect()
+    }
+
+    /// Queues a frame for encoding, handing any encoded data that is ready to `sink`.
+    ///
+    /// `frame` holds the rows of the frame back to back with no padding. Blocks while every output
+    /// buffer is still held by the driver.
+    pub fn encode(&mut self, frame: &[u8], sink: &mut impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        let index = loop {
+            if let Some(index) = self.free.pop() {
+                break index;
+            }
+            self.wait(libc::POLLIN | libc::POLLOUT)?;
+            self.drain(sink)?;
+            match v4l2::dequeue_m2m_buffer(&self.device, M2mQueue::Output) {
+                Ok(buffer) => self.free.push(buffer.index),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        };
+
+        //Copy the frame row by row, as the driver pads its rows
+        let (row_bytes, bytes_per_line) = (self.row_bytes, self.bytes_per_line.max(self.row_bytes));
+        let buffer = self.output[index as usize].as_mut_slice();
+        let rows = buffer.chunks_mut(bytes_per_line).zip(frame.chunks(row_bytes)).take(self.height);
+        for (dst, src) in rows {
+            let length = src.len().min(dst.len());
+            dst[..length].copy_from_slice(&src[..length]);
+        }
+
+        let bytes_used = self.frame_size.min(buffer.len() as u32);
+        v4l2::queue_m2m_buffer(&self.device, M2mQueue::Output, index, bytes_used)?;
+        self.drain(sink)
+    }
+
+    /// Waits for the frames already queued to be encoded, handing the rest of the data to `sink`.
+    pub fn finish(mut self, sink: &mut impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        match v4l2::stop_encoder(&self.device) {
+            Ok(()) => {
+                while !self.finished {
+                    self.wait(libc::POLLIN)?;
+                    self.drain(sink)?;
+                }
+            }
+            //Without the stop command there is no way to tell when the last frame is out, so wait
+            //until the encoder goes quiet
+            Err(_) => {
+                while self.poll(libc::POLLIN, TIMEOUT_MS / 4)? {
+                    self.drain(sink)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands every encoded buffer that is ready to `sink` and gives it back to the driver.
+    fn drain(&mut self, sink: &mut impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        while !self.finished {
+            let buffer = match v4l2::dequeue_m2m_buffer(&self.device, M2mQueue::Capture) {
+                Ok(buffer) => buffer,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            let data = self.capture[buffer.index as usize].as_slice();
+            let start = (buffer.offset as usize).min(data.len());
+            let end = (start + buffer.length as usize).min(data.len());
+            if end > start {
+                sink(&data[start..end])?;
+            }
+
+            if buffer.last {
+                self.finished = true;
+            } else {
+                v4l2::queue_m2m_buffer(&self.device, M2mQueue::Capture, buffer.index, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for the driver to have a buffer ready, failing if it takes too long.
+    fn wait(&self, events: libc::c_short) -> io::Result<()> {
+        if self.poll(events, TIMEOUT_MS)? {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "The H.264 encoder stopped responding"))
+        }
+    }
+
+    /// Waits up to `timeout_ms` for one of `events`, returning `false` on timeout.
+    fn poll(&self, events: libc::c_short, timeout_ms: i32) -> io::Result<bool> {
+        let mut fd = libc::pollfd { fd: self.device.as_raw_fd(), events, revents: 0 };
+        loop {
+            let result = unsafe { libc::poll(&mut fd, 1, timeout_ms) };
+            if result >= 0 {
+                return Ok(result > 0);
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        //The mappings are released when the fields are dropped afterwards
+        let _ = v4l2::stream_off(&self.device, M2mQueue::Output);
+        let _ = v4l2::stream_off(&self.device, M2mQueue::Capture);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::convert::Channels;
+use std::f32::consts::PI;
+
+//Maps zig-zag coefficient order to row-major order within an 8x8 block
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21,
+    28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61,
+    54, 47, 55, 62, 63,
+];
+
+//Quantization tables from Annex K of the JPEG specification, in row-major order
+const LUMA_QUANT: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29,
+    51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121,
+    120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANT: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99, 47, 66, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+//Huffman tables from Annex K, as code counts per length followed by the symbols
+const LUMA_DC_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const CHROMA_DC_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const LUMA_AC_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07, 0x22, 0x71,
+    0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72,
+    0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37,
+    0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59,
+    0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83,
+    0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3,
+    0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+];
+const CHROMA_AC_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71, 0x13, 0x22,
+    0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1,
+    0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36,
+    0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58,
+    0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a,
+    0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a,
+    0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba,
+    0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+];
+
+/// Encodes an image as a baseline JFIF JPEG.
+///
+/// Colour images are stored with 4:2:0 chroma subsampling. If `pixels` holds fewer than
+/// `width * height` pixels the missing ones are encoded as black.
+///
+/// # Arguments
+///
+/// * `pixels` - The image data, row by row.
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `channels` - Channel layout of `pixels`.
+/// * `quality` - Encoding quality from 1 (smallest) to 100 (best), clamped to that range.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the JPEG file.
+pub fn encode(pixels: &[u8], width: u32, height: u32, channels: Channels, quality: u8) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() / 8);
+    Encoder::new().encode_into(pixels, width, height, channels, quality, &mut out);
+    out
+}
+
+/// A JPEG encoder that keeps its tables and working buffers between images, for encoding a stream
+/// of frames without reallocating for each one.
+pub struct Encoder {
+    planes: Planes,
+    dct: DctTable,
+    luma: (HuffmanTable, HuffmanTable),
+    chroma: (HuffmanTable, HuffmanTable),
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder {
+            planes: Planes::new(),
+            dct: DctTable::new(),
+            luma: (
+                HuffmanTable::new(&LUMA_DC_BITS, &DC_VALUES),
+                HuffmanTable::new(&LUMA_AC_BITS, &LUMA_AC_VALUES),
+            ),
+            chroma: (
+                HuffmanTable::new(&CHROMA_DC_BITS, &DC_VALUES),
+                HuffmanTable::new(&CHROMA_AC_BITS, &CHROMA_AC_VALUES),
+            ),
+        }
+    }
+
+    /// Encodes an image like [`encode`], replacing the contents of `out` with the JPEG file.
+    pub fn encode_into(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        channels: Channels,
+        quality: u8,
+        out: &mut Vec<u8>,
+    ) {
+        let (width, height) = (width.clamp(1, u16::MAX as u32), height.clamp(1, u16::MAX as u32));
+        let quality = quality.clamp(1, 100);
+        let luma_quant = scale_quant(&LUMA_QUANT, quality);
+        let chroma_quant = scale_quant(&CHROMA_QUANT, quality);
+
+        self.planes.fill(pixels, width as usize, height as usize, channels);
+        let planes = &self.planes;
+        let colour = channels == Channels::Rgb;
+
+        out.clear();
+        write_headers(out, width, height, colour, &luma_quant, &chroma_quant);
+
+        let mut bits = BitWriter::new(out);
+        let mut previous_dc = [0i32; 3];
+        let mut block = [0f32; 64];
+
+        //Colour images use 16x16 MCUs of four luma blocks and one block of each subsampled chroma plane
+        let mcu = if colour { 16 } else { 8 };
+        for mcu_y in (0..planes.height).step_by(mcu) {
+            for mcu_x in (0..planes.width).step_by(mcu) {
+                for (dx, dy) in [(0, 0), (8, 0), (0, 8), (8, 8)].into_iter().take(if colour { 4 } else { 1 }) {
+                    planes.luma_block(mcu_x + dx, mcu_y + dy, &mut block);
+                    encode_block(&mut bits, &self.dct, &block, &luma_quant, &self.luma, &mut previous_dc[0]);
+                }
+                if colour {
+                    for (plane, dc) in [(&planes.cb, 1), (&planes.cr, 2)] {
+                        planes.chroma_block(plane, mcu_x, mcu_y, &mut block);
+                        encode_block(&mut bits, &self.dct, &block, &chroma_quant, &self.chroma, &mut previous_dc[dc]);
+                    }
+                }
+            }
+        }
+        bits.finish();
+
+        out.extend_from_slice(&[0xff, 0xd9]); //EOI
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scales a quantization table to the given quality using the IJG formula.
+fn scale_quant(base: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = quality as u32;
+    let scale = if quality < 50 { 5000 / quality } else { 200 - quality * 2 };
+
+    let mut table = [0u8; 64];
+    for (value, &base) in table.iter_mut().zip(base.iter()) {
+        *value = ((base as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    table
+}
+
+/// Writes the JFIF, quantization, frame, Huffman and scan headers.
+fn write_headers(out: &mut Vec<u8>, width: u32, height: u32, colour: bool, luma: &[u8; 64], chroma: &[u8; 64]) {
+    out.extend_from_slice(&[0xff, 0xd8]); //SOI
+    out.extend_from_slice(&[0xff, 0xe0, 0, 16, b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0]);
+
+    let tables: &[&[u8; 64]] = if colour { &[luma, chroma] } else { &[luma] };
+    for (id, table) in tables.iter().enumerate() {
+        out.extend_from_slice(&[0xff, 0xdb, 0, 67, id as u8]);
+        out.extend(ZIGZAG.iter().map(|&i| table[i]));
+    }
+
+    //Component ID, sampling factors and quantization table for each channel
+    let components: &[[u8; 3]] = if colour {
+        &[[1, 0x22, 0], [2, 0x11, 1], [3, 0x11, 1]]
+    } else {
+        &[[1, 0x11, 0]]
+    };
+    let length = 8 + 3 * components.len() as u16;
+    out.extend_from_slice(&[0xff, 0xc0]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.push(8);
+    out.extend_from_slice(&(height as u16).to_be_bytes());
+    out.extend_from_slice(&(width as u16).to_be_bytes());
+    out.push(components.len() as u8);
+    for component in components {
+        out.extend_from_slice(component);
+    }
+
+    let mut huffman: Vec<(u8, &[u8; 16], &[u8])> = vec![(0x00, &LUMA_DC_BITS, &DC_VALUES), (0x10, &LUMA_AC_BITS, &LUMA_AC_VALUES)];
+    if colour {
+        huffman.push((0x01, &CHROMA_DC_BITS, &DC_VALUES));
+        huffman.push((0x11, &CHROMA_AC_BITS, &CHROMA_AC_VALUES));
+    }
+    for (class_and_id, bits, values) in huffman {
+        let length = 2 + 17 + values.len() as u16;
+        out.extend_from_slice(&[0xff, 0xc4]);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.push(class_and_id);
+        out.extend_from_slice(bits);
+        out.extend_from_slice(values);
+    }
+
+    let length = 6 + 2 * components.len() as u16;
+    out.extend_from_slice(&[0xff, 0xda]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.push(components.len() as u8);
+    for (i, component) in components.iter().enumerate() {
+        let tables = if i == 0 { 0x00 } else { 0x11 };
+        out.extend_from_slice(&[component[0], tables]);
+    }
+    out.extend_from_slice(&[0, 63, 0]);
+}
+
+/// Transforms, quantizes and entropy codes one 8x8 block of level-shifted samples.
+fn encode_block(
+    bits: &mut BitWriter,
+    dct: &DctTable,
+    block: &[f32; 64],
+    quant: &[u8; 64],
+    (dc_table, ac_table): &(HuffmanTable, HuffmanTable),
+    previous_dc: &mut i32,
+) {
+    let coefficients = dct.forward(block);
+
+    let mut quantized = [0i32; 64];
+    for (k, &i) in ZIGZAG.iter().enumerate() {
+        quantized[k] = (coefficients[i] / quant[i] as f32).round() as i32;
+    }
+
+    let diff = quantized[0] - *previous_dc;
+    *previous_dc = quantized[0];
+    let size = magnitude_size(diff);
+    dc_table.write(bits, size as u8);
+    bits.write(magnitude_bits(diff, size), size);
+
+    let mut run = 0;
+    for &value in &quantized[1..] {
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            ac_table.write(bits, 0xf0); //ZRL, sixteen zeros
+            run -= 16;
+        }
+        let size = magnitude_size(value);
+        ac_table.write(bits, (run << 4) as u8 | size as u8);
+        bits.write(magnitude_bits(value, size), size);
+        run = 0;
+    }
+    if run > 0 {
+        ac_table.write(bits, 0x00); //EOB
+    }
+}
+
+/// Returns the number of bits needed for the magnitude of a coefficient.
+fn magnitude_size(value: i32) -> u32 {
+    32 - value.unsigned_abs().leading_zeros()
+}
+
+/// Returns the bits stored for a coefficient, with negative values in one's complement.
+fn magnitude_bits(value: i32, size: u32) -> u32 {
+    if value < 0 {
+        (value - 1) as u32 & ((1 << size) - 1)
+    } else {
+        value as u32
+    }
+}
+
+/// Full-resolution Y, Cb and Cr planes of the image, level-shifted around zero.
+struct Planes {
+    width: usize,
+    height: usize,
+    y: Vec<f32>,
+    cb: Vec<f32>,
+    cr: Vec<f32>,
+}
+
+impl Planes {
+    fn new() -> Self {
+        Planes {
+            width: 0,
+            height: 0,
+            y: Vec::new(),
+            cb: Vec::new(),
+            cr: Vec::new(),
+        }
+    }
+
+    /// Converts an image into the planes, reusing their buffers. Grayscale images only fill the luma plane.
+    fn fill(&mut self, pixels: &[u8], width: usize, height: usize, channels: Channels) {
+        let count = width * height;
+        self.width = width;
+        self.height = height;
+        self.y.clear();
+        self.y.resize(count, -128.0);
+
+        match channels {
+            Channels::Gray => {
+                for (y, &value) in self.y.iter_mut().zip(pixels) {
+                    *y = value as f32 - 128.0;
+                }
+            }
+            Channels::Rgb => {
+                self.cb.clear();
+                self.cb.resize(count, 0.0);
+                self.cr.clear();
+                self.cr.resize(count, 0.0);
+                for (i, rgb) in pixels.chunks_exact(3).take(count).enumerate() {
+                    let (r, g, b) = (rgb[0] as f32, rgb[1] as f32, rgb[2] as f32);
+                    self.y[i] = 0.299 * r + 0.587 * g + 0.114 * b - 128.0;
+                    self.cb[i] = -0.168_736 * r - 0.331_264 * g + 0.5 * b;
+                    self.cr[i] = 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+                }
+            }
+        }
+    }
+
+    /// Reads a sample, repeating the edge pixels for blocks that overhang the image.
+    fn sample(&self, plane: &[f32], x: usize, y: usize) -> f32 {
+        plane[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+
+    /// Copies the 8x8 luma block whose top-left corner is at (`x`, `y`).
+    fn luma_block(&self, x: usize, y: usize, block: &mut [f32; 64]) {
+        for (i, value) in block.iter_mut().enumerate() {
+            *value = self.sample(&self.y, x + i % 8, y + i / 8);
+        }
+    }
+
+    /// Averages the 16x16 chroma region at (`x`, `y`) down to an 8x8 block.
+    fn chroma_block(&self, plane: &[f32], x: usize, y: usize, block: &mut [f32; 64]) {
+        for (i, value) in block.iter_mut().enumerate() {
+            let (sx, sy) = (x + i % 8 * 2, y + i / 8 * 2);
+            *value = (self.sample(plane, sx, sy)
+                + self.sample(plane, sx + 1, sy)
+                + self.sample(plane, sx, sy + 1)
+                + self.sample(plane, sx + 1, sy + 1))
+                / 4.0;
+        }
+    }
+}
+
+/// Precomputed cosine terms for the separable 8x8 forward DCT.
+struct DctTable {
+    cos: [[f32; 8]; 8], //cos[u][x] = C(u) / 2 * cos((2x + 1) * u * PI / 16)
+}
+
+impl DctTable {
+    fn new() -> Self {
+        let mut cos = [[0f32; 8]; 8];
+        for (u, row) in cos.iter_mut().enumerate() {
+            let scale = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = scale * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+            }
+        }
+        DctTable { cos }
+    }
+
+    /// Returns the DCT coefficients of a block, in row-major order.
+    fn forward(&self, block: &[f32; 64]) -> [f32; 64] {
+        //Transform the rows, then the columns
+        let mut rows = [0f32; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8).map(|x| self.cos[u][x] * block[y * 8 + x]).sum();
+            }
+        }
+
+        let mut out = [0f32; 64];
+        for u in 0..8 {
+            for v in 0..8 {
+                out[v * 8 + u] = (0..8).map(|y| self.cos[v][y] * rows[y * 8 + u]).sum();
+            }
+        }
+        out
+    }
+}
+
+/// Codes and lengths for each symbol of a Huffman table.
+struct HuffmanTable {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanTable {
+    /// Builds the canonical codes for a table given as code counts per length and symbols.
+    fn new(bits: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0u16, 0u8); 256];
+        let mut code = 0u16;
+        let mut symbols = values.iter();
+
+        for (length, &count) in bits.iter().enumerate() {
+            for _ in 0..count {
+                if let Some(&symbol) = symbols.next() {
+                    codes[symbol as usize] = (code, length as u8 + 1);
+                }
+                code += 1;
+            }
+            code <<= 1;
+        }
+
+        HuffmanTable { codes }
+    }
+
+    fn write(&self, bits: &mut BitWriter, symbol: u8) {
+        let (code, length) = self.codes[symbol as usize];
+        bits.write(code as u32, length as u32);
+    }
+}
+
+/// Packs entropy-coded bits into bytes, stuffing a zero after every 0xFF.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        BitWriter { out, buffer: 0, count: 0 }
+    }
+
+    /// Appends the low `length` bits of `value`, most significant first.
+    fn write(&mut self, value: u32, length: u32) {
+        if length == 0 {
+            return;
+        }
+
+        self.buffer = (self.buffer << length) | (value & ((1 << length) - 1));
+        self.count += length;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.buffer >> self.count) as u8;
+            self.out.push(byte);
+            if byte == 0xff {
+                self.out.push(0);
+            }
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    /// Pads the final byte with one bits.
+    fn finish(mut self) {
+        if self.count > 0 {
+            self.write(0x7f, 8 - self.count);
+        }
+    }
+}
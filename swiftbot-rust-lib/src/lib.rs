@@ -5,23 +5,62 @@ mod buttons;
 mod motors;
 mod sensors;
 mod utils;
-mod camera;
+mod apriltag;
+mod avi;
+mod blob;
+mod bmp;
+pub mod camera;
+mod convert;
+mod exif;
+mod frame_buffer;
+mod gif;
+mod h264;
+mod jpeg;
+mod mjpeg_server;
+mod motion;
+mod mp4;
+mod overlay;
+mod png;
+mod prebuffer;
+mod ppm;
+mod qr;
+mod recorder;
+mod settings;
 mod sn3218;
+mod v4l2;
+mod y4m;
 
 use crate::sn3218::UnderlightLeds;
 use crate::motors::Motors;
 use crate::sensors::Sensors;
 use crate::buttons::{notify_button_pressed, notify_button_released, Buttons};
-use crate::camera::CameraController;
+use crate::camera::{
+    h264_available, interval_rate, is_reconnecting, list_devices, stop_recording, wait_for_frame, BufferedFrame,
+    CameraConfig, CameraController, CameraError, ColorMode, ControlRange, FrameTiming, ImageFormat, PowerLineFrequency,
+    TagDetection, VideoCodec,
+};
+use crate::convert::{ColorFilter, PreFilter, Rect};
+use crate::mjpeg_server::{start_mjpeg_server, stop_mjpeg_server};
+use crate::overlay::{Corner, Overlay};
+use crate::prebuffer::{dump_prebuffer, is_prebuffering, start_prebuffer, stop_prebuffer, PrebufferLimits};
+use crate::recorder::{
+    background_recording_segments, background_recording_timing, is_background_recording,
+    is_background_recording_paused, set_background_recording_paused, set_background_recording_progress,
+    start_background_recording, stop_background_recording, ProgressInterval, RecordScale, RecordingProgress, Rollover,
+};
 use crate::config::{BUTTON_A_PIN, BUTTON_B_PIN, BUTTON_X_PIN, BUTTON_Y_PIN};
 
-use jni::objects::JClass;
-use jni::sys::{jboolean, jbyteArray, jdouble, jint};
+use jni::objects::{JBooleanArray, JByteBuffer, JClass, JObject, JString, JThrowable, JValue};
+use jni::sys::{
+    jboolean, jbyteArray, jdouble, jdoubleArray, jint, jintArray, jlong, jlongArray, jobject, jobjectArray, jstring,
+};
 use jni::JNIEnv;
-use std::sync::{mpsc, Arc, Mutex};
+use std::io;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError, TryLockError};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use rppal::gpio::{Gpio, InputPin};
+use rscam::IntervalInfo;
 
 //Use lazy_static to create static instances accessible across JNI calls
 lazy_static! {
@@ -43,7 +82,10 @@ lazy_static! {
     static ref SENSORS: Mutex<Sensors> = Mutex::new(Sensors::new(&GPIO).unwrap());
 
     /// Shared instance of the `CameraController` struct, protected by a `Mutex` for thread safety.
-    static ref CAMERA: Mutex<CameraController> = Mutex::new(CameraController::new().unwrap());
+    static ref CAMERA: Mutex<CameraController> = Mutex::new(CameraController::new());
+
+    /// Frames Java holds direct buffers over, one entry per `acquireCameraFrameBuffer` call not yet released.
+    static ref HELD_FRAMES: Mutex<Vec<Arc<BufferedFrame>>> = Mutex::new(Vec::new());
 
     //Button pins
     static ref BUTTON_A_INPUT: InputPin = GPIO.get(BUTTON_A_PIN).unwrap().into_input_pulldown();
@@ -472,7 +514,7 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearUnderlightin
 ///
 /// # Returns
 ///
-/// A Java byte array containing the JPEG image data.
+/// A Java byte array containing the JPEG image data, or raw pixels in the selected color mode.
 ///
 /// # Safety
 ///
@@ -480,7 +522,7 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearUnderlightin
 ///
 /// # Errors
 ///
-/// Throws a Java `Exception` if there is an error capturing the image.
+/// Throws a Java `Exception` if there is an error capturing the image, see [`throw_camera_error`].
 ///
 /// # JNI Signature
 ///
@@ -492,21 +534,4809 @@ pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureImage(
     mut env: JNIEnv,
     _class: JClass,
 ) -> jbyteArray {
-    let mut camera = CAMERA.lock().unwrap();
+    let mut camera = lock_camera();
     match camera.capture_image() {
-        Ok(image_data) => {
-            //Convert Rust Vec<u8> to Java byte array
-            let buf = env.byte_array_from_slice(&image_data).unwrap();
-            **buf
-        }
+        //Convert Rust Vec<u8> to Java byte array
+        Ok(image_data) => match env.byte_array_from_slice(&image_data) {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_jni_error(&mut env, &e);
+                std::ptr::null_mut()
+            }
+        },
         Err(e) => {
-            let _ = env.throw_new("java/lang/Exception", format!("{}", e));
+            throw_camera_error(&mut env, &e);
             //Return null in case of error
             std::ptr::null_mut()
         }
     }
 }
 
+/// Captures a single still from the camera and returns it as a JPEG.
+///
+/// The camera is opened just for the snapshot and closed again afterwards, so no stream needs to be
+/// running. Grayscale mode produces a grayscale JPEG, every other mode a colour one. The JPEG
+/// carries EXIF metadata with the time it was taken, its size, and the exposure and gain.
+///
+/// # Arguments
+///
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best).
+///
+/// # Returns
+///
+/// A Java byte array containing the JPEG image data.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the quality is out of range, or an `Exception` if the
+/// camera is already streaming or the snapshot fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] captureSnapshot(int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureSnapshot(
+    mut env: JNIEnv,
+    _class: JClass,
+    quality: jint,
+) -> jbyteArray {
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return std::ptr::null_mut();
+    }
+
+    let mut camera = lock_camera();
+    match camera.capture_snapshot(Some(quality as u8)) {
+        Ok(jpeg) => match env.byte_array_from_slice(&jpeg) {
+            Ok(array) => array.into_raw(),
+            Err(e) => {
+                throw_jni_error(&mut env, &e);
+                std::ptr::null_mut()
+            }
+        },
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Captures a single still from the camera and saves it losslessly as a PNG file.
+///
+/// Takes the next frame if the camera is streaming, otherwise the camera is opened just for the
+/// snapshot and closed again afterwards. The file is synced to disk before this returns.
+///
+/// # Arguments
+///
+/// * `path` - Path of the PNG file to write, replacing any existing file.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera is streaming in the JPEG color mode, the snapshot fails
+/// or the file cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void captureSnapshotPng(String path);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureSnapshotPng(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.capture_snapshot_png(&path) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Captures a single still from the camera and saves it as a JPEG, PNG, BMP or PPM file.
+///
+/// Works whether or not the camera is streaming: a running stream supplies the next frame and is
+/// left running, otherwise the camera is opened just for the still and closed again. The format is
+/// never taken from the file name. JPEG stills carry EXIF metadata with the time they were taken,
+/// their size, and the exposure and gain. The file is synced to disk before this returns.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to write, replacing any existing file.
+/// * `format` - 0 for JPEG, or 1 for PNG, 2 for BMP or 3 for PPM (PGM in grayscale mode), which are
+///   lossless.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best), ignored for the lossless formats.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path, format or quality is invalid, or an
+/// `Exception` if the capture fails, a lossless format is requested while streaming in JPEG mode,
+/// or the file cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void captureStill(String path, int format, int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureStill(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    format: jint,
+    quality: jint,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    let format = match format {
+        0 => ImageFormat::Jpeg,
+        1 => ImageFormat::Png,
+        2 => ImageFormat::Bmp,
+        3 => ImageFormat::Ppm,
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown image format {}", format));
+            return;
+        }
+    };
+    if format == ImageFormat::Jpeg && !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.capture_still(&path, format, Some(quality as u8)) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Saves the last frame the running camera captured as a JPEG file, without capturing a new one or
+/// touching the device.
+///
+/// The frame is copied under the camera lock and encoded and written after releasing it, so a
+/// background recording or MJPEG server streaming from the camera only waits for the copy. JPEG
+/// mode frames are saved as the camera delivered them. The file carries EXIF metadata like
+/// `captureSnapshot` and is synced to disk before this returns.
+///
+/// # Arguments
+///
+/// * `path` - Path of the JPEG file to write, replacing any existing file.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best).
+///
+/// # Returns
+///
+/// The ID of the frame saved, as returned by `getCameraFrameId`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path or quality is invalid, an
+/// `IllegalStateException` if the camera isn't running or hasn't captured a frame yet, or an
+/// `Exception` if the file cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long snapshotFromStream(String path, int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_snapshotFromStream(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    quality: jint,
+) -> jlong {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+
+    match CameraController::snapshot_from_stream(&CAMERA, &path, Some(quality as u8)) {
+        Ok(id) => id as jlong,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Writes one frame to a file exactly as the driver delivered it, for debugging colour or layout
+/// problems.
+///
+/// The file holds the unconverted YUYV bytes, or the camera's JPEG data in JPEG mode, and a sidecar
+/// file named `path` with `.toml` appended gives its width, height, pixel format, bytes per row and
+/// bytes used. Uses the running stream if there is one, otherwise the camera is opened just for
+/// the frame.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to write, replacing any existing file.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path is invalid, or an `Exception` if the camera
+/// is busy, e.g. recording, the capture fails or the files cannot be written.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void captureRawFrame(String path);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureRawFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut camera = match lock_camera_within(Duration::from_secs(1)) {
+        Some(camera) => camera,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalStateException", "Camera is busy, try again once it is free");
+            return;
+        }
+    };
+    if let Err(e) = camera.capture_raw_frame(&path) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Records camera frames to a video file, blocking until the recording ends.
+///
+/// `codec` 0 records Motion JPEG into an AVI file. `codec` 1 records H.264 into an MP4 file using the
+/// hardware encoder, or Motion JPEG if the device has none; in the JPEG and grayscale color modes
+/// the camera must be stopped first. `codec` 2 writes the JPEG frames back to back with no container,
+/// as older versions did, along with a `path.csv` index of each frame's offset, length and capture
+/// time in microseconds since the Unix epoch. `codec` 3 writes the uncompressed YUV 4:2:2 frames to
+/// a `.y4m` stream without JPEG encoding; in the JPEG color mode the camera must be stopped first.
+/// The file plays back at the configured frame rate, or at `outputFps`, in
+/// which case the camera keeps capturing at its own rate and only some of the frames are written.
+/// The recording ends after `durationSeconds`, or earlier if `stopRecording` is called from another
+/// thread. Uses the running stream if there is one, otherwise the camera is opened and left running.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to write, replacing any existing file.
+/// * `duration_seconds` - Length of the recording in seconds.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+/// * `codec` - 0 for Motion JPEG, 1 for H.264, 2 for raw concatenated JPEGs, 3 for YUV4MPEG2.
+/// * `output_fps` - Frame rate to record at, lowered to the rate the camera captures at if it is
+///   higher, or 0 to record every frame.
+///
+/// # Returns
+///
+/// The number of frames written, or -1 on error. `getCameraVideoTiming` tells whether the recording
+/// kept up with the camera.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path, duration, quality or frame rate is invalid,
+/// or an `Exception` if the camera or file fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureVideo(String path, int durationSeconds, int quality, int codec, int outputFps);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureVideo(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    duration_seconds: jint,
+    quality: jint,
+    codec: jint,
+    output_fps: jint,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if duration_seconds <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Recording duration must be positive");
+        return -1;
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+    let codec = match video_codec(codec) {
+        Some(codec) => codec,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown video codec {}", codec));
+            return -1;
+        }
+    };
+    if output_fps < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Output frame rate cannot be negative");
+        return -1;
+    }
+    let output_fps = (output_fps > 0).then_some(output_fps as u32);
+
+    let mut camera = lock_camera();
+    match camera.capture_video(&path, duration_seconds as u32, Some(quality as u8), codec, output_fps) {
+        Ok(stats) => stats.frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Records exactly `frameCount` frames to a video file, blocking until the recording ends.
+///
+/// Works like `captureVideo`, with the same codecs, but ends once `frameCount` frames have been
+/// written rather than after a duration, e.g. for tests and scripted captures that need a known
+/// number of frames. With an `outputFps` only the frames written count. `cancelCapture` called from
+/// another thread cancels the recording within a frame, and the file is finished with the frames
+/// written so far.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to write, replacing any existing file.
+/// * `frame_count` - Number of frames to write.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+/// * `codec` - 0 for Motion JPEG, 1 for H.264, 2 for raw concatenated JPEGs, 3 for YUV4MPEG2.
+/// * `output_fps` - Frame rate to record at, lowered to the rate the camera captures at if it is
+///   higher, or 0 to record every frame.
+///
+/// # Returns
+///
+/// The number of frames written, fewer than `frameCount` if the recording was cancelled, or -1 on
+/// error. `getCameraVideoTiming` tells whether the recording kept up with the camera.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path, frame count, quality or frame rate is
+/// invalid, or an `Exception` if the camera or file fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureVideoFrames(String path, int frameCount, int quality, int codec, int outputFps);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureVideoFrames(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    frame_count: jint,
+    quality: jint,
+    codec: jint,
+    output_fps: jint,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if frame_count <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Recording frame count must be positive");
+        return -1;
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+    let codec = match video_codec(codec) {
+        Some(codec) => codec,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown video codec {}", codec));
+            return -1;
+        }
+    };
+    if output_fps < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Output frame rate cannot be negative");
+        return -1;
+    }
+    let output_fps = (output_fps > 0).then_some(output_fps as u32);
+
+    let mut camera = lock_camera();
+    match camera.capture_video_frames(&path, frame_count as u32, Some(quality as u8), codec, output_fps) {
+        Ok(stats) => stats.frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Returns how the last recording made by `captureVideo`, `captureVideoToMemory` or
+/// `captureVideoH264` kept up with the camera, including one that failed part way.
+///
+/// # Returns
+///
+/// A Java long array as described for `new_timing_array`, or null before the first recording.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long[] getCameraVideoTiming();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraVideoTiming(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlongArray {
+    let timing = match lock_camera().last_video_timing() {
+        Some(timing) => timing,
+        None => return std::ptr::null_mut(),
+    };
+    match new_timing_array(&mut env, &timing, &[]) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Records a Motion JPEG AVI clip into memory and returns it, blocking until the recording ends.
+///
+/// Nothing is written to the SD card, so short clips can be sent over the network without wearing
+/// it. The recording ends after `durationSeconds`, when `stopRecording` is called from another
+/// thread, or when the next frame would take the clip over `maxBytes`, in which case the clip is
+/// returned as it is and marked as truncated. Uses the running stream if there is one, otherwise
+/// the camera is opened and left running.
+///
+/// # Arguments
+///
+/// * `duration_seconds` - Length of the recording in seconds.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+/// * `max_bytes` - Largest size the clip may grow to, e.g. 64000000.
+/// * `output_fps` - Frame rate to record at, lowered to the rate the camera captures at if it is
+///   higher, or 0 to record every frame.
+/// * `truncated` - Set to `true` in its first element if the size limit ended the recording, or
+///   null if this isn't needed.
+///
+/// # Returns
+///
+/// A Java byte array holding the AVI file, or null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the duration, quality, size limit or frame rate is
+/// invalid, or an `Exception` if the camera fails. Nothing is returned if a capture fails part way
+/// through.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] captureVideoToMemory(int durationSeconds, int quality, int maxBytes, int outputFps,
+///         boolean[] truncated);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureVideoToMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    duration_seconds: jint,
+    quality: jint,
+    max_bytes: jint,
+    output_fps: jint,
+    truncated: JBooleanArray,
+) -> jbyteArray {
+    if duration_seconds <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Recording duration must be positive");
+        return std::ptr::null_mut();
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return std::ptr::null_mut();
+    }
+    if max_bytes <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Size limit must be positive");
+        return std::ptr::null_mut();
+    }
+    if output_fps < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Output frame rate cannot be negative");
+        return std::ptr::null_mut();
+    }
+    let output_fps = (output_fps > 0).then_some(output_fps as u32);
+
+    let mut camera = lock_camera();
+    let video = camera.capture_video_to_memory(
+        duration_seconds as u32,
+        Some(quality as u8),
+        max_bytes as usize,
+        output_fps,
+    );
+    drop(camera);
+    let video = match video {
+        Ok(video) => video,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    if !truncated.is_null() {
+        if let Err(e) = env.set_boolean_array_region(&truncated, 0, &[video.truncated as jboolean]) {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    }
+    match env.byte_array_from_slice(&video.data) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Captures consecutive frames as fast as the camera delivers them into numbered JPEG files.
+///
+/// The files are named `frame_0001.jpg` onwards in `dir`, which is created if needed. Uses the
+/// running stream if there is one, otherwise the camera is opened and left running.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to write the frames to, replacing any files of the same names.
+/// * `count` - Number of frames to capture.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+///
+/// # Returns
+///
+/// The number of frames saved, always `count`, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the directory, count or quality is invalid, or the
+/// exception for the camera or file error that stopped the burst. Its message says how many frames
+/// had been saved by then, and those files are kept.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureBurst(String dir, int count, int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureBurst(
+    mut env: JNIEnv,
+    _class: JClass,
+    dir: JString,
+    count: jint,
+    quality: jint,
+) -> jint {
+    let dir: String = match env.get_string(&dir) {
+        Ok(dir) => dir.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if count <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Burst frame count must be positive");
+        return -1;
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+
+    let mut camera = lock_camera();
+    match camera.capture_burst(&dir, count as u32, Some(quality as u8)) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Captures consecutive frames as fast as the camera delivers them into memory, e.g. to stack
+/// them to take out sensor noise.
+///
+/// Uses the running stream if there is one, otherwise the camera is opened and left running. In
+/// JPEG mode the camera must be stopped first. Each frame takes `width * height * 3` bytes, so
+/// bursts are limited to 32 frames, about 29 MB at 640x480.
+///
+/// # Arguments
+///
+/// * `count` - Number of frames to capture, from 1 to 32.
+///
+/// # Returns
+///
+/// A Java array of `count` byte arrays, one per frame, each holding packed RGB pixels row by row.
+/// Returns null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the count is out of range, a `RuntimeException` if
+/// the camera is running in JPEG mode, or the exception for the camera error that stopped the
+/// burst. Its message says how many frames had been captured by then.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[][] captureBurstToMemory(int count);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureBurstToMemory(
+    mut env: JNIEnv,
+    _class: JClass,
+    count: jint,
+) -> jobjectArray {
+    if count <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Burst frame count must be positive");
+        return std::ptr::null_mut();
+    }
+
+    let frames = match lock_camera().capture_burst_to_memory(count as usize) {
+        Ok(frames) => frames,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    match new_frame_array(&mut env, &frames) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Records every captured frame as a JPEG file of its own, named by a printf-style pattern, blocking
+/// until the recording ends.
+///
+/// Frames are numbered from 1, e.g. `run3_000001.jpg` onwards for `run3_%06d.jpg`, in `dir`, which
+/// is created if needed. The recording ends after `durationSeconds`, after `maxFrames` files, or if
+/// `stopRecording` is called from another thread. Uses the running stream if there is one,
+/// otherwise the camera is opened and left running.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to write the frames to, replacing any files of the same names.
+/// * `pattern` - File name with exactly one `%d`, `%i` or `%u` placeholder, optionally with a width
+///   and `0` flag. `%%` stands for a literal `%`.
+/// * `duration_seconds` - How long to record for, or 0 for no time limit.
+/// * `max_frames` - How many files to write at most, or 0 for no limit. At least one limit is needed.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+///
+/// # Returns
+///
+/// The number of files written, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the directory, pattern, limits or quality are
+/// invalid, or an `Exception` if a capture fails or a file cannot be written, e.g. because the disk
+/// is full. The message says how many files had been written, and the incomplete file is removed.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureFrameSequence(String dir, String pattern, int durationSeconds, int maxFrames, int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureFrameSequence(
+    mut env: JNIEnv,
+    _class: JClass,
+    dir: JString,
+    pattern: JString,
+    duration_seconds: jint,
+    max_frames: jint,
+    quality: jint,
+) -> jint {
+    let (dir, pattern): (String, String) = match (env.get_string(&dir), env.get_string(&pattern)) {
+        (Ok(dir), Ok(pattern)) => (dir.into(), pattern.into()),
+        (Err(e), _) | (_, Err(e)) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if duration_seconds < 0 || max_frames < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Frame sequence limits can't be negative");
+        return -1;
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+
+    let duration = (duration_seconds > 0).then(|| Duration::from_secs(duration_seconds as u64));
+    let max_frames = (max_frames > 0).then_some(max_frames as u32);
+    let mut camera = lock_camera();
+    match camera.capture_frame_sequence(&dir, &pattern, duration, max_frames, Some(quality as u8)) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Records raw H.264 from the camera through the Raspberry Pi's hardware encoder, blocking until the
+/// recording ends.
+///
+/// The file holds the bare H.264 stream, without an MP4 container. In the JPEG and grayscale color
+/// modes the camera must be stopped first. The recording ends after `durationSeconds`, or earlier
+/// if `stopRecording` is called from another thread.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to write, replacing any existing file.
+/// * `duration_seconds` - Length of the recording in seconds.
+/// * `bitrate` - Bits per second to encode at.
+///
+/// # Returns
+///
+/// The number of frames recorded, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path, duration or bitrate is invalid, or an
+/// `Exception` if there is no hardware H.264 encoder or the camera, encoder or file fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureVideoH264(String path, int durationSeconds, int bitrate);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureVideoH264(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    duration_seconds: jint,
+    bitrate: jint,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if duration_seconds <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Recording duration must be positive");
+        return -1;
+    }
+    if bitrate <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "H.264 bitrate must be positive");
+        return -1;
+    }
+
+    let mut camera = lock_camera();
+    match camera.capture_video_h264(&path, duration_seconds as u32, Some(bitrate as u32)) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Records a short clip from the camera into an animated GIF file that loops forever, blocking until
+/// the recording ends.
+///
+/// Each frame is shrunk by `scale` and reduced to 256 colours, and shown for as long as it took the
+/// camera to deliver the next one. In JPEG mode the camera must be stopped first. The recording
+/// ends after `durationMs`, or earlier if `stopRecording` is called from another thread.
+///
+/// # Arguments
+///
+/// * `path` - Path of the GIF file to write, replacing any existing file.
+/// * `duration_ms` - Length of the clip in milliseconds, at most 10 seconds.
+/// * `fps` - Frames per second to take, from 1 to 50.
+/// * `scale` - How many times smaller each side of the frames becomes, 1 to keep their size.
+///
+/// # Returns
+///
+/// The number of frames written, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an argument is invalid, an `IllegalStateException`
+/// if the camera is running in JPEG mode, or an `Exception` if the camera or file fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureGif(String path, int durationMs, int fps, int scale);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureGif(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    duration_ms: jint,
+    fps: jint,
+    scale: jint,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if duration_ms <= 0 || fps <= 0 || scale <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Duration, frame rate and scale must be positive");
+        return -1;
+    }
+
+    let mut camera = lock_camera();
+    match camera.capture_gif(&path, Duration::from_millis(duration_ms as u64), fps as u32, scale as u32) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Starts recording the camera to a Motion JPEG AVI file in the background, returning straight away.
+///
+/// Frames are taken from the same camera `captureImage` uses, one at a time, so other camera calls
+/// keep working during the recording. The camera is opened if needed and left running. Call
+/// `stopBackgroundRecording` to finish the file.
+///
+/// With a size or length limit the recording is split into numbered files next to `path`, e.g.
+/// `capture_0001.avi`, `capture_0002.avi` and so on for `capture.avi`, each playable on its own.
+/// `getBackgroundRecordingSegments` lists them.
+///
+/// # Arguments
+///
+/// * `path` - Path of the AVI file to write, replacing any existing file.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+/// * `max_megabytes` - Size in megabytes (1,000,000 bytes) a file is kept under before the recording
+///   moves on to the next one, or 0 for no limit.
+/// * `max_seconds` - Seconds of recording a file holds before the recording moves on to the next one,
+///   or 0 for no limit.
+/// * `scale` - How many times smaller each side of the recorded frames is than the camera's, 1, 2 or
+///   4. The camera keeps capturing full size frames for everything else. Only 1 works in JPEG mode.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path, quality, a limit or the scale is invalid, an
+/// `IllegalStateException` if a background recording is already running, or an `Exception` if the
+/// frames can't be shrunk in the current color mode or the file cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startBackgroundRecording(String path, int quality, int maxMegabytes, int maxSeconds, int scale);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startBackgroundRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    quality: jint,
+    max_megabytes: jint,
+    max_seconds: jint,
+    scale: jint,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return;
+    }
+
+    if max_megabytes < 0 || max_seconds < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Rollover limits cannot be negative");
+        return;
+    }
+
+    let scale = match scale {
+        1 => RecordScale::Full,
+        2 => RecordScale::Half,
+        4 => RecordScale::Quarter,
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Recording scale must be 1, 2 or 4");
+            return;
+        }
+    };
+
+    let rollover = Rollover {
+        max_bytes: (max_megabytes > 0).then(|| max_megabytes as u64 * 1_000_000),
+        max_duration: (max_seconds > 0).then(|| Duration::from_secs(max_seconds as u64)),
+    };
+    if let Err(e) = start_background_recording(&CAMERA, &path, Some(quality as u8), rollover, scale) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Stops the recording started by `startBackgroundRecording`, blocking until the file is finished.
+///
+/// # Returns
+///
+/// A Java long array holding the number of frames written, the length of the recording in
+/// milliseconds and the number of files written, followed by the timing of the recording as
+/// described for `new_timing_array`, or null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if no background recording is running, or an `Exception`
+/// if a capture failed during the recording or the file cannot be finished. The file holds the
+/// frames recorded before a failed capture.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long[] stopBackgroundRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopBackgroundRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlongArray {
+    let stats = match stop_background_recording() {
+        Ok(stats) => stats,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let values = [stats.frames as jlong, stats.duration.as_millis() as jlong, stats.segments.len() as jlong];
+    match new_timing_array(&mut env, &stats.timing, &values) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns how the recording started by `startBackgroundRecording` is keeping up with the camera so
+/// far, e.g. to warn the operator while it runs.
+///
+/// # Returns
+///
+/// A Java long array as described for `new_timing_array`, or null if no background recording is
+/// running.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long[] getBackgroundRecordingTiming();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getBackgroundRecordingTiming(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlongArray {
+    let timing = match background_recording_timing() {
+        Some(timing) => timing,
+        None => return std::ptr::null_mut(),
+    };
+    match new_timing_array(&mut env, &timing, &[]) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Lists the files written by the background recording in progress, or by the last one once it has
+/// stopped.
+///
+/// # Returns
+///
+/// A Java String array holding the file paths in recording order, the last still being written while
+/// the recording runs. Holds the single file given to `startBackgroundRecording` unless the recording
+/// is split, and is empty before the first recording.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String[] getBackgroundRecordingSegments();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getBackgroundRecordingSegments(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let segments: Vec<String> = background_recording_segments()
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    match new_string_array(&mut env, &segments) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Checks whether a recording started by `startBackgroundRecording` is running.
+///
+/// # Returns
+///
+/// `true` until `stopBackgroundRecording` is called.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isBackgroundRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isBackgroundRecording(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    is_background_recording() as jboolean
+}
+
+/// Pauses the recording started by `startBackgroundRecording`, keeping its file open.
+///
+/// No frames are captured until `resumeBackgroundRecording` is called, and playback carries straight
+/// on from the last frame before the pause. Does nothing if the recording is already paused.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if no background recording is running.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void pauseBackgroundRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_pauseBackgroundRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    if let Err(e) = set_background_recording_paused(true) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Resumes a recording paused by `pauseBackgroundRecording`, appending to the same file.
+///
+/// Does nothing if the recording isn't paused.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if no background recording is running.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void resumeBackgroundRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_resumeBackgroundRecording(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    if let Err(e) = set_background_recording_paused(false) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Checks whether the recording started by `startBackgroundRecording` is paused.
+///
+/// # Returns
+///
+/// `true` if it is paused, or `false` if it is running or there is no background recording.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isBackgroundRecordingPaused();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isBackgroundRecordingPaused(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    is_background_recording_paused().unwrap_or(false) as jboolean
+}
+
+/// Calls `NativeBindings.onRecordingProgress` as background recordings get on, replacing any
+/// listener already started, so a UI can show what is actually being recorded.
+///
+/// The calls are made from the recording thread, which is attached to the JVM the first time, as a
+/// daemon so it doesn't keep the JVM from exiting, and detached again when it exits. The listener
+/// holds up the recording while it runs. An exception it throws is printed and the recording
+/// carries on.
+///
+/// # Arguments
+///
+/// * `every_frames` - How many frames to write between calls, or 0 to call it once a second of
+///   recording time instead.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if `every_frames` is negative, or an `Exception` if the
+/// listener can't be set up.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startRecordingProgress(int everyFrames);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startRecordingProgress(
+    mut env: JNIEnv,
+    class: JClass,
+    every_frames: jint,
+) {
+    let interval = match every_frames {
+        0 => ProgressInterval::Period(Duration::from_secs(1)),
+        1.. => ProgressInterval::Frames(every_frames as u32),
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Frames between calls must not be negative");
+            return;
+        }
+    };
+    let (jvm, class) = match (env.get_java_vm(), env.new_global_ref(&class)) {
+        (Ok(jvm), Ok(class)) => (jvm, class),
+        _ => {
+            let _ = env.throw_new("java/lang/RuntimeException", "Failed to set up the recording progress listener");
+            return;
+        }
+    };
+
+    let callback = Box::new(move |progress: &RecordingProgress| {
+        let mut env = match jvm.attach_current_thread_as_daemon() {
+            Ok(env) => env,
+            Err(_) => return,
+        };
+        let args = [
+            JValue::Long(progress.elapsed.as_millis() as jlong),
+            JValue::Long(progress.frames as jlong),
+            JValue::Long(progress.bytes.min(i64::MAX as u64) as jlong),
+        ];
+        if env.call_static_method(&class, "onRecordingProgress", "(JJJ)V", &args).is_err() {
+            //Print what the listener threw and keep recording
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+    });
+    if let Err(e) = set_background_recording_progress(Some((interval, callback))) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Stops calling the listener started by `startRecordingProgress`.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopRecordingProgress();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopRecordingProgress(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    let _ = set_background_recording_progress(None);
+}
+
+/// Starts keeping the last few seconds of camera frames in memory, returning straight away, so the
+/// moments before an event can be saved with `dumpCameraPrebuffer` once it has happened.
+///
+/// Frames are taken from the same camera `captureImage` uses, one at a time, so other camera calls
+/// keep working. The camera is opened if needed and left running. Call `stopCameraPrebuffer` to stop
+/// and free the frames.
+///
+/// # Arguments
+///
+/// * `seconds` - Seconds of frames to keep.
+/// * `max_megabytes` - Size in megabytes (1,000,000 bytes) the frames kept are held under, dropping
+///   the oldest first even if they are younger than `seconds`.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the quality or a limit is invalid, or an
+/// `IllegalStateException` if a pre-record buffer is already running.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startCameraPrebuffer(int seconds, int maxMegabytes, int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startCameraPrebuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+    seconds: jint,
+    max_megabytes: jint,
+    quality: jint,
+) {
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return;
+    }
+    if seconds <= 0 || max_megabytes <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Pre-record limits must be greater than zero");
+        return;
+    }
+
+    let limits = PrebufferLimits {
+        duration: Duration::from_secs(seconds as u64),
+        max_bytes: max_megabytes as u64 * 1_000_000,
+    };
+    if let Err(e) = start_prebuffer(&CAMERA, limits, Some(quality as u8)) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Saves the frames kept by `startCameraPrebuffer` to a Motion JPEG AVI file, followed by the frames
+/// captured in the next `afterSeconds`, blocking until the file is finished.
+///
+/// The buffer keeps running, so it can be saved again for a later event. Calls made while a file is
+/// being saved wait for it to finish.
+///
+/// # Arguments
+///
+/// * `path` - Path of the AVI file to write, replacing any existing file.
+/// * `after_seconds` - Seconds to keep recording after the buffered frames, or 0 for none.
+///
+/// # Returns
+///
+/// The number of frames written, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the path or `afterSeconds` is invalid, an
+/// `IllegalStateException` if no pre-record buffer is running, or an `Exception` if the file cannot
+/// be written or a capture failed. The file holds the frames saved before a failed capture.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int dumpCameraPrebuffer(String path, int afterSeconds);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_dumpCameraPrebuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    after_seconds: jint,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if after_seconds < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Seconds after the event cannot be negative");
+        return -1;
+    }
+
+    match dump_prebuffer(&path, Duration::from_secs(after_seconds as u64)) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Stops the pre-record buffer started by `startCameraPrebuffer` and frees its frames. A file being
+/// saved by `dumpCameraPrebuffer` is finished with the frames it has.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if no pre-record buffer is running.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopCameraPrebuffer();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopCameraPrebuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    if let Err(e) = stop_prebuffer() {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Checks whether a pre-record buffer started by `startCameraPrebuffer` is running.
+///
+/// # Returns
+///
+/// `true` until `stopCameraPrebuffer` is called.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraPrebuffering();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraPrebuffering(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    is_prebuffering() as jboolean
+}
+
+/// Takes a photo at a fixed interval into numbered JPEG files, blocking until the timelapse ends.
+///
+/// The files are named `frame_0001.jpg` onwards in `dir`, which is created if needed, and each is
+/// synced to disk as it is written. The timelapse ends after `frames` photos, or earlier if
+/// `stopRecording` is called from another thread. With `powerDown` the camera is closed between
+/// shots, otherwise it is left running.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to write the photos to, replacing any files of the same names.
+/// * `interval_ms` - Time between shots in milliseconds.
+/// * `frames` - Number of photos to take.
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best) for frames the camera delivers raw.
+/// * `power_down` - Whether to close the camera between shots.
+///
+/// # Returns
+///
+/// The number of photos taken, or -1 on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if an argument is invalid, or an `Exception` saying how
+/// many photos were saved if a capture or write fails.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int captureTimelapse(String dir, int intervalMs, int frames, int quality, boolean powerDown);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_captureTimelapse(
+    mut env: JNIEnv,
+    _class: JClass,
+    dir: JString,
+    interval_ms: jint,
+    frames: jint,
+    quality: jint,
+    power_down: jboolean,
+) -> jint {
+    let dir: String = match env.get_string(&dir) {
+        Ok(dir) => dir.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return -1;
+        }
+    };
+    if interval_ms < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Timelapse interval must not be negative");
+        return -1;
+    }
+    if frames <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Timelapse frame count must be positive");
+        return -1;
+    }
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return -1;
+    }
+
+    let interval = Duration::from_millis(interval_ms as u64);
+    let mut camera = lock_camera();
+    match camera.capture_timelapse(&dir, interval, frames as u32, Some(quality as u8), power_down != 0) {
+        Ok(frames) => frames as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Starts serving the camera as a Motion JPEG stream over HTTP, for viewing in a browser.
+///
+/// Any path on the port returns the live stream, e.g. `http://<robot>:8080/`. Frames come from the
+/// same camera `captureImage` uses and are only captured while someone is watching. Raw frames are
+/// encoded at the quality set by `setCameraJpegQuality`.
+///
+/// # Arguments
+///
+/// * `port` - TCP port to listen on, from 1 to 65535.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the port is out of range, an `IllegalStateException`
+/// if the server is already running, or an `UncheckedIOException` if the port cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startMjpegServer(int port);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startMjpegServer(
+    mut env: JNIEnv,
+    _class: JClass,
+    port: jint,
+) {
+    if !(1..=65535).contains(&port) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Invalid port {}", port));
+        return;
+    }
+
+    match start_mjpeg_server(&CAMERA, port as u16) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let _ = env.throw_new("java/lang/IllegalStateException", format!("{}", e));
+        }
+        Err(e) => throw_io_error(&mut env, &format!("Failed to start the MJPEG server on port {}: {}", port, e)),
+    }
+}
+
+/// Stops the server started by `startMjpegServer`, releasing the camera before returning.
+///
+/// Does nothing if the server isn't running.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopMjpegServer();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopMjpegServer(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    stop_mjpeg_server();
+}
+
+/// Returns whether the device has a hardware H.264 encoder that `captureVideo` can record with.
+///
+/// # Returns
+///
+/// `true` if H.264 recordings are encoded as H.264 rather than falling back to Motion JPEG.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraH264Available();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraH264Available(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    h264_available() as jboolean
+}
+
+/// Stops a recording started by `captureVideo` or `captureVideoFrames`, a timelapse started by
+/// `captureTimelapse` or a frame sequence started by `captureFrameSequence`, after the frame it is
+/// currently capturing.
+///
+/// Doesn't wait for the camera lock, so it can be called while either is blocking another thread.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopRecording();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopRecording(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    stop_recording();
+}
+
+/// Cancels a recording in progress in `captureVideo` or `captureVideoFrames` within one frame. The
+/// file is finished with the frames written so far, and the recording returns their number.
+///
+/// This is the same as `stopRecording`, under the name scripted captures look for.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void cancelCapture();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_cancelCapture(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    stop_recording();
+}
+
+/// Sets the default quality that frames are JPEG encoded at by this library, for recordings and snapshots.
+///
+/// Takes effect without restarting the camera. Frames the driver delivers as MJPEG are passed through
+/// as they are.
+///
+/// # Arguments
+///
+/// * `quality` - JPEG quality from 1 (smallest) to 100 (best).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the quality is out of range.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraJpegQuality(int quality);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraJpegQuality(
+    mut env: JNIEnv,
+    _class: JClass,
+    quality: jint,
+) {
+    if !(1..=100).contains(&quality) {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "JPEG quality must be between 1 and 100");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_jpeg_quality(quality as u8) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Returns the quality that frames are JPEG encoded at by this library.
+///
+/// # Returns
+///
+/// The JPEG quality, from 1 to 100.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraJpegQuality();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraJpegQuality(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    camera.jpeg_quality() as jint
+}
+
+/// Sets how the camera is reopened if it disappears mid-stream, e.g. because its USB cable was pulled.
+///
+/// Captures wait for the camera to come back for up to `attempts` tries, `intervalMs` apart, before
+/// failing. The default is 10 attempts one second apart.
+///
+/// # Arguments
+///
+/// * `attempts` - Number of times to try reopening the camera, 0 to fail straight away.
+/// * `interval_ms` - Wait before each attempt, in milliseconds.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if either argument is negative.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraReconnect(int attempts, int intervalMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraReconnect(
+    mut env: JNIEnv,
+    _class: JClass,
+    attempts: jint,
+    interval_ms: jint,
+) {
+    if attempts < 0 || interval_ms < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Reconnect attempts and interval must not be negative");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    camera.set_reconnect_policy(attempts as u32, Duration::from_millis(interval_ms as u64));
+}
+
+/// Returns whether a capture is waiting for a disconnected camera to be plugged back in.
+///
+/// Doesn't wait for the camera lock, so it can be polled while a capture is blocked reconnecting.
+///
+/// # Returns
+///
+/// `true` while the camera is reconnecting.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraReconnecting();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraReconnecting(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    is_reconnecting() as jboolean
+}
+
+/// Stops the camera and releases the device. It is reopened on the next capture.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the stream cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopCamera();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopCamera(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.stop() {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Loads the camera configuration from a settings file.
+///
+/// Settings the file doesn't mention are reset to their defaults, so other camera setters should be
+/// called after this. If the camera is running it is restarted with the new settings. A file can
+/// also be loaded at startup by naming it in the `SWIFTBOT_CAMERA_CONFIG` environment variable.
+///
+/// # Arguments
+///
+/// * `path` - Path of the settings file.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` describing the problem if the file cannot be read, contains an
+/// invalid setting, or the camera refuses the configuration.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void loadCameraConfig(String path);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_loadCameraConfig(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.load_config(&path) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Sets the resolution used for camera captures.
+///
+/// The camera is reopened with the new resolution on the next capture, which fails if the camera
+/// does not support the requested size.
+///
+/// # Arguments
+///
+/// * `width` - Frame width in pixels.
+/// * `height` - Frame height in pixels.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the resolution is invalid or the camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraResolution(int width, int height);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraResolution(
+    mut env: JNIEnv,
+    _class: JClass,
+    width: jint,
+    height: jint,
+) {
+    if width <= 0 || height <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Resolution must be positive");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_resolution(width as u32, height as u32) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Changes the camera resolution and frame rate together.
+///
+/// A running camera is restarted with the new settings immediately rather than on the next capture.
+/// If the camera refuses them, the previous settings are restored.
+///
+/// # Arguments
+///
+/// * `width` - Frame width in pixels.
+/// * `height` - Frame height in pixels.
+/// * `fps` - Target frames per second.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if any value is not positive, or an `Exception` if the
+/// camera refuses the new settings.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void reconfigureCamera(int width, int height, int fps);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_reconfigureCamera(
+    mut env: JNIEnv,
+    _class: JClass,
+    width: jint,
+    height: jint,
+    fps: jint,
+) {
+    if width <= 0 || height <= 0 || fps <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Resolution and frame rate must be positive");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    let config = CameraConfig {
+        width: width as u32,
+        height: height as u32,
+        fps: fps as u32,
+        ..camera.config().clone()
+    };
+    if let Err(e) = camera.reconfigure(config) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Selects the camera device by path, e.g. `/dev/video2`.
+///
+/// The device is opened on the next capture.
+///
+/// # Arguments
+///
+/// * `path` - Path of the V4L2 device node.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the path is invalid or the current camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraDevice(String path);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraDevice(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) {
+    let path: String = match env.get_string(&path) {
+        Ok(path) => path.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return;
+        }
+    };
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_device(&path) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Selects the camera device by index, i.e. `/dev/video<index>`.
+///
+/// The device is opened on the next capture.
+///
+/// # Arguments
+///
+/// * `index` - Index of the V4L2 device node.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the index is negative, or an `Exception` if the
+/// current camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraDeviceIndex(int index);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraDeviceIndex(
+    mut env: JNIEnv,
+    _class: JClass,
+    index: jint,
+) {
+    if index < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Device index must not be negative");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_device_index(index as u32) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Sets the target camera frame rate.
+///
+/// The rate is programmed into the driver when the camera is next opened. If the driver refuses it,
+/// captures are paced in software instead.
+///
+/// # Arguments
+///
+/// * `fps` - Target frames per second.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the frame rate is not positive, or an `Exception` if
+/// the current camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraFrameRate(int fps);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraFrameRate(
+    mut env: JNIEnv,
+    _class: JClass,
+    fps: jint,
+) {
+    if fps <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Frame rate must be positive");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_frame_rate(fps as u32) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Sets the number of mmap buffers the camera driver captures into.
+///
+/// The camera is reopened with the new buffer count on the next capture.
+///
+/// # Arguments
+///
+/// * `buffers` - Number of capture buffers, at least 2.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if fewer than 2 buffers are requested, or an `Exception`
+/// if the current camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraBufferCount(int buffers);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraBufferCount(
+    mut env: JNIEnv,
+    _class: JClass,
+    buffers: jint,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_buffer_count(buffers.max(0) as u32) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Sets how many frames are thrown away each time the camera is opened.
+///
+/// The first frames after opening are often dark or badly coloured while auto exposure and white
+/// balance settle. Takes effect the next time the camera is opened.
+///
+/// # Arguments
+///
+/// * `frames` - Number of frames to discard, 0 to keep every frame.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if `frames` is negative.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraWarmupFrames(int frames);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraWarmupFrames(
+    mut env: JNIEnv,
+    _class: JClass,
+    frames: jint,
+) {
+    if frames < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Warm-up frame count must not be negative");
+        return;
+    }
+
+    let mut camera = lock_camera();
+    camera.set_warmup_frames(frames as u32);
+}
+
+/// Returns how many frames were thrown away when the camera was last opened.
+///
+/// # Returns
+///
+/// The number of discarded warm-up frames, or 0 if the camera hasn't been opened yet.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraWarmupFramesDiscarded();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraWarmupFramesDiscarded(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    camera.warmup_frames_discarded().min(jint::MAX as u32) as jint
+}
+
+/// Sets the layout of the image data returned by `captureImage`.
+///
+/// The camera is restarted in the matching pixel format on the next capture.
+///
+/// # Arguments
+///
+/// * `mode` - `0` for JPEG frames, `1` for grayscale frames with one luma byte per pixel, `2` for
+///   RGB or `3` for BGR frames with three bytes per pixel.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the mode is unknown, or an `Exception` if the
+/// current camera cannot be stopped.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraColorMode(int mode);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraColorMode(
+    mut env: JNIEnv,
+    _class: JClass,
+    mode: jint,
+) {
+    let mode = match mode {
+        0 => ColorMode::Jpeg,
+        1 => ColorMode::Grayscale,
+        2 => ColorMode::Rgb,
+        3 => ColorMode::Bgr,
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown color mode {}", mode));
+            return;
+        }
+    };
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_color_mode(mode) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Returns the layout of the image data returned by `captureImage`, so callers know how many
+/// channels a frame has.
+///
+/// # Returns
+///
+/// `0` for JPEG frames, `1` for grayscale frames with one byte per pixel, `2` for RGB or `3` for
+/// BGR frames with three bytes per pixel.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraColorMode();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraColorMode(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    color_mode_id(camera.color_mode())
+}
+
+/// Returns the number Java uses for a color mode, as taken by `setCameraColorMode`.
+fn color_mode_id(mode: ColorMode) -> jint {
+    match mode {
+        ColorMode::Jpeg => 0,
+        ColorMode::Grayscale => 1,
+        ColorMode::Rgb => 2,
+        ColorMode::Bgr => 3,
+    }
+}
+
+/// Mirrors captured frames horizontally and/or vertically.
+///
+/// The camera's flip controls are used where supported. Otherwise frames are flipped in software,
+/// which only applies to the raw color modes. The setting is reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `horizontal` - `true` to mirror each frame left to right.
+/// * `vertical` - `true` to mirror each frame top to bottom.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraFlip(boolean horizontal, boolean vertical);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraFlip(
+    mut env: JNIEnv,
+    _class: JClass,
+    horizontal: jboolean,
+    vertical: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_flip(horizontal != 0, vertical != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Crops captured frames to a region of the image.
+///
+/// The camera crops at the sensor where the driver supports it, scaling the region to the configured
+/// resolution. Otherwise the region is cut out in software, which only applies to the raw color
+/// modes and makes frames the size of the region. Takes effect without restarting the camera.
+///
+/// # Arguments
+///
+/// * `x` - Left edge of the region in pixels.
+/// * `y` - Top edge of the region in pixels.
+/// * `width` - Width of the region in pixels.
+/// * `height` - Height of the region in pixels.
+///
+/// # Returns
+///
+/// `true` if the camera crops in hardware, `false` if frames are cropped in software.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the region is empty, negative or outside the frame.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean setCameraCrop(int x, int y, int width, int height);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraCrop(
+    mut env: JNIEnv,
+    _class: JClass,
+    x: jint,
+    y: jint,
+    width: jint,
+    height: jint,
+) -> jboolean {
+    if x < 0 || y < 0 || width <= 0 || height <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Crop region must be non-empty and non-negative");
+        return 0;
+    }
+
+    let mut camera = lock_camera();
+    let crop = Rect { x: x as u32, y: y as u32, width: width as u32, height: height as u32 };
+    match camera.set_crop(Some(crop)) {
+        Ok(hardware) => hardware as jboolean,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            0
+        }
+    }
+}
+
+/// Restores full-frame capture after `setCameraCrop`, without restarting the camera.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void resetCameraCrop();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_resetCameraCrop(
+    mut env: JNIEnv,
+    _class: JClass,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_crop(None) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Scales raw frames down while they are converted, without changing the camera's format.
+///
+/// Each output pixel is copied from the nearest captured one, after any software crop. Frames are
+/// never enlarged, and JPEG frames are passed through unscaled. `getCameraFrameWidth` and
+/// `getCameraFrameHeight` report the scaled size. Takes effect from the next capture.
+///
+/// # Arguments
+///
+/// * `width` - Width to scale frames to in pixels, or 0 together with `height` to stop scaling.
+/// * `height` - Height to scale frames to in pixels.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if only one of the sizes is 0, or either is negative.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraOutputSize(int width, int height);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraOutputSize(
+    mut env: JNIEnv,
+    _class: JClass,
+    width: jint,
+    height: jint,
+) {
+    let size = match (width, height) {
+        (0, 0) => None,
+        (width, height) if width > 0 && height > 0 => Some((width as u32, height as u32)),
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Output size must be positive, or 0 by 0");
+            return;
+        }
+    };
+
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_output_size(size) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Burns the current date and time into every raw frame, optionally followed by the frame's ID, as
+/// white text on a black box in a corner of the frame.
+///
+/// The overlay appears the same way in recordings, stills, the MJPEG server and captured images.
+/// JPEG frames aren't decoded, so in JPEG mode frames are left as the camera delivered them.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to draw the overlay.
+/// * `corner` - 0 for top left, 1 for top right, 2 for bottom left or 3 for bottom right.
+/// * `frame_counter` - Whether to show the frame ID after the time.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the corner is unknown.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraOverlay(boolean enabled, int corner, boolean frameCounter);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraOverlay(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+    corner: jint,
+    frame_counter: jboolean,
+) {
+    let corner = match corner {
+        0 => Corner::TopLeft,
+        1 => Corner::TopRight,
+        2 => Corner::BottomLeft,
+        3 => Corner::BottomRight,
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown overlay corner {}", corner));
+            return;
+        }
+    };
+
+    let overlay = (enabled != 0).then_some(Overlay { corner, frame_counter: frame_counter != 0 });
+    lock_camera().set_overlay(overlay);
+}
+
+/// Turns motion detection on or off.
+///
+/// While it is on, every captured frame is compared with the one before it, see
+/// `getCameraMotionScore`. Only the luma of the previous frame is kept. Turning it off also removes
+/// the listener started by `startMotionMonitoring`.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to compare frames.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraMotionDetection(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraMotionDetection(
+    _env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    lock_camera().set_motion_detection(enabled != 0);
+}
+
+/// Returns how much the last captured frame differed from the one before it.
+///
+/// # Returns
+///
+/// The mean absolute luma difference from 0.0 (no change) to 1.0 (black to white everywhere), or
+/// -1.0 while motion detection is off, before two frames of the same size have been captured, and
+/// in JPEG mode, where frames aren't decoded.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double getCameraMotionScore();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraMotionScore(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jdouble {
+    lock_camera().motion_score().unwrap_or(-1.0)
+}
+
+/// Turns motion detection on and calls `NativeBindings.onMotion` for every frame whose motion score
+/// is at least `threshold`, replacing any listener already started.
+///
+/// The calls are made from a thread of their own, so the listener doesn't hold up capturing and can
+/// use the camera itself. Frames that score while the listener is still handling an earlier one are
+/// skipped. Motion is only seen while frames are being captured.
+///
+/// # Arguments
+///
+/// * `threshold` - Lowest score that counts as motion, from 0.0 to 1.0.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if `threshold` is out of range, or an `Exception` if the
+/// listener thread can't be set up.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void startMotionMonitoring(double threshold);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startMotionMonitoring(
+    mut env: JNIEnv,
+    class: JClass,
+    threshold: jdouble,
+) {
+    let (jvm, class) = match (env.get_java_vm(), env.new_global_ref(&class)) {
+        (Ok(jvm), Ok(class)) => (jvm, class),
+        _ => {
+            let _ = env.throw_new("java/lang/RuntimeException", "Failed to set up the motion listener");
+            return;
+        }
+    };
+
+    //Hold at most one event, so a slow listener drops frames instead of falling behind
+    let (tx, rx) = mpsc::sync_channel(1);
+    let callback = Box::new(move |score: f64, frame_id: u64| {
+        let _ = tx.try_send((score, frame_id));
+    });
+    if let Err(e) = lock_camera().set_motion_callback(threshold, callback) {
+        throw_camera_error(&mut env, &e);
+        return;
+    }
+
+    //Runs until the callback, and with it the sender, is replaced or cleared
+    thread::spawn(move || {
+        let mut env = match jvm.attach_current_thread() {
+            Ok(env) => env,
+            Err(_) => return,
+        };
+        while let Ok((score, frame_id)) = rx.recv() {
+            let args = [JValue::Double(score), JValue::Long(frame_id as jlong)];
+            if env.call_static_method(&class, "onMotion", "(DJ)V", &args).is_err() {
+                //An exception thrown by the listener is dropped so later events still arrive
+                let _ = env.exception_clear();
+            }
+        }
+    });
+}
+
+/// Stops calling the listener started by `startMotionMonitoring`, leaving motion detection on.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void stopMotionMonitoring();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_stopMotionMonitoring(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    lock_camera().clear_motion_callback();
+}
+
+/// Returns the luma histogram of the last captured frame, for experimenting with exposure.
+///
+/// The histogram is kept up to date as frames are captured, so this doesn't process the frame again.
+/// Colour pixels are measured as `(r + 2g + b) / 4`.
+///
+/// # Returns
+///
+/// A Java int array of 256 pixel counts, from black at index 0 to white at index 255, or null
+/// before the first frame and in JPEG mode, where frames aren't decoded.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] getCameraHistogram();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraHistogram(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jintArray {
+    let histogram = match lock_camera().frame_histogram() {
+        Some(histogram) => histogram,
+        None => return std::ptr::null_mut(),
+    };
+
+    let values = histogram.map(|count| count.min(i32::MAX as u32) as jint);
+    let array = match env.new_int_array(values.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if let Err(e) = env.set_int_array_region(&array, 0, &values) {
+        throw_jni_error(&mut env, &e);
+        return std::ptr::null_mut();
+    }
+    array.into_raw()
+}
+
+/// Returns the mean luma of the last captured frame.
+///
+/// # Returns
+///
+/// The average brightness from 0.0 for black to 255.0 for white, or -1.0 before the first frame and
+/// in JPEG mode.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double getCameraAverageBrightness();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraAverageBrightness(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jdouble {
+    lock_camera().average_brightness().map_or(-1.0, f64::from)
+}
+
+/// Turns Sobel edge detection on or off. It is off by default.
+///
+/// While it is on, every raw frame captured is filtered into an edge map, see `getCameraEdgeMap`.
+/// Turning it off frees the edge map.
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to filter frames.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraEdgeDetection(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraEdgeDetection(
+    _env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    lock_camera().set_edge_detection(enabled != 0);
+}
+
+/// Sets how much frames are blurred before edges are detected and QR codes are read, e.g. to keep
+/// sensor noise out of the edge map. Frames aren't blurred by default.
+///
+/// # Arguments
+///
+/// * `radius` - How far the Gaussian blur reaches either side of a pixel, from 1 to 32 pixels, or 0
+///   for no blur.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the radius is out of range.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraBlur(int radius);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraBlur(
+    mut env: JNIEnv,
+    _class: JClass,
+    radius: jint,
+) {
+    let prefilter = match radius {
+        0 => PreFilter::None,
+        1.. => PreFilter::GaussianBlur { radius: radius as u32 },
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Blur radius cannot be negative");
+            return;
+        }
+    };
+    if let Err(e) = lock_camera().set_prefilter(prefilter) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Returns the Sobel edge map of the last captured frame.
+///
+/// # Returns
+///
+/// A Java byte array with one byte per pixel, row by row, that is bright along edges and dark in
+/// flat areas, at the size reported by `getCameraFrameWidth` and `getCameraFrameHeight`. Returns
+/// null while edge detection is off, before the first frame and in JPEG mode.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] getCameraEdgeMap();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraEdgeMap(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    let camera = lock_camera();
+    let edges = match camera.edge_map() {
+        Some(edges) => edges,
+        None => return std::ptr::null_mut(),
+    };
+    match env.byte_array_from_slice(&edges.data) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Starts masking every colour frame captured by a range of HSV colours, e.g. to track a ball of a
+/// known colour, see `getCameraColorMask`. This only works in the RGB and BGR colour modes.
+///
+/// # Arguments
+///
+/// * `hue_min` - The lowest hue to match, in degrees from 0 to 359.
+/// * `hue_max` - The highest hue to match, in degrees from 0 to 359. If it is below `hue_min` the
+///   range wraps around through 0, e.g. 340 to 20 for reds.
+/// * `saturation_min` - The lowest saturation to match, from 0 to 255.
+/// * `saturation_max` - The highest saturation to match, from 0 to 255.
+/// * `value_min` - The lowest value, or brightness, to match, from 0 to 255.
+/// * `value_max` - The highest value to match, from 0 to 255.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if a value is out of range, or a saturation or value
+/// range has its minimum above its maximum.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraColorFilter(int hueMin, int hueMax, int saturationMin, int saturationMax, int valueMin, int valueMax);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraColorFilter(
+    mut env: JNIEnv,
+    _class: JClass,
+    hue_min: jint,
+    hue_max: jint,
+    saturation_min: jint,
+    saturation_max: jint,
+    value_min: jint,
+    value_max: jint,
+) {
+    let hue = match (u16::try_from(hue_min), u16::try_from(hue_max)) {
+        (Ok(min), Ok(max)) => (min, max),
+        _ => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Hues must be between 0 and 359 degrees");
+            return;
+        }
+    };
+    let ranges = (
+        u8::try_from(saturation_min),
+        u8::try_from(saturation_max),
+        u8::try_from(value_min),
+        u8::try_from(value_max),
+    );
+    let (saturation, value) = match ranges {
+        (Ok(s_min), Ok(s_max), Ok(v_min), Ok(v_max)) => ((s_min, s_max), (v_min, v_max)),
+        _ => {
+            let _ = env.throw_new(
+                "java/lang/IllegalArgumentException",
+                "Saturation and value must be between 0 and 255",
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = lock_camera().set_color_filter(Some(ColorFilter { hue, saturation, value })) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Stops masking frames by colour and frees the mask.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void clearCameraColorFilter();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_clearCameraColorFilter(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    let _ = lock_camera().set_color_filter(None);
+}
+
+/// Returns the colour mask of the last captured frame.
+///
+/// # Returns
+///
+/// A Java byte array with one byte per pixel, row by row, that is 255 (-1 as a Java byte) where the
+/// colour lies within the filter and 0 elsewhere, at the size reported by `getCameraFrameWidth` and
+/// `getCameraFrameHeight`. Returns null without a filter, before the first frame and in the JPEG
+/// and grayscale modes.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native byte[] getCameraColorMask();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraColorMask(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    let camera = lock_camera();
+    let mask = match camera.mask_buffer() {
+        Some(mask) => mask,
+        None => return std::ptr::null_mut(),
+    };
+    match env.byte_array_from_slice(&mask.data) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the largest patch of matching pixels in the last colour mask, see `setCameraColorFilter`.
+///
+/// # Returns
+///
+/// A Java int array holding the column and row of the patch's centroid, rounded to the nearest
+/// pixel, its area in pixels, and the left column, top row, width and height of its bounding box.
+/// Returns null if no pixel matched, and when `getCameraColorMask` would return null.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] getCameraLargestBlob();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraLargestBlob(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jintArray {
+    let blob = match lock_camera().largest_blob() {
+        Some(blob) => blob,
+        None => return std::ptr::null_mut(),
+    };
+
+    let values = [
+        blob.cx.round() as jint,
+        blob.cy.round() as jint,
+        blob.area.min(i32::MAX as u32) as jint,
+        blob.bbox.x as jint,
+        blob.bbox.y as jint,
+        blob.bbox.width as jint,
+        blob.bbox.height as jint,
+    ];
+    let array = match env.new_int_array(values.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if let Err(e) = env.set_int_array_region(&array, 0, &values) {
+        throw_jni_error(&mut env, &e);
+        return std::ptr::null_mut();
+    }
+    array.into_raw()
+}
+
+/// Captures a frame and reads every QR code in it. Decoding is slow next to capturing, so it only
+/// happens when this is called.
+///
+/// In JPEG mode the camera must be stopped, and is opened in a raw mode just for the frame.
+/// Otherwise the running stream is used, or the camera is opened and left running.
+///
+/// # Returns
+///
+/// A Java string array with the payload of each code found, empty if there are none. The corners
+/// of the codes are returned by `getCameraQrCorners`, in the same order.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the frame cannot be captured, including in JPEG mode while the
+/// camera is streaming, or if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String[] decodeCameraQr();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_decodeCameraQr(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let texts: Vec<String> = match lock_camera().decode_qr() {
+        Ok(codes) => codes.into_iter().map(|code| code.text).collect(),
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_string_array(&mut env, &texts) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the corners of the QR codes read by the last call to `decodeCameraQr`, from which their
+/// distance and angle can be worked out.
+///
+/// # Returns
+///
+/// A Java double array with eight values per code, in the order the payloads were returned: the
+/// x and y coordinates in pixels of its top left, top right, bottom right and bottom left corners,
+/// as the code reads rather than as it lies in the frame. Empty before the first decode.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double[] getCameraQrCorners();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraQrCorners(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jdoubleArray {
+    let corners: Vec<jdouble> = lock_camera()
+        .last_qr_codes()
+        .iter()
+        .flat_map(|code| code.corners)
+        .flat_map(|(x, y)| [x as jdouble, y as jdouble])
+        .collect();
+
+    let array = match env.new_double_array(corners.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if let Err(e) = env.set_double_array_region(&array, 0, &corners) {
+        throw_jni_error(&mut env, &e);
+        return std::ptr::null_mut();
+    }
+    array.into_raw()
+}
+
+/// Finds the tag36h11 AprilTags in the last frame captured from the running stream. Detection is
+/// slow next to capturing, so it only happens when this is called, and the camera lock is only held
+/// while the frame is copied.
+///
+/// Only tag IDs 0 to 33 are recognised so far.
+///
+/// # Returns
+///
+/// A Java array of double arrays, one per tag found, empty if there are none. Each holds eleven
+/// values: the tag's ID, the x and y coordinates in pixels of its centre, then those of its top
+/// left, top right, bottom right and bottom left corners, as the tag reads rather than as it lies
+/// in the frame. Returns null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if the camera isn't running or hasn't captured a frame yet,
+/// or a `RuntimeException` in JPEG mode or if the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double[][] detectCameraAprilTags();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_detectCameraAprilTags(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let tags = match CameraController::detect_apriltags(&CAMERA) {
+        Ok(tags) => tags,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_tag_array(&mut env, &tags) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the frame rate negotiated with the camera driver, opening the camera if needed.
+///
+/// # Returns
+///
+/// The frame rate the driver is streaming at, in frames per second.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera cannot be opened.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double getCameraFrameRate();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameRate(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jdouble {
+    let mut camera = lock_camera();
+    match camera.frame_rate() {
+        Ok(fps) => fps as jdouble,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1.0
+        }
+    }
+}
+
+/// Returns the ID of the most recently captured camera frame.
+///
+/// IDs increase by one with every `captureImage` call and keep counting across camera restarts.
+///
+/// # Returns
+///
+/// The frame ID, or `0` if no frame has been captured yet.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long getCameraFrameId();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameId(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jlong {
+    let camera = lock_camera();
+    camera.current_frame_id() as jlong
+}
+
+/// Blocks until the camera captures a new frame, e.g. for a background recording or the MJPEG
+/// server, and returns its ID.
+///
+/// The camera lock isn't taken, so this waits alongside a thread that is capturing rather than
+/// behind it. Only frames captured after the call count.
+///
+/// # Arguments
+///
+/// * `timeout_ms` - Longest time to wait, in milliseconds.
+///
+/// # Returns
+///
+/// The ID of the newest frame, or `-1` if no frame was captured within the timeout.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if `timeout_ms` is negative.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long waitForCameraFrame(long timeoutMs);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_waitForCameraFrame(
+    mut env: JNIEnv,
+    _class: JClass,
+    timeout_ms: jlong,
+) -> jlong {
+    if timeout_ms < 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "The timeout must not be negative");
+        return -1;
+    }
+    match wait_for_frame(Duration::from_millis(timeout_ms as u64)) {
+        Ok(id) => id as jlong,
+        Err(_) => -1,
+    }
+}
+
+/// Hands Java the last frame captured from the running stream as a direct `ByteBuffer` over native
+/// memory, without copying it into the Java heap.
+///
+/// Every call must be paired with a `releaseCameraFrameBuffer` call for the buffer it returned. The
+/// memory stays valid until then, even if the camera captures newer frames or stops, and must not be
+/// read through the buffer afterwards. Calls for the same frame return buffers over the same memory
+/// and each counts as its own hold, so each one needs its own release. The buffer must be treated as
+/// read only, as every holder of the frame shares it.
+///
+/// # Returns
+///
+/// A direct buffer holding the frame as it was captured: JPEG data in JPEG mode, or raw pixels laid
+/// out as `getCameraFrameBufferInfo` says. Returns null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully. The buffer
+/// points at native memory, so reading it after it was released reads freed memory.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalStateException` if the camera isn't running or hasn't captured a frame yet,
+/// or a `RuntimeException` if the buffer cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native ByteBuffer acquireCameraFrameBuffer();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_acquireCameraFrameBuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobject {
+    let frame = match lock_camera().acquire_frame_buffer() {
+        Ok(frame) => frame,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    //The hold is taken before Java can see the buffer, so the memory outlives every reference to it
+    let (address, length) = (frame.data.as_ptr() as *mut u8, frame.data.len());
+    let mut held = HELD_FRAMES.lock().unwrap_or_else(PoisonError::into_inner);
+    held.push(frame);
+    match unsafe { env.new_direct_byte_buffer(address, length) } {
+        Ok(buffer) => buffer.into_raw(),
+        Err(e) => {
+            held.pop();
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Gives back a buffer returned by `acquireCameraFrameBuffer`, freeing its memory once no other
+/// hold on the same frame is left.
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer to release. It must not be read again afterwards.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the buffer isn't held, e.g. because it has already
+/// been released as many times as it was acquired.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void releaseCameraFrameBuffer(ByteBuffer buffer);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_releaseCameraFrameBuffer(
+    mut env: JNIEnv,
+    _class: JClass,
+    buffer: JByteBuffer,
+) {
+    let released = {
+        let mut held = HELD_FRAMES.lock().unwrap_or_else(PoisonError::into_inner);
+        match held_frame(&mut env, &held, &buffer) {
+            Some(index) => {
+                held.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    };
+    if !released {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Buffer is not a held camera frame");
+    }
+}
+
+/// Describes the frame a buffer returned by `acquireCameraFrameBuffer` holds.
+///
+/// # Arguments
+///
+/// * `buffer` - A buffer that has been acquired and not yet released.
+///
+/// # Returns
+///
+/// A Java array of four longs: the frame's ID, its width and height in pixels, and the color mode it
+/// was captured in, numbered as `getCameraColorMode` numbers them. Returns null on error.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the buffer isn't held, or a `RuntimeException` if
+/// the array cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native long[] getCameraFrameBufferInfo(ByteBuffer buffer);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameBufferInfo(
+    mut env: JNIEnv,
+    _class: JClass,
+    buffer: JByteBuffer,
+) -> jlongArray {
+    let info = {
+        let held = HELD_FRAMES.lock().unwrap_or_else(PoisonError::into_inner);
+        held_frame(&mut env, &held, &buffer).map(|index| {
+            let frame = &held[index];
+            [frame.id as jlong, frame.width as jlong, frame.height as jlong, color_mode_id(frame.mode) as jlong]
+        })
+    };
+    let info = match info {
+        Some(info) => info,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", "Buffer is not a held camera frame");
+            return std::ptr::null_mut();
+        }
+    };
+
+    let array = match env.new_long_array(info.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if let Err(e) = env.set_long_array_region(&array, 0, &info) {
+        throw_jni_error(&mut env, &e);
+        return std::ptr::null_mut();
+    }
+    array.into_raw()
+}
+
+/// Finds the held frame a direct buffer points at, by the address of its memory.
+fn held_frame(env: &mut JNIEnv, held: &[Arc<BufferedFrame>], buffer: &JByteBuffer) -> Option<usize> {
+    let address = env.get_direct_buffer_address(buffer).ok()? as *const u8;
+    held.iter().position(|frame| frame.data.as_ptr() == address)
+}
+
+/// Returns the width of the frames `captureImage` returns with the current settings.
+///
+/// Takes any software crop into account, and holds whether or not the camera is running.
+///
+/// # Returns
+///
+/// The frame width in pixels.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraFrameWidth();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameWidth(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    camera.frame_size().0 as jint
+}
+
+/// Returns the height of the frames `captureImage` returns with the current settings.
+///
+/// Takes any software crop into account, and holds whether or not the camera is running.
+///
+/// # Returns
+///
+/// The frame height in pixels.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraFrameHeight();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameHeight(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    camera.frame_size().1 as jint
+}
+
+/// Returns the number of bytes per pixel in the frames `captureImage` returns.
+///
+/// # Returns
+///
+/// 1 in grayscale mode, 3 in RGB and BGR mode, or 0 in JPEG mode, where frames are compressed.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraFrameChannels();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameChannels(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    camera.frame_channels() as jint
+}
+
+/// Describes the configured camera for diagnostics.
+///
+/// Reports the card, driver and bus names, driver version, capability flags and current capture format.
+/// Works whether or not the camera is running, and still reports whatever could be read if some
+/// queries fail.
+///
+/// # Returns
+///
+/// A Java string describing the camera.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the string cannot be created.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String getCameraInfo();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraInfo(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let camera = lock_camera();
+    match env.new_string(camera.info().to_string()) {
+        Ok(info) => info.into_raw(),
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Lists the camera devices attached to the system.
+///
+/// # Returns
+///
+/// A Java string array with one entry per `/dev/video*` node, describing its index, path, card and
+/// driver names, whether it supports capture and streaming, and the formats and resolutions it
+/// supports. Nodes that could not be queried are listed with the error that occurred.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the devices cannot be scanned.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String[] listCameras();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_listCameras(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let descriptions: Vec<String> = match list_devices() {
+        Ok(devices) => devices.iter().map(|device| device.to_string()).collect(),
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_string_array(&mut env, &descriptions) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Lists the pixel formats and frame sizes supported by the selected camera device.
+///
+/// # Returns
+///
+/// A Java string array with one entry per pixel format, containing its FourCC, description and
+/// either the discrete frame sizes or the stepwise size range it supports.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the device cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native String[] getCameraFormats();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFormats(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let camera = lock_camera();
+    let descriptions: Vec<String> = match camera.supported_formats() {
+        Ok(formats) => formats.iter().map(|format| format.to_string()).collect(),
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_string_array(&mut env, &descriptions) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Lists the frame rates the selected camera device supports for a pixel format and frame size.
+///
+/// # Arguments
+///
+/// * `format` - FourCC of the pixel format, e.g. `MJPG` or `YUYV`.
+/// * `width` - Frame width in pixels.
+/// * `height` - Frame height in pixels.
+///
+/// # Returns
+///
+/// A Java double array of frame rates in frames per second, fastest first. For devices that
+/// support a continuous range, it contains the fastest and slowest rates of that range.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the format or frame size is invalid, or an
+/// `Exception` if the device cannot be opened or queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native double[] getCameraFrameRates(String format, int width, int height);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFrameRates(
+    mut env: JNIEnv,
+    _class: JClass,
+    format: JString,
+    width: jint,
+    height: jint,
+) -> jdoubleArray {
+    let format: String = match env.get_string(&format) {
+        Ok(format) => format.into(),
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("{}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    if width <= 0 || height <= 0 {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", "Frame size must be positive");
+        return std::ptr::null_mut();
+    }
+
+    let camera = lock_camera();
+    let mut rates: Vec<f64> = match camera.frame_intervals(&format, width as u32, height as u32) {
+        Ok(IntervalInfo::Discretes(intervals)) => intervals.into_iter().map(interval_rate).collect(),
+        Ok(IntervalInfo::Stepwise { min, max, .. }) => vec![interval_rate(min), interval_rate(max)],
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    rates.sort_by(|a, b| b.total_cmp(a));
+
+    let array = match env.new_double_array(rates.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if let Err(e) = env.set_double_array_region(&array, 0, &rates) {
+        throw_jni_error(&mut env, &e);
+        return std::ptr::null_mut();
+    }
+    array.into_raw()
+}
+
+/// Enables or disables automatic exposure on the camera.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` for automatic exposure, `false` for manual exposure.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraExposureAuto(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraExposureAuto(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_exposure_auto(enabled != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Enables or disables automatic exposure on the camera, like `setCameraExposureAuto`.
+///
+/// Works whether or not the camera is streaming, and is reapplied whenever it is reopened.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` for automatic exposure, `false` for manual exposure.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no exposure mode control, or an
+/// `Exception` if it rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraAutoExposure(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraAutoExposure(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_auto_exposure(enabled != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Checks whether the camera is using automatic exposure.
+///
+/// # Returns
+///
+/// `true` if exposure is controlled automatically, `false` otherwise.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the exposure mode cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraExposureAuto();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraExposureAuto(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    let camera = lock_camera();
+    match camera.exposure_auto() {
+        Ok(enabled) => enabled as jboolean,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            0
+        }
+    }
+}
+
+/// Sets the manual exposure time of the camera.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened. Most
+/// cameras ignore it unless automatic exposure is disabled.
+///
+/// # Arguments
+///
+/// * `value` - Exposure time in units of 100 µs.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera rejects the value, describing the accepted range.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraExposure(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraExposure(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_exposure(value as i64) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Reads the current exposure time of the camera.
+///
+/// # Returns
+///
+/// The exposure time in units of 100 µs.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the exposure cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraExposure();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraExposure(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    match camera.exposure_absolute() {
+        Ok(value) => value as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Enables or disables automatic white balance on the camera.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` for automatic white balance, `false` for manual.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraWhiteBalanceAuto(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraWhiteBalanceAuto(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_white_balance_auto(enabled != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Enables or disables automatic white balance on the camera, like `setCameraWhiteBalanceAuto`.
+///
+/// Works whether or not the camera is streaming, and is reapplied whenever it is reopened.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` for automatic white balance, `false` for manual.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no automatic white balance, or
+/// an `Exception` if it rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraAutoWhiteBalance(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraAutoWhiteBalance(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_auto_white_balance(enabled != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Checks whether the camera is using automatic white balance.
+///
+/// # Returns
+///
+/// `true` if white balance is controlled automatically, `false` otherwise.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the white balance mode cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraWhiteBalanceAuto();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraWhiteBalanceAuto(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    let camera = lock_camera();
+    match camera.white_balance_auto() {
+        Ok(enabled) => enabled as jboolean,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            0
+        }
+    }
+}
+
+/// Sets the manual white balance temperature of the camera.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened. Most
+/// cameras ignore it unless automatic white balance is disabled.
+///
+/// # Arguments
+///
+/// * `kelvin` - White balance temperature in Kelvin.
+///
+/// # Returns
+///
+/// The temperature accepted by the camera, which may be rounded to a coarser step.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the camera rejects the value, describing the accepted range.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraWhiteBalanceTemperature(int kelvin);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraWhiteBalanceTemperature(
+    mut env: JNIEnv,
+    _class: JClass,
+    kelvin: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_white_balance_temperature(kelvin as i64) {
+        Ok(accepted) => accepted as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Reads the current white balance temperature of the camera.
+///
+/// # Returns
+///
+/// The white balance temperature in Kelvin.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the temperature cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraWhiteBalanceTemperature();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraWhiteBalanceTemperature(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    match camera.white_balance_temperature() {
+        Ok(kelvin) => kelvin as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Sets a V4L2 control on the camera, such as brightness, contrast or saturation.
+///
+/// The value is clamped to the range the control supports, takes effect immediately and is
+/// reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `control_id` - The V4L2 control ID, e.g. `0x00980900` for brightness.
+/// * `value` - The value to set.
+///
+/// # Returns
+///
+/// The value that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the control is unsupported, or an `Exception`
+/// if the camera rejects the value.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraControl(int controlId, int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraControl(
+    mut env: JNIEnv,
+    _class: JClass,
+    control_id: jint,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_control(control_id as u32, value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Reads the current value of a V4L2 control on the camera.
+///
+/// # Arguments
+///
+/// * `control_id` - The V4L2 control ID, e.g. `0x00980900` for brightness.
+///
+/// # Returns
+///
+/// The current value of the control.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the control cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraControl(int controlId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraControl(
+    mut env: JNIEnv,
+    _class: JClass,
+    control_id: jint,
+) -> jint {
+    let camera = lock_camera();
+    match camera.control(control_id as u32) {
+        Ok(value) => value as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Reads the valid range, default and current value of a V4L2 control on the camera.
+///
+/// Booleans are reported as a 0 to 1 range and menus as the range of their item indices.
+///
+/// # Arguments
+///
+/// * `control_id` - The V4L2 control ID, e.g. `0x00980900` for brightness.
+///
+/// # Returns
+///
+/// A Java int array holding the minimum, maximum, step, default and current value, followed by
+/// 1 if the control is enabled and 1 if it is read-only (0 otherwise), or null if the camera
+/// doesn't implement the control.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `Exception` if the device cannot be opened or the control cannot be queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] getCameraControlRange(int controlId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraControlRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    control_id: jint,
+) -> jintArray {
+    let camera = lock_camera();
+    let range = match camera.control_range(control_id as u32) {
+        Ok(Some(range)) => range,
+        Ok(None) => return std::ptr::null_mut(),
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_range_array(&mut env, &range) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Reads the valid range, default and current value of a V4L2 control on the camera, failing
+/// for controls it doesn't implement rather than returning null like `getCameraControlRange`.
+///
+/// # Arguments
+///
+/// * `control_id` - The V4L2 control ID, e.g. `0x00980900` for brightness.
+///
+/// # Returns
+///
+/// A Java int array holding the minimum, maximum, step, default and current value, followed by
+/// 1 if the control is enabled and 1 if it is read-only (0 otherwise).
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera doesn't implement the control, or an
+/// `Exception` if the device cannot be opened or the control cannot be queried.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int[] queryCameraControlRange(int controlId);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_queryCameraControlRange(
+    mut env: JNIEnv,
+    _class: JClass,
+    control_id: jint,
+) -> jintArray {
+    let camera = lock_camera();
+    let range = match camera.query_control_range(control_id as u32) {
+        Ok(range) => range,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match new_range_array(&mut env, &range) {
+        Ok(array) => array,
+        Err(e) => {
+            throw_jni_error(&mut env, &e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Sets the picture brightness of the camera, clamped to the range it supports.
+///
+/// The setting takes effect immediately, without restarting a running stream, and is reapplied
+/// whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `value` - The brightness to set.
+///
+/// # Returns
+///
+/// The value that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no brightness control, or an
+/// `Exception` if it rejects the value.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraBrightness(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraBrightness(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_brightness(value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Sets the picture contrast of the camera, clamped to the range it supports.
+///
+/// The setting takes effect immediately, without restarting a running stream, and is reapplied
+/// whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `value` - The contrast to set.
+///
+/// # Returns
+///
+/// The value that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no contrast control, or an
+/// `Exception` if it rejects the value.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraContrast(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraContrast(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_contrast(value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Sets the picture saturation of the camera, clamped to the range it supports.
+///
+/// The setting takes effect immediately, without restarting a running stream, and is reapplied
+/// whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `value` - The saturation to set.
+///
+/// # Returns
+///
+/// The value that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no saturation control, or an
+/// `Exception` if it rejects the value.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraSaturation(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraSaturation(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_saturation(value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Sets the sensor gain of the camera, clamped to the range it supports.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened. Raising
+/// the gain brightens frames in low light at the cost of more noise.
+///
+/// # Arguments
+///
+/// * `value` - The gain to set.
+///
+/// # Returns
+///
+/// The gain that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no gain control, or an
+/// `Exception` if the value is rejected.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraGain(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraGain(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_gain(value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Reads the current sensor gain of the camera.
+///
+/// # Returns
+///
+/// The current gain.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no gain control, or an
+/// `Exception` if the gain cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraGain();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraGain(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    match camera.gain() {
+        Ok(value) => value as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Sets the power line frequency the camera compensates for to avoid banding under mains lighting.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `frequency` - `0` to disable compensation, `1` for 50 Hz, `2` for 60 Hz or `3` for automatic.
+///
+/// # Returns
+///
+/// `true` if the camera accepted the setting, `false` if it doesn't offer it.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `IllegalArgumentException` if the value is unknown, an
+/// `UnsupportedOperationException` if the camera has no anti-flicker control, or an `Exception` if
+/// the setting cannot be applied.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean setCameraPowerLineFrequency(int frequency);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraPowerLineFrequency(
+    mut env: JNIEnv,
+    _class: JClass,
+    frequency: jint,
+) -> jboolean {
+    let frequency = match PowerLineFrequency::from_value(frequency as i64) {
+        Some(frequency) => frequency,
+        None => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("Unknown power line frequency {}", frequency));
+            return 0;
+        }
+    };
+
+    let mut camera = lock_camera();
+    match camera.set_power_line_frequency(frequency) {
+        Ok(accepted) => accepted as jboolean,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            0
+        }
+    }
+}
+
+/// Reads the power line frequency setting of the camera.
+///
+/// # Returns
+///
+/// `0` if compensation is disabled, `1` for 50 Hz, `2` for 60 Hz or `3` for automatic.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no anti-flicker control, or an
+/// `Exception` if the setting cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraPowerLineFrequency();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraPowerLineFrequency(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    match camera.power_line_frequency() {
+        Ok(frequency) => frequency as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Enables or disables continuous autofocus on the camera.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened.
+///
+/// # Arguments
+///
+/// * `enabled` - `true` for autofocus, `false` to hold the current lens position.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no autofocus, or an `Exception`
+/// if the camera rejects the setting.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native void setCameraAutofocus(boolean enabled);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraAutofocus(
+    mut env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+) {
+    let mut camera = lock_camera();
+    if let Err(e) = camera.set_autofocus(enabled != 0) {
+        throw_camera_error(&mut env, &e);
+    }
+}
+
+/// Checks whether the camera is focusing automatically.
+///
+/// # Returns
+///
+/// `true` if autofocus is enabled, `false` otherwise.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no autofocus, or an `Exception`
+/// if the setting cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native boolean isCameraAutofocus();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_isCameraAutofocus(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jboolean {
+    let camera = lock_camera();
+    match camera.autofocus() {
+        Ok(enabled) => enabled as jboolean,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            0
+        }
+    }
+}
+
+/// Sets the lens position of the camera, clamped to the range it supports.
+///
+/// The setting takes effect immediately and is reapplied whenever the camera is reopened. Most
+/// cameras ignore it unless autofocus is disabled.
+///
+/// # Arguments
+///
+/// * `value` - The lens position to set.
+///
+/// # Returns
+///
+/// The lens position that was applied after clamping.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no focus control, or an
+/// `Exception` if the value is rejected.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int setCameraFocus(int value);
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_setCameraFocus(
+    mut env: JNIEnv,
+    _class: JClass,
+    value: jint,
+) -> jint {
+    let mut camera = lock_camera();
+    match camera.set_focus_absolute(value as i64) {
+        Ok(applied) => applied as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Reads the current lens position of the camera.
+///
+/// # Returns
+///
+/// The current lens position.
+///
+/// # Safety
+///
+/// This function interacts with hardware through JNI calls and must be used carefully.
+///
+/// # Errors
+///
+/// Throws a Java `UnsupportedOperationException` if the camera has no focus control, or an
+/// `Exception` if the position cannot be read.
+///
+/// # JNI Signature
+///
+/// ```java
+/// public static native int getCameraFocus();
+/// ```
+#[no_mangle]
+pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_getCameraFocus(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    let camera = lock_camera();
+    match camera.focus_absolute() {
+        Ok(value) => value as jint,
+        Err(e) => {
+            throw_camera_error(&mut env, &e);
+            -1
+        }
+    }
+}
+
+/// Converts a slice of Rust strings into a Java `String[]`.
+fn new_string_array(env: &mut JNIEnv, values: &[String]) -> jni::errors::Result<jobjectArray> {
+    let array = env.new_object_array(values.len() as i32, "java/lang/String", JObject::null())?;
+    for (i, value) in values.iter().enumerate() {
+        let value = env.new_string(value)?;
+        env.set_object_array_element(&array, i as i32, value)?;
+    }
+    Ok(array.into_raw())
+}
+
+/// Converts frames into a Java `byte[][]`, one array per frame.
+fn new_frame_array(env: &mut JNIEnv, frames: &[Vec<u8>]) -> jni::errors::Result<jobjectArray> {
+    let array = env.new_object_array(frames.len() as i32, "[B", JObject::null())?;
+    for (i, frame) in frames.iter().enumerate() {
+        let frame = env.byte_array_from_slice(frame)?;
+        env.set_object_array_element(&array, i as i32, frame)?;
+    }
+    Ok(array.into_raw())
+}
+
+/// Converts AprilTag detections into a Java `double[][]`, one array per tag of its ID, centre and
+/// corners.
+fn new_tag_array(env: &mut JNIEnv, tags: &[TagDetection]) -> jni::errors::Result<jobjectArray> {
+    let array = env.new_object_array(tags.len() as i32, "[D", JObject::null())?;
+    for (i, tag) in tags.iter().enumerate() {
+        let mut values = vec![tag.id as jdouble, tag.center.0 as jdouble, tag.center.1 as jdouble];
+        values.extend(tag.corners.iter().flat_map(|&(x, y)| [x as jdouble, y as jdouble]));
+        let tag = env.new_double_array(values.len() as i32)?;
+        env.set_double_array_region(&tag, 0, &values)?;
+        env.set_object_array_element(&array, i as i32, tag)?;
+    }
+    Ok(array.into_raw())
+}
+
+/// Converts the range of a control into a Java `int[]` of its minimum, maximum, step, default and
+/// current value, then 1 if it is enabled and 1 if it is read-only.
+fn new_range_array(env: &mut JNIEnv, range: &ControlRange) -> jni::errors::Result<jintArray> {
+    let values = [
+        range.minimum as jint,
+        range.maximum as jint,
+        range.step as jint,
+        range.default as jint,
+        range.value as jint,
+        range.enabled as jint,
+        range.read_only as jint,
+    ];
+    let array = env.new_int_array(values.len() as i32)?;
+    env.set_int_array_region(&array, 0, &values)?;
+    Ok(array.into_raw())
+}
+
+/// Converts the timing of a recording into a Java `long[]`, after the values in `prefix`.
+///
+/// The timing takes six elements: frames captured, frames written, the average and the longest
+/// time spent encoding and writing a frame in microseconds, the number of frame deadlines at the
+/// playback rate that passed without a frame being written, and the frame rate actually achieved in
+/// thousandths of a frame per second.
+fn new_timing_array(env: &mut JNIEnv, timing: &FrameTiming, prefix: &[jlong]) -> jni::errors::Result<jlongArray> {
+    let mut values = prefix.to_vec();
+    values.extend_from_slice(&[
+        timing.captured as jlong,
+        timing.written as jlong,
+        timing.average_processing().as_micros() as jlong,
+        timing.max_processing.as_micros() as jlong,
+        timing.missed_deadlines as jlong,
+        (timing.actual_fps() * 1000.0).round() as jlong,
+    ]);
+    let array = env.new_long_array(values.len() as i32)?;
+    env.set_long_array_region(&array, 0, &values)?;
+    Ok(array.into_raw())
+}
+
+/// Maps the codec numbers taken by `captureVideo` and `captureVideoFrames` to codecs.
+fn video_codec(codec: jint) -> Option<VideoCodec> {
+    match codec {
+        0 => Some(VideoCodec::Mjpeg),
+        1 => Some(VideoCodec::H264),
+        2 => Some(VideoCodec::RawMjpeg),
+        3 => Some(VideoCodec::Y4m),
+        _ => None,
+    }
+}
+
+/// Locks the camera, recovering it if a thread panicked while holding the lock.
+///
+/// The controller stays usable after such a panic, at worst with the stream to restart, so one
+/// failed call doesn't make every later camera call panic too.
+fn lock_camera() -> MutexGuard<'static, CameraController> {
+    CAMERA.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks the camera like [`lock_camera`], giving up after `timeout` instead of waiting for a long
+/// capture, such as a recording, to finish.
+fn lock_camera_within(timeout: Duration) -> Option<MutexGuard<'static, CameraController>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match CAMERA.try_lock() {
+            Ok(camera) => return Some(camera),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Err(TryLockError::WouldBlock) => return None,
+            Err(TryLockError::Poisoned(e)) => return Some(e.into_inner()),
+        }
+    }
+}
+
+/// Throws a camera error as a Java exception.
+///
+/// Unsupported controls are thrown as `UnsupportedOperationException` so the Java side can hide
+/// the corresponding setting, invalid settings as `IllegalArgumentException`, a camera that is busy
+/// or already streaming or not streaming yet and background recordings started or stopped twice as
+/// `IllegalStateException`, device failures as an `UncheckedIOException` wrapping an `IOException`,
+/// see [`throw_io_error`], and every other error as `RuntimeException`.
+fn throw_camera_error(env: &mut JNIEnv, e: &CameraError) {
+    //An interrupted burst is thrown as the kind of error that stopped it
+    let cause = match e {
+        CameraError::Interrupted(_, cause) => cause.as_ref(),
+        e => e,
+    };
+    let class = match cause {
+        CameraError::ControlUnsupported(_) => "java/lang/UnsupportedOperationException",
+        CameraError::InvalidArgument(_) => "java/lang/IllegalArgumentException",
+        CameraError::Busy
+        | CameraError::StreamRunning
+        | CameraError::RecordingRunning
+        | CameraError::NotRecording
+        | CameraError::NotStarted
+        | CameraError::NoFrame => "java/lang/IllegalStateException",
+        CameraError::DeviceOpen(..)
+        | CameraError::Disconnected(_)
+        | CameraError::NotCaptureDevice(_)
+        | CameraError::Io(_) => return throw_io_error(env, &e.to_string()),
+        _ => "java/lang/RuntimeException",
+    };
+    let _ = env.throw_new(class, format!("{}", e));
+}
+
+/// Throws a device failure as a Java `UncheckedIOException` whose cause is an `IOException` with the
+/// same message.
+///
+/// `IOException` is a checked exception and the natives don't declare it, so Java code couldn't
+/// catch it around them. Callers catch the `UncheckedIOException` and can rethrow its cause.
+fn throw_io_error(env: &mut JNIEnv, message: &str) {
+    let exception = (|| {
+        let message = JObject::from(env.new_string(message)?);
+        let cause = env.new_object("java/io/IOException", "(Ljava/lang/String;)V", &[JValue::Object(&message)])?;
+        env.new_object(
+            "java/io/UncheckedIOException",
+            "(Ljava/lang/String;Ljava/io/IOException;)V",
+            &[JValue::Object(&message), JValue::Object(&cause)],
+        )
+    })();
+    match exception {
+        Ok(exception) => {
+            let _ = env.throw(JThrowable::from(exception));
+        }
+        Err(e) => throw_jni_error(env, &e),
+    }
+}
+
+/// Throws a failed JNI call, such as an array that couldn't be allocated, as a Java
+/// `RuntimeException`, unless the call already left an exception like `OutOfMemoryError` pending.
+fn throw_jni_error(env: &mut JNIEnv, e: &jni::errors::Error) {
+    if matches!(e, jni::errors::Error::JavaException) || env.exception_check().unwrap_or(false) {
+        return;
+    }
+    let _ = env.throw_new("java/lang/RuntimeException", format!("{}", e));
+}
+
 #[no_mangle]
 pub extern "system" fn Java_bisocm_swiftbot_lib_NativeBindings_startButtonMonitoring(
     mut env: JNIEnv,
@@ -0,0 +1,223 @@
+use crate::camera::{CameraController, CapturedFrame};
+use crate::jpeg;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+//Separates the JPEG parts of the multipart response
+const BOUNDARY: &str = "frame";
+
+//How often the accept loop and idle clients check whether the server is stopping
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+//How long to wait before capturing again after the camera fails, e.g. while it is reconfigured
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+//The running server, if any
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Threads of a running server.
+struct Server {
+    shared: Arc<Shared>,
+    accept: JoinHandle<()>,
+    capture: JoinHandle<()>,
+}
+
+/// State shared between the capture thread and the client threads.
+struct Shared {
+    running: AtomicBool,
+    clients: AtomicUsize, //Number of connected clients, frames are only captured while there are some
+    latest: Mutex<Latest>,
+    updated: Condvar, //Signalled whenever `latest` is replaced
+}
+
+/// The most recently captured frame.
+#[derive(Default)]
+struct Latest {
+    id: u64, //Counts up with every frame, 0 before the first
+    jpeg: Vec<u8>,
+}
+
+/// Starts serving the camera as a Motion JPEG stream over HTTP on `port`.
+///
+/// Every request, whatever its path, gets a `multipart/x-mixed-replace` response that browsers show
+/// as live video. Frames are captured through `camera` only while a client is connected, and each
+/// frame is sent to every client, so viewers don't open the device a second time or slow each other
+/// down. Other users of `camera` keep working, they take turns with the server for the lock.
+///
+/// # Errors
+///
+/// Returns an `AlreadyExists` error if the server is already running, or the error from binding the
+/// port.
+pub fn start_mjpeg_server(camera: &'static Mutex<CameraController>, port: u16) -> io::Result<()> {
+    let mut server = SERVER.lock().unwrap_or_else(PoisonError::into_inner);
+    if server.is_some() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "The MJPEG server is already running"));
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+
+    let shared = Arc::new(Shared {
+        running: AtomicBool::new(true),
+        clients: AtomicUsize::new(0),
+        latest: Mutex::new(Latest::default()),
+        updated: Condvar::new(),
+    });
+
+    let accept = {
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || accept_clients(listener, shared))
+    };
+    let capture = {
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || capture_frames(camera, shared))
+    };
+
+    *server = Some(Server { shared, accept, capture });
+    Ok(())
+}
+
+/// Stops the server started by [`start_mjpeg_server`], if it is running.
+///
+/// Waits for the capture to finish, so the camera is free once this returns. Connected clients are
+/// disconnected shortly afterwards.
+pub fn stop_mjpeg_server() {
+    let server = SERVER.lock().unwrap_or_else(PoisonError::into_inner).take();
+    if let Some(server) = server {
+        server.shared.running.store(false, Ordering::SeqCst);
+        server.shared.updated.notify_all();
+        let _ = server.accept.join();
+        let _ = server.capture.join();
+    }
+}
+
+/// Accepts connections until the server stops, handing each to a thread of its own.
+fn accept_clients(listener: TcpListener, shared: Arc<Shared>) {
+    while shared.running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    shared.clients.fetch_add(1, Ordering::SeqCst);
+                    let _ = serve_client(stream, &shared);
+                    shared.clients.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            //Nothing to accept yet, or a client gave up before it was accepted
+            Err(_) => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// Captures frames into `shared.latest` while any client is connected.
+fn capture_frames(camera: &Mutex<CameraController>, shared: Arc<Shared>) {
+    let mut frame = CapturedFrame::default();
+    let mut encoder = jpeg::Encoder::new();
+    let mut encoded = Vec::new();
+
+    while shared.running.load(Ordering::SeqCst) {
+        if shared.clients.load(Ordering::SeqCst) == 0 {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        //Only hold the camera for the capture itself
+        let mut controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = controller.capture_jpeg_into(&mut frame, &mut encoder, &mut encoded, None).map(|jpeg| {
+            let mut latest = shared.latest.lock().unwrap_or_else(PoisonError::into_inner);
+            latest.jpeg.clear();
+            latest.jpeg.extend_from_slice(jpeg);
+            latest.id += 1;
+        });
+        drop(controller);
+
+        match result {
+            Ok(()) => shared.updated.notify_all(),
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
+
+/// Answers one HTTP request with the multipart stream, sending each new frame until the client
+/// disconnects or the server stops.
+fn serve_client(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_nodelay(true)?;
+
+    //The request itself doesn't matter, but a browser expects it to be read before the response
+    let mut request = [0; 4096];
+    let mut received = 0;
+    while received < request.len() && !request[..received].windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut request[received..])? {
+            0 => return Ok(()),
+            count => received += count,
+        }
+    }
+
+    write!(
+        stream,
+        "HTTP/1.0 200 OK\r\n\
+         Content-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache, no-store\r\n\
+         Pragma: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+
+    //Copy each frame out so the capture thread isn't held up while it is sent
+    let mut jpeg = Vec::new();
+    let mut sent = 0;
+    while shared.running.load(Ordering::SeqCst) {
+        {
+            let latest = shared.latest.lock().unwrap_or_else(PoisonError::into_inner);
+            let (latest, _) = shared
+                .updated
+                .wait_timeout_while(latest, POLL_INTERVAL * 10, |latest| {
+                    latest.id == sent && shared.running.load(Ordering::SeqCst)
+                })
+                .unwrap_or_else(PoisonError::into_inner);
+            if latest.id == sent {
+                continue;
+            }
+            sent = latest.id;
+            jpeg.clear();
+            jpeg.extend_from_slice(&latest.jpeg);
+        }
+
+        write!(stream, "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", BOUNDARY, jpeg.len())?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_of_two_simultaneous_starts_runs_a_server() {
+        //Nothing is captured without clients, so the camera is never opened
+        let camera: &'static Mutex<CameraController> = Box::leak(Box::new(Mutex::new(CameraController::new())));
+
+        let starts: Vec<_> = (0..2).map(|_| thread::spawn(move || start_mjpeg_server(camera, 0))).collect();
+        let results: Vec<_> = starts.into_iter().map(|start| start.join().unwrap()).collect();
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Err(e) if e.kind() == io::ErrorKind::AlreadyExists)));
+
+        stop_mjpeg_server();
+        assert!(SERVER.lock().unwrap().is_none());
+
+        //A stopped server can be started again
+        start_mjpeg_server(camera, 0).unwrap();
+        stop_mjpeg_server();
+    }
+}
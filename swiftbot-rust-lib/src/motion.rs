@@ -0,0 +1,54 @@
+use crate::convert::{self, Channels};
+
+/// Measures how much the scene changes from one frame to the next.
+///
+/// Only the luma of the previous frame is kept, one byte per pixel, so memory stays bounded by the
+/// frame size however long detection runs.
+#[derive(Default)]
+pub struct Detector {
+    previous: Vec<u8>, //Luma of the last frame
+    size: (u32, u32),  //Size of the last frame, the next one is only compared if it matches
+    score: Option<f64>,
+}
+
+impl Detector {
+    /// Compares a frame against the previous one and keeps its luma, as [`convert::luma`] measures
+    /// it, for the next comparison.
+    ///
+    /// # Returns
+    ///
+    /// The mean absolute luma difference from the previous frame, from 0.0 for identical frames to
+    /// 1.0 for a frame that went from black to white, or `None` for the first frame or one that is a
+    /// different size from the last.
+    pub fn update(&mut self, pixels: &[u8], width: u32, height: u32, channels: Channels) -> Option<f64> {
+        let count = match channels {
+            Channels::Gray => pixels.len(),
+            Channels::Rgb => pixels.len() / 3,
+        };
+        let comparable = self.size == (width, height) && self.previous.len() == count;
+        if !comparable {
+            self.previous.clear();
+            self.previous.resize(count, 0);
+            self.size = (width, height);
+        }
+
+        let mut total = 0u64;
+        for (previous, luma) in self.previous.iter_mut().zip(convert::luma(pixels, channels)) {
+            total += luma.abs_diff(*previous) as u64;
+            *previous = luma;
+        }
+
+        self.score = (comparable && count > 0).then(|| total as f64 / (count as f64 * 255.0));
+        self.score
+    }
+
+    /// Returns the score from the last call to [`update`](Self::update).
+    pub fn score(&self) -> Option<f64> {
+        self.score
+    }
+
+    /// Forgets the previous frame, so the next one starts a new comparison.
+    pub fn reset(&mut self) {
+        *self = Detector::default();
+    }
+}
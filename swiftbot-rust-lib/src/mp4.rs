@@ -0,0 +1,262 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+//NAL unit types
+const NAL_IDR_SLICE: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_ACCESS_UNIT_DELIMITER: u8 = 9;
+
+//Identity transformation matrix, in the 16.16 and 2.30 fixed point formats MP4 uses
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// Writes an H.264 stream into an MP4 file with a single video track.
+///
+/// Frames are written to the `mdat` box as they arrive and the `moov` box describing them is
+/// appended by [`Writer::finish`], so a file that isn't finished can't be played. Sample offsets
+/// are 32-bit, which limits the file to 4 GiB.
+pub struct Writer<W: Write + Seek> {
+    inner: W,
+    mdat_start: u64, //Position of the `mdat` box header in `inner`
+    position: u64,   //Position the next sample is written at
+    fps: u32,
+    width: u32,
+    height: u32,
+    sps: Vec<u8>,        //First sequence parameter set in the stream, without its start code
+    pps: Vec<u8>,        //First picture parameter set in the stream, without its start code
+    sample: Vec<u8>,     //The frame being converted to length-prefixed NAL units
+    sizes: Vec<u32>,     //Size of each sample
+    offsets: Vec<u32>,   //File position of each sample
+    keyframes: Vec<u32>, //Numbers of the samples that start with an IDR slice, counting from 1
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Starts an MP4 file at the current position of `inner` for `width` by `height` frames played
+    /// back at `fps` frames per second.
+    pub fn new(mut inner: W, fps: u32, width: u32, height: u32) -> io::Result<Self> {
+        let mut ftyp = Vec::with_capacity(24);
+        ftyp.extend_from_slice(b"isom");
+        ftyp.extend_from_slice(&0x200u32.to_be_bytes());
+        ftyp.extend_from_slice(b"isomiso2avc1mp41");
+        inner.write_all(&atom(b"ftyp", &ftyp))?;
+
+        //The size of the media data box is filled in once it is known
+        let mdat_start = inner.stream_position()?;
+        inner.write_all(&[0, 0, 0, 0])?;
+        inner.write_all(b"mdat")?;
+
+        Ok(Writer {
+            inner,
+            mdat_start,
+            position: mdat_start + 8,
+            fps: fps.max(1),
+            width,
+            height,
+            sps: Vec::new(),
+            pps: Vec::new(),
+            sample: Vec::new(),
+            sizes: Vec::new(),
+            offsets: Vec::new(),
+            keyframes: Vec::new(),
+        })
+    }
+
+    /// Appends one frame of encoded data in Annex B format, as H.264 encoders output it.
+    ///
+    /// Parameter sets are moved into the track header, so data holding nothing else doesn't count
+    /// as a frame.
+    pub fn write_frame(&mut self, data: &[u8]) -> io::Result<()> {
+        self.sample.clear();
+        let mut keyframe = false;
+
+        for unit in nal_units(data) {
+            match unit[0] & 0x1F {
+                NAL_SPS if self.sps.is_empty() => self.sps = unit.to_vec(),
+                NAL_PPS if self.pps.is_empty() => self.pps = unit.to_vec(),
+                NAL_SPS | NAL_PPS | NAL_ACCESS_UNIT_DELIMITER => {}
+                kind => {
+                    keyframe |= kind == NAL_IDR_SLICE;
+                    self.sample.extend_from_slice(&(unit.len() as u32).to_be_bytes());
+                    self.sample.extend_from_slice(unit);
+                }
+            }
+        }
+
+        if self.sample.is_empty() {
+            return Ok(());
+        }
+        if self.position + self.sample.len() as u64 > u32::MAX as u64 {
+            return Err(io::Error::other("Recording is too large for an MP4 file"));
+        }
+
+        self.inner.write_all(&self.sample)?;
+        self.sizes.push(self.sample.len() as u32);
+        self.offsets.push(self.position as u32);
+        if keyframe {
+            self.keyframes.push(self.sizes.len() as u32);
+        }
+        self.position += self.sample.len() as u64;
+        Ok(())
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> u32 {
+        self.sizes.len() as u32
+    }
+
+    /// Fills in the `mdat` size and appends the `moov` box, leaving `inner` positioned at the end of
+    /// the file.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.sps.is_empty() || self.pps.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The H.264 stream has no SPS or PPS"));
+        }
+
+        let mdat_size = (self.position - self.mdat_start) as u32;
+        self.inner.seek(SeekFrom::Start(self.mdat_start))?;
+        self.inner.write_all(&mdat_size.to_be_bytes())?;
+        self.inner.seek(SeekFrom::Start(self.position))?;
+
+        let moov = self.moov();
+        self.inner.write_all(&moov)?;
+        Ok(self.inner)
+    }
+
+    /// Builds the `moov` box describing the track and where its samples are.
+    fn moov(&self) -> Vec<u8> {
+        let frames = self.frame_count();
+        let duration_ms = (frames as u64 * 1000 / self.fps as u64).min(u32::MAX as u64) as u32;
+
+        //Creation and modification times, timescale, duration, rate, volume, reserved, matrix,
+        //predefined and the next track ID
+        let mut mvhd = words(&[0, 0, 1000, duration_ms, 0x0001_0000]);
+        mvhd.extend_from_slice(&[0x01, 0x00, 0, 0]);
+        mvhd.extend(words(&[0, 0]));
+        mvhd.extend(words(&MATRIX));
+        mvhd.extend(words(&[0; 6]));
+        mvhd.extend(words(&[2]));
+
+        //Creation and modification times, track ID, reserved, duration, reserved, layer, alternate
+        //group, volume, reserved, matrix, and the size in 16.16 fixed point
+        let mut tkhd = words(&[0, 0, 1, 0, duration_ms, 0, 0, 0, 0]);
+        tkhd.extend(words(&MATRIX));
+        tkhd.extend(words(&[self.width << 16, self.height << 16]));
+
+        //Creation and modification times, timescale, duration, and the language code for undefined
+        let mut mdhd = words(&[0, 0, self.fps, frames]);
+        mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+
+        let mut hdlr = words(&[0]);
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend(words(&[0, 0, 0]));
+        hdlr.extend_from_slice(b"VideoHandler\0");
+
+        let dref = [words(&[1]), full_atom(b"url ", 0, 1, &[])].concat();
+
+        //Sample table: description, timing, keyframes, samples per chunk, sizes and chunk offsets,
+        //with every sample in a chunk of its own
+        let stbl = [
+            full_atom(b"stsd", 0, 0, &[words(&[1]), self.avc1()].concat()),
+            full_atom(b"stts", 0, 0, &words(&[1, frames, 1])),
+            full_atom(b"stss", 0, 0, &[words(&[self.keyframes.len() as u32]), words(&self.keyframes)].concat()),
+            full_atom(b"stsc", 0, 0, &words(&[1, 1, 1, 1])),
+            full_atom(b"stsz", 0, 0, &[words(&[0, frames]), words(&self.sizes)].concat()),
+            full_atom(b"stco", 0, 0, &[words(&[frames]), words(&self.offsets)].concat()),
+        ]
+        .concat();
+
+        let minf = [
+            full_atom(b"vmhd", 0, 1, &[0; 8]),
+            atom(b"dinf", &full_atom(b"dref", 0, 0, &dref)),
+            atom(b"stbl", &stbl),
+        ]
+        .concat();
+
+        let mdia = [
+            full_atom(b"mdhd", 0, 0, &mdhd),
+            full_atom(b"hdlr", 0, 0, &hdlr),
+            atom(b"minf", &minf),
+        ]
+        .concat();
+
+        let trak = [full_atom(b"tkhd", 0, 3, &tkhd), atom(b"mdia", &mdia)].concat();
+        atom(b"moov", &[full_atom(b"mvhd", 0, 0, &mvhd), atom(b"trak", &trak)].concat())
+    }
+
+    /// Builds the `avc1` sample description, which carries the parameter sets.
+    fn avc1(&self) -> Vec<u8> {
+        //Profile, compatibility and level are copied from the SPS, and NAL units are prefixed
+        //with 4-byte lengths
+        let sps = |index: usize| self.sps.get(index).copied().unwrap_or(0);
+        let mut avcc = vec![1, sps(1), sps(2), sps(3), 0xFF, 0xE1];
+        avcc.extend_from_slice(&(self.sps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(&self.sps);
+        avcc.push(1);
+        avcc.extend_from_slice(&(self.pps.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(&self.pps);
+
+        //Reserved, data reference index, predefined and reserved fields, size, resolution in
+        //pixels per inch, reserved, frames per sample, an empty compressor name, depth and a
+        //predefined -1
+        let mut avc1 = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        avc1.extend(words(&[0, 0, 0, 0]));
+        avc1.extend_from_slice(&(self.width as u16).to_be_bytes());
+        avc1.extend_from_slice(&(self.height as u16).to_be_bytes());
+        avc1.extend(words(&[0x0048_0000, 0x0048_0000, 0]));
+        avc1.extend_from_slice(&[0, 1]);
+        avc1.extend_from_slice(&[0; 32]);
+        avc1.extend_from_slice(&[0x00, 0x18, 0xFF, 0xFF]);
+        avc1.extend(atom(b"avcC", &avcc));
+        atom(b"avc1", &avc1)
+    }
+}
+
+/// Splits Annex B data into NAL units, dropping the start codes and any zero padding between units.
+fn nal_units(data: &[u8]) -> AnnexB<'_> {
+    AnnexB { rest: data }
+}
+
+/// Iterator over the NAL units in Annex B data.
+struct AnnexB<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for AnnexB<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            //Skip to just after the next `00 00 01` start code
+            let start = self.rest.windows(3).position(|window| window == [0, 0, 1])? + 3;
+            let rest = &self.rest[start..];
+            let end = rest.windows(3).position(|window| window == [0, 0, 1]).unwrap_or(rest.len());
+
+            //Zeros before the next start code are padding, or part of a 4-byte start code
+            let unit = &rest[..end];
+            let length = unit.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+            self.rest = &rest[end..];
+            if length > 0 {
+                return Some(&unit[..length]);
+            }
+        }
+    }
+}
+
+/// Builds a box with the given type.
+fn atom(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut atom = Vec::with_capacity(8 + body.len());
+    atom.extend_from_slice(&(8 + body.len() as u32).to_be_bytes());
+    atom.extend_from_slice(id);
+    atom.extend_from_slice(body);
+    atom
+}
+
+/// Builds a box that starts with a version and flags.
+fn full_atom(id: &[u8; 4], version: u8, flags: u32, body: &[u8]) -> Vec<u8> {
+    let mut full = (flags & 0x00FF_FFFF | (version as u32) << 24).to_be_bytes().to_vec();
+    full.extend_from_slice(body);
+    atom(id, &full)
+}
+
+/// Encodes 32-bit values in big-endian order.
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_be_bytes()).collect()
+}
@@ -0,0 +1,123 @@
+use crate::convert::Channels;
+use crate::utils::local_time;
+use std::time::SystemTime;
+
+//Glyphs are 5 pixels wide and 7 high, each row a byte with the leftmost pixel in bit 4
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const FONT: [(char, [u8; GLYPH_HEIGHT]); 13] = [
+    ('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+    ('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+    ('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+    ('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+    ('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+    ('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+    ('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+    ('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+    ('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+    (':', [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00]),
+    ('#', [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a]),
+];
+
+//Pixels of background around the text and between characters, before scaling
+const PADDING: usize = 1;
+const SPACING: usize = 1;
+
+/// Corner of the frame an overlay is drawn in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// A timestamp burnt into every raw frame, so recordings show when they were made.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Overlay {
+    pub corner: Corner,
+    pub frame_counter: bool, //Also show the frame's ID after the time
+}
+
+impl Overlay {
+    /// Draws the current local date and time, and the frame ID if the overlay asks for it, into a
+    /// frame as white text on a black box.
+    ///
+    /// The text is scaled up by a whole factor for every 240 rows of the frame, so it stays legible
+    /// at higher resolutions, and clipped if the frame is too small for it. Only the pixels under
+    /// the box are touched, so this is cheap enough for every frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - The frame's pixels, row by row.
+    /// * `width` - Frame width in pixels.
+    /// * `height` - Frame height in pixels.
+    /// * `channels` - Channel layout of `pixels`, red and blue may be either way round.
+    /// * `frame_id` - ID of the frame, shown if the frame counter is on.
+    pub fn draw(&self, pixels: &mut [u8], width: u32, height: u32, channels: Channels, frame_id: u64) {
+        let time = local_time(SystemTime::now());
+        let mut text = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            time.tm_year + 1900,
+            time.tm_mon + 1,
+            time.tm_mday,
+            time.tm_hour,
+            time.tm_min,
+            time.tm_sec
+        );
+        if self.frame_counter {
+            text.push_str(&format!(" #{}", frame_id));
+        }
+
+        let bytes_per_pixel = match channels {
+            Channels::Gray => 1,
+            Channels::Rgb => 3,
+        };
+        let (width, height) = (width as usize, height as usize);
+        if pixels.len() < width * height * bytes_per_pixel {
+            return;
+        }
+
+        let scale = (height / 240).max(1);
+        let count = text.chars().count();
+        let box_width = ((2 * PADDING + count * (GLYPH_WIDTH + SPACING) - SPACING) * scale).min(width);
+        let box_height = ((2 * PADDING + GLYPH_HEIGHT) * scale).min(height);
+        let left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => 0,
+            Corner::TopRight | Corner::BottomRight => width - box_width,
+        };
+        let top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => 0,
+            Corner::BottomLeft | Corner::BottomRight => height - box_height,
+        };
+
+        let glyphs: Vec<&[u8; GLYPH_HEIGHT]> = text.chars().map(glyph).collect();
+        let cell = GLYPH_WIDTH + SPACING;
+        for y in 0..box_height {
+            let start = ((top + y) * width + left) * bytes_per_pixel;
+            let row = &mut pixels[start..start + box_width * bytes_per_pixel];
+            let glyph_row = (y / scale).checked_sub(PADDING).filter(|&row| row < GLYPH_HEIGHT);
+            for (x, pixel) in row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+                //Find the glyph and the column within it that this pixel falls in, if any
+                let lit = match (glyph_row, (x / scale).checked_sub(PADDING)) {
+                    (Some(glyph_row), Some(column)) => {
+                        let (index, glyph_column) = (column / cell, column % cell);
+                        glyph_column < GLYPH_WIDTH
+                            && glyphs.get(index).is_some_and(|glyph| glyph[glyph_row] & (0x10 >> glyph_column) != 0)
+                    }
+                    _ => false,
+                };
+                pixel.fill(if lit { 255 } else { 0 });
+            }
+        }
+    }
+}
+
+/// Returns the glyph for a character, or a blank one for spaces and anything else the font doesn't
+/// have.
+fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    FONT.iter().find(|(glyph, _)| *glyph == c).map_or(&[0; GLYPH_HEIGHT], |(_, rows)| rows)
+}
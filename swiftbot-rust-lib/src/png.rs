@@ -0,0 +1,123 @@
+use crate::convert::Channels;
+
+//Every PNG file starts with this signature
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+//Largest payload of a stored deflate block
+const MAX_STORED_BLOCK: usize = 65535;
+
+//CRC-32 lookup table for the chunk checksums, using the reflected polynomial from the PNG specification
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Encodes an image as an 8-bit PNG.
+///
+/// The pixel data is stored uncompressed, which keeps encoding cheap on the Pi at the cost of larger
+/// files. If `pixels` holds fewer than `width * height` pixels the missing ones are stored as black.
+///
+/// # Arguments
+///
+/// * `pixels` - The image data, row by row.
+/// * `width` - Image width in pixels, greater than zero.
+/// * `height` - Image height in pixels, greater than zero.
+/// * `channels` - Channel layout of `pixels`.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the PNG file.
+pub fn encode(pixels: &[u8], width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let (bytes_per_pixel, colour_type) = match channels {
+        Channels::Gray => (1, 0),
+        Channels::Rgb => (3, 2),
+    };
+    let row_bytes = width as usize * bytes_per_pixel;
+
+    //Each row is prefixed with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((row_bytes + 1) * height as usize);
+    for row in 0..height as usize {
+        let start = (row * row_bytes).min(pixels.len());
+        let end = (start + row_bytes).min(pixels.len());
+        raw.push(0);
+        raw.extend_from_slice(&pixels[start..end]);
+        raw.resize(raw.len() + row_bytes - (end - start), 0);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, colour_type, 0, 0, 0]); //Bit depth, colour type, compression, filter, interlace
+
+    let data = zlib_stored(&raw);
+    let mut out = Vec::with_capacity(data.len() + 64);
+    out.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &data);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Appends a chunk with its length, type, data and CRC.
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    //The CRC covers the chunk type and data but not the length
+    let crc = out[start..].iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    });
+    out.extend_from_slice(&(!crc).to_be_bytes());
+}
+
+/// Wraps data in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    out.extend_from_slice(&[0x78, 0x01]); //Deflate with a 32K window and no preset dictionary
+
+    for block in 0..blocks {
+        let start = block * MAX_STORED_BLOCK;
+        let chunk = &data[start..(start + MAX_STORED_BLOCK).min(data.len())];
+        let length = chunk.len() as u16;
+
+        out.push((block == blocks - 1) as u8); //Final block flag, block type 0 for stored
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Computes the Adler-32 checksum that ends a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    //5552 bytes is the most that can be summed before the 32-bit sums need reducing
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Builds the CRC-32 lookup table at compile time.
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
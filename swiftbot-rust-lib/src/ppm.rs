@@ -0,0 +1,63 @@
+use crate::convert::Channels;
+
+/// Encodes an image as a binary PPM (`P6`), or a binary PGM (`P5`) if it is grayscale.
+///
+/// The header gives the size and a maximum value of 255, followed by the pixels row by row from the
+/// top, one byte per channel, exactly as captured. If `pixels` holds fewer than `width * height`
+/// pixels the missing ones are stored as black.
+///
+/// # Arguments
+///
+/// * `pixels` - The image data, row by row.
+/// * `width` - Image width in pixels, greater than zero.
+/// * `height` - Image height in pixels, greater than zero.
+/// * `channels` - Channel layout of `pixels`.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the PPM or PGM file.
+pub fn encode(pixels: &[u8], width: u32, height: u32, channels: Channels) -> Vec<u8> {
+    let (magic, bytes_per_pixel) = match channels {
+        Channels::Gray => ("P5", 1),
+        Channels::Rgb => ("P6", 3),
+    };
+    let size = width as usize * height as usize * bytes_per_pixel;
+
+    let header = format!("{}\n{} {}\n255\n", magic, width, height);
+    let mut out = Vec::with_capacity(header.len() + size);
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(&pixels[..size.min(pixels.len())]);
+    out.resize(header.len() + size, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_gradient_has_p6_header_and_raw_pixels() {
+        let pixels: Vec<u8> = (0..4u8).flat_map(|x| [x * 85, 255 - x * 85, 128]).collect();
+        let ppm = encode(&pixels, 2, 2, Channels::Rgb);
+
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(&ppm[header.len()..], &pixels[..]);
+    }
+
+    #[test]
+    fn gray_gradient_has_p5_header() {
+        let pixels = [0, 64, 128, 192, 255, 32];
+        let pgm = encode(&pixels, 3, 2, Channels::Gray);
+
+        let header = b"P5\n3 2\n255\n";
+        assert_eq!(&pgm[..header.len()], header);
+        assert_eq!(&pgm[header.len()..], &pixels);
+    }
+
+    #[test]
+    fn missing_pixels_are_black() {
+        let pgm = encode(&[9], 2, 1, Channels::Gray);
+        assert_eq!(pgm, b"P5\n2 1\n255\n\x09\x00");
+    }
+}
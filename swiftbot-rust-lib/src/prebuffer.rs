@@ -0,0 +1,264 @@
+use crate::avi;
+use crate::camera::{CameraController, CameraError, CapturedFrame};
+use crate::jpeg;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+//The pre-record buffer being filled, if any
+static PREBUFFER: Mutex<Option<Prebuffer>> = Mutex::new(None);
+
+//Held while a dump is written, so dumps made at the same time go into their files one after the other
+static DUMPING: Mutex<()> = Mutex::new(());
+
+/// How much a pre-record buffer holds. Frames are dropped, oldest first, as soon as either limit
+/// is passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrebufferLimits {
+    pub duration: Duration, //Age of the oldest frame kept
+    pub max_bytes: u64,     //Total size of the JPEG frames kept
+}
+
+/// A buffer being filled on a thread of its own.
+struct Prebuffer {
+    stop: Arc<AtomicBool>,
+    ring: Arc<Ring>,
+    fps: u32, //Frame rate dumps play back at
+    thread: JoinHandle<()>,
+}
+
+/// The frames of a buffer, shared between the thread filling it and the dumps reading it.
+struct Ring {
+    state: Mutex<RingState>,
+    added: Condvar, //Signalled when a frame is added or the buffer stops
+}
+
+struct RingState {
+    frames: VecDeque<BufferedFrame>,
+    bytes: u64, //Total size of `frames`
+    next: u64,  //Number the next frame captured will get
+    stopped: bool,
+    error: Option<String>, //Why the buffer stopped capturing, if it failed
+}
+
+/// One JPEG frame in a buffer.
+#[derive(Clone)]
+struct BufferedFrame {
+    number: u64, //Frames are numbered in the order they are captured, from 0
+    time: Instant,
+    jpeg: Arc<Vec<u8>>, //Shared, so dumps can take the frames without copying them
+    width: u32,
+    height: u32,
+}
+
+impl Ring {
+    fn lock(&self) -> MutexGuard<'_, RingState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Starts keeping the last few seconds of camera frames in memory as JPEG, so the moments before
+/// an event can be saved with [`dump_prebuffer`] once it has happened, e.g. when a button is
+/// pressed or the ultrasonic sensor sees something.
+///
+/// Raw frames are encoded at `quality`, or the configured JPEG quality if it is `None`. Frames are
+/// captured through `camera` one at a time, as for a background recording, so other users of the
+/// camera keep working. The camera is opened if needed and left running. Call
+/// [`stop_prebuffer`] to stop capturing and free the frames.
+///
+/// # Errors
+///
+/// Returns [`CameraError::RecordingRunning`] if a buffer is already being filled, or
+/// [`CameraError::InvalidArgument`] if a limit is zero or the quality is out of range.
+pub fn start_prebuffer(
+    camera: &'static Mutex<CameraController>,
+    limits: PrebufferLimits,
+    quality: Option<u8>,
+) -> Result<(), CameraError> {
+    let mut prebuffer = PREBUFFER.lock().unwrap_or_else(PoisonError::into_inner);
+    if prebuffer.is_some() {
+        return Err(CameraError::RecordingRunning);
+    }
+    if let Some(quality) = quality {
+        if !(1..=100).contains(&quality) {
+            return Err(CameraError::InvalidArgument(format!(
+                "JPEG quality must be between 1 and 100, got {}",
+                quality
+            )));
+        }
+    }
+    if limits.duration == Duration::ZERO || limits.max_bytes == 0 {
+        return Err(CameraError::InvalidArgument(String::from("Pre-record limits must be greater than zero")));
+    }
+
+    let fps = camera.lock().unwrap_or_else(PoisonError::into_inner).config().fps;
+    let ring = Arc::new(Ring {
+        state: Mutex::new(RingState {
+            frames: VecDeque::new(),
+            bytes: 0,
+            next: 0,
+            stopped: false,
+            error: None,
+        }),
+        added: Condvar::new(),
+    });
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (stop, ring) = (Arc::clone(&stop), Arc::clone(&ring));
+        thread::spawn(move || fill(camera, &ring, limits, quality, &stop))
+    };
+
+    *prebuffer = Some(Prebuffer { stop, ring, fps, thread });
+    Ok(())
+}
+
+/// Stops filling the buffer started by [`start_prebuffer`] and frees its frames. A dump in
+/// progress carries on with the frames it has and finishes its file straight away.
+///
+/// # Errors
+///
+/// Returns [`CameraError::NotRecording`] if no buffer is being filled.
+pub fn stop_prebuffer() -> Result<(), CameraError> {
+    let prebuffer = PREBUFFER.lock().unwrap_or_else(PoisonError::into_inner).take();
+    let prebuffer = match prebuffer {
+        Some(prebuffer) => prebuffer,
+        None => return Err(CameraError::NotRecording),
+    };
+
+    prebuffer.stop.store(true, Ordering::SeqCst);
+    let _ = prebuffer.thread.join();
+    let mut state = prebuffer.ring.lock();
+    state.frames.clear();
+    state.bytes = 0;
+    Ok(())
+}
+
+/// Returns `true` while a buffer started by [`start_prebuffer`] is being filled.
+pub fn is_prebuffering() -> bool {
+    PREBUFFER.lock().unwrap_or_else(PoisonError::into_inner).is_some()
+}
+
+/// Writes the frames in the buffer started by [`start_prebuffer`] to a Motion JPEG AVI file at
+/// `path`, and carries on writing the frames captured after them for `after`, blocking until the
+/// file is finished and synced to disk.
+///
+/// The buffer keeps filling during the dump, so a later dump holds these frames too. Dumps made
+/// while one is being written wait their turn, each going into a file of its own.
+///
+/// Returns the number of frames written.
+///
+/// # Errors
+///
+/// Returns [`CameraError::NotRecording`] if no buffer is being filled, or the error from creating
+/// the file. If the buffer stops capturing because a capture failed, the file is finished with
+/// the frames written up to then and the capture error is returned.
+pub fn dump_prebuffer(path: &str, after: Duration) -> Result<u32, CameraError> {
+    let (ring, fps) = match PREBUFFER.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(prebuffer) => (Arc::clone(&prebuffer.ring), prebuffer.fps),
+        None => return Err(CameraError::NotRecording),
+    };
+    let _dumping = DUMPING.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let file = fs::File::create(path)
+        .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path, e)))?;
+    let mut video = avi::Writer::new(BufWriter::new(file), fps)?;
+
+    //The deadline counts from the call, so the time spent writing the buffered frames isn't added
+    let deadline = Instant::now() + after;
+    let mut next = 0;
+    let mut result = Ok(());
+    loop {
+        //Take the frames not written yet, waiting for new ones if there are none
+        let (frames, finished) = {
+            let mut state = ring.lock();
+            while !state.stopped && state.next <= next && Instant::now() < deadline {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                state = ring.added.wait_timeout(state, timeout).unwrap_or_else(PoisonError::into_inner).0;
+            }
+            let frames: Vec<BufferedFrame> = state
+                .frames
+                .iter()
+                .filter(|frame| frame.number >= next && frame.time <= deadline)
+                .cloned()
+                .collect();
+            if let Some(error) = state.error.as_ref() {
+                result = Err(CameraError::Other(error.clone()));
+            }
+            (frames, state.stopped || Instant::now() >= deadline)
+        };
+
+        for frame in &frames {
+            if let Err(e) = video.write_frame(&frame.jpeg, frame.width, frame.height) {
+                result = Err(e.into());
+                break;
+            }
+            next = frame.number + 1;
+        }
+        if finished || result.is_err() {
+            break;
+        }
+    }
+
+    let frames = video.frame_count();
+    let file = video.finish()?.into_inner().map_err(|e| CameraError::Io(e.into_error()))?;
+    file.sync_all()?;
+    result.map(|_| frames)
+}
+
+/// Captures frames into the ring until `stop` is set or a capture fails, dropping the oldest as
+/// the limits are passed.
+fn fill(
+    camera: &Mutex<CameraController>,
+    ring: &Ring,
+    limits: PrebufferLimits,
+    quality: Option<u8>,
+    stop: &AtomicBool,
+) {
+    let mut frame = CapturedFrame::default();
+    let mut encoder = jpeg::Encoder::new();
+    let mut encoded = Vec::new();
+
+    let mut error = None;
+    while !stop.load(Ordering::SeqCst) {
+        //Only hold the camera for the capture itself
+        let mut controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+        let jpeg = match controller.capture_jpeg_into(&mut frame, &mut encoder, &mut encoded, quality) {
+            Ok(jpeg) => jpeg.to_vec(),
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        };
+        let (width, height) = controller.frame_size();
+        drop(controller);
+
+        let time = Instant::now();
+        let mut state = ring.lock();
+        let number = state.next;
+        state.bytes += jpeg.len() as u64;
+        state.frames.push_back(BufferedFrame { number, time, jpeg: Arc::new(jpeg), width, height });
+        state.next += 1;
+
+        //Always keep the newest frame, even one larger than the size limit on its own
+        while state.frames.len() > 1
+            && (state.bytes > limits.max_bytes
+                || state.frames.front().is_some_and(|oldest| time - oldest.time > limits.duration))
+        {
+            if let Some(oldest) = state.frames.pop_front() {
+                state.bytes -= oldest.jpeg.len() as u64;
+            }
+        }
+        drop(state);
+        ring.added.notify_all();
+    }
+
+    let mut state = ring.lock();
+    state.stopped = true;
+    state.error = error;
+    drop(state);
+    ring.added.notify_all();
+}
@@ -0,0 +1,950 @@
+//Error correction codewords per block, by error correction level (L, M, Q, H) and then version
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+//Error correction blocks, likewise
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17,
+        18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29,
+        31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38,
+        40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45,
+        48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+//Error correction levels in the order of the tables above, indexed by the two level bits of the format
+const FORMAT_LEVELS: [usize; 4] = [1, 0, 3, 2];
+
+//Characters of the alphanumeric mode, by value
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+//Most finder pattern candidates looked at when pairing them up into codes
+const MAX_FINDERS: usize = 15;
+
+/// A QR code found in a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QrResult {
+    pub text: String,             //The payload, read as UTF-8, or as Latin-1 if it isn't valid UTF-8
+    pub corners: [(f32, f32); 4], //Outer corners in pixels, clockwise from the top left as the code reads
+}
+
+/// Finds and decodes every QR code in a grayscale image.
+///
+/// Codes may be rotated and seen at an angle, as long as their three finder patterns, the squares in
+/// their corners, are in view. Codes that are damaged beyond what their error correction can repair,
+/// or that hold only Kanji, are skipped. Kanji characters elsewhere come out as U+FFFD.
+///
+/// # Arguments
+///
+/// * `pixels` - The luma of each pixel, row by row.
+/// * `width` - The width of the image in pixels.
+/// * `height` - The height of the image in pixels.
+///
+/// # Returns
+///
+/// A `Vec<QrResult>` with one entry per code decoded, empty if there are none.
+pub fn decode(pixels: &[u8], width: u32, height: u32) -> Vec<QrResult> {
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 || pixels.len() < width * height {
+        return Vec::new();
+    }
+
+    let bitmap = Bitmap::new(pixels, width, height);
+    let finders = bitmap.find_finders();
+    group_finders(&finders)
+        .into_iter()
+        .filter_map(|[top_left, top_right, bottom_left]| bitmap.decode_symbol(top_left, top_right, bottom_left))
+        .collect()
+}
+
+/// An image split into dark and light pixels.
+pub(crate) struct Bitmap {
+    pub(crate) dark: Vec<bool>,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+}
+
+/// The centre of a finder pattern, in pixels.
+#[derive(Clone, Copy, Debug)]
+struct Finder {
+    x: f32,
+    y: f32,
+    module: f32, //Size of one module in pixels
+    hits: u32,   //Number of rows the pattern was found on
+}
+
+impl Bitmap {
+    /// Thresholds an image against the mean of the area around each pixel, so uneven lighting
+    /// doesn't turn half a code dark.
+    pub(crate) fn new(pixels: &[u8], width: usize, height: usize) -> Bitmap {
+        //Summed area table, with a row and column of zeros in front
+        let mut sums = vec![0u64; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row = 0u64;
+            for x in 0..width {
+                row += pixels[y * width + x] as u64;
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+            }
+        }
+
+        //The window has to be larger than the dark centre of a finder pattern seen up close
+        let radius = (width.min(height) / 8).max(8);
+        let mut dark = vec![false; width * height];
+        for y in 0..height {
+            let (top, bottom) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+                let sum = sums[bottom * (width + 1) + right] + sums[top * (width + 1) + left]
+                    - sums[top * (width + 1) + right]
+                    - sums[bottom * (width + 1) + left];
+                let count = ((bottom - top) * (right - left)) as u64;
+                dark[y * width + x] = pixels[y * width + x] as u64 * count * 10 < sum * 9;
+            }
+        }
+        Bitmap { dark, width, height }
+    }
+
+    /// Returns `true` if the pixel is dark. Pixels outside the image are light.
+    fn get(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+            && self.dark[y as usize * self.width + x as usize]
+    }
+
+    /// Samples the image at a point given in pixels.
+    pub(crate) fn sample(&self, (x, y): (f32, f32)) -> bool {
+        self.get(x.floor() as i32, y.floor() as i32)
+    }
+
+    /// Scans every row for the 1:1:3:1:1 runs of dark and light across a finder pattern, checking
+    /// each hit across the other directions and merging hits on the same pattern.
+    fn find_finders(&self) -> Vec<Finder> {
+        let mut finders: Vec<Finder> = Vec::new();
+        let mut runs = Vec::new();
+        for y in 0..self.height {
+            //Runs of the row as (start, length), starting with a dark one
+            runs.clear();
+            let row = &self.dark[y * self.width..(y + 1) * self.width];
+            let mut start = 0;
+            while start < row.len() {
+                let length = row[start..].iter().take_while(|&&dark| dark == row[start]).count();
+                if row[start] || !runs.is_empty() {
+                    runs.push((start, length));
+                }
+                start += length;
+            }
+
+            //Every other run is dark, so a pattern starts on every even one
+            for window in runs.windows(5).step_by(2) {
+                let counts = [window[0].1, window[1].1, window[2].1, window[3].1, window[4].1];
+                if !is_finder_ratio(&counts) {
+                    continue;
+                }
+                let centre = window[2].0 as f32 + window[2].1 as f32 / 2.0;
+                if let Some(finder) = self.check_finder(centre, y as f32 + 0.5, counts.iter().sum()) {
+                    merge_finder(&mut finders, finder);
+                }
+            }
+        }
+        finders
+    }
+
+    /// Checks a possible finder pattern found along a row, returning its refined centre if the
+    /// vertical and diagonal lines through it cross a finder pattern of about the same size too.
+    fn check_finder(&self, x: f32, y: f32, horizontal: usize) -> Option<Finder> {
+        let limit = horizontal as f32 * 2.0;
+        let ((_, y), vertical) = self.cross_check((x, y), (0.0, 1.0), limit)?;
+        if 5.0 * (vertical - horizontal as f32).abs() >= 2.0 * horizontal as f32 {
+            return None;
+        }
+        let ((x, _), horizontal) = self.cross_check((x, y), (1.0, 0.0), limit)?;
+        self.cross_check((x, y), (1.0, 1.0), limit * 1.5)?;
+        Some(Finder { x, y, module: (horizontal + vertical) / 14.0, hits: 1 })
+    }
+
+    /// Measures the runs of dark, light, dark, light and dark along a line through `centre`, taking
+    /// samples `step` apart, none of the runs longer than `limit` samples.
+    ///
+    /// Returns the middle of the centre run and the length of all five runs, in samples, if they are
+    /// in a finder pattern's proportions.
+    fn cross_check(&self, centre: (f32, f32), step: (f32, f32), limit: f32) -> Option<((f32, f32), f32)> {
+        //Sample the middle of pixels, so axis aligned lines don't sit on their edges
+        let centre = (centre.0.floor() + 0.5, centre.1.floor() + 0.5);
+        let at = |k: i32| self.sample((centre.0 + step.0 * k as f32, centre.1 + step.1 * k as f32));
+        if !at(0) {
+            return None;
+        }
+        let limit = limit as i32;
+        let run = |from: i32, sign: i32, dark: bool| {
+            let mut length = 0;
+            while length <= limit && at(from + sign * length) == dark {
+                length += 1;
+            }
+            length
+        };
+
+        //Out from the centre backwards, then forwards
+        let back_centre = run(0, -1, true);
+        let back_light = run(-back_centre, -1, false);
+        let back_dark = run(-(back_centre + back_light), -1, true);
+        let fore_centre = run(1, 1, true);
+        let fore_light = run(1 + fore_centre, 1, false);
+        let fore_dark = run(1 + fore_centre + fore_light, 1, true);
+
+        let counts = [back_dark, back_light, back_centre + fore_centre, fore_light, fore_dark];
+        let counts = counts.map(|count| count as usize);
+        if counts.iter().any(|&count| count == 0 || count > limit as usize) || !is_finder_ratio(&counts) {
+            return None;
+        }
+        let offset = (fore_centre - back_centre + 1) as f32 / 2.0;
+        let middle = (centre.0 + step.0 * offset, centre.1 + step.1 * offset);
+        Some((middle, counts.iter().sum::<usize>() as f32))
+    }
+
+    /// Measures the size of a module of a finder pattern along the line towards another pattern,
+    /// which is more accurate than its size along the rows when the code is turned.
+    fn module_towards(&self, finder: &Finder, other: &Finder) -> f32 {
+        let length = distance(finder, other);
+        let step = ((other.x - finder.x) / length, (other.y - finder.y) / length);
+        match self.cross_check((finder.x, finder.y), step, finder.module * 14.0) {
+            Some((_, total)) => total / 7.0,
+            None => finder.module,
+        }
+    }
+
+    /// Samples and decodes the code with finder patterns at the given corners, trying the versions
+    /// around the one their spacing suggests.
+    fn decode_symbol(&self, top_left: Finder, top_right: Finder, bottom_left: Finder) -> Option<QrResult> {
+        //Measure modules along the sides, as the rows through a turned pattern are longer
+        let module = |a: &Finder, b: &Finder| (self.module_towards(a, b) + self.module_towards(b, a)) / 2.0;
+        let across = distance(&top_left, &top_right) / module(&top_left, &top_right);
+        let down = distance(&top_left, &bottom_left) / module(&top_left, &bottom_left);
+        let estimate = (((across + down) / 2.0 + 7.0 - 17.0) / 4.0).round() as i32;
+
+        //Large codes spell out their version, which is tried first, smaller ones have to be measured
+        let read = (estimate >= 6).then(|| self.read_version(top_left, top_right, bottom_left)).flatten();
+        let mut candidates: Vec<usize> = read.into_iter().collect();
+        for delta in [0, -1, 1, -2, 2] {
+            let version = estimate + delta;
+            if (1..=40).contains(&version) && read != Some(version as usize) {
+                candidates.push(version as usize);
+            }
+        }
+
+        candidates.into_iter().find_map(|version| {
+            let size = version * 4 + 17;
+            let transform = self.locate(top_left, top_right, bottom_left, version)?;
+            let mut grid = vec![false; size * size];
+            for y in 0..size {
+                for x in 0..size {
+                    grid[y * size + x] = self.sample(transform.map(x as f32 + 0.5, y as f32 + 0.5));
+                }
+            }
+
+            let text = decode_grid(&grid, version)?;
+            let edge = size as f32;
+            let corners = [(0.0, 0.0), (edge, 0.0), (edge, edge), (0.0, edge)].map(|(x, y)| transform.map(x, y));
+            Some(QrResult { text, corners })
+        })
+    }
+
+    /// Works out where the modules of a code of `version` lie in the image, using its alignment
+    /// pattern as a fourth point where it has one and can be found.
+    fn locate(&self, top_left: Finder, top_right: Finder, bottom_left: Finder, version: usize) -> Option<Transform> {
+        let size = version as f32 * 4.0 + 17.0;
+        let (near, far) = (3.5, size - 3.5);
+        let affine_corner = (
+            top_right.x + bottom_left.x - top_left.x,
+            top_right.y + bottom_left.y - top_left.y,
+        );
+        let mut points = [
+            ((near, near), (top_left.x, top_left.y)),
+            ((far, near), (top_right.x, top_right.y)),
+            ((near, far), (bottom_left.x, bottom_left.y)),
+            ((far, far), affine_corner),
+        ];
+
+        if version >= 2 {
+            let affine = Transform::from_points(&points)?;
+            let centre = size - 6.5;
+            if let Some(found) = self.find_alignment(&affine, centre) {
+                points[3] = ((centre, centre), found);
+            }
+        }
+        Transform::from_points(&points)
+    }
+
+    /// Searches around where `transform` puts the alignment pattern centred on module `centre` for
+    /// the spot that best matches its 5x5 rings of dark and light. The search reaches a long way,
+    /// as perspective can move the pattern well away from where the three finder patterns suggest.
+    fn find_alignment(&self, transform: &Transform, centre: f32) -> Option<(f32, f32)> {
+        let expected = transform.map(centre, centre);
+        let origin = transform.map(centre - 0.5, centre - 0.5);
+        let right = transform.map(centre + 0.5, centre - 0.5);
+        let down = transform.map(centre - 0.5, centre + 0.5);
+        let across = (right.0 - origin.0, right.1 - origin.1);
+        let along = (down.0 - origin.0, down.1 - origin.1);
+        let module = (across.0.hypot(across.1) + along.0.hypot(along.1)) / 2.0;
+
+        //Positions a quarter of a module apart, or every pixel for small codes
+        let step = (module / 4.0).max(1.0);
+        let radius = (module * 12.0 / step).ceil() as i32;
+        let (mut best, mut matches) = (21, Vec::new());
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let point = (expected.0 + dx as f32 * step, expected.1 + dy as f32 * step);
+                let mut score = 0;
+                for v in -2..=2i32 {
+                    for u in -2..=2i32 {
+                        let sample = (
+                            point.0 + across.0 * u as f32 + along.0 * v as f32,
+                            point.1 + across.1 * u as f32 + along.1 * v as f32,
+                        );
+                        score += (self.sample(sample) == (u.abs().max(v.abs()) != 1)) as u32;
+                    }
+                }
+                if score > best {
+                    (best, matches) = (score, Vec::new());
+                }
+                if score == best {
+                    matches.push(point);
+                }
+            }
+        }
+
+        //The pattern matches over a spot about a module wide, so take the middle of the spot nearest
+        //to where it was expected
+        let offset = |point: &(f32, f32)| (point.0 - expected.0).hypot(point.1 - expected.1);
+        let nearest = *matches.iter().min_by(|a, b| offset(a).total_cmp(&offset(b)))?;
+        let spot: Vec<_> = matches
+            .into_iter()
+            .filter(|point| (point.0 - nearest.0).hypot(point.1 - nearest.1) <= module)
+            .collect();
+        let total = spot.iter().fold((0.0, 0.0), |total, point| (total.0 + point.0, total.1 + point.1));
+        Some((total.0 / spot.len() as f32, total.1 / spot.len() as f32))
+    }
+
+    /// Reads the version from the two copies of it beside the top right and bottom left finder
+    /// patterns of a code of version 7 or over, measuring from the patterns themselves.
+    fn read_version(&self, top_left: Finder, top_right: Finder, bottom_left: Finder) -> Option<usize> {
+        let unit = |to: &Finder| {
+            let length = distance(&top_left, to);
+            ((to.x - top_left.x) / length, (to.y - top_left.y) / length)
+        };
+        let (across, down) = (unit(&top_right), unit(&bottom_left));
+        let read = |finder: &Finder, transpose: bool| {
+            (0..18).fold(0u32, |bits, i| {
+                let (mut u, mut v) = ((i % 3) as f32 - 7.0, (i / 3) as f32 - 3.0);
+                if transpose {
+                    (u, v) = (v, u);
+                }
+                let point = (
+                    finder.x + (across.0 * u + down.0 * v) * finder.module,
+                    finder.y + (across.1 * u + down.1 * v) * finder.module,
+                );
+                bits | (self.sample(point) as u32) << i
+            })
+        };
+
+        let copies = [read(&top_right, false), read(&bottom_left, true)];
+        (7..=40).find(|&version| {
+            copies.iter().any(|&bits| (bits ^ version_bits(version)).count_ones() <= 3)
+        })
+    }
+}
+
+/// Returns `true` if five runs are in the 1:1:3:1:1 proportions of a finder pattern, allowing each
+/// to be off by half.
+fn is_finder_ratio(counts: &[usize; 5]) -> bool {
+    let total: usize = counts.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f32 / 7.0;
+    let tolerance = module / 2.0;
+    counts.iter().zip([1.0, 1.0, 3.0, 1.0, 1.0]).all(|(&count, expected)| {
+        (count as f32 - module * expected).abs() < tolerance * expected
+    })
+}
+
+/// Adds a finder pattern to those found, averaging it into one found before at the same spot.
+fn merge_finder(finders: &mut Vec<Finder>, finder: Finder) {
+    let same = finders.iter_mut().find(|other| {
+        (other.x - finder.x).abs() <= other.module
+            && (other.y - finder.y).abs() <= other.module
+            && (other.module - finder.module).abs() <= other.module.max(1.0)
+    });
+    match same {
+        Some(other) => {
+            let hits = other.hits as f32;
+            other.x = (other.x * hits + finder.x) / (hits + 1.0);
+            other.y = (other.y * hits + finder.y) / (hits + 1.0);
+            other.module = (other.module * hits + finder.module) / (hits + 1.0);
+            other.hits += 1;
+        }
+        None => finders.push(finder),
+    }
+}
+
+/// Pairs finder patterns up into the three corners of codes: top left, top right and bottom left.
+///
+/// Three patterns make a code when they are about the same size and lie on the corners of a right
+/// isosceles triangle. The best fitting triangles are taken first, each pattern used at most once.
+fn group_finders(finders: &[Finder]) -> Vec<[Finder; 3]> {
+    //A pattern found on a single row is more likely noise than a code
+    let mut finders: Vec<Finder> = finders.iter().copied().filter(|finder| finder.hits >= 2).collect();
+    finders.sort_by_key(|finder| std::cmp::Reverse(finder.hits));
+    finders.truncate(MAX_FINDERS);
+
+    let mut triangles = Vec::new();
+    for a in 0..finders.len() {
+        for b in a + 1..finders.len() {
+            for c in b + 1..finders.len() {
+                if let Some((score, corners)) = fit_triangle([finders[a], finders[b], finders[c]]) {
+                    triangles.push((score, [a, b, c], corners));
+                }
+            }
+        }
+    }
+    triangles.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut used = vec![false; finders.len()];
+    let mut codes = Vec::new();
+    for (_, indices, corners) in triangles {
+        if indices.iter().any(|&index| used[index]) {
+            continue;
+        }
+        indices.iter().for_each(|&index| used[index] = true);
+        codes.push(corners);
+    }
+    codes
+}
+
+/// Scores how well three finder patterns fit the corners of a code, lower being better, and puts
+/// them in order: top left, top right and bottom left as the code reads.
+fn fit_triangle(finders: [Finder; 3]) -> Option<(f32, [Finder; 3])> {
+    let smallest = finders.iter().map(|finder| finder.module).fold(f32::MAX, f32::min);
+    let largest = finders.iter().map(|finder| finder.module).fold(0.0, f32::max);
+    if largest > smallest * 1.6 {
+        return None;
+    }
+
+    //The top left corner is the one opposite the longest side
+    let sides = [1, 2, 0].map(|i| distance(&finders[i], &finders[(i + 1) % 3]));
+    let corner = (0..3).max_by(|&a, &b| sides[a].total_cmp(&sides[b]))?;
+    let top_left = finders[corner];
+    let (mut top_right, mut bottom_left) = (finders[(corner + 1) % 3], finders[(corner + 2) % 3]);
+    let (first, second) = (distance(&top_left, &top_right), distance(&top_left, &bottom_left));
+    let hypotenuse = distance(&top_right, &bottom_left);
+
+    let module = (smallest + largest) / 2.0;
+    let (short, long) = (first.min(second), first.max(second));
+    //Modules measured along the rows of a turned code come out up to 1.4 times too large
+    if long > short * 1.5 || short / module < 9.0 || long / module > 180.0 {
+        return None;
+    }
+    let squareness = (hypotenuse * hypotenuse / (first * first + second * second) - 1.0).abs();
+    if squareness > 0.35 {
+        return None;
+    }
+
+    //Reading clockwise from the top left, with y pointing down the image, comes top right first
+    let cross = (top_right.x - top_left.x) * (bottom_left.y - top_left.y)
+        - (top_right.y - top_left.y) * (bottom_left.x - top_left.x);
+    if cross < 0.0 {
+        (top_right, bottom_left) = (bottom_left, top_right);
+    }
+    Some(((long - short) / long + squareness, [top_left, top_right, bottom_left]))
+}
+
+fn distance(a: &Finder, b: &Finder) -> f32 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+//A position in module coordinates or in pixels
+pub(crate) type Point = (f32, f32);
+
+/// A perspective transform from module coordinates to pixels.
+pub(crate) struct Transform {
+    h: [f32; 8],
+}
+
+impl Transform {
+    /// Fits the transform that takes each of four module coordinates to its pixel, or `None` if
+    /// three of them lie on a line.
+    pub(crate) fn from_points(points: &[(Point, Point); 4]) -> Option<Transform> {
+        //Each point gives two equations in the eight unknowns, solved by Gaussian elimination
+        let mut rows = [[0.0f64; 9]; 8];
+        for (i, &((u, v), (x, y))) in points.iter().enumerate() {
+            let (u, v, x, y) = (u as f64, v as f64, x as f64, y as f64);
+            rows[i * 2] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+            rows[i * 2 + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+        }
+        for column in 0..8 {
+            let pivot = (column..8).max_by(|&a, &b| rows[a][column].abs().total_cmp(&rows[b][column].abs()))?;
+            if rows[pivot][column].abs() < 1e-9 {
+                return None;
+            }
+            rows.swap(column, pivot);
+            let pivot = rows[column];
+            for (index, row) in rows.iter_mut().enumerate() {
+                if index != column {
+                    let factor = row[column] / pivot[column];
+                    for (value, pivot) in row.iter_mut().zip(pivot).skip(column) {
+                        *value -= factor * pivot;
+                    }
+                }
+            }
+        }
+        Some(Transform { h: std::array::from_fn(|i| (rows[i][8] / rows[i][i]) as f32) })
+    }
+
+    pub(crate) fn map(&self, u: f32, v: f32) -> (f32, f32) {
+        let h = &self.h;
+        let w = h[6] * u + h[7] * v + 1.0;
+        ((h[0] * u + h[1] * v + h[2]) / w, (h[3] * u + h[4] * v + h[5]) / w)
+    }
+}
+
+/// Decodes the modules of a code, row by row, returning its text if the format can be read and
+/// every block can be corrected.
+fn decode_grid(grid: &[bool], version: usize) -> Option<String> {
+    let size = version * 4 + 17;
+    let module = |x: usize, y: usize| grid[y * size + x];
+
+    //Both copies of the format are read, and the closest valid format to either is taken
+    let first = (0..15).fold(0u32, |bits, i| {
+        let (x, y) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        bits | (module(x, y) as u32) << i
+    });
+    let second = (0..15).fold(0u32, |bits, i| {
+        let (x, y) = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+        bits | (module(x, y) as u32) << i
+    });
+    let (distance, format) = (0..32)
+        .map(|format| {
+            let bits = format_bits(format);
+            (((first ^ bits).count_ones()).min((second ^ bits).count_ones()), format)
+        })
+        .min()?;
+    if distance > 3 {
+        return None;
+    }
+    let (level, mask) = (FORMAT_LEVELS[(format >> 3) as usize], format & 7);
+
+    //Read the codewords in their two module wide columns, zigzagging up and down from the right
+    let function = function_modules(version);
+    let raw = raw_data_modules(version) / 8;
+    let mut codewords = vec![0u8; raw];
+    let mut bit = 0;
+    let mut right = size - 1;
+    while right >= 1 && bit < raw * 8 {
+        if right == 6 {
+            right = 5;
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vertical in 0..size {
+            let y = if upward { size - 1 - vertical } else { vertical };
+            for x in [right, right - 1] {
+                if function[y * size + x] || bit >= raw * 8 {
+                    continue;
+                }
+                if module(x, y) != is_masked(mask, x, y) {
+                    codewords[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        right = right.saturating_sub(2);
+        if right == 0 {
+            break;
+        }
+    }
+
+    let data = correct_blocks(&codewords, version, level)?;
+    read_segments(&data, version)
+}
+
+/// Splits interleaved codewords into their blocks and corrects each, returning the data codewords
+/// in order.
+fn correct_blocks(codewords: &[u8], version: usize, level: usize) -> Option<Vec<u8>> {
+    let blocks = ERROR_CORRECTION_BLOCKS[level][version] as usize;
+    let ecc = ECC_CODEWORDS_PER_BLOCK[level][version] as usize;
+    let short_blocks = blocks - codewords.len() % blocks;
+    let short_data = codewords.len() / blocks - ecc;
+
+    //The data codewords go one from each block in turn, the long blocks having one more at the end,
+    //followed by the error correction codewords the same way
+    let mut split: Vec<Vec<u8>> = (0..blocks).map(|_| Vec::with_capacity(short_data + 1 + ecc)).collect();
+    let mut next = codewords.iter();
+    for i in 0..=short_data {
+        for (j, block) in split.iter_mut().enumerate() {
+            if i < short_data || j >= short_blocks {
+                block.push(*next.next()?);
+            }
+        }
+    }
+    for _ in 0..ecc {
+        for block in split.iter_mut() {
+            block.push(*next.next()?);
+        }
+    }
+
+    let gf = Galois::new();
+    let mut data = Vec::new();
+    for mut block in split {
+        gf.correct(&mut block, ecc)?;
+        data.extend_from_slice(&block[..block.len() - ecc]);
+    }
+    Some(data)
+}
+
+/// Reads the segments of a code's data bit stream into its text.
+fn read_segments(data: &[u8], version: usize) -> Option<String> {
+    let mut bits = BitReader { data, position: 0 };
+    let size_class = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+
+    let mut text = Vec::new();
+    while bits.remaining() >= 4 {
+        match bits.read(4)? {
+            0 => break,
+            1 => {
+                let mut count = bits.read([10, 12, 14][size_class])? as usize;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = bits.read([0, 4, 7, 10][digits])?;
+                    if value >= 10u32.pow(digits as u32) {
+                        return None;
+                    }
+                    text.extend(format!("{:0width$}", value, width = digits).bytes());
+                    count -= digits;
+                }
+            }
+            2 => {
+                let mut count = bits.read([9, 11, 13][size_class])? as usize;
+                while count >= 2 {
+                    let value = bits.read(11)? as usize;
+                    text.push(*ALPHANUMERIC.get(value / 45)?);
+                    text.push(*ALPHANUMERIC.get(value % 45)?);
+                    count -= 2;
+                }
+                if count == 1 {
+                    text.push(*ALPHANUMERIC.get(bits.read(6)? as usize)?);
+                }
+            }
+            4 => {
+                let count = bits.read([8, 16, 16][size_class])?;
+                for _ in 0..count {
+                    text.push(bits.read(8)? as u8);
+                }
+            }
+            8 => {
+                let count = bits.read([8, 10, 12][size_class])?;
+                for _ in 0..count {
+                    bits.read(13)?;
+                    text.extend("\u{fffd}".bytes());
+                }
+            }
+            //An ECI designator, taken as a hint only as the text is read as UTF-8 where it can be
+            7 => {
+                let first = bits.read(8)?;
+                if first & 0x80 != 0 {
+                    bits.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            //Structured append header, and the FNC1 markers for GS1 and other industry formats
+            3 => {
+                bits.read(16)?;
+            }
+            5 => {}
+            9 => {
+                bits.read(8)?;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(match String::from_utf8(text) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+    })
+}
+
+/// Reads a bit stream most significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, count: usize) -> Option<u32> {
+        if count > self.remaining() {
+            return None;
+        }
+        let value = (0..count).fold(0, |value, i| {
+            let bit = self.position + i;
+            value << 1 | ((self.data[bit / 8] >> (7 - bit % 8)) & 1) as u32
+        });
+        self.position += count;
+        Some(value)
+    }
+}
+
+/// Returns the 15 bits stored for a format, its two level bits and three mask bits followed by
+/// their BCH code, masked so the format is never all light.
+fn format_bits(format: u32) -> u32 {
+    let mut remainder = format;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (format << 10 | remainder) ^ 0x5412
+}
+
+/// Returns the 18 bits stored for a version, the version followed by its BCH code.
+fn version_bits(version: usize) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+    }
+    (version as u32) << 12 | remainder
+}
+
+/// Returns `true` where a data mask pattern flips the module in column `x` of row `y`.
+fn is_masked(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// Returns the centres of the alignment patterns along each axis of a code.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 { 26 } else { (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2 };
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| version * 4 + 10 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Returns the number of modules of a code that hold codewords, including any left over bits.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// Marks the modules of a code that are taken by its finder, timing and alignment patterns and its
+/// format and version information, row by row.
+fn function_modules(version: usize) -> Vec<bool> {
+    let size = version * 4 + 17;
+    let mut function = vec![false; size * size];
+    let mut mark = |x: usize, y: usize, width: usize, height: usize| {
+        for row in y..y + height {
+            function[row * size + x..row * size + x + width].fill(true);
+        }
+    };
+
+    //Finder patterns with their separators and the format information beside them
+    mark(0, 0, 9, 9);
+    mark(size - 8, 0, 8, 9);
+    mark(0, size - 8, 9, 8);
+    //Timing patterns
+    mark(6, 0, 1, size);
+    mark(0, 6, size, 1);
+
+    let positions = alignment_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, &x) in positions.iter().enumerate() {
+        for (j, &y) in positions.iter().enumerate() {
+            if (i, j) != (0, 0) && (i, j) != (0, last) && (i, j) != (last, 0) {
+                mark(x - 2, y - 2, 5, 5);
+            }
+        }
+    }
+
+    if version >= 7 {
+        mark(size - 11, 0, 3, 6);
+        mark(0, size - 11, 6, 3);
+    }
+    function
+}
+
+/// Arithmetic in GF(256) with the QR code polynomial, for Reed-Solomon error correction.
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Galois {
+    fn new() -> Galois {
+        let (mut exp, mut log) = ([0; 512], [0; 256]);
+        let mut value = 1u16;
+        for (i, exp) in exp.iter_mut().enumerate().take(255) {
+            *exp = value as u8;
+            log[value as usize] = i as u8;
+            value <<= 1;
+            if value & 0x100 != 0 {
+                value ^= 0x11d;
+            }
+        }
+        for i in 255..512 {
+            exp[i] = exp[i - 255];
+        }
+        Galois { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + 255 - self.log[b as usize] as usize]
+    }
+
+    /// Returns α raised to `power`, which may be negative.
+    fn pow(&self, power: i32) -> u8 {
+        self.exp[power.rem_euclid(255) as usize]
+    }
+
+    /// Evaluates a polynomial, lowest coefficient first, at `x`.
+    fn eval(&self, polynomial: &[u8], x: u8) -> u8 {
+        polynomial.iter().rev().fold(0, |value, &coefficient| self.mul(value, x) ^ coefficient)
+    }
+
+    /// Corrects a block in place, its last `ecc` codewords being the error correction ones.
+    ///
+    /// Finds the errors with the Berlekamp-Massey algorithm and their values with Forney's, and
+    /// returns `None` if there are more than the block can correct.
+    fn correct(&self, block: &mut [u8], ecc: usize) -> Option<()> {
+        //The block is a polynomial with its first codeword as the highest coefficient
+        let n = block.len();
+        let at = |x: u8| block.iter().fold(0, |value, &coefficient| self.mul(value, x) ^ coefficient);
+        let syndromes: Vec<u8> = (0..ecc).map(|i| at(self.pow(i as i32))).collect();
+        if syndromes.iter().all(|&syndrome| syndrome == 0) {
+            return Some(());
+        }
+
+        //Error locator polynomial, lowest coefficient first
+        let (mut locator, mut previous) = (vec![1u8], vec![1u8]);
+        let (mut errors, mut shift, mut last) = (0, 1, 1u8);
+        for i in 0..ecc {
+            let discrepancy = (1..=errors.min(locator.len() - 1))
+                .fold(syndromes[i], |d, j| d ^ self.mul(locator[j], syndromes[i - j]));
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+            let scale = self.div(discrepancy, last);
+            let mut updated = locator.clone();
+            updated.resize(updated.len().max(previous.len() + shift), 0);
+            for (j, &coefficient) in previous.iter().enumerate() {
+                updated[j + shift] ^= self.mul(scale, coefficient);
+            }
+            if 2 * errors <= i {
+                previous = locator;
+                errors = i + 1 - errors;
+                last = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+            locator = updated;
+        }
+        locator.truncate(errors + 1);
+        if 2 * errors > ecc {
+            return None;
+        }
+
+        //Roots of the locator give the error positions, counted as powers from the last codeword
+        let positions: Vec<usize> =
+            (0..n).filter(|&power| self.eval(&locator, self.pow(-(power as i32))) == 0).collect();
+        if positions.len() != errors {
+            return None;
+        }
+
+        //Error evaluator, the syndromes times the locator, cut off above the syndromes
+        let mut evaluator = vec![0u8; ecc];
+        for (i, &syndrome) in syndromes.iter().enumerate() {
+            for (j, &coefficient) in locator.iter().enumerate().take(ecc - i) {
+                evaluator[i + j] ^= self.mul(syndrome, coefficient);
+            }
+        }
+        //Formal derivative of the locator, which keeps only its odd terms in GF(2^8)
+        let derivative: Vec<u8> =
+            locator.iter().enumerate().skip(1).map(|(i, &c)| if i % 2 == 1 { c } else { 0 }).collect();
+
+        for power in positions {
+            let inverse = self.pow(-(power as i32));
+            let denominator = self.eval(&derivative, inverse);
+            if denominator == 0 {
+                return None;
+            }
+            let magnitude = self.mul(self.pow(power as i32), self.div(self.eval(&evaluator, inverse), denominator));
+            block[n - 1 - power] ^= magnitude;
+        }
+
+        let at = |x: u8| block.iter().fold(0, |value, &coefficient| self.mul(value, x) ^ coefficient);
+        (0..ecc).all(|i| at(self.pow(i as i32)) == 0).then_some(())
+    }
+}
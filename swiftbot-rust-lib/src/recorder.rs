@@ -0,0 +1,510 @@
+use crate::avi;
+use crate::camera::{swap_red_blue, CameraController, CameraError, CapturedFrame, ColorMode, FrameTimer, FrameTiming};
+use crate::convert::{self, Channels};
+use crate::jpeg;
+use std::fs;
+use std::io::BufWriter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+//The recording in progress, if any
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+//Files written by the recording in progress, or the last one once it has stopped
+static SEGMENTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+//Listener told how recordings are getting on, if any
+static PROGRESS: Mutex<Option<ProgressListener>> = Mutex::new(None);
+
+//How often a paused recording checks whether it has been resumed or stopped
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A recording running on a thread of its own.
+struct Recorder {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    timing: Arc<Mutex<FrameTiming>>, //Updated after every frame, for watching the recording live
+    thread: JoinHandle<Result<RecordingStats, CameraError>>,
+}
+
+/// What a finished recording holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordingStats {
+    pub frames: u32,            //Frames written, across every file
+    pub duration: Duration,     //Time spent recording, from the first frame to the stop, not counting pauses
+    pub segments: Vec<PathBuf>, //Files written, in order
+    pub timing: FrameTiming,
+}
+
+/// How far a recording has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordingProgress {
+    pub elapsed: Duration, //Time spent recording so far, not counting pauses
+    pub frames: u32,       //Frames written, across every file
+    pub bytes: u64,        //Bytes written, across every file
+}
+
+/// Called with the progress of a background recording, from the recording's own thread.
+pub type ProgressCallback = Box<dyn FnMut(&RecordingProgress) + Send>;
+
+/// How often a progress callback is called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressInterval {
+    Frames(u32),      //Every time this many more frames have been written
+    Period(Duration), //With the first frame written once this much more recording time has passed
+}
+
+/// A progress callback and when to call it.
+struct ProgressListener {
+    interval: ProgressInterval,
+    callback: Arc<Mutex<ProgressCallback>>, //Shared, so it isn't called with the listener slot locked
+}
+
+/// When a recording closes its file and carries on in a new one.
+///
+/// With neither limit set the whole recording goes into one file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rollover {
+    pub max_bytes: Option<u64>,         //Move on before a file would grow past this size
+    pub max_duration: Option<Duration>, //Move on once a file holds this much recording time
+}
+
+impl Rollover {
+    fn enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_duration.is_some()
+    }
+}
+
+/// How much a recording shrinks frames before encoding them, e.g. to keep the camera at the
+/// resolution its auto-exposure works best at while saving space and encoding time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordScale {
+    #[default]
+    Full,
+    Half,    //Half the width and height, e.g. 320x240 from 640x480
+    Quarter, //A quarter of the width and height
+}
+
+impl RecordScale {
+    /// Returns how many times smaller each side of a frame becomes.
+    pub fn factor(self) -> u32 {
+        match self {
+            RecordScale::Full => 1,
+            RecordScale::Half => 2,
+            RecordScale::Quarter => 4,
+        }
+    }
+}
+
+/// Buffers a recording reuses for every frame, so it doesn't allocate per frame.
+struct FrameBuffers {
+    frame: CapturedFrame,
+    scaled: Vec<u8>, //The frame shrunk by the recording scale
+    encoder: jpeg::Encoder,
+    encoded: Vec<u8>,
+}
+
+/// Starts recording the camera to a Motion JPEG AVI file at `path` in the background, returning
+/// straight away.
+///
+/// Raw frames are encoded at `quality`, or the configured JPEG quality if it is `None`, and the file
+/// plays back at the configured frame rate. Frames are captured through `camera`, taking the lock for
+/// one frame at a time, so `captureImage`, the MJPEG server and other users of the camera keep
+/// working during the recording, taking turns with it for frames. The camera is opened if needed and
+/// left running. Call [`stop_background_recording`] to finish the file.
+///
+/// If `rollover` sets a limit, the recording is split into numbered files next to `path` instead,
+/// e.g. `capture_0001.avi`, `capture_0002.avi` and so on for `capture.avi`. A file is finished as
+/// soon as the next frame would take it past a limit, so every file is playable on its own and no
+/// frame is split between them. A file always holds at least one frame, even one over the size limit.
+///
+/// With a `scale` other than [`RecordScale::Full`] each frame is shrunk by averaging blocks of pixels
+/// before it is encoded, and the file gives the shrunk size. The camera, and every other user of it,
+/// keeps getting full size frames. Shrinking needs raw pixels, so it can't be done in JPEG mode.
+///
+/// # Errors
+///
+/// Returns [`CameraError::RecordingRunning`] if a recording is already in progress,
+/// [`CameraError::FormatUnsupported`] if the camera is in JPEG mode and `scale` shrinks frames, or the
+/// error from validating the arguments or creating the first file.
+pub fn start_background_recording(
+    camera: &'static Mutex<CameraController>,
+    path: &str,
+    quality: Option<u8>,
+    rollover: Rollover,
+    scale: RecordScale,
+) -> Result<(), CameraError> {
+    let mut recorder = RECORDER.lock().unwrap_or_else(PoisonError::into_inner);
+    if recorder.is_some() {
+        return Err(CameraError::RecordingRunning);
+    }
+    if let Some(quality) = quality {
+        if !(1..=100).contains(&quality) {
+            return Err(CameraError::InvalidArgument(format!(
+                "JPEG quality must be between 1 and 100, got {}",
+                quality
+            )));
+        }
+    }
+    if rollover.max_bytes == Some(0) || rollover.max_duration == Some(Duration::ZERO) {
+        return Err(CameraError::InvalidArgument(String::from("Rollover limits must be greater than zero")));
+    }
+
+    let (fps, mode) = {
+        let controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+        (controller.config().fps, controller.color_mode())
+    };
+    if scale != RecordScale::Full && mode == ColorMode::Jpeg {
+        return Err(shrink_unsupported());
+    }
+    let first = match rollover.enabled() {
+        true => segment_path(Path::new(path), 1),
+        false => PathBuf::from(path),
+    };
+    let video = create_segment(&first, fps)?;
+    *SEGMENTS.lock().unwrap_or_else(PoisonError::into_inner) = vec![first];
+    let output = Output { video, path: PathBuf::from(path), rollover, fps, number: 1, frames: 0, bytes: 0 };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let paused = Arc::new(AtomicBool::new(false));
+    let timing = Arc::new(Mutex::new(FrameTiming::default()));
+    let thread = {
+        let (stop, paused, timing) = (Arc::clone(&stop), Arc::clone(&paused), Arc::clone(&timing));
+        thread::spawn(move || record(camera, output, quality, scale, &stop, &paused, &timing))
+    };
+
+    *recorder = Some(Recorder { stop, paused, timing, thread });
+    Ok(())
+}
+
+/// Stops the recording started by [`start_background_recording`], waiting for the file to be
+/// finished and synced to disk. A paused recording is finished as it is.
+///
+/// # Errors
+///
+/// Returns [`CameraError::NotRecording`] if no recording is in progress. If a capture failed part
+/// way through, the file is still finished with the frames recorded up to then and the capture error
+/// is returned. [`background_recording_segments`] still lists the files written.
+pub fn stop_background_recording() -> Result<RecordingStats, CameraError> {
+    let recorder = RECORDER.lock().unwrap_or_else(PoisonError::into_inner).take();
+    let recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return Err(CameraError::NotRecording),
+    };
+
+    recorder.stop.store(true, Ordering::SeqCst);
+    recorder
+        .thread
+        .join()
+        .unwrap_or_else(|_| Err(CameraError::Other(String::from("The recording thread panicked"))))
+}
+
+/// Returns `true` while a recording started by [`start_background_recording`] is in progress.
+pub fn is_background_recording() -> bool {
+    RECORDER.lock().unwrap_or_else(PoisonError::into_inner).is_some()
+}
+
+/// Returns the files written by the recording in progress, in order, the last of them still being
+/// written. Once the recording has stopped, returns the files it wrote until the next one starts.
+pub fn background_recording_segments() -> Vec<PathBuf> {
+    SEGMENTS.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Returns how the recording started by [`start_background_recording`] is keeping up with the
+/// camera so far, e.g. to warn that the robot is too loaded to record at the full frame rate, or
+/// `None` if no recording is in progress.
+pub fn background_recording_timing() -> Option<FrameTiming> {
+    let recorder = RECORDER.lock().unwrap_or_else(PoisonError::into_inner);
+    recorder.as_ref().map(|recorder| *recorder.timing.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Sets the callback told how background recordings are getting on, e.g. to drive a recording
+/// timer in a UI, or stops calling it if `listener` is `None`.
+///
+/// The callback is called from the recording thread, after a frame has been written, whenever
+/// `interval` has passed since it was last called in the same recording. It holds up the recording
+/// while it runs, so it should return quickly. Replacing the callback during a recording keeps to
+/// the same schedule, and the callback may replace or clear itself.
+///
+/// # Errors
+///
+/// Returns [`CameraError::InvalidArgument`] if the interval is zero.
+pub fn set_background_recording_progress(
+    listener: Option<(ProgressInterval, ProgressCallback)>,
+) -> Result<(), CameraError> {
+    let listener = match listener {
+        Some((ProgressInterval::Frames(0) | ProgressInterval::Period(Duration::ZERO), _)) => {
+            return Err(CameraError::InvalidArgument(String::from("Progress intervals must be greater than zero")));
+        }
+        Some((interval, callback)) => Some(ProgressListener { interval, callback: Arc::new(Mutex::new(callback)) }),
+        None => None,
+    };
+    *PROGRESS.lock().unwrap_or_else(PoisonError::into_inner) = listener;
+    Ok(())
+}
+
+/// Pauses or resumes the recording started by [`start_background_recording`].
+///
+/// While paused no frames are captured or written and the camera is left to other users, but the
+/// file stays open, so resuming carries on in the same file. The frames either side of a pause play
+/// back one after the other, without a frozen gap. Pausing a paused recording, or resuming a running
+/// one, does nothing.
+///
+/// # Errors
+///
+/// Returns [`CameraError::NotRecording`] if no recording is in progress.
+pub fn set_background_recording_paused(paused: bool) -> Result<(), CameraError> {
+    match RECORDER.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(recorder) => {
+            recorder.paused.store(paused, Ordering::SeqCst);
+            Ok(())
+        }
+        None => Err(CameraError::NotRecording),
+    }
+}
+
+/// Returns `true` if the recording started by [`start_background_recording`] is paused, or `None` if
+/// no recording is in progress.
+pub fn is_background_recording_paused() -> Option<bool> {
+    let recorder = RECORDER.lock().unwrap_or_else(PoisonError::into_inner);
+    recorder.as_ref().map(|recorder| recorder.paused.load(Ordering::SeqCst))
+}
+
+/// Captures frames into `output` until `stop` is set or a capture fails, then finishes the file.
+/// Nothing is captured while `paused` is set. `timing` is kept up to date as frames are written.
+fn record(
+    camera: &Mutex<CameraController>,
+    mut output: Output,
+    quality: Option<u8>,
+    scale: RecordScale,
+    stop: &AtomicBool,
+    paused: &AtomicBool,
+    timing: &Mutex<FrameTiming>,
+) -> Result<RecordingStats, CameraError> {
+    let mut buffers = FrameBuffers {
+        frame: CapturedFrame::default(),
+        scaled: Vec::new(),
+        encoder: jpeg::Encoder::new(),
+        encoded: Vec::new(),
+    };
+    let mut timer = FrameTimer::new(output.fps);
+
+    let mut recorded = Duration::ZERO;       //Time spent recording before the current stretch
+    let mut stretch: Option<Instant> = None; //When the current stretch between pauses started
+    let mut file_start = Duration::ZERO;     //Recording time when the current file was started
+    let mut reported = (Duration::ZERO, 0);  //Recording time and frames when progress was last reported
+    let mut result = Ok(());
+    while !stop.load(Ordering::SeqCst) {
+        if paused.load(Ordering::SeqCst) {
+            if let Some(start) = stretch.take() {
+                recorded += start.elapsed();
+            }
+            timer.pause();
+            thread::sleep(PAUSE_POLL_INTERVAL);
+            continue;
+        }
+
+        let (jpeg, width, height, processing) = match capture(camera, &mut buffers, quality, scale, &mut timer) {
+            Ok(captured) => captured,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+
+        let start = *stretch.get_or_insert_with(Instant::now);
+        let elapsed = recorded + start.elapsed();
+
+        //Move on to the next file between frames, so each file can be played on its own
+        if output.is_full(jpeg.len(), elapsed - file_start) {
+            if let Err(e) = output.roll_over() {
+                result = Err(e);
+                break;
+            }
+            file_start = elapsed;
+        }
+
+        if let Err(e) = output.video.write_frame(jpeg, width, height) {
+            result = Err(e.into());
+            break;
+        }
+        timer.written(processing);
+        *timing.lock().unwrap_or_else(PoisonError::into_inner) = timer.timing();
+
+        let progress = RecordingProgress {
+            elapsed,
+            frames: output.frames + output.video.frame_count(),
+            bytes: output.bytes + output.video.size(),
+        };
+        report_progress(&mut reported, &progress);
+    }
+
+    let stats = RecordingStats {
+        frames: output.frames + output.video.frame_count(),
+        duration: recorded + stretch.map_or(Duration::ZERO, |start| start.elapsed()),
+        segments: background_recording_segments(),
+        timing: timer.timing(),
+    };
+    finish_segment(output.video)?;
+    result.map(|_| stats)
+}
+
+/// Captures the next frame through `camera` as JPEG data shrunk by `scale`, returning it with its
+/// size and when its processing started.
+///
+/// Only the capture itself holds the camera. At full size the frame is encoded during the capture.
+/// Otherwise the raw frame is copied out under the lock and shrunk and encoded once it is released,
+/// so a new frame arriving can't change the one being shrunk.
+fn capture<'a>(
+    camera: &Mutex<CameraController>,
+    buffers: &'a mut FrameBuffers,
+    quality: Option<u8>,
+    scale: RecordScale,
+    timer: &mut FrameTimer,
+) -> Result<(&'a [u8], u32, u32, Instant), CameraError> {
+    let mut controller = camera.lock().unwrap_or_else(PoisonError::into_inner);
+    if scale == RecordScale::Full {
+        let FrameBuffers { frame, encoder, encoded, .. } = buffers;
+        let jpeg = controller.capture_jpeg_into(frame, encoder, encoded, quality)?;
+        let (width, height) = controller.frame_size();
+        drop(controller);
+        //The frame is encoded during the capture, so only writing it counts as processing here
+        return Ok((jpeg, width, height, timer.captured()));
+    }
+
+    controller.capture_frame_into(&mut buffers.frame)?;
+    let (mode, quality) = (controller.color_mode(), quality.unwrap_or(controller.jpeg_quality()));
+    drop(controller);
+    let processing = timer.captured();
+
+    let channels = match mode {
+        ColorMode::Jpeg => return Err(shrink_unsupported()),
+        ColorMode::Grayscale => Channels::Gray,
+        ColorMode::Rgb | ColorMode::Bgr => Channels::Rgb,
+    };
+    let frame = &buffers.frame;
+    let (width, height) =
+        convert::shrink(&frame.data, frame.width, frame.height, channels, scale.factor(), &mut buffers.scaled);
+    if mode == ColorMode::Bgr {
+        //The encoder expects red first
+        swap_red_blue(&mut buffers.scaled);
+    }
+    buffers.encoder.encode_into(&buffers.scaled, width, height, channels, quality, &mut buffers.encoded);
+    Ok((&buffers.encoded, width, height, processing))
+}
+
+fn shrink_unsupported() -> CameraError {
+    CameraError::FormatUnsupported(String::from("Recording at a smaller scale needs a raw color mode"))
+}
+
+/// Calls the progress callback if one is set and its interval has passed since `reported`, the
+/// recording time and frame count it was last called at.
+fn report_progress(reported: &mut (Duration, u32), progress: &RecordingProgress) {
+    let (interval, callback) = match PROGRESS.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(listener) => (listener.interval, Arc::clone(&listener.callback)),
+        None => return,
+    };
+    let due = match interval {
+        ProgressInterval::Frames(frames) => progress.frames - reported.1 >= frames,
+        ProgressInterval::Period(period) => progress.elapsed - reported.0 >= period,
+    };
+    if due {
+        *reported = (progress.elapsed, progress.frames);
+        (callback.lock().unwrap_or_else(PoisonError::into_inner))(progress);
+    }
+}
+
+/// The file a recording is being written to, and where it goes next if it is split.
+struct Output {
+    video: avi::Writer<BufWriter<fs::File>>,
+    path: PathBuf, //Path the recording was started with
+    rollover: Rollover,
+    fps: u32,
+    number: u32, //Number of the file being written, counting from 1
+    frames: u32, //Frames in the files already finished
+    bytes: u64,  //Size of the files already finished
+}
+
+impl Output {
+    /// Returns `true` if the current file, holding `duration` of recording, should be finished
+    /// before a frame of `length` bytes is added to it.
+    fn is_full(&self, length: usize, duration: Duration) -> bool {
+        let too_large = self.rollover.max_bytes.is_some_and(|max| self.video.size_with_frame(length) > max);
+        let too_long = self.rollover.max_duration.is_some_and(|max| duration >= max);
+        self.video.frame_count() > 0 && (too_large || too_long)
+    }
+
+    /// Finishes the current file and carries on in the next numbered one.
+    ///
+    /// The next file is created first, so if that fails the current one is kept and can still be
+    /// finished.
+    fn roll_over(&mut self) -> Result<(), CameraError> {
+        let path = segment_path(&self.path, self.number + 1);
+        let next = create_segment(&path, self.fps)?;
+        let finished = mem::replace(&mut self.video, next);
+        self.number += 1;
+        self.frames += finished.frame_count();
+        SEGMENTS.lock().unwrap_or_else(PoisonError::into_inner).push(path);
+        self.bytes += finish_segment(finished)?;
+        Ok(())
+    }
+}
+
+/// Creates a file and starts an AVI file in it.
+fn create_segment(path: &Path, fps: u32) -> Result<avi::Writer<BufWriter<fs::File>>, CameraError> {
+    let file = fs::File::create(path)
+        .map_err(|e| CameraError::Other(format!("Failed to create {}: {}", path.display(), e)))?;
+    Ok(avi::Writer::new(BufWriter::new(file), fps)?)
+}
+
+/// Writes the index and headers of an AVI file and syncs it to disk, returning its size.
+fn finish_segment(video: avi::Writer<BufWriter<fs::File>>) -> Result<u64, CameraError> {
+    let file = video.finish()?.into_inner().map_err(|e| CameraError::Io(e.into_error()))?;
+    file.sync_all()?;
+    Ok(file.metadata()?.len())
+}
+
+/// Returns the path of the `number`th file of a split recording, e.g. `capture_0002.avi` for the
+/// second file of `capture.avi`.
+fn segment_path(path: &Path, number: u32) -> PathBuf {
+    let stem = path.file_stem().map_or_else(Default::default, |stem| stem.to_string_lossy());
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{:04}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}_{:04}", stem, number),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_start_is_rejected_while_recording() {
+        let camera: &'static Mutex<CameraController> = Box::leak(Box::new(Mutex::new(CameraController::new())));
+        let path = std::env::temp_dir().join("swiftbot_background_recording_test.avi");
+
+        let starts: Vec<_> = (0..2)
+            .map(|_| {
+                let path = path.to_str().unwrap().to_string();
+                thread::spawn(move || {
+                    start_background_recording(camera, &path, None, Rollover::default(), RecordScale::Full)
+                })
+            })
+            .collect();
+        let results: Vec<_> = starts.into_iter().map(|start| start.join().unwrap()).collect();
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results.iter().any(|result| matches!(result, Err(CameraError::RecordingRunning))));
+        assert!(is_background_recording());
+
+        //There is no camera to record from here, so only the stop itself matters
+        let _ = stop_background_recording();
+        assert!(!is_background_recording());
+        assert!(matches!(stop_background_recording(), Err(CameraError::NotRecording)));
+        let _ = fs::remove_file(&path);
+    }
+}
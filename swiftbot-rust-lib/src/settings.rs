@@ -0,0 +1,235 @@
+/// A value in a settings file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// Returns the name of the value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// A `key = value` line from a settings file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub line: usize,   //Line number, starting at 1
+    pub table: String, //Name of the `[table]` the key is in, empty for keys before the first table
+    pub key: String,
+    pub value: Value,
+}
+
+/// Parses a settings file written in a subset of TOML.
+///
+/// Supported are `[table]` headers, bare and quoted keys, basic and literal strings, decimal,
+/// hexadecimal and binary integers, booleans, single-line arrays and `#` comments. Keys may only
+/// appear once per table.
+///
+/// # Arguments
+///
+/// * `text` - The contents of the settings file.
+///
+/// # Returns
+///
+/// The entries in the order they appear in the file, or a message naming the offending line.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table = String::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| format!("line {}: {}", number, message);
+
+        let mut cursor = Cursor::new(line);
+        cursor.skip_whitespace();
+        if cursor.at_end_of_line() {
+            continue;
+        }
+
+        if cursor.eat('[') {
+            cursor.skip_whitespace();
+            table = cursor.key().map_err(error)?;
+            cursor.skip_whitespace();
+            if !cursor.eat(']') {
+                return Err(error("expected `]` after the table name".into()));
+            }
+        } else {
+            let key = cursor.key().map_err(error)?;
+            cursor.skip_whitespace();
+            if !cursor.eat('=') {
+                return Err(error(format!("expected `=` after `{}`", key)));
+            }
+            cursor.skip_whitespace();
+            let value = cursor.value().map_err(error)?;
+
+            if entries.iter().any(|entry| entry.table == table && entry.key == key) {
+                return Err(error(format!("`{}` is set more than once", key)));
+            }
+            entries.push(Entry { line: number, table: table.clone(), key, value });
+        }
+
+        cursor.skip_whitespace();
+        if !cursor.at_end_of_line() {
+            return Err(error("unexpected text after the value".into()));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads tokens from a single line.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Self {
+        Cursor { rest: line }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.rest = &self.rest[expected.len_utf8()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t']);
+    }
+
+    /// Returns `true` if only a comment, if anything, is left on the line.
+    fn at_end_of_line(&self) -> bool {
+        self.rest.is_empty() || self.rest.starts_with('#')
+    }
+
+    /// Takes the longest prefix whose characters match `accept`.
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &'a str {
+        let end = self.rest.find(|c: char| !accept(c)).unwrap_or(self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string(),
+            _ => {
+                let key = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if key.is_empty() {
+                    return Err("expected a key".into());
+                }
+                Ok(key.to_string())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some(_) => {
+                let word = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-');
+                match word {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => parse_integer(word).map(Value::Integer),
+                }
+            }
+            None => Err("expected a value".into()),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.eat('[');
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.skip_whitespace();
+                if self.eat(']') {
+                    return Ok(Value::Array(values));
+                }
+                return Err("expected `,` or `]` in array (arrays must fit on one line)".into());
+            }
+        }
+    }
+
+    /// Reads a basic `"..."` string with escapes, or a literal `'...'` string without.
+    fn string(&mut self) -> Result<String, String> {
+        let literal = self.eat('\'');
+        if !literal {
+            self.eat('"');
+        }
+
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\'' if literal => {
+                    self.rest = &self.rest[index + 1..];
+                    return Ok(value);
+                }
+                '"' if !literal => {
+                    self.rest = &self.rest[index + 1..];
+                    return Ok(value);
+                }
+                '\\' if !literal => match chars.next().map(|(_, c)| c) {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => return Err(format!("unsupported escape `\\{}` in string", other)),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+
+        Err("unterminated string".into())
+    }
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary integer, allowing `_` between digits.
+pub fn parse_integer(word: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid value `{}`", word);
+    let digits = word.replace('_', "");
+
+    let (negative, unsigned) = match digits.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+    };
+    let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(binary) = unsigned.strip_prefix("0b") {
+        (2, binary)
+    } else {
+        (10, unsigned)
+    };
+
+    if digits.is_empty() || word.starts_with('_') || word.ends_with('_') {
+        return Err(invalid());
+    }
+    let magnitude = i64::from_str_radix(digits, radix).map_err(|_| invalid())?;
+    Ok(if negative { -magnitude } else { magnitude })
+}
@@ -1,3 +1,6 @@
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 /// Clamps a value between a minimum and maximum value.
 ///
 /// # Arguments
@@ -17,4 +20,21 @@ pub fn clamp<T: PartialOrd>(value: T, min: T, max: T) -> T {
     } else {
         value
     }
-}
\ No newline at end of file
+}
+/// Breaks a time down into its date and time of day in the local time zone.
+///
+/// # Arguments
+///
+/// * `time` - The time to break down, times before 1970 are treated as 1970.
+///
+/// # Returns
+///
+/// The broken-down time, with the year counted from 1900 and the month from 0 as in C.
+pub fn local_time(time: SystemTime) -> libc::tm {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()) as libc::time_t;
+    let mut local: libc::tm = unsafe { mem::zeroed() };
+    unsafe {
+        libc::localtime_r(&seconds, &mut local);
+    }
+    local
+}
@@ -0,0 +1,652 @@
+use crate::convert::Rect;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::raw::c_void;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+//ioctl request codes
+const VIDIOC_QUERYCAP: u32 = 0x8068_5600;
+const VIDIOC_REQBUFS: u32 = 0xC014_5608;
+const VIDIOC_G_SELECTION: u32 = 0xC040_565E;
+const VIDIOC_S_SELECTION: u32 = 0xC040_565F;
+const VIDIOC_ENUM_FMT: u32 = 0xC040_5602;
+const VIDIOC_S_CTRL: u32 = 0xC008_561C;
+const VIDIOC_STREAMON: u32 = 0x4004_5612;
+const VIDIOC_STREAMOFF: u32 = 0x4004_5613;
+const VIDIOC_ENCODER_CMD: u32 = 0xC028_564D;
+//The sizes of struct v4l2_format and v4l2_buffer, and so the request codes, differ between 32 and
+//64-bit targets
+const VIDIOC_G_FMT: u32 = 0xC000_5604 | (mem::size_of::<RawFormat>() as u32) << 16;
+const VIDIOC_S_FMT: u32 = 0xC000_5605 | (mem::size_of::<RawFormat>() as u32) << 16;
+const VIDIOC_QUERYBUF: u32 = 0xC000_5609 | (mem::size_of::<RawBuffer>() as u32) << 16;
+const VIDIOC_QBUF: u32 = 0xC000_560F | (mem::size_of::<RawBuffer>() as u32) << 16;
+const VIDIOC_DQBUF: u32 = 0xC000_5611 | (mem::size_of::<RawBuffer>() as u32) << 16;
+
+//Buffer types and memory modes
+const BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+const BUF_TYPE_VIDEO_CAPTURE_MPLANE: u32 = 9;
+const BUF_TYPE_VIDEO_OUTPUT_MPLANE: u32 = 10;
+const MEMORY_MMAP: u32 = 1;
+
+//Buffer flags
+const BUF_FLAG_LAST: u32 = 0x0010_0000;
+
+//Encoder commands
+const ENC_CMD_STOP: u32 = 1;
+
+//Selection targets
+const SEL_TGT_CROP: u32 = 0x0000;
+const SEL_TGT_CROP_DEFAULT: u32 = 0x0001;
+const SEL_TGT_CROP_BOUNDS: u32 = 0x0002;
+
+//Capability flags
+pub const CAP_VIDEO_CAPTURE: u32 = 0x0000_0001;
+pub const CAP_VIDEO_M2M_MPLANE: u32 = 0x0000_4000;
+pub const CAP_STREAMING: u32 = 0x0400_0000;
+const CAP_DEVICE_CAPS: u32 = 0x8000_0000;
+
+/// Mirrors `struct v4l2_capability` from the kernel headers.
+#[repr(C)]
+#[allow(dead_code)] //Filled in by the kernel, not every field is read
+struct RawCapability {
+    driver: [u8; 16],
+    card: [u8; 32],
+    bus_info: [u8; 32],
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+    reserved: [u32; 3],
+}
+
+/// Mirrors `struct v4l2_requestbuffers` from the kernel headers.
+#[repr(C)]
+struct RawRequestBuffers {
+    count: u32,
+    buffer_type: u32,
+    memory: u32,
+    reserved: [u32; 2],
+}
+
+/// Mirrors `struct v4l2_selection` from the kernel headers.
+#[repr(C)]
+struct RawSelection {
+    buffer_type: u32,
+    target: u32,
+    flags: u32,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+    reserved: [u32; 9],
+}
+
+/// Mirrors `struct v4l2_pix_format` from the kernel headers.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)] //Filled in by the kernel, not every field is read
+struct RawPixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    private: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+/// Mirrors `struct v4l2_plane_pix_format` from the kernel headers.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct RawPlanePixFormat {
+    sizeimage: u32,
+    bytesperline: u32,
+    reserved: [u16; 6],
+}
+
+/// Mirrors `struct v4l2_pix_format_mplane` from the kernel headers.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)] //Filled in by the kernel, not every field is read
+struct RawPixFormatMplane {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    colorspace: u32,
+    plane_fmt: [RawPlanePixFormat; 8],
+    num_planes: u8,
+    flags: u8,
+    ycbcr_enc: u8,
+    quantization: u8,
+    xfer_func: u8,
+    reserved: [u8; 7],
+}
+
+/// Mirrors the format union in `struct v4l2_format`, which is pointer-aligned.
+#[repr(C)]
+union RawFormatData {
+    pix: RawPixFormat,
+    pix_mp: RawPixFormatMplane,
+    raw_data: [u8; 200],
+    _align: *const c_void,
+}
+
+/// Mirrors `struct v4l2_format` from the kernel headers.
+#[repr(C)]
+struct RawFormat {
+    buffer_type: u32,
+    fmt: RawFormatData,
+}
+
+/// Mirrors `struct v4l2_fmtdesc` from the kernel headers.
+#[repr(C)]
+#[allow(dead_code)] //Filled in by the kernel, not every field is read
+struct RawFormatDesc {
+    index: u32,
+    buffer_type: u32,
+    flags: u32,
+    description: [u8; 32],
+    pixelformat: u32,
+    mbus_code: u32,
+    reserved: [u32; 3],
+}
+
+/// Mirrors `struct v4l2_control` from the kernel headers.
+#[repr(C)]
+struct RawControl {
+    id: u32,
+    value: i32,
+}
+
+/// Mirrors `struct v4l2_encoder_cmd` from the kernel headers.
+#[repr(C)]
+struct RawEncoderCmd {
+    cmd: u32,
+    flags: u32,
+    data: [u32; 8],
+}
+
+/// Mirrors `struct v4l2_timecode` from the kernel headers.
+#[repr(C)]
+#[allow(dead_code)] //Only passed through to the kernel
+struct RawTimecode {
+    timecode_type: u32,
+    flags: u32,
+    frames: u8,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    userbits: [u8; 4],
+}
+
+/// Mirrors the memory union in `struct v4l2_plane`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+union RawPlaneMemory {
+    mem_offset: u32,
+    userptr: libc::c_ulong,
+    fd: i32,
+}
+
+/// Mirrors `struct v4l2_plane` from the kernel headers.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawPlane {
+    bytesused: u32,
+    length: u32,
+    m: RawPlaneMemory,
+    data_offset: u32,
+    reserved: [u32; 11],
+}
+
+/// Mirrors the memory union in `struct v4l2_buffer`.
+#[repr(C)]
+union RawBufferMemory {
+    offset: u32,
+    userptr: libc::c_ulong,
+    planes: *mut RawPlane,
+    fd: i32,
+}
+
+/// Mirrors `struct v4l2_buffer` from the kernel headers.
+#[repr(C)]
+#[allow(dead_code)] //Filled in by the kernel, not every field is read
+struct RawBuffer {
+    index: u32,
+    buffer_type: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    timecode: RawTimecode,
+    sequence: u32,
+    memory: u32,
+    m: RawBufferMemory,
+    length: u32,
+    reserved2: u32,
+    request_fd: i32,
+}
+
+/// The capture format currently programmed into a device, as reported by `VIDIOC_G_FMT`.
+#[derive(Clone, Copy, Debug)]
+pub struct PixFormat {
+    pub width: u32,
+    pub height: u32,
+    pub fourcc: [u8; 4],
+    pub bytes_per_line: u32, //Row length in bytes, including any padding the driver adds
+    pub size_image: u32,     //Bytes needed to hold one frame
+}
+
+/// One of the two buffer queues of a memory-to-memory device such as a hardware encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum M2mQueue {
+    Output,  //Frames the application hands to the device
+    Capture, //Frames the device hands back once it has processed them
+}
+
+impl M2mQueue {
+    fn buffer_type(self) -> u32 {
+        match self {
+            M2mQueue::Output => BUF_TYPE_VIDEO_OUTPUT_MPLANE,
+            M2mQueue::Capture => BUF_TYPE_VIDEO_CAPTURE_MPLANE,
+        }
+    }
+}
+
+/// A buffer dequeued from a memory-to-memory device.
+#[derive(Clone, Copy, Debug)]
+pub struct DequeuedBuffer {
+    pub index: u32,
+    pub offset: u32, //Start of the data within the buffer
+    pub length: u32, //Bytes of data after `offset`
+    pub last: bool,  //The device has no more buffers to return after this one
+}
+
+/// Driver and card information reported by `VIDIOC_QUERYCAP`.
+pub struct Capability {
+    pub driver: String,
+    pub card: String,
+    pub bus_info: String,
+    pub version: u32,      //Driver version, packed as major << 16 | minor << 8 | patch
+    pub capabilities: u32, //Capabilities of the physical device as a whole
+    pub device_caps: u32,  //Capabilities of this particular device node
+}
+
+impl Capability {
+    /// Returns `true` if the device node supports the given capability flag.
+    pub fn supports(&self, flag: u32) -> bool {
+        self.device_caps & flag != 0
+    }
+}
+
+/// Returns the numbers of the `/dev/videoN` device nodes, in ascending order.
+pub fn device_indices() -> io::Result<Vec<u32>> {
+    let mut indices: Vec<u32> = fs::read_dir("/dev")?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry.file_name().to_str()
+                .and_then(|name| name.strip_prefix("video"))
+                .and_then(|index| index.parse().ok())
+        })
+        .collect();
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+/// Opens a V4L2 device node for ioctls without starting a stream on it.
+///
+/// Opening a node doesn't interfere with a stream running on another handle to the same device.
+pub fn open(path: &str) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(path)
+}
+
+/// Opens a V4L2 device node so that dequeueing a buffer fails with `WouldBlock` instead of waiting.
+pub fn open_nonblocking(path: &str) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).custom_flags(libc::O_NONBLOCK).open(path)
+}
+
+/// Queries the driver name, card name, bus info and capabilities of a V4L2 device node.
+pub fn query_capability(device: &File) -> io::Result<Capability> {
+    let mut raw = RawCapability {
+        driver: [0; 16],
+        card: [0; 32],
+        bus_info: [0; 32],
+        version: 0,
+        capabilities: 0,
+        device_caps: 0,
+        reserved: [0; 3],
+    };
+
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_QUERYCAP as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    //Older drivers don't fill in per-node capabilities
+    let device_caps = if raw.capabilities & CAP_DEVICE_CAPS != 0 {
+        raw.device_caps
+    } else {
+        raw.capabilities
+    };
+
+    Ok(Capability {
+        driver: c_string(&raw.driver),
+        card: c_string(&raw.card),
+        bus_info: c_string(&raw.bus_info),
+        version: raw.version,
+        capabilities: raw.capabilities,
+        device_caps,
+    })
+}
+
+/// Reads the capture format currently programmed into a device node.
+///
+/// The format is shared by every handle to the device, so this reflects a stream started elsewhere.
+pub fn query_format(device: &File) -> io::Result<PixFormat> {
+    let mut raw = RawFormat {
+        buffer_type: BUF_TYPE_VIDEO_CAPTURE,
+        fmt: RawFormatData { raw_data: [0; 200] },
+    };
+
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_G_FMT as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    //The kernel fills in the pix member for capture buffers
+    let pix = unsafe { raw.fmt.pix };
+    Ok(PixFormat {
+        width: pix.width,
+        height: pix.height,
+        fourcc: pix.pixelformat.to_le_bytes(),
+        bytes_per_line: pix.bytesperline,
+        size_image: pix.sizeimage,
+    })
+}
+
+/// Returns `true` if a queue of a memory-to-memory device supports the given pixel format.
+pub fn supports_m2m_format(device: &File, queue: M2mQueue, fourcc: &[u8; 4]) -> bool {
+    let pixelformat = u32::from_le_bytes(*fourcc);
+    (0..).map_while(|index| {
+        let mut raw = RawFormatDesc {
+            index,
+            buffer_type: queue.buffer_type(),
+            flags: 0,
+            description: [0; 32],
+            pixelformat: 0,
+            mbus_code: 0,
+            reserved: [0; 3],
+        };
+        let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_ENUM_FMT as _, &mut raw) };
+        (result >= 0).then_some(raw.pixelformat)
+    })
+    .any(|format| format == pixelformat)
+}
+
+/// Programs a single-plane format into a queue of a memory-to-memory device.
+///
+/// Returns the format the driver actually chose, which may have a padded row length or a different
+/// size. A `size_image` of 0 leaves the buffer size up to the driver.
+pub fn set_m2m_format(
+    device: &File,
+    queue: M2mQueue,
+    width: u32,
+    height: u32,
+    fourcc: &[u8; 4],
+    size_image: u32,
+) -> io::Result<PixFormat> {
+    let mut raw = RawFormat {
+        buffer_type: queue.buffer_type(),
+        fmt: RawFormatData { raw_data: [0; 200] },
+    };
+    let mut pix = unsafe { raw.fmt.pix_mp };
+    pix.width = width;
+    pix.height = height;
+    pix.pixelformat = u32::from_le_bytes(*fourcc);
+    pix.num_planes = 1;
+    pix.plane_fmt[0].sizeimage = size_image;
+    raw.fmt.pix_mp = pix;
+
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_S_FMT as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let pix = unsafe { raw.fmt.pix_mp };
+    let plane = pix.plane_fmt[0];
+    Ok(PixFormat {
+        width: pix.width,
+        height: pix.height,
+        fourcc: pix.pixelformat.to_le_bytes(),
+        bytes_per_line: plane.bytesperline,
+        size_image: plane.sizeimage,
+    })
+}
+
+/// Sets a control on a device node, such as the bitrate of a hardware encoder.
+pub fn set_control(device: &File, id: u32, value: i32) -> io::Result<()> {
+    let mut raw = RawControl { id, value };
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_S_CTRL as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sets the sensor region the driver captures from, using the selection API.
+///
+/// Returns the region the driver actually chose, which may be adjusted to its alignment rules.
+pub fn set_crop(device: &File, rect: Rect) -> io::Result<Rect> {
+    let mut raw = RawSelection::new(SEL_TGT_CROP);
+    raw.left = rect.x as i32;
+    raw.top = rect.y as i32;
+    raw.width = rect.width;
+    raw.height = rect.height;
+
+    selection_ioctl(device, VIDIOC_S_SELECTION, &mut raw)?;
+    Ok(raw.rect())
+}
+
+/// Returns the sensor area the driver can crop from.
+pub fn crop_bounds(device: &File) -> io::Result<Rect> {
+    let mut raw = RawSelection::new(SEL_TGT_CROP_BOUNDS);
+    selection_ioctl(device, VIDIOC_G_SELECTION, &mut raw)?;
+    Ok(raw.rect())
+}
+
+/// Resets the capture region to the driver's default, normally the full sensor.
+pub fn reset_crop(device: &File) -> io::Result<Rect> {
+    let mut raw = RawSelection::new(SEL_TGT_CROP_DEFAULT);
+    selection_ioctl(device, VIDIOC_G_SELECTION, &mut raw)?;
+    set_crop(device, raw.rect())
+}
+
+impl RawSelection {
+    fn new(target: u32) -> Self {
+        RawSelection {
+            buffer_type: BUF_TYPE_VIDEO_CAPTURE,
+            target,
+            flags: 0,
+            left: 0,
+            top: 0,
+            width: 0,
+            height: 0,
+            reserved: [0; 9],
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        Rect {
+            x: self.left.max(0) as u32,
+            y: self.top.max(0) as u32,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+/// Issues a selection ioctl for capture buffers.
+fn selection_ioctl(device: &File, request: u32, raw: &mut RawSelection) -> io::Result<()> {
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), request as _, raw as *mut RawSelection) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Asks the driver for `count` mmap capture buffers and returns how many it would grant.
+///
+/// The buffers are released again straight away, so this must be called while no stream is running
+/// on the device.
+pub fn probe_buffer_count(device: &File, count: u32) -> io::Result<u32> {
+    let granted = request_buffers(device, BUF_TYPE_VIDEO_CAPTURE, count)?;
+    request_buffers(device, BUF_TYPE_VIDEO_CAPTURE, 0)?;
+    Ok(granted)
+}
+
+/// Allocates `count` mmap buffers on a queue of a memory-to-memory device, returning how many the
+/// driver granted.
+pub fn request_m2m_buffers(device: &File, queue: M2mQueue, count: u32) -> io::Result<u32> {
+    request_buffers(device, queue.buffer_type(), count)
+}
+
+/// Returns the mmap offset and length of a buffer on a queue of a memory-to-memory device.
+pub fn query_m2m_buffer(device: &File, queue: M2mQueue, index: u32) -> io::Result<(u32, u32)> {
+    let mut plane = RawPlane::default();
+    let mut raw = RawBuffer::new(queue, index, &mut plane);
+    buffer_ioctl(device, VIDIOC_QUERYBUF, &mut raw)?;
+    Ok((unsafe { plane.m.mem_offset }, plane.length))
+}
+
+/// Hands a buffer holding `bytes_used` bytes of data to a memory-to-memory device.
+pub fn queue_m2m_buffer(device: &File, queue: M2mQueue, index: u32, bytes_used: u32) -> io::Result<()> {
+    let mut plane = RawPlane { bytesused: bytes_used, ..RawPlane::default() };
+    let mut raw = RawBuffer::new(queue, index, &mut plane);
+    buffer_ioctl(device, VIDIOC_QBUF, &mut raw)
+}
+
+/// Takes back a buffer the device has finished with.
+///
+/// Fails with `WouldBlock` if none is ready and the device was opened with [`open_nonblocking`].
+pub fn dequeue_m2m_buffer(device: &File, queue: M2mQueue) -> io::Result<DequeuedBuffer> {
+    let mut plane = RawPlane::default();
+    let mut raw = RawBuffer::new(queue, 0, &mut plane);
+    buffer_ioctl(device, VIDIOC_DQBUF, &mut raw)?;
+    Ok(DequeuedBuffer {
+        index: raw.index,
+        offset: plane.data_offset,
+        length: plane.bytesused.saturating_sub(plane.data_offset),
+        last: raw.flags & BUF_FLAG_LAST != 0,
+    })
+}
+
+/// Starts streaming on a queue of a memory-to-memory device.
+pub fn stream_on(device: &File, queue: M2mQueue) -> io::Result<()> {
+    stream_ioctl(device, VIDIOC_STREAMON, queue)
+}
+
+/// Stops streaming on a queue of a memory-to-memory device, returning all of its buffers.
+pub fn stream_off(device: &File, queue: M2mQueue) -> io::Result<()> {
+    stream_ioctl(device, VIDIOC_STREAMOFF, queue)
+}
+
+/// Asks an encoder to finish the frames it has been given, after which the last encoded buffer it
+/// returns is flagged as [`DequeuedBuffer::last`].
+pub fn stop_encoder(device: &File) -> io::Result<()> {
+    let mut raw = RawEncoderCmd { cmd: ENC_CMD_STOP, flags: 0, data: [0; 8] };
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_ENCODER_CMD as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Default for RawPlane {
+    fn default() -> Self {
+        RawPlane {
+            bytesused: 0,
+            length: 0,
+            m: RawPlaneMemory { mem_offset: 0 },
+            data_offset: 0,
+            reserved: [0; 11],
+        }
+    }
+}
+
+impl RawBuffer {
+    /// Describes a single-plane mmap buffer, with `plane` receiving the plane details.
+    fn new(queue: M2mQueue, index: u32, plane: &mut RawPlane) -> Self {
+        RawBuffer {
+            index,
+            buffer_type: queue.buffer_type(),
+            bytesused: 0,
+            flags: 0,
+            field: 0,
+            timestamp: libc::timeval { tv_sec: 0, tv_usec: 0 },
+            timecode: RawTimecode {
+                timecode_type: 0,
+                flags: 0,
+                frames: 0,
+                seconds: 0,
+                minutes: 0,
+                hours: 0,
+                userbits: [0; 4],
+            },
+            sequence: 0,
+            memory: MEMORY_MMAP,
+            m: RawBufferMemory { planes: plane },
+            length: 1,
+            reserved2: 0,
+            request_fd: 0,
+        }
+    }
+}
+
+/// Issues a buffer ioctl.
+fn buffer_ioctl(device: &File, request: u32, raw: &mut RawBuffer) -> io::Result<()> {
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), request as _, raw as *mut RawBuffer) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Issues `VIDIOC_STREAMON` or `VIDIOC_STREAMOFF`, which take the buffer type by pointer.
+fn stream_ioctl(device: &File, request: u32, queue: M2mQueue) -> io::Result<()> {
+    let buffer_type = queue.buffer_type();
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), request as _, &buffer_type) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Issues `VIDIOC_REQBUFS` for mmap buffers, returning the count the driver granted.
+fn request_buffers(device: &File, buffer_type: u32, count: u32) -> io::Result<u32> {
+    let mut raw = RawRequestBuffers {
+        count,
+        buffer_type,
+        memory: MEMORY_MMAP,
+        reserved: [0; 2],
+    };
+
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_REQBUFS as _, &mut raw) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(raw.count)
+}
+
+/// Converts a NUL-terminated byte buffer into a `String`.
+fn c_string(buffer: &[u8]) -> String {
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
@@ -0,0 +1,55 @@
+use std::io::{self, Write};
+
+/// Writes planar YUV 4:2:2 frames into a YUV4MPEG2 (`.y4m`) stream, the uncompressed format that
+/// ffmpeg, x264 and most video tools read directly.
+///
+/// The stream header is written when the writer is created, so unlike a container the file is
+/// valid after every frame, even if the recording is interrupted.
+pub struct Writer<W: Write> {
+    inner: W,
+    frame_size: usize, //Bytes in one frame, all three planes
+    frames: u32,
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts a stream of `width` by `height` frames played back at `rate`, a `(numerator,
+    /// denominator)` frame rate in frames per second, e.g. `(30000, 1001)` for 29.97 fps.
+    ///
+    /// The header marks the frames progressive with square pixels and 4:2:2 chroma, i.e. chroma
+    /// at half the luma width and full height, as the camera captured it.
+    pub fn new(mut inner: W, width: u32, height: u32, rate: (u32, u32)) -> io::Result<Self> {
+        if width == 0 || height == 0 || width & 1 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cannot write {}x{} frames as YUV 4:2:2", width, height),
+            ));
+        }
+        writeln!(inner, "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C422", width, height, rate.0.max(1), rate.1.max(1))?;
+        Ok(Writer { inner, frame_size: width as usize * height as usize * 2, frames: 0 })
+    }
+
+    /// Appends one frame, the full size Y plane followed by the half width U and V planes.
+    pub fn write_frame(&mut self, planes: &[u8]) -> io::Result<()> {
+        if planes.len() != self.frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Frame holds {} bytes, the stream's frames hold {}", planes.len(), self.frame_size),
+            ));
+        }
+        self.inner.write_all(b"FRAME\n")?;
+        self.inner.write_all(planes)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> u32 {
+        self.frames
+    }
+
+    /// Flushes the stream and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}